@@ -111,6 +111,7 @@ impl MainState {
             color: Some(AQUA),
             font: Some(graphics::Font::default()),
             scale: Some(graphics::PxScale::from(300.0)),
+            ..Default::default()
         });
         let demo_spritebatch = graphics::spritebatch::SpriteBatch::new(demo_image.clone());
 
@@ -35,10 +35,19 @@ impl MainState {
         self.sound.play(ctx)
     }
 
-    fn stop(&self, ctx: &mut Context) -> GameResult {
+    fn stop(&mut self, ctx: &mut Context) -> GameResult {
         self.sound.stop(ctx)
     }
 
+    fn toggle_pause(&mut self, ctx: &mut Context) -> GameResult {
+        if self.sound.paused() {
+            self.sound.resume(ctx)
+        } else {
+            self.sound.pause(ctx);
+            Ok(())
+        }
+    }
+
     fn increase_volume(&mut self, ctx: &mut Context) -> GameResult {
         self.volume += 0.1;
         self.sound.set_volume(ctx, self.volume)
@@ -62,7 +71,7 @@ impl event::EventHandler<ggez::GameError> for MainState {
     fn draw(&mut self, ctx: &mut Context, quad_ctx: &mut miniquad::GraphicsContext) -> GameResult {
         graphics::clear(ctx, quad_ctx, [0.1, 0.2, 0.3, 1.0].into());
 
-        let text = graphics::Text::new("Press number key 1 to to play a sound,\n2 to play repeated,\n3 to stop,\nUp to increase volume,\nDown to decrease volume,\nor escape to quit.");
+        let text = graphics::Text::new("Press number key 1 to to play a sound,\n2 to play repeated,\n3 to stop,\n4 to pause or resume,\nUp to increase volume,\nDown to decrease volume,\nor escape to quit.");
         graphics::draw(
             ctx,
             quad_ctx,
@@ -86,6 +95,7 @@ impl event::EventHandler<ggez::GameError> for MainState {
             event::KeyCode::Key1 => self.play_once(ctx).unwrap(),
             event::KeyCode::Key2 => self.play_repeating(ctx).unwrap(),
             event::KeyCode::Key3 => self.stop(ctx).unwrap(),
+            event::KeyCode::Key4 => self.toggle_pause(ctx).unwrap(),
             event::KeyCode::Up => self.increase_volume(ctx).unwrap(),
             event::KeyCode::Down => self.decrease_volume(ctx).unwrap(),
             event::KeyCode::Escape => {
@@ -0,0 +1,456 @@
+//! Non-blocking loading of images, sounds, fonts and shaders.
+//!
+//! All constructors like [`Image::new`](../graphics/struct.Image.html#method.new) block until
+//! the file has been read, which works fine on desktop, but is impossible on WASM for anything
+//! that is not inside of a mounted layer, as the file has to be fetched through http first.
+//!
+//! An [`AssetServer`](struct.AssetServer.html) instead starts loading right away and hands out
+//! a typed [`Handle`](struct.Handle.html), which can be resolved into the actual asset once it's
+//! done. Call [`AssetServer::update`](struct.AssetServer.html#method.update) once per frame to
+//! make progress:
+//!
+//! ```rust, ignore
+//! fn update(&mut self, ctx: &mut Context, quad_ctx: &mut GraphicsContext) -> GameResult {
+//!     self.assets.update(ctx, quad_ctx);
+//!     if !self.assets.is_done() {
+//!         // still on the loading screen
+//!         self.progress = self.assets.progress();
+//!         return Ok(());
+//!     }
+//!     // `self.assets.get(&self.player_image)` returns `Some` from now on
+//!     Ok(())
+//! }
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::audio::Source;
+use crate::filesystem::{File, Filesystem};
+use crate::graphics::{BlendMode, Font, Image, Shader, ShaderId, ShaderMeta};
+use crate::{Context, GameError, GameResult};
+
+type Constructor = Box<
+    dyn FnOnce(
+        &mut Context,
+        &mut miniquad::GraphicsContext,
+        Vec<Vec<u8>>,
+    ) -> GameResult<Box<dyn Any>>,
+>;
+
+/// A typed reference to an asset loaded by an [`AssetServer`](struct.AssetServer.html).
+///
+/// Handles are cheap to copy; the asset itself is owned by the `AssetServer`.
+pub struct Handle<T> {
+    /// The `AssetServer` the handle belongs to.
+    server: usize,
+    id: usize,
+    // `fn() -> T` keeps the handle `Copy`, `Send` and `Sync` no matter what `T` is
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(server: usize, id: usize) -> Self {
+        Handle {
+            server,
+            id,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.server == other.server && self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.server.hash(state);
+        self.id.hash(state)
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id)
+    }
+}
+
+/// The state an asset is in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    /// Its files are still being read or fetched.
+    Loading,
+    /// The asset is ready to be used.
+    Loaded,
+    /// Loading failed, see [`AssetServer::error`](struct.AssetServer.html#method.error).
+    Failed,
+}
+
+enum Slot {
+    Loading {
+        files: Vec<Arc<Mutex<Option<GameResult<File>>>>>,
+        constructor: Constructor,
+    },
+    Loaded(Box<dyn Any>),
+    Failed(GameError),
+}
+
+struct Asset {
+    paths: Vec<PathBuf>,
+    slot: Slot,
+}
+
+/// Loads assets in the background and keeps them around, see the [module level docs](index.html).
+///
+/// Requesting the same file(s) as the same type of asset twice returns the same handle, so the
+/// asset is only ever loaded once.
+pub struct AssetServer {
+    /// Tells apart the handles of different servers.
+    server: usize,
+    assets: Vec<Asset>,
+    /// Maps the type of an asset and the paths it was loaded from to its id, for deduplication.
+    ids: HashMap<(TypeId, Vec<PathBuf>), usize>,
+}
+
+impl fmt::Debug for AssetServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<AssetServer: {} assets, {} loading, {} failed>",
+            self.assets.len(),
+            self.loading_count(),
+            self.failed_count()
+        )
+    }
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        static NEXT_SERVER: AtomicUsize = AtomicUsize::new(0);
+        AssetServer {
+            server: NEXT_SERVER.fetch_add(1, Ordering::Relaxed),
+            assets: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+}
+
+impl AssetServer {
+    /// Creates a new, empty `AssetServer`.
+    pub fn new() -> AssetServer {
+        AssetServer::default()
+    }
+
+    /// Returns the asset the handle refers to, or `None` for handles of other servers.
+    fn asset<T>(&self, handle: &Handle<T>) -> Option<&Asset> {
+        if handle.server == self.server {
+            self.assets.get(handle.id)
+        } else {
+            None
+        }
+    }
+
+    fn load<T: 'static>(
+        &mut self,
+        fs: &mut Filesystem,
+        paths: Vec<PathBuf>,
+        constructor: Constructor,
+    ) -> Handle<T> {
+        // `/a.png` and `a.png` are the same file
+        let paths = paths
+            .into_iter()
+            .map(|path| Filesystem::sanitize_path(&path).unwrap_or(path))
+            .collect();
+        let key = (TypeId::of::<T>(), paths);
+        if let Some(&id) = self.ids.get(&key) {
+            return Handle::new(self.server, id);
+        }
+        let paths = key.1.clone();
+
+        let files = paths.iter().map(|path| fs.open_async(path)).collect();
+        let id = self.assets.len();
+        self.assets.push(Asset {
+            paths,
+            slot: Slot::Loading { files, constructor },
+        });
+        self.ids.insert(key, id);
+        Handle::new(self.server, id)
+    }
+
+    /// Starts loading an image.
+    pub fn load_image<P: AsRef<Path>>(&mut self, ctx: &mut Context, path: P) -> Handle<Image> {
+        self.load(
+            &mut ctx.filesystem,
+            vec![path.as_ref().to_path_buf()],
+            Box::new(|ctx, quad_ctx, mut bytes| {
+                let image = Image::from_png_bytes(ctx, quad_ctx, &bytes.remove(0))?;
+                Ok(Box::new(image))
+            }),
+        )
+    }
+
+    /// Starts loading a sound.
+    pub fn load_source<P: AsRef<Path>>(&mut self, ctx: &mut Context, path: P) -> Handle<Source> {
+        self.load(
+            &mut ctx.filesystem,
+            vec![path.as_ref().to_path_buf()],
+            Box::new(|ctx, _quad_ctx, mut bytes| {
+                let source = Source::from_bytes(ctx, &bytes.remove(0))?;
+                Ok(Box::new(source))
+            }),
+        )
+    }
+
+    /// Starts loading a TrueType font.
+    pub fn load_font<P: AsRef<Path>>(&mut self, ctx: &mut Context, path: P) -> Handle<Font> {
+        self.load(
+            &mut ctx.filesystem,
+            vec![path.as_ref().to_path_buf()],
+            Box::new(|ctx, _quad_ctx, mut bytes| {
+                let font = Font::new_glyph_font_bytes(ctx, &bytes.remove(0))?;
+                Ok(Box::new(font))
+            }),
+        )
+    }
+
+    /// Starts loading a shader, see [`Shader::new`](../graphics/struct.Shader.html#method.new).
+    pub fn load_shader<P: AsRef<Path>>(
+        &mut self,
+        ctx: &mut Context,
+        vertex_path: P,
+        pixel_path: P,
+        shader_meta: ShaderMeta,
+        blend_mode: Option<BlendMode>,
+    ) -> Handle<ShaderId> {
+        self.load(
+            &mut ctx.filesystem,
+            vec![
+                vertex_path.as_ref().to_path_buf(),
+                pixel_path.as_ref().to_path_buf(),
+            ],
+            Box::new(move |ctx, quad_ctx, bytes| {
+                let id =
+                    Shader::from_u8(ctx, quad_ctx, &bytes[0], &bytes[1], shader_meta, blend_mode)?;
+                Ok(Box::new(id))
+            }),
+        )
+    }
+
+    /// Checks on all assets still loading and creates those whose files have arrived.
+    ///
+    /// Call this once per frame, e.g. at the start of `EventHandler::update`.
+    pub fn update(&mut self, ctx: &mut Context, quad_ctx: &mut miniquad::GraphicsContext) {
+        for asset in self.assets.iter_mut() {
+            let files = match asset.slot {
+                Slot::Loading { ref files, .. } => files,
+                _ => continue,
+            };
+            if files.iter().any(|file| file.lock().unwrap().is_none()) {
+                continue;
+            }
+
+            let slot = std::mem::replace(&mut asset.slot, Slot::Loaded(Box::new(())));
+            if let Slot::Loading { files, constructor } = slot {
+                let bytes: GameResult<Vec<Vec<u8>>> = files
+                    .iter()
+                    .map(|file| {
+                        let file = file.lock().unwrap().take().unwrap();
+                        file.map(|file| file.bytes.into_inner())
+                    })
+                    .collect();
+                asset.slot = match bytes.and_then(|bytes| constructor(ctx, quad_ctx, bytes)) {
+                    Ok(value) => Slot::Loaded(value),
+                    Err(e) => {
+                        warn!("Couldn't load asset {:?}: {}", asset.paths, e);
+                        Slot::Failed(e)
+                    }
+                };
+            }
+        }
+    }
+
+    /// Returns the asset, if it has been loaded already.
+    ///
+    /// Returns `None` for handles of other `AssetServer`s.
+    pub fn get<T: 'static>(&self, handle: &Handle<T>) -> Option<&T> {
+        match self.asset(handle)?.slot {
+            Slot::Loaded(ref value) => value.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the asset mutably, if it has been loaded already.
+    ///
+    /// Returns `None` for handles of other `AssetServer`s.
+    pub fn get_mut<T: 'static>(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        if handle.server != self.server {
+            return None;
+        }
+        match self.assets.get_mut(handle.id)?.slot {
+            Slot::Loaded(ref mut value) => value.downcast_mut(),
+            _ => None,
+        }
+    }
+
+    /// Returns the state the asset is in, or `None` for handles of other `AssetServer`s.
+    pub fn state<T>(&self, handle: &Handle<T>) -> Option<LoadState> {
+        Some(match self.asset(handle)?.slot {
+            Slot::Loading { .. } => LoadState::Loading,
+            Slot::Loaded(_) => LoadState::Loaded,
+            Slot::Failed(_) => LoadState::Failed,
+        })
+    }
+
+    /// Returns the error that occurred while loading the asset, if any.
+    pub fn error<T>(&self, handle: &Handle<T>) -> Option<&GameError> {
+        match self.asset(handle)?.slot {
+            Slot::Failed(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Returns all errors that occurred so far, together with the paths of the failed assets.
+    pub fn errors(&self) -> impl Iterator<Item = (&[PathBuf], &GameError)> {
+        self.assets.iter().filter_map(|asset| match asset.slot {
+            Slot::Failed(ref e) => Some((asset.paths.as_slice(), e)),
+            _ => None,
+        })
+    }
+
+    /// Returns the number of assets still loading.
+    pub fn loading_count(&self) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| matches!(asset.slot, Slot::Loading { .. }))
+            .count()
+    }
+
+    /// Returns the number of assets which failed to load.
+    pub fn failed_count(&self) -> usize {
+        self.assets
+            .iter()
+            .filter(|asset| matches!(asset.slot, Slot::Failed(_)))
+            .count()
+    }
+
+    /// Returns the number of assets requested so far, no matter their state.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Returns whether no asset has been requested so far.
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Returns the fraction of assets which are done loading (successfully or not), from 0.0 to 1.0.
+    pub fn progress(&self) -> f32 {
+        if self.assets.is_empty() {
+            1.0
+        } else {
+            1.0 - self.loading_count() as f32 / self.assets.len() as f32
+        }
+    }
+
+    /// Returns whether all requested assets are done loading (successfully or not).
+    pub fn is_done(&self) -> bool {
+        self.loading_count() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::Conf;
+    use crate::filesystem::MemoryStorage;
+
+    fn filesystem() -> Filesystem {
+        let mut fs = Filesystem::new(&Conf::default());
+        fs.set_user_storage(Box::new(MemoryStorage::new()));
+        fs
+    }
+
+    fn load<T: 'static>(
+        server: &mut AssetServer,
+        fs: &mut Filesystem,
+        paths: &[&str],
+    ) -> Handle<T> {
+        let paths = paths.iter().map(PathBuf::from).collect();
+        server.load(fs, paths, Box::new(|_, _, _| Ok(Box::new(()))))
+    }
+
+    #[test]
+    fn deduplicates_sanitized_paths() {
+        let mut fs = filesystem();
+        let mut server = AssetServer::new();
+        let a = load::<Image>(&mut server, &mut fs, &["/img/a.png"]);
+        assert_eq!(load::<Image>(&mut server, &mut fs, &["img/a.png"]), a);
+        assert_eq!(load::<Image>(&mut server, &mut fs, &["/img//a.png"]), a);
+        assert_eq!(load::<Image>(&mut server, &mut fs, &["/img/./a.png"]), a);
+        assert_eq!(server.len(), 1);
+
+        // other files, other types and other combinations of files are loaded separately
+        let b = load::<Image>(&mut server, &mut fs, &["/img/b.png"]);
+        assert_ne!(b, a);
+        let _ = load::<Source>(&mut server, &mut fs, &["/img/a.png"]);
+        let _ = load::<ShaderId>(&mut server, &mut fs, &["/a.glsl", "/b.glsl"]);
+        let _ = load::<ShaderId>(&mut server, &mut fs, &["/b.glsl", "/a.glsl"]);
+        assert_eq!(server.len(), 5);
+        assert_eq!(
+            load::<ShaderId>(&mut server, &mut fs, &["a.glsl", "b.glsl"]),
+            load::<ShaderId>(&mut server, &mut fs, &["/a.glsl", "/b.glsl"])
+        );
+        assert_eq!(server.len(), 5);
+
+        assert_eq!(server.state(&a), Some(LoadState::Loading));
+        assert_eq!(server.loading_count(), 5);
+        assert_eq!(server.progress(), 0.0);
+        assert!(!server.is_done());
+    }
+
+    #[test]
+    fn ignores_handles_of_other_servers() {
+        let mut fs = filesystem();
+        let mut first = AssetServer::new();
+        let mut second = AssetServer::new();
+        let a = load::<Image>(&mut first, &mut fs, &["/a.png"]);
+        let b = load::<Image>(&mut second, &mut fs, &["/a.png"]);
+        // both have the same id, but belong to different servers
+        assert_ne!(a, b);
+
+        assert_eq!(first.state(&a), Some(LoadState::Loading));
+        assert_eq!(first.state(&b), None);
+        assert!(first.get(&b).is_none());
+        assert!(first.get_mut(&b).is_none());
+        assert!(first.error(&b).is_none());
+        assert_eq!(second.state(&a), None);
+    }
+
+    #[test]
+    fn empty_server_is_done() {
+        let server = AssetServer::new();
+        assert!(server.is_empty());
+        assert!(server.is_done());
+        assert_eq!(server.progress(), 1.0);
+        assert_eq!(server.errors().count(), 0);
+    }
+}
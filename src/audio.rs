@@ -3,8 +3,22 @@
 //! Note that audio functionality in good-web-game is very different from ggez, as it uses quad-snd
 //! instead of rodio, for maximum portability.
 
-use crate::{filesystem, Context, GameResult};
+use crate::{filesystem, timer, Context, GameError, GameResult};
+use std::cell::{OnceCell, RefCell};
 use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+mod mixer;
+mod pcm;
+mod spatial;
+mod stream;
+
+pub use self::mixer::Bus;
+use self::mixer::Mixer;
+use self::pcm::{Pcm, MAX_SPEED, MIN_SPEED};
+pub use self::spatial::{Attenuation, SpatialSource};
+pub use self::stream::{AudioStream, StreamSource};
 
 #[cfg(all(feature = "audio", not(target_os = "ios")))]
 use quad_snd::{AudioContext as QuadSndContext, Sound as QuadSndSound};
@@ -31,7 +45,7 @@ mod dummy_audio {
     pub struct Sound {}
 
     impl Sound {
-        pub fn load(_ctx: &mut AudioContext, _data: &[u8]) -> Sound {
+        pub fn load(_ctx: &AudioContext, _data: &[u8]) -> Sound {
             Sound {}
         }
 
@@ -39,11 +53,13 @@ mod dummy_audio {
             true
         }
 
-        pub fn play(&mut self, _ctx: &mut AudioContext, _params: PlaySoundParams) {}
+        pub fn play(&self, _ctx: &AudioContext, _params: PlaySoundParams) {}
 
-        pub fn stop(&mut self, _ctx: &mut AudioContext) {}
+        pub fn stop(&self, _ctx: &AudioContext) {}
 
-        pub fn set_volume(&mut self, _ctx: &mut AudioContext, _volume: f32) {}
+        pub fn set_volume(&self, _ctx: &AudioContext, _volume: f32) {}
+
+        pub fn delete(&self, _ctx: &AudioContext) {}
     }
 }
 
@@ -56,10 +72,160 @@ pub struct PlaySoundParams {
     pub volume: f32,
 }
 
+#[cfg(any(not(feature = "audio"), target_os = "ios"))]
+impl Default for PlaySoundParams {
+    fn default() -> PlaySoundParams {
+        PlaySoundParams {
+            looped: false,
+            volume: 1.,
+        }
+    }
+}
+
+/// How a sound is played, handed to [`AudioContext::play_sound`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Playback {
+    pub(crate) looped: bool,
+    /// The volume of the sound itself, before the mixer is applied.
+    pub(crate) volume: f32,
+    /// The speed the sound is played back at.
+    pub(crate) pitch: f32,
+    /// How long it takes the sound to fade in from silence, in seconds.
+    pub(crate) fade_in: f64,
+    /// Where in the sound playback starts, in seconds.
+    pub(crate) offset: f64,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            looped: false,
+            volume: 1.0,
+            pitch: 1.0,
+            fade_in: 0.0,
+            offset: 0.0,
+        }
+    }
+}
+
+/// How many resampled versions of a sound are kept around, see `SoundData::resampled`.
+const RESAMPLED_CACHE_SIZE: usize = 4;
+
+struct SoundState {
+    /// The sound as loaded from the file, played whenever playback starts at the beginning.
+    native: QuadSndSound,
+    /// The sound resampled to the given pitch, played whenever playback with that pitch starts
+    /// at the beginning.
+    pitched: Option<(f32, QuadSndSound)>,
+    /// A sound derived from `data` with another starting point, played instead of the above if
+    /// needed.
+    derived: Option<QuadSndSound>,
+    data: SoundData,
+    bus: Bus,
+    playback: Playback,
+    /// The length of the sound in seconds, if it could be determined.
+    duration: Option<f64>,
+    /// When the latest playback started at `playback.offset`, as in `timer::time`. `None`
+    /// while stopped or paused.
+    started: Option<f64>,
+    /// Whether the sound was paused by its bus and resumes along with it.
+    paused_by_bus: bool,
+    /// Whether playback waits for the native sound to be loaded, which on WASM happens
+    /// asynchronously. `started` is moved along until then.
+    loading: bool,
+    /// Whether to delete the sound once it's done playing.
+    transient: bool,
+    /// The sound is deleted once this can't be upgraded anymore, as its owner was dropped.
+    alive: Option<Weak<()>>,
+}
+
+impl SoundState {
+    /// Returns the native sound currently used for playback.
+    fn current(&self) -> &QuadSndSound {
+        match (&self.derived, &self.pitched) {
+            (Some(derived), _) => derived,
+            (None, Some((pitch, pitched))) if *pitch == self.playback.pitch => pitched,
+            _ => &self.native,
+        }
+    }
+
+    /// Returns where in the sound playback is, in seconds.
+    fn position(&self, now: f64) -> f64 {
+        let mut position = self.playback.offset;
+        if let Some(started) = self.started {
+            position += (now - started) * f64::from(self.playback.pitch);
+        }
+        match self.duration {
+            Some(duration) if self.playback.looped && duration > 0.0 => position % duration,
+            Some(duration) => position.min(duration),
+            None => position,
+        }
+    }
+
+    /// Returns whether the sound is playing, meaning it's neither stopped, paused, nor done
+    /// playing. Sounds of unknown length play until they're stopped.
+    fn playing(&self, now: f64) -> bool {
+        let started = match self.started {
+            Some(started) => started,
+            None => return false,
+        };
+        match self.duration {
+            Some(duration) if !self.playback.looped => {
+                self.playback.offset + (now - started) * f64::from(self.playback.pitch) < duration
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the volume the sound is actually played at, after fading and mixing.
+    fn effective_volume(&self, mixer: &Mixer, now: f64) -> f32 {
+        let fade = match self.started {
+            Some(started) if self.playback.fade_in > 0.0 => {
+                ((now - started) / self.playback.fade_in).clamp(0.0, 1.0) as f32
+            }
+            _ => 1.0,
+        };
+        self.playback.volume * fade * mixer.gain(self.bus)
+    }
+
+    /// Returns whether the current native sound can be played, which on WASM takes a moment
+    /// as the browser decodes sounds asynchronously.
+    fn loaded(&self) -> bool {
+        #[cfg(target_arch = "wasm32")]
+        return self.current().is_loaded();
+        #[cfg(not(target_arch = "wasm32"))]
+        true
+    }
+
+    /// Plays the current native sound, or waits for it to be loaded first.
+    fn play_current(&mut self, native_ctx: &QuadSndContext, mixer: &Mixer, now: f64) {
+        self.started = Some(now);
+        self.loading = !self.loaded();
+        if !self.loading {
+            let params = PlaySoundParams {
+                looped: self.playback.looped,
+                volume: self.effective_volume(mixer, now),
+            };
+            self.current().play(native_ctx, params);
+        }
+    }
+
+    /// Stops playback, remembering the position to continue from.
+    fn pause(&mut self, native_ctx: &QuadSndContext, now: f64) {
+        self.playback.offset = self.position(now);
+        self.started = None;
+        self.loading = false;
+        self.current().stop(native_ctx);
+    }
+}
+
 pub struct AudioContext {
     native_ctx: QuadSndContext,
-    sounds: HashMap<usize, QuadSndSound>,
+    sounds: HashMap<usize, SoundState>,
+    streams: HashMap<usize, stream::Stream>,
     id: usize,
+    mixer: Mixer,
+    last_update: f64,
 }
 
 impl AudioContext {
@@ -67,10 +233,276 @@ impl AudioContext {
         AudioContext {
             native_ctx: QuadSndContext::new(),
             sounds: HashMap::new(),
+            streams: HashMap::new(),
             id: 0,
+            mixer: Mixer::default(),
+            last_update: timer::time(),
         }
     }
 
+    /// Loads the sound into the native context, returning the id it can be played by.
+    pub(crate) fn load_sound(&mut self, data: SoundData) -> Sound {
+        let native = QuadSndSound::load(&self.native_ctx, &data.bytes);
+        let id = self.id;
+        self.sounds.insert(
+            id,
+            SoundState {
+                native,
+                pitched: None,
+                derived: None,
+                duration: data.duration().map(|duration| duration.as_secs_f64()),
+                data,
+                bus: Bus::default(),
+                playback: Playback::default(),
+                started: None,
+                paused_by_bus: false,
+                loading: false,
+                transient: false,
+                alive: None,
+            },
+        );
+        self.id += 1;
+        Sound(id)
+    }
+
+    /// Frees the sound, stopping it if it's still playing.
+    pub(crate) fn delete_sound(&mut self, sound: Sound) {
+        if let Some(state) = self.sounds.remove(&sound.0) {
+            let pitched = state.pitched.map(|(_, pitched)| pitched);
+            for native in std::iter::once(state.native)
+                .chain(pitched)
+                .chain(state.derived)
+            {
+                native.stop(&self.native_ctx);
+                native.delete(&self.native_ctx);
+            }
+        }
+    }
+
+    /// Marks the sound to be deleted once it's done playing.
+    pub(crate) fn set_sound_transient(&mut self, sound: Sound) {
+        self.sounds.get_mut(&sound.0).unwrap().transient = true;
+    }
+
+    /// Ties the sound to `alive`, deleting it at the start of the frame after `alive` was
+    /// dropped.
+    pub(crate) fn set_sound_owner(&mut self, sound: Sound, alive: &Rc<()>) {
+        self.sounds.get_mut(&sound.0).unwrap().alive = Some(Rc::downgrade(alive));
+    }
+
+    // sounds marked transient may be gone already, so the following functions ignore those
+
+    /// Plays the sound, stopping it first if it's already playing.
+    ///
+    /// Sounds on a paused bus only start once the bus is resumed.
+    pub(crate) fn play_sound(&mut self, sound: Sound, playback: Playback) -> GameResult {
+        if let Some(state) = self.sounds.get_mut(&sound.0) {
+            state.current().stop(&self.native_ctx);
+            state.playback = playback;
+            state.started = None;
+            state.loading = false;
+            state.paused_by_bus = self.mixer.bus(state.bus).paused;
+            if !state.paused_by_bus {
+                self.start(sound.0, timer::time())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts playback of the sound at `playback.offset`.
+    fn start(&mut self, id: usize, now: f64) -> GameResult {
+        let state = match self.sounds.get_mut(&id) {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        if let Some(derived) = state.derived.take() {
+            derived.stop(&self.native_ctx);
+            derived.delete(&self.native_ctx);
+        }
+
+        let playback = state.playback;
+        if playback.offset != 0.0 {
+            let data = state.data.resampled(playback.pitch)?;
+            let pcm = data.pcm()?;
+            let seconds = playback.offset / f64::from(playback.pitch);
+            let frame = (seconds * f64::from(pcm.sample_rate)) as usize;
+            let pcm = pcm.starting_at(frame, playback.looped);
+            state.derived = Some(QuadSndSound::load(&self.native_ctx, &pcm.to_wav()));
+        } else if playback.pitch != 1.0
+            && state
+                .pitched
+                .as_ref()
+                .is_none_or(|(pitch, _)| *pitch != playback.pitch)
+        {
+            let data = state.data.resampled(playback.pitch)?;
+            let native = QuadSndSound::load(&self.native_ctx, &data.bytes);
+            if let Some((_, previous)) = state.pitched.replace((playback.pitch, native)) {
+                previous.stop(&self.native_ctx);
+                previous.delete(&self.native_ctx);
+            }
+        }
+
+        state.play_current(&self.native_ctx, &self.mixer, now);
+        Ok(())
+    }
+
+    pub(crate) fn stop_sound(&mut self, sound: Sound) {
+        if let Some(state) = self.sounds.get_mut(&sound.0) {
+            state.playback.offset = 0.0;
+            state.started = None;
+            state.loading = false;
+            state.paused_by_bus = false;
+            state.current().stop(&self.native_ctx);
+        }
+    }
+
+    /// Pauses the sound, returning whether it was playing or waiting for its bus to resume.
+    pub(crate) fn pause_sound(&mut self, sound: Sound) -> bool {
+        let now = timer::time();
+        match self.sounds.get_mut(&sound.0) {
+            Some(state) if state.paused_by_bus => {
+                state.paused_by_bus = false;
+                true
+            }
+            Some(state) if state.playing(now) => {
+                state.pause(&self.native_ctx, now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn sound_playing(&self, sound: Sound) -> bool {
+        self.sounds
+            .get(&sound.0)
+            .is_some_and(|state| state.playing(timer::time()))
+    }
+
+    /// Returns where in the sound playback is or was paused at, in seconds.
+    pub(crate) fn sound_position(&self, sound: Sound) -> f64 {
+        self.sounds
+            .get(&sound.0)
+            .map_or(0.0, |state| state.position(timer::time()))
+    }
+
+    /// Sets where playback continues once a paused sound is resumed.
+    pub(crate) fn set_sound_position(&mut self, sound: Sound, position: f64) {
+        if let Some(state) = self.sounds.get_mut(&sound.0) {
+            if state.started.is_none() {
+                state.playback.offset = position;
+            }
+        }
+    }
+
+    /// Returns how many seconds of playback the sound has left, 0 if it's stopped or gone.
+    pub(crate) fn sound_remaining(&self, sound: Sound) -> f64 {
+        let state = match self.sounds.get(&sound.0) {
+            Some(state) if state.started.is_some() || state.paused_by_bus => state,
+            _ => return 0.0,
+        };
+        state.duration.map_or(f64::INFINITY, |duration| {
+            (duration - state.position(timer::time())).max(0.0) / f64::from(state.playback.pitch)
+        })
+    }
+
+    pub(crate) fn set_sound_volume(&mut self, sound: Sound, volume: f32) {
+        if let Some(state) = self.sounds.get_mut(&sound.0) {
+            state.playback.volume = volume;
+            self.apply_volume(sound.0);
+        }
+    }
+
+    pub(crate) fn set_sound_bus(&mut self, sound: Sound, bus: Bus) {
+        if let Some(state) = self.sounds.get_mut(&sound.0) {
+            state.bus = bus;
+            self.apply_volume(sound.0);
+        }
+    }
+
+    fn apply_volume(&self, id: usize) {
+        let state = &self.sounds[&id];
+        let volume = state.effective_volume(&self.mixer, timer::time());
+        state.current().set_volume(&self.native_ctx, volume);
+    }
+
+    fn apply_volumes(&self) {
+        for id in self.sounds.keys() {
+            self.apply_volume(*id);
+        }
+    }
+
+    fn pause_bus(&mut self, bus: Bus) {
+        let now = timer::time();
+        self.mixer.bus_mut(bus).paused = true;
+        for state in self.sounds.values_mut() {
+            if state.bus == bus && state.playing(now) {
+                state.pause(&self.native_ctx, now);
+                state.paused_by_bus = true;
+            }
+        }
+    }
+
+    fn resume_bus(&mut self, bus: Bus) {
+        let now = timer::time();
+        self.mixer.bus_mut(bus).paused = false;
+        let paused: Vec<usize> = self
+            .sounds
+            .iter()
+            .filter(|(_, state)| state.bus == bus && state.paused_by_bus)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in paused {
+            self.sounds.get_mut(&id).unwrap().paused_by_bus = false;
+            if let Err(e) = self.start(id, now) {
+                warn!("Couldn't resume sound: {}", e);
+            }
+        }
+    }
+
+    /// Starts sounds which finished loading, fades sounds in, ducks the music while a voice is
+    /// playing, frees finished transient sounds as well as those of dropped sources and keeps
+    /// streams going.
+    ///
+    /// Called at the start of every frame.
+    pub(crate) fn update(&mut self) {
+        let now = timer::time();
+        let dt = (now - self.last_update) as f32;
+        self.last_update = now;
+
+        for state in self.sounds.values_mut().filter(|state| state.loading) {
+            state.play_current(&self.native_ctx, &self.mixer, now);
+        }
+
+        let mut finished = Vec::new();
+        let mut voice_playing = false;
+        for (id, state) in self.sounds.iter() {
+            let playing = state.playing(now);
+            let dropped = state
+                .alive
+                .as_ref()
+                .is_some_and(|alive| alive.upgrade().is_none());
+            if dropped || (state.transient && !playing && !state.paused_by_bus) {
+                finished.push(Sound(*id));
+            }
+            voice_playing |= state.bus == Bus::Voice && playing;
+        }
+        for sound in finished {
+            self.delete_sound(sound);
+        }
+
+        let ducking_changed = self.mixer.update_ducking(voice_playing, dt);
+        for (id, state) in self.sounds.iter() {
+            let fading = state
+                .started
+                .is_some_and(|started| now - dt as f64 <= started + state.playback.fade_in);
+            if fading || (ducking_changed && state.bus == Bus::Music) {
+                self.apply_volume(*id);
+            }
+        }
+
+        stream::update(self);
+    }
+
     #[cfg(target_os = "android")]
     pub fn pause(&mut self) {
         self.native_ctx.pause()
@@ -88,12 +520,189 @@ impl Default for AudioContext {
     }
 }
 
+/// Sets the volume all buses are multiplied with.
+///
+/// Default: `1.0`
+pub fn set_master_volume(ctx: &mut Context, volume: f32) {
+    ctx.audio_context.mixer.master_volume = volume;
+    ctx.audio_context.apply_volumes();
+}
+
+pub fn master_volume(ctx: &Context) -> f32 {
+    ctx.audio_context.mixer.master_volume
+}
+
+/// Sets the volume every sound on the bus is multiplied with.
+///
+/// Default: `1.0`
+pub fn set_bus_volume(ctx: &mut Context, bus: Bus, volume: f32) {
+    ctx.audio_context.mixer.bus_mut(bus).volume = volume;
+    ctx.audio_context.apply_volumes();
+}
+
+pub fn bus_volume(ctx: &Context, bus: Bus) -> f32 {
+    ctx.audio_context.mixer.bus(bus).volume
+}
+
+/// Silences the bus, without touching its volume.
+pub fn set_bus_muted(ctx: &mut Context, bus: Bus, muted: bool) {
+    ctx.audio_context.mixer.bus_mut(bus).muted = muted;
+    ctx.audio_context.apply_volumes();
+}
+
+pub fn bus_muted(ctx: &Context, bus: Bus) -> bool {
+    ctx.audio_context.mixer.bus(bus).muted
+}
+
+/// Pauses all sounds playing on the bus, e.g. the sound effects of the game world while the
+/// pause menu is open. Sounds played on the bus while it's paused wait for it to resume.
+///
+/// Resuming a sound somewhere in the middle requires decoding it, so sounds which couldn't be
+/// decoded (see [`SoundData`](struct.SoundData.html)) stay stopped.
+pub fn pause_bus(ctx: &mut Context, bus: Bus) {
+    if !bus_paused(ctx, bus) {
+        ctx.audio_context.pause_bus(bus);
+    }
+}
+
+/// Resumes all sounds on the bus paused by [`pause_bus`](fn.pause_bus.html).
+pub fn resume_bus(ctx: &mut Context, bus: Bus) {
+    if bus_paused(ctx, bus) {
+        ctx.audio_context.resume_bus(bus);
+    }
+}
+
+pub fn bus_paused(ctx: &Context, bus: Bus) -> bool {
+    ctx.audio_context.mixer.bus(bus).paused
+}
+
+/// Sets the factor the volume of the music bus is multiplied with while a sound on the voice bus
+/// is playing. The music fades down and back up again over a quarter of a second.
+///
+/// Default: `0.3`, use `1.0` to turn ducking off
+pub fn set_ducking(ctx: &mut Context, volume: f32) {
+    ctx.audio_context.mixer.ducking = volume;
+}
+
+pub fn ducking(ctx: &Context) -> f32 {
+    ctx.audio_context.mixer.ducking
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Sound(usize);
 
+/// The contents of a sound file, loaded once and shared by all
+/// [`Source`](struct.Source.html)s created from it.
+#[derive(Debug, Clone)]
+pub struct SoundData {
+    bytes: Rc<[u8]>,
+    /// The length of the sound, read from the headers of the file.
+    duration: Option<Duration>,
+    /// The samples, decoded the first time they're needed. `None` inside if the format is only
+    /// supported by the browser.
+    pcm: Rc<OnceCell<Option<Pcm>>>,
+    /// The sound resampled to the speeds it was played at most recently, most recent last.
+    resampled: Rc<RefCell<Vec<(f32, SoundData)>>>,
+}
+
+impl SoundData {
+    /// Load the file at the given path.
+    pub fn new(ctx: &mut Context, path: &str) -> GameResult<SoundData> {
+        use std::io::Read;
+
+        let mut file = filesystem::open(ctx, path)?;
+
+        let mut bytes = vec![];
+        file.bytes.read_to_end(&mut bytes)?;
+
+        Ok(Self::from_bytes(&bytes))
+    }
+
+    /// Wraps the contents of a WAV or OGG Vorbis file.
+    ///
+    /// Other formats can only be played through the browser on WASM, without support for
+    /// seeking, pitch or pausing.
+    pub fn from_bytes(data: &[u8]) -> SoundData {
+        SoundData {
+            bytes: data.into(),
+            duration: Pcm::probe_duration(data),
+            pcm: Rc::new(OnceCell::new()),
+            resampled: Rc::default(),
+        }
+    }
+
+    /// Wraps raw, interleaved samples ranging from -1.0 to 1.0, e.g. generated by a synthesizer.
+    ///
+    /// Fails if there are no channels or the sample rate is 0.
+    pub fn from_samples(samples: &[f32], sample_rate: u32, channels: u16) -> GameResult<SoundData> {
+        pcm::validate_format(channels, sample_rate)?;
+        Ok(Self::from_pcm(Pcm {
+            samples: samples.to_vec(),
+            channels,
+            sample_rate,
+        }))
+    }
+
+    pub(crate) fn from_pcm(pcm: Pcm) -> SoundData {
+        SoundData {
+            bytes: pcm.to_wav().into(),
+            duration: Some(pcm.duration()),
+            pcm: Rc::new(OnceCell::from(Some(pcm))),
+            resampled: Rc::default(),
+        }
+    }
+
+    /// Returns the length of the sound, if it could be read from the file.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    fn pcm(&self) -> GameResult<&Pcm> {
+        self.pcm
+            .get_or_init(|| match Pcm::decode(&self.bytes) {
+                Ok(pcm) => Some(pcm),
+                Err(e) => {
+                    warn!("{}, playback will be limited", e);
+                    None
+                }
+            })
+            .as_ref()
+            .ok_or(GameError::SoundError)
+    }
+
+    /// Returns the sound played back `speed` times as fast, resampling it only if it wasn't
+    /// played at that speed recently.
+    fn resampled(&self, speed: f32) -> GameResult<SoundData> {
+        if speed == 1.0 {
+            return Ok(self.clone());
+        }
+        let mut cache = self.resampled.borrow_mut();
+        let data = match cache.iter().position(|(cached, _)| *cached == speed) {
+            Some(index) => cache.remove(index).1,
+            None => SoundData::from_pcm(self.pcm()?.resample(speed)),
+        };
+        if cache.len() == RESAMPLED_CACHE_SIZE {
+            cache.remove(0);
+        }
+        cache.push((speed, data.clone()));
+        Ok(data)
+    }
+}
+
+/// A sound which can be played, paused, resumed and seeked.
+///
+/// Dropping a source stops it and frees its sound at the start of the next frame.
 pub struct Source {
+    data: SoundData,
     sound: Sound,
+    /// Keeps `sound` loaded.
+    _alive: Rc<()>,
     params: PlaySoundParams,
+    bus: Bus,
+    pitch: f32,
+    fade_in: Duration,
+    /// Whether playback was paused through [`pause`](#method.pause).
+    paused: bool,
 }
 
 impl Source {
@@ -101,63 +710,173 @@ impl Source {
     ///
     /// Attempts to automatically detect the format of the source of data.
     pub fn new(ctx: &mut Context, path: &str) -> GameResult<Source> {
-        use std::io::Read;
-
-        let mut file = filesystem::open(ctx, path)?;
-
-        let mut bytes = vec![];
-        file.bytes.read_to_end(&mut bytes)?;
-
-        Self::from_bytes(ctx, bytes.as_slice())
+        let data = SoundData::new(ctx, path)?;
+        Self::from_data(ctx, data)
     }
 
     /// Load audio from file.
     ///
     /// Attempts to automatically detect the format of the source of data.
     pub fn from_bytes(ctx: &mut Context, bytes: &[u8]) -> GameResult<Source> {
-        let sound = QuadSndSound::load(&mut ctx.audio_context.native_ctx, bytes);
+        Self::from_data(ctx, SoundData::from_bytes(bytes))
+    }
 
-        // only on wasm the sound is not ready right away
-        #[cfg(target_arch = "wasm32")]
-        while sound.is_loaded() {
-            std::thread::yield_now();
-        }
+    /// Creates a new source playing raw, interleaved samples ranging from -1.0 to 1.0, e.g.
+    /// generated by a synthesizer.
+    ///
+    /// To generate samples while the sound plays use a [`StreamSource`](struct.StreamSource.html).
+    ///
+    /// Fails if there are no channels or the sample rate is 0.
+    pub fn from_samples(
+        ctx: &mut Context,
+        samples: &[f32],
+        sample_rate: u32,
+        channels: u16,
+    ) -> GameResult<Source> {
+        Self::from_data(
+            ctx,
+            SoundData::from_samples(samples, sample_rate, channels)?,
+        )
+    }
 
-        let id = ctx.audio_context.id;
-        ctx.audio_context.sounds.insert(id, sound);
-        ctx.audio_context.id += 1;
+    /// Creates a new source from already loaded sound data.
+    pub fn from_data(ctx: &mut Context, data: SoundData) -> GameResult<Source> {
+        let sound = ctx.audio_context.load_sound(data.clone());
+        let alive = Rc::new(());
+        ctx.audio_context.set_sound_owner(sound, &alive);
         Ok(Source {
-            sound: Sound(id),
+            data,
+            sound,
+            _alive: alive,
             params: PlaySoundParams::default(),
+            bus: Bus::default(),
+            pitch: 1.0,
+            fade_in: Duration::from_secs(0),
+            paused: false,
         })
     }
 
-    pub fn play(&self, ctx: &mut Context) -> GameResult<()> {
-        let ctx = &mut ctx.audio_context;
-        let sound = &mut ctx.sounds.get_mut(&self.sound.0).unwrap();
-
-        let params = PlaySoundParams {
+    fn playback(&self, offset: f64) -> Playback {
+        Playback {
             looped: self.params.looped,
             volume: self.params.volume,
+            pitch: self.pitch,
+            fade_in: self.fade_in.as_secs_f64(),
+            offset,
+        }
+    }
+
+    /// Plays the sound from the beginning, stopping it first if it's already playing.
+    pub fn play(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.paused = false;
+        ctx.audio_context.play_sound(self.sound, self.playback(0.0))
+    }
+
+    /// Plays an independent copy of the sound, with the current volume, pitch and bus, which
+    /// can't be paused or stopped and keeps playing even if the source is dropped.
+    ///
+    /// Can be called repeatedly to play the same sound multiple times at once.
+    pub fn play_detached(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let audio = &mut ctx.audio_context;
+        let sound = audio.load_sound(self.data.resampled(self.pitch)?);
+        audio.set_sound_bus(sound, self.bus);
+        audio.set_sound_transient(sound);
+        let playback = Playback {
+            pitch: 1.0,
+            ..self.playback(0.0)
         };
-        sound.play(&mut ctx.native_ctx, params);
+        audio.play_sound(sound, playback)
+    }
+
+    pub fn stop(&mut self, ctx: &mut Context) -> GameResult {
+        ctx.audio_context.stop_sound(self.sound);
+        self.paused = false;
+        Ok(())
+    }
+
+    /// Pauses playback, remembering the position to [`resume`](#method.resume) from.
+    pub fn pause(&mut self, ctx: &mut Context) {
+        if ctx.audio_context.pause_sound(self.sound) {
+            self.paused = true;
+        }
+    }
+
+    /// Resumes playback where it was paused.
+    ///
+    /// Fails if the sound couldn't be decoded, see [`SoundData`](struct.SoundData.html).
+    pub fn resume(&mut self, ctx: &mut Context) -> GameResult {
+        if self.paused {
+            self.paused = false;
+            let offset = ctx.audio_context.sound_position(self.sound);
+            ctx.audio_context
+                .play_sound(self.sound, self.playback(offset))?;
+        }
         Ok(())
     }
 
-    pub fn stop(&self, ctx: &mut Context) -> GameResult {
-        let ctx = &mut ctx.audio_context;
-        let sound = &mut ctx.sounds.get_mut(&self.sound.0).unwrap();
+    /// Returns whether the sound is playing right now, meaning it's neither stopped, paused
+    /// (by itself or its bus), nor done playing.
+    ///
+    /// Sounds whose length couldn't be read from the file keep playing until they're stopped.
+    pub fn playing(&self, ctx: &Context) -> bool {
+        ctx.audio_context.sound_playing(self.sound)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the current position in the sound.
+    pub fn elapsed(&self, ctx: &Context) -> Duration {
+        Duration::from_secs_f64(ctx.audio_context.sound_position(self.sound))
+    }
 
-        sound.stop(&mut ctx.native_ctx);
+    /// Jumps to the given position in the sound, continuing playback from there if it's playing.
+    ///
+    /// Fails if the sound couldn't be decoded, see [`SoundData`](struct.SoundData.html).
+    pub fn seek(&mut self, ctx: &mut Context, position: Duration) -> GameResult {
+        self.data.pcm()?;
+        let position = position.as_secs_f64();
+        if self.playing(ctx) {
+            ctx.audio_context
+                .play_sound(self.sound, self.playback(position))?;
+        } else {
+            ctx.audio_context.set_sound_position(self.sound, position);
+        }
         Ok(())
     }
 
+    /// Sets the speed the sound is played back at, which raises (above 1.0) or lowers
+    /// (below 1.0) its pitch accordingly.
+    ///
+    /// Takes effect the next time the sound is played, resumed or seeked. The ratio is clamped
+    /// to the range from 0.01 to 100.0, NaN is ignored.
+    ///
+    /// Default: `1.0`
+    pub fn set_pitch(&mut self, ratio: f32) {
+        if !ratio.is_nan() {
+            self.pitch = ratio.clamp(MIN_SPEED, MAX_SPEED);
+        }
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Sets how long it takes the sound to fade in from silence when played or resumed.
+    ///
+    /// Default: `Duration::from_secs(0)`
+    pub fn set_fade_in(&mut self, duration: Duration) {
+        self.fade_in = duration;
+    }
+
+    pub fn fade_in(&self) -> Duration {
+        self.fade_in
+    }
+
     pub fn set_volume(&mut self, ctx: &mut Context, volume: f32) -> GameResult<()> {
-        let ctx = &mut ctx.audio_context;
         self.params.volume = volume;
-        let sound = &mut ctx.sounds.get_mut(&self.sound.0).unwrap();
-
-        sound.set_volume(&mut ctx.native_ctx, volume);
+        ctx.audio_context.set_sound_volume(self.sound, volume);
         Ok(())
     }
 
@@ -172,4 +891,16 @@ impl Source {
     pub fn repeat(&self) -> bool {
         self.params.looped
     }
+
+    /// Assigns the sound to a bus of the mixer.
+    ///
+    /// Default: `Bus::Sfx`
+    pub fn set_bus(&mut self, ctx: &mut Context, bus: Bus) {
+        self.bus = bus;
+        ctx.audio_context.set_sound_bus(self.sound, bus);
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.bus
+    }
 }
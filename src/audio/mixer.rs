@@ -0,0 +1,171 @@
+//! Buses grouping sounds by category, so that each category can be turned up, down or off
+//! on its own.
+
+/// How long it takes to duck the music or bring it back up, in seconds.
+const DUCK_FADE_TIME: f32 = 0.25;
+
+/// The category a sound belongs to.
+///
+/// Every [`Source`](struct.Source.html) plays through exactly one bus, [`Bus::Sfx`](#variant.Sfx)
+/// by default. All buses play through the master volume.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Bus {
+    /// Background music, ducked while a voice is playing.
+    Music,
+    /// Sound effects of the game world.
+    #[default]
+    Sfx,
+    /// Sounds of menus and other user interface elements.
+    Ui,
+    /// Dialogue and narration.
+    Voice,
+}
+
+impl Bus {
+    /// All buses, e.g. to build an options menu from.
+    pub const ALL: [Bus; 4] = [Bus::Music, Bus::Sfx, Bus::Ui, Bus::Voice];
+
+    /// Returns the name of the bus in lowercase, like `"music"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Bus::Music => "music",
+            Bus::Sfx => "sfx",
+            Bus::Ui => "ui",
+            Bus::Voice => "voice",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BusSettings {
+    pub(crate) volume: f32,
+    pub(crate) muted: bool,
+    /// Whether the sounds on the bus are paused, see `pause_bus`.
+    pub(crate) paused: bool,
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        BusSettings {
+            volume: 1.0,
+            muted: false,
+            paused: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mixer {
+    pub(crate) master_volume: f32,
+    buses: [BusSettings; 4],
+    /// The factor the music volume is multiplied with while a voice is playing.
+    pub(crate) ducking: f32,
+    /// The factor the music volume is currently multiplied with, moving towards `ducking`
+    /// while a voice plays and back to 1.0 otherwise.
+    duck_level: f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            master_volume: 1.0,
+            buses: Default::default(),
+            ducking: 0.3,
+            duck_level: 1.0,
+        }
+    }
+}
+
+impl Mixer {
+    pub(crate) fn bus(&self, bus: Bus) -> &BusSettings {
+        &self.buses[bus as usize]
+    }
+
+    pub(crate) fn bus_mut(&mut self, bus: Bus) -> &mut BusSettings {
+        &mut self.buses[bus as usize]
+    }
+
+    /// Returns the factor the volume of every sound on the given bus is multiplied with.
+    pub(crate) fn gain(&self, bus: Bus) -> f32 {
+        let settings = self.bus(bus);
+        // the sounds of a paused bus are stopped anyway, this only keeps them from being heard
+        // should they get started before the bus is resumed
+        if settings.muted || settings.paused {
+            return 0.0;
+        }
+        let duck = if bus == Bus::Music {
+            self.duck_level
+        } else {
+            1.0
+        };
+        self.master_volume * settings.volume * duck
+    }
+
+    /// Moves the ducking of the music `dt` seconds further, returning whether it changed.
+    pub(crate) fn update_ducking(&mut self, voice_playing: bool, dt: f32) -> bool {
+        let target = if voice_playing { self.ducking } else { 1.0 };
+        let step = dt / DUCK_FADE_TIME;
+        let level = if self.duck_level < target {
+            (self.duck_level + step).min(target)
+        } else {
+            (self.duck_level - step).max(target)
+        };
+        let changed = level != self.duck_level;
+        self.duck_level = level;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn gain() {
+        let mut mixer = Mixer::default();
+        mixer.master_volume = 0.5;
+        mixer.bus_mut(Bus::Sfx).volume = 0.5;
+        assert_relative_eq!(mixer.gain(Bus::Sfx), 0.25);
+        assert_relative_eq!(mixer.gain(Bus::Ui), 0.5);
+
+        mixer.bus_mut(Bus::Sfx).muted = true;
+        assert_eq!(mixer.gain(Bus::Sfx), 0.0);
+        mixer.bus_mut(Bus::Sfx).muted = false;
+        mixer.bus_mut(Bus::Sfx).paused = true;
+        assert_eq!(mixer.gain(Bus::Sfx), 0.0);
+        mixer.bus_mut(Bus::Sfx).paused = false;
+        assert_relative_eq!(mixer.gain(Bus::Sfx), 0.25);
+        // other buses are left alone
+        assert_relative_eq!(mixer.gain(Bus::Ui), 0.5);
+    }
+
+    #[test]
+    fn ducking() {
+        let mut mixer = Mixer::default();
+        mixer.ducking = 0.3;
+
+        // attack, taking `DUCK_FADE_TIME` per full volume
+        assert!(mixer.update_ducking(true, 0.1));
+        assert_relative_eq!(mixer.gain(Bus::Music), 0.6);
+        assert!(mixer.update_ducking(true, 0.1));
+        assert_relative_eq!(mixer.gain(Bus::Music), 0.3);
+        assert!(!mixer.update_ducking(true, 0.1));
+        assert_relative_eq!(mixer.gain(Bus::Music), 0.3);
+        // only the music is ducked
+        assert_eq!(mixer.gain(Bus::Voice), 1.0);
+        assert_eq!(mixer.gain(Bus::Sfx), 1.0);
+
+        // release
+        assert!(mixer.update_ducking(false, 0.1));
+        assert_relative_eq!(mixer.gain(Bus::Music), 0.7);
+        assert!(mixer.update_ducking(false, 0.1));
+        assert_eq!(mixer.gain(Bus::Music), 1.0);
+        assert!(!mixer.update_ducking(false, 1.0));
+        assert_eq!(mixer.gain(Bus::Music), 1.0);
+
+        // a long frame ducks all the way at once
+        assert!(mixer.update_ducking(true, DUCK_FADE_TIME));
+        assert_relative_eq!(mixer.gain(Bus::Music), 0.3);
+    }
+}
@@ -0,0 +1,265 @@
+//! Decoded audio, which can be modified before being handed to quad-snd as a WAV file.
+//!
+//! quad-snd only ever plays whole sound files, with nothing but a volume and a loop flag to
+//! tweak. Everything beyond that (panning, pitch, seeking, ...) is done by decoding the sound
+//! once, deriving modified versions of the samples and loading those as sounds of their own.
+
+use std::time::Duration;
+
+use crate::{GameError, GameResult};
+
+/// The slowest and fastest speed sounds can be played back at. Beyond those sounds are
+/// unrecognizable anyway, while resampling them would produce huge or empty sounds.
+pub(crate) const MIN_SPEED: f32 = 0.01;
+pub(crate) const MAX_SPEED: f32 = 100.0;
+
+/// Interleaved samples, ranging from -1.0 to 1.0.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Pcm {
+    pub(crate) samples: Vec<f32>,
+    pub(crate) channels: u16,
+    pub(crate) sample_rate: u32,
+}
+
+/// Checks that samples with the given layout can be played.
+pub(crate) fn validate_format(channels: u16, sample_rate: u32) -> GameResult {
+    if channels == 0 || sample_rate == 0 {
+        return Err(GameError::ResourceLoadError(format!(
+            "Invalid sound format: {} channels at {} Hz",
+            channels, sample_rate
+        )));
+    }
+    Ok(())
+}
+
+impl Pcm {
+    /// Decodes a WAV or OGG Vorbis file.
+    #[cfg(feature = "audio")]
+    pub(crate) fn decode(bytes: &[u8]) -> GameResult<Pcm> {
+        fn to_error<E: std::fmt::Display>(e: E) -> GameError {
+            GameError::ResourceLoadError(format!("Couldn't decode sound: {}", e))
+        }
+
+        let mut reader = audrey::Reader::new(std::io::Cursor::new(bytes)).map_err(to_error)?;
+        let description = reader.description();
+        let samples = reader
+            .samples::<f32>()
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(to_error)?;
+
+        Ok(Pcm {
+            samples,
+            channels: description.channel_count() as u16,
+            sample_rate: description.sample_rate(),
+        })
+    }
+
+    /// Decodes a WAV or OGG Vorbis file.
+    #[cfg(not(feature = "audio"))]
+    pub(crate) fn decode(_bytes: &[u8]) -> GameResult<Pcm> {
+        Err(GameError::SoundError)
+    }
+
+    /// Reads the length of a WAV or OGG Vorbis file from its headers, without decoding it.
+    #[cfg(feature = "audio")]
+    pub(crate) fn probe_duration(bytes: &[u8]) -> Option<Duration> {
+        use audrey::Reader;
+
+        let reader = Reader::new(std::io::Cursor::new(bytes)).ok()?;
+        let sample_rate = reader.description().sample_rate();
+        let frames = match reader {
+            Reader::Wav(wav) => u64::from(wav.duration()),
+            // the granule position of the last page is the number of frames in the stream
+            Reader::OggVorbis(_) => last_granule_position(bytes)?,
+            #[allow(unreachable_patterns)]
+            _ => return None,
+        };
+        if sample_rate == 0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(
+            frames as f64 / f64::from(sample_rate),
+        ))
+    }
+
+    /// Reads the length of a WAV or OGG Vorbis file from its headers, without decoding it.
+    #[cfg(not(feature = "audio"))]
+    pub(crate) fn probe_duration(_bytes: &[u8]) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the number of samples per channel.
+    pub(crate) fn frames(&self) -> usize {
+        self.samples.len() / usize::from(self.channels.max(1))
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / f64::from(self.sample_rate.max(1)))
+    }
+
+    /// Returns the sound starting at the given frame instead of the beginning.
+    ///
+    /// Looping sounds are rotated instead of cut, so that the skipped part still plays once the
+    /// sound loops around.
+    pub(crate) fn starting_at(&self, frame: usize, looped: bool) -> Pcm {
+        let channels = usize::from(self.channels.max(1));
+        let split = frame.min(self.frames()) * channels;
+        let mut samples = self.samples[split..].to_vec();
+        if looped {
+            samples.extend_from_slice(&self.samples[..split]);
+        }
+        Pcm {
+            samples,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Returns the sound played back `speed` times as fast, which raises or lowers its pitch
+    /// accordingly.
+    ///
+    /// The speed is clamped to `MIN_SPEED..=MAX_SPEED`.
+    pub(crate) fn resample(&self, speed: f32) -> Pcm {
+        let channels = usize::from(self.channels.max(1));
+        let frames = self.frames();
+        let speed = if speed.is_nan() { 1.0 } else { speed };
+        let speed = f64::from(speed.clamp(MIN_SPEED, MAX_SPEED));
+        let new_frames = (frames as f64 / speed) as usize;
+
+        let mut samples = Vec::with_capacity(new_frames * channels);
+        for i in 0..new_frames {
+            // linear interpolation between the two closest frames
+            let position = i as f64 * speed;
+            let frame = position as usize;
+            let next = (frame + 1).min(frames - 1);
+            let t = (position - frame as f64) as f32;
+            for channel in 0..channels {
+                let a = self.samples[frame * channels + channel];
+                let b = self.samples[next * channels + channel];
+                samples.push(a + (b - a) * t);
+            }
+        }
+        Pcm {
+            samples,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Returns a stereo version of the sound only audible on one side: the left one for
+    /// `channel == 0`, the right one otherwise.
+    ///
+    /// Stereo sounds keep the respective channel, mono sounds play their only one on that side.
+    pub(crate) fn isolate_channel(&self, channel: usize) -> Pcm {
+        let channels = usize::from(self.channels.max(1));
+        let source_channel = channel.min(channels - 1);
+        let mut samples = vec![0.0; self.frames() * 2];
+        for (frame, out) in self
+            .samples
+            .chunks_exact(channels)
+            .zip(samples.chunks_exact_mut(2))
+        {
+            out[channel.min(1)] = frame[source_channel];
+        }
+        Pcm {
+            samples,
+            channels: 2,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// Encodes the samples as a 16 bit PCM WAV file, which every backend of quad-snd can load.
+    pub(crate) fn to_wav(&self) -> Vec<u8> {
+        let data_len = (self.samples.len() * 2) as u32;
+        let block_align = self.channels * 2;
+        let byte_rate = self.sample_rate * u32::from(block_align);
+
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // integer PCM
+        wav.extend_from_slice(&self.channels.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&byte_rate.to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in self.samples.iter() {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+/// Returns the granule position of the last page of an Ogg stream, `None` if there is none.
+#[cfg(feature = "audio")]
+fn last_granule_position(bytes: &[u8]) -> Option<u64> {
+    // every page starts with "OggS" and a version of 0, followed by a flags byte and the
+    // granule position
+    let start = bytes.windows(5).rposition(|header| header == b"OggS\0")?;
+    let granule = bytes.get(start + 6..start + 14)?;
+    // -1 marks pages without a finished packet
+    u64::try_from(i64::from_le_bytes(granule.try_into().ok()?)).ok()
+}
+
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use super::*;
+
+    fn probed_and_decoded(bytes: &[u8]) -> (Duration, Duration) {
+        let probed = Pcm::probe_duration(bytes).unwrap();
+        (probed, Pcm::decode(bytes).unwrap().duration())
+    }
+
+    #[test]
+    fn probes_duration_of_wav() {
+        let (probed, decoded) = probed_and_decoded(include_bytes!("../../resources/pew.wav"));
+        assert_eq!(probed, decoded);
+
+        let pcm = Pcm {
+            samples: vec![0.0; 2 * 22050],
+            channels: 2,
+            sample_rate: 44100,
+        };
+        assert_eq!(
+            Pcm::probe_duration(&pcm.to_wav()),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn probes_duration_of_ogg() {
+        for bytes in [
+            &include_bytes!("../../resources/sound.ogg")[..],
+            &include_bytes!("../../resources/boom.ogg")[..],
+        ] {
+            let (probed, decoded) = probed_and_decoded(bytes);
+            let difference = probed.as_secs_f64() - decoded.as_secs_f64();
+            assert!(difference.abs() < 0.001, "{:?} != {:?}", probed, decoded);
+        }
+    }
+
+    #[test]
+    fn resampling_clamps_the_speed() {
+        let pcm = Pcm {
+            samples: vec![0.5; 100],
+            channels: 1,
+            sample_rate: 44100,
+        };
+        assert_eq!(pcm.resample(2.0).frames(), 50);
+        assert_eq!(pcm.resample(0.0).frames(), 10000);
+        assert_eq!(pcm.resample(-1.0).frames(), 10000);
+        assert_eq!(pcm.resample(f32::INFINITY).frames(), 1);
+        assert_eq!(pcm.resample(f32::NAN).frames(), 100);
+    }
+
+    #[test]
+    fn unknown_formats_have_no_duration() {
+        assert_eq!(Pcm::probe_duration(b"ID3\x03\0\0\0\0\0\0"), None);
+    }
+}
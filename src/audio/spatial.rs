@@ -0,0 +1,372 @@
+//! Positional audio on top of quad-snd.
+//!
+//! A `SpatialSource` decodes its sound once and loads each channel as a separate sound, so that
+//! both sides can be played simultaneously with their own volume. This works the same on all
+//! backends of quad-snd, including WASM.
+
+use crate::audio::pcm::Pcm;
+use crate::audio::{Bus, Playback, Sound, SoundData};
+use crate::{filesystem, Context, GameResult};
+use std::rc::Rc;
+
+/// How the volume of a [`SpatialSource`](struct.SpatialSource.html) falls off with the distance
+/// between emitter and listener.
+///
+/// Within the reference distance the sound is always played at full volume and beyond the
+/// max distance it is inaudible, no matter the model.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Attenuation {
+    /// The volume doesn't change with the distance, only the panning does.
+    None,
+    /// `1 - rolloff * (distance - reference) / (max - reference)`
+    Linear,
+    /// `reference / (reference + rolloff * (distance - reference))`
+    Inverse,
+    /// `(distance / reference) ^ -rolloff`
+    Exponential,
+}
+
+/// A sound with a position, played louder or quieter and panned to the left or right depending
+/// on where the listener is.
+///
+/// Dropping the source stops it and frees its sounds at the start of the next frame.
+#[derive(Debug)]
+pub struct SpatialSource {
+    left: Sound,
+    right: Sound,
+    /// Keeps both sounds loaded.
+    _alive: Rc<()>,
+    volume: f32,
+    looped: bool,
+    bus: Bus,
+    position: mint::Point2<f32>,
+    listener: mint::Point2<f32>,
+    attenuation: Attenuation,
+    rolloff: f32,
+    reference_distance: f32,
+    max_distance: f32,
+    pan: f32,
+}
+
+impl SpatialSource {
+    /// Load audio file.
+    ///
+    /// Supports WAV and OGG Vorbis files.
+    pub fn new(ctx: &mut Context, path: &str) -> GameResult<SpatialSource> {
+        use std::io::Read;
+
+        let mut file = filesystem::open(ctx, path)?;
+
+        let mut bytes = vec![];
+        file.bytes.read_to_end(&mut bytes)?;
+
+        Self::from_bytes(ctx, bytes.as_slice())
+    }
+
+    /// Load audio from file.
+    ///
+    /// Supports WAV and OGG Vorbis files.
+    pub fn from_bytes(ctx: &mut Context, bytes: &[u8]) -> GameResult<SpatialSource> {
+        let pcm = Pcm::decode(bytes)?;
+        let audio = &mut ctx.audio_context;
+        let left = audio.load_sound(SoundData::from_pcm(pcm.isolate_channel(0)));
+        let right = audio.load_sound(SoundData::from_pcm(pcm.isolate_channel(1)));
+        let alive = Rc::new(());
+        audio.set_sound_owner(left, &alive);
+        audio.set_sound_owner(right, &alive);
+
+        Ok(SpatialSource {
+            left,
+            right,
+            _alive: alive,
+            volume: 1.0,
+            looped: false,
+            bus: Bus::default(),
+            position: mint::Point2 { x: 0.0, y: 0.0 },
+            listener: mint::Point2 { x: 0.0, y: 0.0 },
+            attenuation: Attenuation::Inverse,
+            rolloff: 1.0,
+            reference_distance: 100.0,
+            max_distance: 1000.0,
+            pan: 0.0,
+        })
+    }
+
+    pub fn play(&self, ctx: &mut Context) -> GameResult {
+        let (left, right) = self.gains();
+        let audio = &mut ctx.audio_context;
+        let playback = |volume| Playback {
+            looped: self.looped,
+            volume: self.volume * volume,
+            ..Playback::default()
+        };
+        audio.play_sound(self.left, playback(left))?;
+        audio.play_sound(self.right, playback(right))
+    }
+
+    pub fn stop(&self, ctx: &mut Context) -> GameResult {
+        let audio = &mut ctx.audio_context;
+        audio.stop_sound(self.left);
+        audio.stop_sound(self.right);
+        Ok(())
+    }
+
+    /// Pushes the current volume of both sides to the sounds, if they are playing.
+    fn apply_gains(&self, ctx: &mut Context) {
+        let (left, right) = self.gains();
+        let audio = &mut ctx.audio_context;
+        audio.set_sound_volume(self.left, self.volume * left);
+        audio.set_sound_volume(self.right, self.volume * right);
+    }
+
+    /// Returns the factors the volume of the left and the right side are multiplied with,
+    /// resulting from attenuation and panning.
+    pub fn gains(&self) -> (f32, f32) {
+        let dx = self.position.x - self.listener.x;
+        let dy = self.position.y - self.listener.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        if distance > self.max_distance {
+            return (0.0, 0.0);
+        }
+
+        let reference = self.reference_distance.max(f32::EPSILON);
+        let clamped = distance.clamp(reference, self.max_distance.max(reference));
+        let gain = match self.attenuation {
+            Attenuation::None => 1.0,
+            Attenuation::Linear => {
+                let range = (self.max_distance - reference).max(f32::EPSILON);
+                1.0 - self.rolloff * (clamped - reference) / range
+            }
+            Attenuation::Inverse => reference / (reference + self.rolloff * (clamped - reference)),
+            Attenuation::Exponential => (clamped / reference).powf(-self.rolloff),
+        }
+        .clamp(0.0, 1.0);
+
+        // sounds right next to the listener are panned less
+        let position_pan = dx / distance.max(reference);
+        let pan = (position_pan + self.pan).clamp(-1.0, 1.0);
+
+        (gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0))
+    }
+
+    /// Sets the position of the sound.
+    pub fn set_position<P: Into<mint::Point2<f32>>>(&mut self, ctx: &mut Context, position: P) {
+        self.position = position.into();
+        self.apply_gains(ctx);
+    }
+
+    pub fn position(&self) -> mint::Point2<f32> {
+        self.position
+    }
+
+    /// Sets the position of the listener, e.g. the player or the center of the camera.
+    pub fn set_listener_position<P: Into<mint::Point2<f32>>>(
+        &mut self,
+        ctx: &mut Context,
+        position: P,
+    ) {
+        self.listener = position.into();
+        self.apply_gains(ctx);
+    }
+
+    pub fn listener_position(&self) -> mint::Point2<f32> {
+        self.listener
+    }
+
+    /// Sets how the volume falls off with the distance to the listener.
+    ///
+    /// Default: `Attenuation::Inverse`
+    pub fn set_attenuation(&mut self, ctx: &mut Context, attenuation: Attenuation) {
+        self.attenuation = attenuation;
+        self.apply_gains(ctx);
+    }
+
+    pub fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+
+    /// Sets how quickly the volume falls off with the distance, see [`Attenuation`](enum.Attenuation.html).
+    ///
+    /// Default: `1.0`
+    pub fn set_rolloff(&mut self, ctx: &mut Context, rolloff: f32) {
+        self.rolloff = rolloff;
+        self.apply_gains(ctx);
+    }
+
+    pub fn rolloff(&self) -> f32 {
+        self.rolloff
+    }
+
+    /// Sets the distance up to which the sound is played at full volume.
+    ///
+    /// Default: `100.0`, which works well with positions in pixels
+    pub fn set_reference_distance(&mut self, ctx: &mut Context, distance: f32) {
+        self.reference_distance = distance;
+        self.apply_gains(ctx);
+    }
+
+    pub fn reference_distance(&self) -> f32 {
+        self.reference_distance
+    }
+
+    /// Sets the distance beyond which the sound can't be heard anymore.
+    ///
+    /// Default: `1000.0`
+    pub fn set_max_distance(&mut self, ctx: &mut Context, distance: f32) {
+        self.max_distance = distance;
+        self.apply_gains(ctx);
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    /// Sets an additional panning, from -1.0 (only left) to 1.0 (only right), which is
+    /// added on top of the panning resulting from the position.
+    ///
+    /// Default: `0.0`
+    pub fn set_pan(&mut self, ctx: &mut Context, pan: f32) {
+        self.pan = pan;
+        self.apply_gains(ctx);
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn set_volume(&mut self, ctx: &mut Context, volume: f32) -> GameResult {
+        self.volume = volume;
+        self.apply_gains(ctx);
+        Ok(())
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_repeat(&mut self, repeat: bool) {
+        self.looped = repeat;
+    }
+
+    pub fn repeat(&self) -> bool {
+        self.looped
+    }
+
+    /// Assigns the sound to a bus of the mixer.
+    ///
+    /// Default: `Bus::Sfx`
+    pub fn set_bus(&mut self, ctx: &mut Context, bus: Bus) {
+        self.bus = bus;
+        ctx.audio_context.set_sound_bus(self.left, bus);
+        ctx.audio_context.set_sound_bus(self.right, bus);
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.bus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn source(attenuation: Attenuation, rolloff: f32) -> SpatialSource {
+        SpatialSource {
+            left: Sound(0),
+            right: Sound(1),
+            _alive: Rc::new(()),
+            volume: 1.0,
+            looped: false,
+            bus: Bus::default(),
+            position: mint::Point2 { x: 0.0, y: 0.0 },
+            listener: mint::Point2 { x: 0.0, y: 0.0 },
+            attenuation,
+            rolloff,
+            reference_distance: 100.0,
+            max_distance: 1000.0,
+            pan: 0.0,
+        }
+    }
+
+    /// Returns the gains of a sound at the given position relative to the listener.
+    fn gains_at(source: &mut SpatialSource, x: f32, y: f32) -> (f32, f32) {
+        source.listener = mint::Point2 { x: 10.0, y: 20.0 };
+        source.position = mint::Point2 {
+            x: 10.0 + x,
+            y: 20.0 + y,
+        };
+        source.gains()
+    }
+
+    #[track_caller]
+    fn assert_gains(gains: (f32, f32), expected: (f32, f32)) {
+        assert_relative_eq!(gains.0, expected.0, epsilon = 1e-6);
+        assert_relative_eq!(gains.1, expected.1, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn full_volume_within_reference_distance() {
+        for attenuation in [
+            Attenuation::None,
+            Attenuation::Linear,
+            Attenuation::Inverse,
+            Attenuation::Exponential,
+        ] {
+            let mut source = source(attenuation, 1.0);
+            assert_gains(gains_at(&mut source, 0.0, 0.0), (1.0, 1.0));
+            assert_gains(gains_at(&mut source, 0.0, 60.0), (1.0, 1.0));
+            assert_gains(gains_at(&mut source, 0.0, -100.0), (1.0, 1.0));
+            // silent beyond the max distance, no matter the model
+            assert_gains(gains_at(&mut source, 0.0, 1001.0), (0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn attenuation_models() {
+        let mut linear = source(Attenuation::Linear, 1.0);
+        assert_gains(gains_at(&mut linear, 0.0, 550.0), (0.5, 0.5));
+        assert_gains(gains_at(&mut linear, 0.0, 1000.0), (0.0, 0.0));
+
+        // a steeper rolloff reaches silence earlier and stays there
+        let mut steep = source(Attenuation::Linear, 2.0);
+        assert_gains(gains_at(&mut steep, 0.0, 325.0), (0.5, 0.5));
+        assert_gains(gains_at(&mut steep, 0.0, 800.0), (0.0, 0.0));
+
+        let mut gentle = source(Attenuation::Linear, 0.5);
+        assert_gains(gains_at(&mut gentle, 0.0, 1000.0), (0.5, 0.5));
+        assert_gains(gains_at(&mut gentle, 0.0, 1000.5), (0.0, 0.0));
+
+        let mut inverse = source(Attenuation::Inverse, 1.0);
+        assert_gains(gains_at(&mut inverse, 0.0, 200.0), (0.5, 0.5));
+        assert_gains(gains_at(&mut inverse, 0.0, 400.0), (0.25, 0.25));
+
+        let mut exponential = source(Attenuation::Exponential, 2.0);
+        assert_gains(gains_at(&mut exponential, 0.0, 200.0), (0.25, 0.25));
+
+        let mut none = source(Attenuation::None, 1.0);
+        assert_gains(gains_at(&mut none, 0.0, 999.0), (1.0, 1.0));
+    }
+
+    #[test]
+    fn panning() {
+        let mut source = source(Attenuation::None, 1.0);
+        // in front of or behind the listener both sides are equally loud
+        assert_gains(gains_at(&mut source, 0.0, 500.0), (1.0, 1.0));
+        assert_gains(gains_at(&mut source, 0.0, -500.0), (1.0, 1.0));
+        // fully on one side
+        assert_gains(gains_at(&mut source, 500.0, 0.0), (0.0, 1.0));
+        assert_gains(gains_at(&mut source, -500.0, 0.0), (1.0, 0.0));
+        // sounds right next to the listener are panned less
+        assert_gains(gains_at(&mut source, 50.0, 0.0), (0.5, 1.0));
+
+        source.pan = -1.0;
+        assert_gains(gains_at(&mut source, 0.0, 0.0), (1.0, 0.0));
+        source.pan = 1.0;
+        assert_gains(gains_at(&mut source, 0.0, 0.0), (0.0, 1.0));
+        // the additional panning is clamped together with the one of the position
+        assert_gains(gains_at(&mut source, 500.0, 0.0), (0.0, 1.0));
+        assert_gains(gains_at(&mut source, -500.0, 0.0), (1.0, 1.0));
+    }
+}
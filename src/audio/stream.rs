@@ -0,0 +1,274 @@
+//! Sounds generated by the game while they play.
+//!
+//! quad-snd has no way to feed it samples continuously, so a stream is played as a sequence of
+//! short sounds: at the start of every frame the next chunk is pulled from the
+//! [`AudioStream`](trait.AudioStream.html) once the previous one is about to end. As chunks can
+//! only be started at frame boundaries, the part of the previous chunk that hasn't been played
+//! yet is cut off and played at the start of the next one instead, so that there are no gaps
+//! or overlaps between them. Only frames taking longer than a tenth of a second starve the
+//! stream.
+//!
+//! On WASM the browser decodes every chunk asynchronously before it can be played, which may
+//! still cause short gaps.
+
+use crate::audio::pcm::{self, Pcm};
+use crate::audio::{AudioContext, Bus, Playback, Sound, SoundData};
+use crate::{Context, GameResult};
+use std::rc::{Rc, Weak};
+
+/// How much audio is pulled from a stream at once, in seconds.
+const CHUNK_LENGTH: f64 = 0.25;
+
+/// How long before the current chunk ends the next one is started, in seconds, to make up
+/// for the next frame (and with it the next chance to start a chunk) being too late.
+const CHUNK_LEAD: f64 = 0.1;
+
+/// A source of samples, generated or decoded by the game on demand.
+///
+/// ```rust, ignore
+/// struct Sine {
+///     phase: f32,
+/// }
+///
+/// impl AudioStream for Sine {
+///     fn sample_rate(&self) -> u32 {
+///         44100
+///     }
+///
+///     fn channels(&self) -> u16 {
+///         1
+///     }
+///
+///     fn fill(&mut self, buffer: &mut [f32]) -> usize {
+///         for sample in buffer.iter_mut() {
+///             *sample = (self.phase * std::f32::consts::TAU).sin() * 0.2;
+///             self.phase = (self.phase + 440.0 / 44100.0) % 1.0;
+///         }
+///         buffer.len()
+///     }
+/// }
+/// ```
+pub trait AudioStream {
+    /// The number of samples per second and channel.
+    fn sample_rate(&self) -> u32;
+
+    /// The number of channels, 1 for mono and 2 for stereo.
+    fn channels(&self) -> u16;
+
+    /// Fills `buffer` with the next interleaved samples, ranging from -1.0 to 1.0, and returns
+    /// how many were written.
+    ///
+    /// Writing fewer samples than fit into the buffer ends the stream.
+    fn fill(&mut self, buffer: &mut [f32]) -> usize;
+}
+
+pub(crate) struct Stream {
+    stream: Box<dyn AudioStream>,
+    bus: Bus,
+    volume: f32,
+    playing: bool,
+    /// The chunk currently playing and its samples.
+    chunk: Option<(Sound, SoundData)>,
+    /// The stream is deleted once this can't be upgraded anymore, as its source was dropped.
+    alive: Weak<()>,
+}
+
+impl Stream {
+    /// Pulls the next chunk from the stream and starts playing it.
+    fn play_next_chunk(&mut self, audio: &mut AudioContext) {
+        let channels = usize::from(self.stream.channels().max(1));
+        let sample_rate = self.stream.sample_rate().max(1);
+        let frames = (CHUNK_LENGTH * f64::from(sample_rate)) as usize;
+
+        // the rest of the current chunk, replaced by the next one
+        let mut samples = match self.chunk.take() {
+            Some((sound, data)) => {
+                let remaining = audio.sound_remaining(sound);
+                audio.delete_sound(sound);
+                match data.pcm() {
+                    Ok(pcm)
+                        if usize::from(pcm.channels) == channels
+                            && pcm.sample_rate == sample_rate =>
+                    {
+                        let kept = (remaining * f64::from(sample_rate)).round() as usize;
+                        let start = pcm.frames().saturating_sub(kept);
+                        pcm.samples[start * channels..].to_vec()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            None => Vec::new(),
+        };
+
+        let rest = samples.len();
+        samples.resize(rest + frames * channels, 0.0);
+        let written = self
+            .stream
+            .fill(&mut samples[rest..])
+            .min(frames * channels);
+        if written < frames * channels {
+            self.playing = false;
+        }
+        samples.truncate(rest + written);
+        if samples.is_empty() {
+            return;
+        }
+
+        let pcm = Pcm {
+            samples,
+            channels: channels as u16,
+            sample_rate,
+        };
+        let data = SoundData::from_pcm(pcm);
+        let sound = audio.load_sound(data.clone());
+        audio.set_sound_bus(sound, self.bus);
+        audio.set_sound_transient(sound);
+        let playback = Playback {
+            volume: self.volume,
+            ..Playback::default()
+        };
+        if let Err(e) = audio.play_sound(sound, playback) {
+            warn!("Couldn't play stream: {}", e);
+        }
+
+        self.chunk = Some((sound, data));
+    }
+}
+
+/// Plays all streams whose current chunk is about to end on to the next one and deletes
+/// those whose source was dropped.
+///
+/// Called at the start of every frame.
+pub(crate) fn update(audio: &mut AudioContext) {
+    let mut streams = std::mem::take(&mut audio.streams);
+    streams.retain(|_, stream| {
+        if stream.alive.upgrade().is_some() {
+            return true;
+        }
+        if let Some((chunk, _)) = stream.chunk.take() {
+            audio.delete_sound(chunk);
+        }
+        false
+    });
+    for stream in streams.values_mut() {
+        // the chunk of a stream on a paused bus is paused as well, and so is the stream
+        let remaining = stream
+            .chunk
+            .as_ref()
+            .map_or(0.0, |(chunk, _)| audio.sound_remaining(*chunk));
+        if stream.playing && remaining <= CHUNK_LEAD {
+            stream.play_next_chunk(audio);
+        }
+    }
+    audio.streams = streams;
+}
+
+/// A sound pulling its samples from an [`AudioStream`](trait.AudioStream.html) while it plays,
+/// e.g. for synthesized sound effects, music generated on the fly or voice chat.
+///
+/// See the [module level docs](index.html) for how streaming works. Dropping the source stops
+/// it at the start of the next frame.
+#[derive(Debug)]
+pub struct StreamSource {
+    id: usize,
+    volume: f32,
+    bus: Bus,
+    /// Keeps the stream alive.
+    _alive: Rc<()>,
+}
+
+impl StreamSource {
+    /// Creates a new source playing the given stream.
+    ///
+    /// Fails if the stream has no channels or a sample rate of 0.
+    pub fn new<S: AudioStream + 'static>(ctx: &mut Context, stream: S) -> GameResult<StreamSource> {
+        pcm::validate_format(stream.channels(), stream.sample_rate())?;
+        let alive = Rc::new(());
+        let audio = &mut ctx.audio_context;
+        let id = audio.id;
+        audio.id += 1;
+        audio.streams.insert(
+            id,
+            Stream {
+                stream: Box::new(stream),
+                bus: Bus::default(),
+                volume: 1.0,
+                playing: false,
+                chunk: None,
+                alive: Rc::downgrade(&alive),
+            },
+        );
+        Ok(StreamSource {
+            id,
+            volume: 1.0,
+            bus: Bus::default(),
+            _alive: alive,
+        })
+    }
+
+    /// Starts pulling samples from the stream, continuing where it left off if it was stopped
+    /// before.
+    pub fn play(&self, ctx: &mut Context) {
+        let audio = &mut ctx.audio_context;
+        if let Some(mut stream) = audio.streams.remove(&self.id) {
+            if !stream.playing {
+                stream.playing = true;
+                stream.play_next_chunk(audio);
+            }
+            audio.streams.insert(self.id, stream);
+        }
+    }
+
+    /// Stops playback and pulling samples from the stream.
+    pub fn stop(&self, ctx: &mut Context) {
+        let audio = &mut ctx.audio_context;
+        if let Some(stream) = audio.streams.get_mut(&self.id) {
+            stream.playing = false;
+            if let Some((chunk, _)) = stream.chunk.take() {
+                audio.delete_sound(chunk);
+            }
+        }
+    }
+
+    /// Returns whether samples are still being pulled from the stream, meaning it hasn't been
+    /// stopped and hasn't ended.
+    pub fn playing(&self, ctx: &Context) -> bool {
+        ctx.audio_context
+            .streams
+            .get(&self.id)
+            .is_some_and(|stream| stream.playing)
+    }
+
+    pub fn set_volume(&mut self, ctx: &mut Context, volume: f32) {
+        self.volume = volume;
+        let audio = &mut ctx.audio_context;
+        if let Some(stream) = audio.streams.get_mut(&self.id) {
+            stream.volume = volume;
+            if let Some(chunk) = stream.chunk.as_ref().map(|(chunk, _)| *chunk) {
+                audio.set_sound_volume(chunk, volume);
+            }
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Assigns the stream to a bus of the mixer.
+    ///
+    /// Default: `Bus::Sfx`
+    pub fn set_bus(&mut self, ctx: &mut Context, bus: Bus) {
+        self.bus = bus;
+        let audio = &mut ctx.audio_context;
+        if let Some(stream) = audio.streams.get_mut(&self.id) {
+            stream.bus = bus;
+            if let Some(chunk) = stream.chunk.as_ref().map(|(chunk, _)| *chunk) {
+                audio.set_sound_bus(chunk, bus);
+            }
+        }
+    }
+
+    pub fn bus(&self) -> Bus {
+        self.bus
+    }
+}
@@ -9,7 +9,9 @@ pub struct Conf {
     ///
     /// Note that this won't work on platforms where `std::fs` is unavailable, like WASM.
     pub physical_root_dir: Option<PathBuf>,
+    pub(crate) game_id: String,
     pub(crate) cache: Option<&'static [u8]>,
+    pub(crate) hot_reload: bool,
     pub(crate) quad_conf: miniquad::conf::Conf,
 }
 
@@ -17,7 +19,9 @@ impl Default for Conf {
     fn default() -> Conf {
         Conf {
             physical_root_dir: None,
+            game_id: "good-web-game".to_string(),
             cache: None,
+            hot_reload: false,
             quad_conf: miniquad::conf::Conf {
                 window_title: "An easy, good game".to_string(),
                 window_width: 800,
@@ -46,6 +50,13 @@ impl Conf {
         self.physical_root_dir = val;
         self
     }
+    /// Set the name of the game, used to find its directories for user data and user config files.
+    ///
+    /// Default: "good-web-game"
+    pub fn game_id(mut self, val: String) -> Self {
+        self.game_id = val;
+        self
+    }
     /// Set the cache, holding embedded files for later use.
     ///
     /// Default: `miniquad::conf::Cache::No`
@@ -53,6 +64,17 @@ impl Conf {
         self.cache = val;
         self
     }
+    /// Enable hot reloading of images, shaders and fonts loaded from the `physical_root_dir`.
+    ///
+    /// Their files are checked for changes twice per second and reloaded in place, so that you
+    /// can iterate on art and GLSL without restarting the game. Images have to keep their size
+    /// to be reloaded. Only available on desktop platforms.
+    ///
+    /// Default: `false`
+    pub fn hot_reload(mut self, val: bool) -> Self {
+        self.hot_reload = val;
+        self
+    }
     /// Set the window title
     ///
     /// Default: "An easy, good game"
@@ -87,10 +87,14 @@ impl Context {
         }
     }
 
+    /// Returns the render pass of the current canvas, to be drawn to.
     pub(crate) fn framebuffer(&mut self) -> Option<miniquad::RenderPass> {
-        self.gfx_context
-            .canvas
-            .as_ref()
-            .map(|canvas| canvas.offscreen_pass)
+        match &self.gfx_context.canvas {
+            Some(canvas) => {
+                canvas.mark_drawn();
+                Some(canvas.offscreen_pass)
+            }
+            None => graphics::screen_pass(),
+        }
     }
 }
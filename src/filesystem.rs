@@ -1,12 +1,32 @@
 // large parts directly stolen from macroquad: https://github.com/not-fl3/macroquad/blob/854aa50302a00ce590d505e28c9ecc42ae24be58/src/file.rs
 
+mod user;
+mod vfs;
+
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, io, path};
+use std::{io, path};
 
 use crate::GameError::ResourceLoadError;
 use crate::{conf::Conf, Context, GameError, GameResult};
 use std::panic::panic_any;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::user::DirectoryStorage;
+pub use self::user::{MemoryStorage, UserStorage};
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::vfs::DirectoryLayer;
+pub use self::vfs::{Layer, MountId, MountInfo, TarLayer, ZipLayer};
+
+/// The priority the [embedded tar file](../conf/struct.Conf.html#method.cache) is mounted with.
+pub const CACHE_PRIORITY: i32 = 0;
+/// The priority the [user data directory](fn.user_data_dir.html) is mounted with.
+pub const USER_DATA_PRIORITY: i32 = -1;
+/// The priority the [`physical_root_dir`](../conf/struct.Conf.html#structfield.physical_root_dir)
+/// is mounted with.
+pub const PHYSICAL_ROOT_PRIORITY: i32 = -2;
+
 #[derive(Debug, Clone)]
 pub struct File {
     pub bytes: io::Cursor<Vec<u8>>,
@@ -18,48 +38,132 @@ impl io::Read for File {
     }
 }
 
+/// A file opened for writing to the user data directory, see [`create`](fn.create.html)
+/// and [`append`](fn.append.html).
+///
+/// Everything written is buffered and only handed to the [`UserStorage`](trait.UserStorage.html)
+/// on [`flush`](#method.flush), or when the `WriteFile` is dropped.
+#[derive(Debug)]
+pub struct WriteFile {
+    path: path::PathBuf,
+    buffer: Vec<u8>,
+    append: bool,
+    storage: Rc<RefCell<Box<dyn UserStorage>>>,
+}
+
+impl io::Write for WriteFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut storage = self.storage.borrow_mut();
+        let result = if self.append {
+            storage.append(&self.path, &self.buffer)
+        } else {
+            // after the first flush all further writes continue the file
+            self.append = true;
+            storage.write(&self.path, &self.buffer)
+        };
+        self.buffer.clear();
+        result.map_err(user::to_io_error)
+    }
+}
+
+impl Drop for WriteFile {
+    fn drop(&mut self) {
+        if let Err(e) = io::Write::flush(self) {
+            warn!("Couldn't write file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
 /// A structure that contains the filesystem state and cache.
 #[derive(Debug)]
 pub struct Filesystem {
     root: Option<path::PathBuf>,
-    files: HashMap<path::PathBuf, File>,
+    /// The layer serving the files of `root`, if any.
+    root_mount: Option<MountId>,
+    /// Sorted by priority, highest first.
+    mounts: Vec<vfs::Mount>,
+    next_mount_id: usize,
+    user_data_dir: path::PathBuf,
+    user_config_dir: path::PathBuf,
+    user_storage: Rc<RefCell<Box<dyn UserStorage>>>,
 }
 
 impl Filesystem {
     #[allow(clippy::redundant_closure)]
     pub(crate) fn new(conf: &Conf) -> Filesystem {
-        let mut files = HashMap::new();
+        let root = conf.physical_root_dir.clone();
+        let (user_data_dir, user_config_dir) = user::user_dirs(&conf.game_id);
+        let user_storage = Rc::new(RefCell::new(user::default_storage(
+            &user_data_dir,
+            &user_config_dir,
+        )));
+        let mut fs = Filesystem {
+            root,
+            root_mount: None,
+            mounts: Vec::new(),
+            next_mount_id: 0,
+            user_data_dir,
+            user_config_dir,
+            user_storage,
+        };
 
         if let Some(tar_file) = conf.cache {
-            let mut archive = tar::Archive::new(tar_file);
-
-            for file in archive.entries().unwrap_or_else(|e| panic_any(e)) {
-                use std::io::Read;
-
-                let mut file = file.unwrap_or_else(|e| panic_any(e));
-                let filename =
-                    std::path::PathBuf::from(file.path().unwrap_or_else(|e| panic_any(e)));
-                let mut buf = vec![];
-
-                file.read_to_end(&mut buf).unwrap_or_else(|e| panic_any(e));
-                if !buf.is_empty() {
-                    files.insert(
-                        filename,
-                        File {
-                            bytes: io::Cursor::new(buf),
-                        },
-                    );
-                }
-            }
+            let cache = TarLayer::from_bytes(tar_file).unwrap_or_else(|e| panic_any(e));
+            fs.mount_layer(path::PathBuf::new(), Box::new(cache), CACHE_PRIORITY);
+        }
+        let user_layer = vfs::UserLayer {
+            storage: fs.user_storage.clone(),
+        };
+        fs.mount_layer(
+            path::PathBuf::new(),
+            Box::new(user_layer),
+            USER_DATA_PRIORITY,
+        );
+        #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
+        if let Some(root) = fs.root.clone() {
+            fs.root_mount = Some(fs.mount_layer(
+                path::PathBuf::new(),
+                Box::new(DirectoryLayer::new(root)),
+                PHYSICAL_ROOT_PRIORITY,
+            ));
         }
 
-        let root = conf.physical_root_dir.clone();
-        Filesystem { root, files }
+        fs
     }
 
-    /// Opens the given `path` and returns the resulting `File`
-    /// in read-only mode.
-    pub fn open<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult<File> {
+    fn mount_layer(
+        &mut self,
+        point: path::PathBuf,
+        layer: Box<dyn Layer>,
+        priority: i32,
+    ) -> MountId {
+        let id = MountId(self.next_mount_id);
+        self.next_mount_id += 1;
+        // layers mounted later win over earlier ones with the same priority
+        let index = self
+            .mounts
+            .iter()
+            .position(|mount| mount.priority <= priority)
+            .unwrap_or(self.mounts.len());
+        self.mounts.insert(
+            index,
+            vfs::Mount {
+                id,
+                point,
+                priority,
+                layer,
+            },
+        );
+        id
+    }
+
+    /// Turns a ggez-style path into the relative form used internally.
+    pub(crate) fn sanitize_path<P: AsRef<path::Path>>(path: P) -> GameResult<path::PathBuf> {
         let mut path = path::PathBuf::from(path.as_ref());
 
         // workaround for ggez-style pathes: in ggez paths starts with "/", while in the cache
@@ -68,14 +172,174 @@ impl Filesystem {
             path = path::PathBuf::from(stripped);
         }
 
-        // first check the cache
-        if self.files.contains_key(&path) {
-            Ok(self.files[&path].clone())
-        } else {
-            // the file is not inside the cache, so it has to be loaded (locally, or via http url)
-            let file = self.load_file(&path)?;
-            Ok(file)
+        if path
+            .components()
+            .any(|c| matches!(c, path::Component::ParentDir | path::Component::Prefix(_)))
+        {
+            return Err(GameError::FilesystemError(format!(
+                "Path {} is not allowed to leave the filesystem root",
+                path.display()
+            )));
+        }
+
+        Ok(path)
+    }
+
+    /// Opens the given `path` and returns the resulting `File`
+    /// in read-only mode.
+    pub fn open<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult<File> {
+        let path = Self::sanitize_path(path)?;
+
+        // first check all mounted layers, highest priority first
+        let mut layer_error = None;
+        for mount in self.mounts.iter_mut() {
+            if let Some(rest) = mount.contains_file(&path) {
+                // a layer failing to read the file shouldn't hide it in the layers below
+                match mount.layer.read(rest) {
+                    Ok(bytes) => {
+                        return Ok(File {
+                            bytes: io::Cursor::new(bytes),
+                        })
+                    }
+                    Err(e) => {
+                        let _ = layer_error.get_or_insert(e);
+                    }
+                }
+            }
         }
+
+        // the file is not inside any layer, so it has to be loaded (locally, or via http url)
+        self.load_file(&path).map_err(|e| layer_error.unwrap_or(e))
+    }
+
+    /// Mounts `layer` at the virtual path `mount_point` with the given priority.
+    pub fn mount<P: AsRef<path::Path>>(
+        &mut self,
+        mount_point: P,
+        layer: Box<dyn Layer>,
+        priority: i32,
+    ) -> GameResult<MountId> {
+        let point = Self::sanitize_path(mount_point)?;
+        Ok(self.mount_layer(point, layer, priority))
+    }
+
+    /// Removes a previously mounted layer, returning it.
+    pub fn unmount(&mut self, id: MountId) -> GameResult<Box<dyn Layer>> {
+        let index = self
+            .mounts
+            .iter()
+            .position(|mount| mount.id == id)
+            .ok_or_else(|| GameError::FilesystemError(format!("{:?} is not mounted", id)))?;
+        Ok(self.mounts.remove(index).layer)
+    }
+
+    /// Returns the layer `open` would serve the given file from.
+    pub fn resolve<P: AsRef<path::Path>>(&self, path: P) -> Option<MountInfo> {
+        let path = Self::sanitize_path(path).ok()?;
+        self.mounts
+            .iter()
+            .find(|mount| mount.contains_file(&path).is_some())
+            .map(vfs::Mount::info)
+    }
+
+    /// Returns all mounted layers, sorted by priority, highest first.
+    pub fn mounts(&self) -> Vec<MountInfo> {
+        self.mounts.iter().map(vfs::Mount::info).collect()
+    }
+
+    /// Creates a new file in the user data directory and opens it to be written to,
+    /// truncating it if it already exists.
+    pub fn create<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult<WriteFile> {
+        let path = Self::sanitize_path(path)?;
+        // truncate right away, just like `std::fs::File::create` would
+        self.user_storage.borrow_mut().write(&path, &[])?;
+        Ok(WriteFile {
+            path,
+            buffer: Vec::new(),
+            append: false,
+            storage: self.user_storage.clone(),
+        })
+    }
+
+    /// Opens a file in the user data directory to append to, creating it if it doesn't exist yet.
+    pub fn append<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult<WriteFile> {
+        let path = Self::sanitize_path(path)?;
+        Ok(WriteFile {
+            path,
+            buffer: Vec::new(),
+            append: true,
+            storage: self.user_storage.clone(),
+        })
+    }
+
+    /// Writes `contents` to a file in the user data directory, replacing it if it already exists.
+    pub fn write<P: AsRef<path::Path>>(&mut self, path: P, contents: &[u8]) -> GameResult {
+        let path = Self::sanitize_path(path)?;
+        self.user_storage.borrow_mut().write(&path, contents)
+    }
+
+    /// Deletes a file or an empty directory from the user data directory.
+    ///
+    /// Files inside of the cache or the physical root can't be deleted.
+    pub fn delete<P: AsRef<path::Path>>(&mut self, path: P) -> GameResult {
+        let path = Self::sanitize_path(path)?;
+        self.user_storage.borrow_mut().delete(&path)
+    }
+
+    /// Checks whether the given file or directory exists in any of the mounted layers.
+    pub fn exists<P: AsRef<path::Path>>(&self, path: P) -> bool {
+        match Self::sanitize_path(path) {
+            Ok(path) => self
+                .mounts
+                .iter()
+                .any(|mount| mount.contains(&path).is_some()),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the direct children of the given directory, collected from all mounted layers.
+    pub fn read_dir<P: AsRef<path::Path>>(&self, path: P) -> GameResult<Vec<path::PathBuf>> {
+        let dir = Self::sanitize_path(path)?;
+        let mut entries = Vec::new();
+        for mount in self.mounts.iter() {
+            if let Ok(rest) = dir.strip_prefix(&mount.point) {
+                entries.extend(
+                    mount
+                        .layer
+                        .list(rest)
+                        .into_iter()
+                        .map(|entry| mount.point.join(entry)),
+                );
+            } else if let Ok(rest) = mount.point.strip_prefix(&dir) {
+                // the mount point itself is somewhere below the directory
+                if let Some(child) = rest.components().next() {
+                    entries.push(dir.join(child));
+                }
+            }
+        }
+        entries.sort();
+        entries.dedup();
+        Ok(entries
+            .into_iter()
+            .map(|entry| path::Path::new("/").join(entry))
+            .collect())
+    }
+
+    /// Returns the directory files written by the game end up in.
+    pub fn user_data_dir(&self) -> &path::Path {
+        &self.user_data_dir
+    }
+
+    /// Returns the directory meant for configuration files of the game, which backs `/config`
+    /// in the user storage on desktop platforms.
+    pub fn user_config_dir(&self) -> &path::Path {
+        &self.user_config_dir
+    }
+
+    /// Replaces the backend used for storing user files.
+    pub fn set_user_storage(&mut self, storage: Box<dyn UserStorage>) {
+        // replace it in place, so the mounted user data layer sees the new storage as well
+        *self.user_storage.borrow_mut() = storage;
     }
 
     #[cfg(not(target_os = "wasm32"))]
@@ -118,8 +382,16 @@ impl Filesystem {
         #[cfg(target_os = "ios")]
         let _ = std::env::set_current_dir(std::env::current_exe().unwrap().parent().unwrap());
 
+        let path = self.physical_path(path.as_ref())?;
+
+        let buf = load_file_inner(&path)?;
+        let bytes = io::Cursor::new(buf);
+        Ok(File { bytes })
+    }
+
+    /// Turns a sanitized path into the one handed to `miniquad::fs::load_file`.
+    fn physical_path(&self, path: &path::Path) -> GameResult<String> {
         let path = path
-            .as_ref()
             .as_os_str()
             .to_os_string()
             .into_string()
@@ -144,9 +416,36 @@ impl Filesystem {
             path
         };
 
-        let buf = load_file_inner(&path)?;
-        let bytes = io::Cursor::new(buf);
-        Ok(File { bytes })
+        Ok(path)
+    }
+
+    /// Returns the file inside of the physical root dir the given path refers to, if that's
+    /// where `open` reads it from, rather than from a layer of higher priority.
+    pub(crate) fn physical_file<P: AsRef<path::Path>>(&self, path: P) -> Option<path::PathBuf> {
+        let path = Self::sanitize_path(path).ok()?;
+        if self.resolve(&path)?.id != self.root_mount? {
+            return None;
+        }
+        Some(self.root.as_ref()?.join(path))
+    }
+
+    /// Like `open`, but instead of blocking, files which are not inside any of the mounted
+    /// layers are loaded through [`load_file_async`](fn.load_file_async.html).
+    pub(crate) fn open_async<P: AsRef<path::Path>>(
+        &mut self,
+        path: P,
+    ) -> Arc<Mutex<Option<GameResult<File>>>> {
+        let path = match Self::sanitize_path(path) {
+            Ok(path) => path,
+            Err(e) => return Arc::new(Mutex::new(Some(Err(e)))),
+        };
+        if self.resolve(&path).is_some() {
+            return Arc::new(Mutex::new(Some(self.open(&path))));
+        }
+        match self.physical_path(&path) {
+            Ok(physical_path) => load_file_async(physical_path),
+            Err(e) => Arc::new(Mutex::new(Some(Err(e)))),
+        }
     }
 
     #[cfg(target_os = "wasm32")]
@@ -163,13 +462,126 @@ impl Filesystem {
 /// Opens the given path and returns the resulting `File`
 /// in read-only mode.
 ///
-/// Checks the [embedded tar file](../conf/struct.Conf.html#method.high_dpi), if there is one, first and if the file cannot be found there
-/// continues to either load the file using the OS-filesystem, or just fail on WASM, as blocking loads
-/// are impossible there.
+/// Checks all [mounted layers](fn.mount.html) first, from the highest to the lowest priority.
+/// By default these are the [embedded tar file](../conf/struct.Conf.html#method.cache), if there
+/// is one, then the [user data directory](fn.user_data_dir.html) and then the physical root dir.
+/// If the file cannot be found there either it continues to either load the file using the
+/// OS-filesystem, or just fail on WASM, as blocking loads are impossible there.
 pub fn open<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<File> {
     ctx.filesystem.open(path)
 }
 
+/// Mounts `layer` at the virtual path `mount_point` with the given priority, returning an id
+/// which can be used to [`unmount`](fn.unmount.html) it again.
+///
+/// Layers with a higher priority shadow the files of layers with a lower one; among layers with
+/// the same priority the one mounted last wins. For reference, the embedded tar file is mounted
+/// at `/` with [`CACHE_PRIORITY`](constant.CACHE_PRIORITY.html), so to let a mod override some
+/// of the base assets it's enough to do
+///
+/// ```rust, ignore
+/// let mut bytes = Vec::new();
+/// filesystem::open(ctx, "/mods/better_trees.zip")?.read_to_end(&mut bytes)?;
+/// let layer = filesystem::ZipLayer::from_bytes(bytes)?;
+/// filesystem::mount(ctx, "/", layer, filesystem::CACHE_PRIORITY + 1)?;
+/// ```
+pub fn mount<P: AsRef<path::Path>, L: Layer + 'static>(
+    ctx: &mut Context,
+    mount_point: P,
+    layer: L,
+    priority: i32,
+) -> GameResult<MountId> {
+    ctx.filesystem.mount(mount_point, Box::new(layer), priority)
+}
+
+/// Removes a previously mounted layer, returning it.
+pub fn unmount(ctx: &mut Context, id: MountId) -> GameResult<Box<dyn Layer>> {
+    ctx.filesystem.unmount(id)
+}
+
+/// Returns information about the layer the given path would be served from by [`open`](fn.open.html),
+/// or `None` if no mounted layer contains it.
+///
+/// Note that [`open`](fn.open.html) may still succeed in the latter case, as it falls back to
+/// loading the file through miniquad, which e.g. reads the assets of the APK on Android.
+pub fn resolve<P: AsRef<path::Path>>(ctx: &Context, path: P) -> Option<MountInfo> {
+    ctx.filesystem.resolve(path)
+}
+
+/// Returns information about all mounted layers, sorted from the highest to the lowest priority.
+pub fn mounts(ctx: &Context) -> Vec<MountInfo> {
+    ctx.filesystem.mounts()
+}
+
+/// Creates a new file in the user data directory and opens it to be written to,
+/// truncating it if it already exists.
+///
+/// The contents are stored once the returned [`WriteFile`](struct.WriteFile.html) is flushed or dropped.
+pub fn create<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<WriteFile> {
+    ctx.filesystem.create(path)
+}
+
+/// Opens a file in the user data directory to append to, creating it if it doesn't exist yet.
+pub fn append<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult<WriteFile> {
+    ctx.filesystem.append(path)
+}
+
+/// Writes `contents` to a file in the user data directory, replacing it if it already exists.
+pub fn write<P: AsRef<path::Path>, C: AsRef<[u8]>>(
+    ctx: &mut Context,
+    path: P,
+    contents: C,
+) -> GameResult {
+    ctx.filesystem.write(path, contents.as_ref())
+}
+
+/// Deletes a file or an empty directory from the user data directory.
+pub fn delete<P: AsRef<path::Path>>(ctx: &mut Context, path: P) -> GameResult {
+    ctx.filesystem.delete(path)
+}
+
+/// Checks whether the given path exists.
+pub fn exists<P: AsRef<path::Path>>(ctx: &Context, path: P) -> bool {
+    ctx.filesystem.exists(path)
+}
+
+/// Returns an iterator over all files and directories directly inside of the given directory,
+/// merged from all mounted layers.
+///
+/// Note that on WASM only archives and the user data directory can be listed.
+pub fn read_dir<P: AsRef<path::Path>>(
+    ctx: &Context,
+    path: P,
+) -> GameResult<Box<dyn Iterator<Item = path::PathBuf>>> {
+    let entries = ctx.filesystem.read_dir(path)?;
+    Ok(Box::new(entries.into_iter()))
+}
+
+/// Returns the directory files written by the game end up in.
+///
+/// On platforms without a user data directory, like WASM, this is a purely virtual path.
+pub fn user_data_dir(ctx: &Context) -> &path::Path {
+    ctx.filesystem.user_data_dir()
+}
+
+/// Returns the directory meant for configuration files of the game.
+///
+/// Files written to `/config` (e.g. `filesystem::write(ctx, "/config/settings.toml", ...)`) end
+/// up here with the default storage on desktop platforms. On platforms without a user config
+/// directory, like WASM, this is a purely virtual path and `/config` is kept in the user storage
+/// like everything else.
+pub fn user_config_dir(ctx: &Context) -> &path::Path {
+    ctx.filesystem.user_config_dir()
+}
+
+/// Replaces the backend used for storing user files.
+///
+/// By default files are written to [`user_data_dir`](fn.user_data_dir.html) on desktop platforms
+/// and kept in memory everywhere else. Use this to plug in a persistent key-value store on WASM.
+pub fn set_user_storage(ctx: &mut Context, storage: Box<dyn UserStorage>) {
+    ctx.filesystem.set_user_storage(storage)
+}
+
 /// Loads a file from the path returning an `Option` that will be `Some` once it has been loaded (or loading it failed).
 /// Will use filesystem on PC and Android and a http request on WASM.
 ///
@@ -204,3 +616,138 @@ pub fn load_file_async<P: AsRef<path::Path>>(path: P) -> Arc<Mutex<Option<GameRe
 
     contents
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Filesystem, MemoryStorage, TarLayer, ZipLayer};
+    use crate::conf::Conf;
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+
+    fn tar_layer(files: &[(&str, &str)]) -> TarLayer {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        TarLayer::from_bytes(&builder.into_inner().unwrap()).unwrap()
+    }
+
+    fn zip_layer(files: &[(&str, &str)]) -> ZipLayer {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            writer
+                .start_file(*path, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        ZipLayer::from_bytes(writer.finish().unwrap().into_inner()).unwrap()
+    }
+
+    fn filesystem() -> Filesystem {
+        let mut fs = Filesystem::new(&Conf::default());
+        fs.set_user_storage(Box::new(MemoryStorage::new()));
+        fs
+    }
+
+    fn read(fs: &mut Filesystem, path: &str) -> String {
+        let mut contents = String::new();
+        let _ = fs
+            .open(path)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    #[test]
+    fn mount_priority() {
+        let mut fs = filesystem();
+        let base = fs
+            .mount("/", Box::new(tar_layer(&[("img/a.png", "base")])), 1)
+            .unwrap();
+        let m = fs
+            .mount("/", Box::new(zip_layer(&[("img/a.png", "mod")])), 2)
+            .unwrap();
+        assert_eq!(read(&mut fs, "/img/a.png"), "mod");
+        assert_eq!(fs.resolve("/img/a.png").unwrap().id, m);
+        assert_eq!(fs.resolve("img/a.png").unwrap().priority, 2);
+
+        // among the same priority the layer mounted last wins
+        let late = fs
+            .mount("/", Box::new(tar_layer(&[("img/a.png", "late")])), 1)
+            .unwrap();
+        let _ = fs.unmount(m).unwrap();
+        assert_eq!(read(&mut fs, "/img/a.png"), "late");
+        let _ = fs.unmount(late).unwrap();
+        assert_eq!(fs.resolve("/img/a.png").unwrap().id, base);
+        assert!(fs.unmount(late).is_err());
+    }
+
+    #[test]
+    fn mount_points() {
+        let mut fs = filesystem();
+        let _ = fs
+            .mount("/mods/trees", Box::new(tar_layer(&[("oak.png", "oak")])), 0)
+            .unwrap();
+        assert_eq!(read(&mut fs, "/mods/trees/oak.png"), "oak");
+        let info = fs.resolve("/mods/trees/oak.png").unwrap();
+        assert_eq!(info.mount_point, PathBuf::from("/mods/trees"));
+        assert!(fs.resolve("/oak.png").is_none());
+        assert!(fs.exists("/mods/trees"));
+        assert_eq!(
+            fs.read_dir("/mods").unwrap(),
+            [PathBuf::from("/mods/trees")]
+        );
+        assert!(fs.open("/../oak.png").is_err());
+    }
+
+    #[test]
+    fn directories_dont_shadow_files() {
+        let mut fs = filesystem();
+        let _ = fs
+            .mount("/", Box::new(tar_layer(&[("a.png", "file")])), 1)
+            .unwrap();
+        let dir = fs
+            .mount("/", Box::new(zip_layer(&[("a.png/readme.txt", "dir")])), 2)
+            .unwrap();
+        assert_eq!(read(&mut fs, "/a.png"), "file");
+        assert_eq!(fs.resolve("/a.png").unwrap().priority, 1);
+        assert!(fs.exists("/a.png"));
+        assert_eq!(fs.resolve("/a.png/readme.txt").unwrap().id, dir);
+        assert_eq!(fs.read_dir("/").unwrap(), [PathBuf::from("/a.png")]);
+    }
+
+    #[test]
+    fn user_files_are_mounted() {
+        let mut fs = filesystem();
+        fs.write("/saves/slot1", b"progress").unwrap();
+        assert_eq!(read(&mut fs, "/saves/slot1"), "progress");
+        assert_eq!(fs.resolve("/saves/slot1").unwrap().description, "user data");
+        fs.delete("/saves/slot1").unwrap();
+        assert!(!fs.exists("/saves"));
+    }
+
+    #[test]
+    fn physical_files_are_only_watched_if_served() {
+        let root = std::env::temp_dir().join(format!("good-web-game-test-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.png"), "disk").unwrap();
+        std::fs::write(root.join("b.png"), "disk").unwrap();
+
+        let mut fs = Filesystem::new(&Conf::default().physical_root_dir(Some(root.clone())));
+        fs.set_user_storage(Box::new(MemoryStorage::new()));
+        let _ = fs
+            .mount("/", Box::new(tar_layer(&[("a.png", "cache")])), 1)
+            .unwrap();
+        assert_eq!(fs.physical_file("/a.png"), None);
+        assert_eq!(fs.physical_file("/b.png"), Some(root.join("b.png")));
+        assert_eq!(fs.physical_file("/c.png"), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
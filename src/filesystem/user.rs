@@ -0,0 +1,353 @@
+//! Writable storage for user data, such as save games and settings.
+//!
+//! On desktop platforms this is a directory per game inside of the OS-specific user data
+//! directory. On WASM (as well as Android and iOS) there is no such directory, so an in-memory
+//! key-value store is used by default. It can be swapped out for something persistent
+//! (like a wrapper around `localStorage`, e.g. [`quad-storage`](https://github.com/optozorax/quad-storage))
+//! through [`filesystem::set_user_storage`](fn.set_user_storage.html).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{GameError, GameResult};
+
+/// A backend able to persist user files.
+///
+/// All paths handed to a `UserStorage` are relative and free of `..` components.
+/// Directories are implicit: writing `saves/slot1` makes `saves` show up in
+/// [`list`](#tymethod.list) without it ever being created explicitly.
+pub trait UserStorage: fmt::Debug {
+    /// Reads the whole file at `path`.
+    fn read(&self, path: &Path) -> GameResult<Vec<u8>>;
+
+    /// Writes `bytes` to the file at `path`, replacing its contents if it already exists.
+    fn write(&mut self, path: &Path, bytes: &[u8]) -> GameResult;
+
+    /// Appends `bytes` to the file at `path`, creating it if necessary.
+    fn append(&mut self, path: &Path, bytes: &[u8]) -> GameResult {
+        let mut contents = if self.exists(path) {
+            self.read(path)?
+        } else {
+            Vec::new()
+        };
+        contents.extend_from_slice(bytes);
+        self.write(path, &contents)
+    }
+
+    /// Deletes the file (or the empty directory) at `path`.
+    fn delete(&mut self, path: &Path) -> GameResult;
+
+    /// Returns whether there is a file or directory at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Returns whether there is a file at `path`.
+    ///
+    /// By default anything that exists and has no children counts as a file.
+    fn is_file(&self, path: &Path) -> bool {
+        self.exists(path) && self.list(path).map_or(true, |entries| entries.is_empty())
+    }
+
+    /// Returns the direct children of the directory at `dir`, as paths relative to the storage root.
+    fn list(&self, dir: &Path) -> GameResult<Vec<PathBuf>>;
+}
+
+/// A `UserStorage` keeping everything in memory.
+///
+/// This is the default on platforms without a user data directory, meaning that files written
+/// to it do not survive a restart of the game. Directories only exist as long as there are files
+/// inside of them, so there are never any empty ones and deleting a directory always fails.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryStorage {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    /// Creates a new, empty `MemoryStorage`.
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+fn not_found(path: &Path) -> GameError {
+    GameError::ResourceNotFound(path.to_string_lossy().into_owned(), Vec::new())
+}
+
+impl UserStorage for MemoryStorage {
+    fn read(&self, path: &Path) -> GameResult<Vec<u8>> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    fn write(&mut self, path: &Path, bytes: &[u8]) -> GameResult {
+        self.files.insert(path.to_path_buf(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn append(&mut self, path: &Path, bytes: &[u8]) -> GameResult {
+        self.files
+            .entry(path.to_path_buf())
+            .or_default()
+            .extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &Path) -> GameResult {
+        if self.files.remove(path).is_some() {
+            Ok(())
+        } else if self.exists(path) {
+            Err(GameError::FilesystemError(format!(
+                "Directory {} is not empty",
+                path.display()
+            )))
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file.starts_with(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn list(&self, dir: &Path) -> GameResult<Vec<PathBuf>> {
+        let mut entries: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter_map(|file| file.strip_prefix(dir).ok())
+            .filter_map(|rest| rest.components().next())
+            .map(|child| dir.join(child))
+            .collect();
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+/// A `UserStorage` backed by a directory of the OS filesystem.
+///
+/// Optionally the `config` directory of the storage is kept in a separate directory of the OS
+/// filesystem, see [`with_config_dir`](#method.with_config_dir).
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct DirectoryStorage {
+    root: PathBuf,
+    config_root: Option<PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DirectoryStorage {
+    /// Creates a new `DirectoryStorage` keeping its files inside of `root`.
+    ///
+    /// The directory itself is only created once the first file is written.
+    pub fn new<P: Into<PathBuf>>(root: P) -> DirectoryStorage {
+        DirectoryStorage {
+            root: root.into(),
+            config_root: None,
+        }
+    }
+
+    /// Keeps the files inside of the `config` directory of the storage in `config_root` instead
+    /// of inside of the root directory.
+    pub fn with_config_dir<P: Into<PathBuf>>(mut self, config_root: P) -> DirectoryStorage {
+        self.config_root = Some(config_root.into());
+        self
+    }
+
+    /// Returns the directory this storage writes to.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the OS path of a path inside of the storage.
+    fn full_path(&self, path: &Path) -> PathBuf {
+        match (&self.config_root, path.strip_prefix(CONFIG_DIR)) {
+            (Some(config_root), Ok(rest)) => config_root.join(rest),
+            _ => self.root.join(path),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl UserStorage for DirectoryStorage {
+    fn read(&self, path: &Path) -> GameResult<Vec<u8>> {
+        Ok(std::fs::read(self.full_path(path))?)
+    }
+
+    fn write(&mut self, path: &Path, bytes: &[u8]) -> GameResult {
+        let full_path = self.full_path(path);
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(std::fs::write(full_path, bytes)?)
+    }
+
+    fn append(&mut self, path: &Path, bytes: &[u8]) -> GameResult {
+        use std::io::Write;
+
+        let full_path = self.full_path(path);
+        if let Some(parent) = full_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(full_path)?;
+        Ok(file.write_all(bytes)?)
+    }
+
+    fn delete(&mut self, path: &Path) -> GameResult {
+        let full_path = self.full_path(path);
+        if full_path.is_dir() {
+            Ok(std::fs::remove_dir(full_path)?)
+        } else {
+            Ok(std::fs::remove_file(full_path)?)
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.full_path(path).exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.full_path(path).is_file()
+    }
+
+    fn list(&self, dir: &Path) -> GameResult<Vec<PathBuf>> {
+        let mut entries = Vec::new();
+        // the config dir shows up in the root, wherever it's actually kept
+        if dir.as_os_str().is_empty() && self.exists(Path::new(CONFIG_DIR)) {
+            entries.push(PathBuf::from(CONFIG_DIR));
+        }
+        let full_path = self.full_path(dir);
+        if full_path.is_dir() {
+            for entry in std::fs::read_dir(full_path)? {
+                entries.push(dir.join(entry?.file_name()));
+            }
+        }
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+}
+
+/// The directory of the user storage backed by the user config directory on desktop platforms.
+pub(crate) const CONFIG_DIR: &str = "config";
+
+/// Returns the directories used for user data and user config files, in that order.
+///
+/// Follows the usual conventions of each desktop OS. Platforms without such directories
+/// get virtual paths, only used for display purposes.
+pub(crate) fn user_dirs(game_id: &str) -> (PathBuf, PathBuf) {
+    #[cfg(not(any(target_arch = "wasm32", target_os = "ios", target_os = "android",)))]
+    {
+        let env_dir = |var: &str| std::env::var_os(var).map(PathBuf::from);
+        let home = env_dir("HOME").unwrap_or_else(|| PathBuf::from("."));
+
+        #[cfg(target_os = "windows")]
+        let (data, config) = {
+            let app_data = env_dir("APPDATA").unwrap_or(home);
+            let base = app_data.join(game_id);
+            (base.join("data"), base.join("config"))
+        };
+        #[cfg(target_os = "macos")]
+        let (data, config) = {
+            let base = home
+                .join("Library")
+                .join("Application Support")
+                .join(game_id);
+            (base.clone(), base)
+        };
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        let (data, config) = {
+            let data = env_dir("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local/share"));
+            let config = env_dir("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config"));
+            (data.join(game_id), config.join(game_id))
+        };
+
+        (data, config)
+    }
+    #[cfg(any(target_arch = "wasm32", target_os = "ios", target_os = "android",))]
+    {
+        let base = PathBuf::from("/").join(game_id);
+        (base.join("data"), base.join("config"))
+    }
+}
+
+/// Creates the default storage of the current platform.
+pub(crate) fn default_storage(
+    user_data_dir: &Path,
+    user_config_dir: &Path,
+) -> Box<dyn UserStorage> {
+    #[cfg(not(any(target_arch = "wasm32", target_os = "ios", target_os = "android",)))]
+    {
+        Box::new(DirectoryStorage::new(user_data_dir).with_config_dir(user_config_dir))
+    }
+    #[cfg(any(target_arch = "wasm32", target_os = "ios", target_os = "android",))]
+    {
+        let _ = (user_data_dir, user_config_dir);
+        Box::new(MemoryStorage::new())
+    }
+}
+
+pub(crate) fn to_io_error(e: GameError) -> io::Error {
+    match e {
+        GameError::IOError(e) => e,
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryStorage, UserStorage};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn memory_storage_files() {
+        let mut storage = MemoryStorage::new();
+        let path = Path::new("saves/slot1");
+        assert!(storage.read(path).is_err());
+
+        storage.write(path, b"level 1").unwrap();
+        assert_eq!(storage.read(path).unwrap(), b"level 1");
+        storage.write(path, b"level 2").unwrap();
+        storage.append(path, b", 3 lives").unwrap();
+        assert_eq!(storage.read(path).unwrap(), b"level 2, 3 lives");
+        storage.append(Path::new("log"), b"started").unwrap();
+        assert_eq!(storage.read(Path::new("log")).unwrap(), b"started");
+
+        assert!(storage.exists(path));
+        assert!(storage.exists(Path::new("saves")));
+        assert!(!storage.exists(Path::new("sav")));
+    }
+
+    #[test]
+    fn memory_storage_directories() {
+        let mut storage = MemoryStorage::new();
+        for path in ["saves/slot1", "saves/slot2", "saves/old/slot1", "settings"] {
+            storage.write(Path::new(path), &[]).unwrap();
+        }
+        assert_eq!(
+            storage.list(Path::new("")).unwrap(),
+            [PathBuf::from("saves"), PathBuf::from("settings")]
+        );
+        assert_eq!(
+            storage.list(Path::new("saves")).unwrap(),
+            [
+                PathBuf::from("saves/old"),
+                PathBuf::from("saves/slot1"),
+                PathBuf::from("saves/slot2")
+            ]
+        );
+        assert!(storage.list(Path::new("missing")).unwrap().is_empty());
+
+        // directories only go away with their last file
+        assert!(storage.delete(Path::new("saves/old")).is_err());
+        storage.delete(Path::new("saves/old/slot1")).unwrap();
+        assert!(!storage.exists(Path::new("saves/old")));
+        assert!(storage.delete(Path::new("saves/old")).is_err());
+        assert!(storage.exists(Path::new("saves")));
+    }
+}
@@ -0,0 +1,291 @@
+//! The layers the virtual filesystem is made of.
+//!
+//! Every layer is mounted at a virtual path with a priority. When looking up a file the
+//! mounted layers are checked from the highest to the lowest priority and the first one
+//! containing the file serves it, which makes it easy to e.g. let a mod override some of
+//! the base assets of a game.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::user::UserStorage;
+use crate::{GameError, GameResult};
+
+/// Something that can be mounted into the virtual filesystem, see [`mount`](fn.mount.html).
+///
+/// All paths handed to a `Layer` are relative to its mount point and free of `..` components.
+pub trait Layer: fmt::Debug {
+    /// A short human readable description of the layer, used by [`resolve`](fn.resolve.html).
+    fn describe(&self) -> String;
+
+    /// Reads the whole file at `path`.
+    fn read(&mut self, path: &Path) -> GameResult<Vec<u8>>;
+
+    /// Returns whether there is a file or directory at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Returns whether there is a file at `path`.
+    ///
+    /// By default anything that exists and has no children counts as a file.
+    fn is_file(&self, path: &Path) -> bool {
+        self.exists(path) && self.list(path).is_empty()
+    }
+
+    /// Returns the direct children of the directory at `dir`, as paths relative to the layer root.
+    fn list(&self, dir: &Path) -> Vec<PathBuf>;
+}
+
+/// Returns the direct children of `dir` from a list of (possibly deeply nested) file paths.
+fn children<'a, I: Iterator<Item = &'a Path>>(paths: I, dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = paths
+        .filter_map(|file| file.strip_prefix(dir).ok())
+        .filter_map(|rest| rest.components().next())
+        .map(|child| dir.join(child))
+        .collect();
+    entries.sort();
+    entries.dedup();
+    entries
+}
+
+/// A tar archive, unpacked into memory.
+///
+/// This is what the cache set through [`Conf::cache`](../conf/struct.Conf.html#method.cache)
+/// gets mounted as.
+#[derive(Debug, Clone)]
+pub struct TarLayer {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl TarLayer {
+    /// Unpacks the given tar archive.
+    pub fn from_bytes(bytes: &[u8]) -> GameResult<TarLayer> {
+        let mut files = HashMap::new();
+        let mut archive = tar::Archive::new(bytes);
+
+        for file in archive.entries()? {
+            let mut file = file?;
+            let filename = PathBuf::from(file.path()?);
+            let mut buf = vec![];
+
+            file.read_to_end(&mut buf)?;
+            if !buf.is_empty() {
+                files.insert(filename, buf);
+            }
+        }
+
+        Ok(TarLayer { files })
+    }
+}
+
+impl Layer for TarLayer {
+    fn describe(&self) -> String {
+        format!("tar archive ({} files)", self.files.len())
+    }
+
+    fn read(&mut self, path: &Path) -> GameResult<Vec<u8>> {
+        self.files.get(path).cloned().ok_or_else(|| {
+            GameError::ResourceNotFound(path.to_string_lossy().into_owned(), Vec::new())
+        })
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.keys().any(|file| file.starts_with(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        children(self.files.keys().map(PathBuf::as_path), dir)
+    }
+}
+
+/// A zip archive, which is decompressed file by file once a file is actually read.
+pub struct ZipLayer {
+    archive: zip::ZipArchive<io::Cursor<Vec<u8>>>,
+}
+
+impl fmt::Debug for ZipLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<ZipLayer: {} files>", self.archive.len())
+    }
+}
+
+impl ZipLayer {
+    /// Opens the given zip archive.
+    pub fn from_bytes(bytes: Vec<u8>) -> GameResult<ZipLayer> {
+        let archive = zip::ZipArchive::new(io::Cursor::new(bytes))?;
+        Ok(ZipLayer { archive })
+    }
+
+    /// Zip archives always use `/` as separator, no matter the platform.
+    fn zip_name(path: &Path) -> String {
+        path.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Layer for ZipLayer {
+    fn describe(&self) -> String {
+        format!("zip archive ({} files)", self.archive.len())
+    }
+
+    fn read(&mut self, path: &Path) -> GameResult<Vec<u8>> {
+        let name = Self::zip_name(path);
+        let mut file = self.archive.by_name(&name)?;
+        let mut buf = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.archive
+            .file_names()
+            .any(|name| Path::new(name).starts_with(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        let name = Self::zip_name(path);
+        self.archive.file_names().any(|file_name| file_name == name)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        children(self.archive.file_names().map(Path::new), dir)
+    }
+}
+
+/// A directory of the OS filesystem.
+///
+/// The [`physical_root_dir`](../conf/struct.Conf.html#structfield.physical_root_dir) gets
+/// mounted as one of these.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct DirectoryLayer {
+    root: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DirectoryLayer {
+    /// Creates a new layer serving the files inside of `root`.
+    pub fn new<P: Into<PathBuf>>(root: P) -> DirectoryLayer {
+        DirectoryLayer { root: root.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Layer for DirectoryLayer {
+    fn describe(&self) -> String {
+        format!("directory {}", self.root.display())
+    }
+
+    fn read(&mut self, path: &Path) -> GameResult<Vec<u8>> {
+        Ok(std::fs::read(self.root.join(path))?)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.root.join(path).exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(self.root.join(dir))
+            .map(|read_dir| {
+                read_dir
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| dir.join(entry.file_name()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort();
+        entries
+    }
+}
+
+/// Exposes the user storage to the virtual filesystem, so that written files can be read back.
+#[derive(Debug)]
+pub(crate) struct UserLayer {
+    pub(crate) storage: Rc<RefCell<Box<dyn UserStorage>>>,
+}
+
+impl Layer for UserLayer {
+    fn describe(&self) -> String {
+        "user data".to_string()
+    }
+
+    fn read(&mut self, path: &Path) -> GameResult<Vec<u8>> {
+        self.storage.borrow().read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.storage.borrow().exists(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.storage.borrow().is_file(path)
+    }
+
+    fn list(&self, dir: &Path) -> Vec<PathBuf> {
+        self.storage.borrow().list(dir).unwrap_or_default()
+    }
+}
+
+/// Identifies a mounted layer, returned by [`mount`](fn.mount.html).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MountId(pub(crate) usize);
+
+/// Information about a mounted layer, as returned by [`resolve`](fn.resolve.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    /// The id the layer was mounted with.
+    pub id: MountId,
+    /// The virtual path the layer is mounted at.
+    pub mount_point: PathBuf,
+    /// The priority the layer was mounted with.
+    pub priority: i32,
+    /// What kind of layer this is, see [`Layer::describe`](trait.Layer.html#tymethod.describe).
+    pub description: String,
+}
+
+#[derive(Debug)]
+pub(crate) struct Mount {
+    pub(crate) id: MountId,
+    pub(crate) point: PathBuf,
+    pub(crate) priority: i32,
+    pub(crate) layer: Box<dyn Layer>,
+}
+
+impl Mount {
+    /// Returns the path relative to this mount, if the mount contains the given file or
+    /// directory.
+    pub(crate) fn contains<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.point)
+            .ok()
+            .filter(|rest| self.layer.exists(rest))
+    }
+
+    /// Returns the path relative to this mount, if the mount contains the given file.
+    pub(crate) fn contains_file<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.point)
+            .ok()
+            .filter(|rest| self.layer.is_file(rest))
+    }
+
+    pub(crate) fn info(&self) -> MountInfo {
+        MountInfo {
+            id: self.id,
+            mount_point: Path::new("/").join(&self.point),
+            priority: self.priority,
+            description: self.layer.describe(),
+        }
+    }
+}
@@ -1,6 +1,10 @@
 mod camera;
 
+pub mod animation;
 pub mod matrix_transform_2d;
+pub mod particles;
 pub mod scene;
+pub mod tilemap;
+pub mod tween;
 
 pub use self::camera::Camera;
@@ -0,0 +1,644 @@
+//! Frame by frame animation of sprite sheets.
+//!
+//! A [`SpriteSheet`](struct.SpriteSheet.html) knows where each frame is located in its image and
+//! holds named [`Clip`](struct.Clip.html)s, sequences of frames with a duration each. An
+//! [`AnimationPlayer`](struct.AnimationPlayer.html) plays one clip at a time, advancing it by
+//! `timer::delta` on every update, and tells which part of the image to draw:
+//!
+//! ```rust, ignore
+//! let mut sheet = SpriteSheet::from_grid(Image::new(ctx, quad_ctx, "/player.png")?, 32, 32);
+//! sheet.add_clip("run", Clip::new(8..16, Duration::from_millis(80), PlayMode::Loop))?;
+//! let mut player = AnimationPlayer::new();
+//! player.play(&sheet, "run");
+//!
+//! // in `update`
+//! player.update(ctx);
+//! for event in player.events() {
+//!     if let AnimationEvent::Marker { name, .. } = event {
+//!         // play a footstep sound
+//!     }
+//! }
+//!
+//! // in `draw`
+//! graphics::draw(ctx, quad_ctx, sheet.image(), player.draw_param(&sheet).dest(position))?;
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::graphics::{self, DrawParam, Image, Rect, TextureAtlas};
+use crate::{timer, Context, GameError, GameResult};
+
+/// How a [`Clip`](struct.Clip.html) continues once its last frame is over.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PlayMode {
+    /// Starts over from the first frame.
+    #[default]
+    Loop,
+    /// Plays backwards until the first frame, then forwards again, and so on.
+    PingPong,
+    /// Stops on the last frame.
+    Once,
+}
+
+/// A sequence of frames of a [`SpriteSheet`](struct.SpriteSheet.html), each shown for its own
+/// duration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    /// Indices into the frames of the sheet.
+    frames: Vec<usize>,
+    durations: Vec<Duration>,
+    mode: PlayMode,
+    /// Named markers, emitted as events once the frame at the given position is entered.
+    markers: Vec<(usize, String)>,
+}
+
+impl Clip {
+    /// Creates a clip showing the given frames for the same duration each.
+    pub fn new<I: IntoIterator<Item = usize>>(
+        frames: I,
+        frame_duration: Duration,
+        mode: PlayMode,
+    ) -> Self {
+        let frames: Vec<usize> = frames.into_iter().collect();
+        let durations = vec![frame_duration; frames.len()];
+        Clip {
+            frames,
+            durations,
+            mode,
+            markers: Vec::new(),
+        }
+    }
+
+    /// Creates a clip from pairs of frame index and duration.
+    pub fn with_durations<I: IntoIterator<Item = (usize, Duration)>>(
+        frames: I,
+        mode: PlayMode,
+    ) -> Self {
+        let (frames, durations) = frames.into_iter().unzip();
+        Clip {
+            frames,
+            durations,
+            mode,
+            markers: Vec::new(),
+        }
+    }
+
+    /// Adds a named marker, which makes the [`AnimationPlayer`](struct.AnimationPlayer.html)
+    /// emit an [`AnimationEvent::Marker`](enum.AnimationEvent.html#variant.Marker) every time the
+    /// `position`th frame of the clip is entered, e.g. to sync footstep sounds.
+    pub fn add_marker(&mut self, position: usize, name: &str) -> &mut Self {
+        self.markers.push((position, name.to_string()));
+        self
+    }
+
+    /// Returns the indices of the frames of the clip, in the order they're shown in.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames
+    }
+
+    /// Returns how long each frame is shown.
+    pub fn durations(&self) -> &[Duration] {
+        &self.durations
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: PlayMode) {
+        self.mode = mode;
+    }
+
+    /// Returns the duration of a single pass through all frames.
+    pub fn duration(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+/// An image divided into frames, along with named clips of these frames.
+#[derive(Debug, Clone)]
+pub struct SpriteSheet {
+    image: Image,
+    /// The frames in pixels.
+    frames: Vec<Rect>,
+    clips: HashMap<String, Clip>,
+}
+
+impl SpriteSheet {
+    /// Creates a sheet from frames of the given size, laid out in a grid and numbered row by row
+    /// starting at the top left.
+    pub fn from_grid(image: Image, frame_width: u16, frame_height: u16) -> Self {
+        let columns = image.width() / frame_width.max(1);
+        let rows = image.height() / frame_height.max(1);
+        let frames = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| {
+                    Rect::new(
+                        f32::from(column * frame_width),
+                        f32::from(row * frame_height),
+                        f32::from(frame_width),
+                        f32::from(frame_height),
+                    )
+                })
+            })
+            .collect();
+        Self::from_frames(image, frames)
+    }
+
+    /// Creates a sheet from the regions of an atlas, numbered in the order of
+    /// `TextureAtlas::regions`.
+    pub fn from_atlas(atlas: &TextureAtlas) -> Self {
+        let frames = atlas.regions().map(|(_, rect)| rect).collect();
+        Self::from_frames(atlas.image().clone(), frames)
+    }
+
+    /// Creates a sheet from frames given in pixels.
+    pub fn from_frames(image: Image, frames: Vec<Rect>) -> Self {
+        SpriteSheet {
+            image,
+            frames,
+            clips: HashMap::new(),
+        }
+    }
+
+    /// Loads a sprite sheet exported by Aseprite as JSON (as hash or array), turning its frame
+    /// tags into clips with the durations set in Aseprite.
+    ///
+    /// The image is loaded from the path stored in the sheet, relative to the sheet itself.
+    pub fn from_aseprite<P: AsRef<Path>>(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        path: P,
+    ) -> GameResult<Self> {
+        let path = path.as_ref();
+        let (image, frames, tags) = graphics::load_sprite_sheet(ctx, quad_ctx, path)?;
+        let durations: Vec<Duration> = frames
+            .iter()
+            .map(|frame| Duration::from_millis(u64::from(frame.duration.unwrap_or(100))))
+            .collect();
+        let mut sheet = Self::from_frames(image, frames.into_iter().map(|f| f.rect).collect());
+
+        for tag in tags {
+            if tag.from > tag.to || tag.to >= durations.len() {
+                return Err(GameError::ResourceLoadError(format!(
+                    "Invalid sprite sheet {}: tag '{}' is out of range",
+                    path.display(),
+                    tag.name
+                )));
+            }
+            let mut indices: Vec<usize> = (tag.from..=tag.to).collect();
+            let mode = match tag.direction.as_str() {
+                "pingpong" => PlayMode::PingPong,
+                "pingpong_reverse" => {
+                    indices.reverse();
+                    PlayMode::PingPong
+                }
+                "reverse" => {
+                    indices.reverse();
+                    PlayMode::Loop
+                }
+                _ => PlayMode::Loop,
+            };
+            let clip = Clip::with_durations(indices.into_iter().map(|i| (i, durations[i])), mode);
+            sheet.add_clip(&tag.name, clip)?;
+        }
+
+        Ok(sheet)
+    }
+
+    /// Adds a clip, replacing any clip of the same name.
+    ///
+    /// Fails if the clip refers to frames the sheet doesn't have.
+    pub fn add_clip(&mut self, name: &str, clip: Clip) -> GameResult<&mut Self> {
+        if let Some(frame) = clip
+            .frames
+            .iter()
+            .find(|frame| **frame >= self.frames.len())
+        {
+            return Err(GameError::RenderError(format!(
+                "Clip '{}' shows frame {}, but the sprite sheet only has {} frames",
+                name,
+                frame,
+                self.frames.len()
+            )));
+        }
+        self.clips.insert(name.to_string(), clip);
+        Ok(self)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+
+    pub fn clip_mut(&mut self, name: &str) -> Option<&mut Clip> {
+        self.clips.get_mut(name)
+    }
+
+    /// Returns the names of all clips, in no particular order.
+    pub fn clip_names(&self) -> impl Iterator<Item = &str> {
+        self.clips.keys().map(|name| name.as_str())
+    }
+
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns the number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the given frame in pixels.
+    pub fn frame(&self, index: usize) -> Option<Rect> {
+        self.frames.get(index).copied()
+    }
+
+    /// Returns the given frame relative to the size of the image, as expected by
+    /// `DrawParam::src`, or the whole image if there's no such frame.
+    pub fn src(&self, index: usize) -> Rect {
+        match self.frame(index) {
+            Some(rect) => {
+                let width = f32::from(self.image.width());
+                let height = f32::from(self.image.height());
+                Rect::new(
+                    rect.x / width,
+                    rect.y / height,
+                    rect.w / width,
+                    rect.h / height,
+                )
+            }
+            None => Rect::one(),
+        }
+    }
+}
+
+/// Something that happened while advancing an [`AnimationPlayer`](struct.AnimationPlayer.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnimationEvent {
+    /// The `position`th frame of the clip, which is frame `frame` of the sheet, was entered.
+    Frame { position: usize, frame: usize },
+    /// A frame with a marker was entered, see [`Clip::add_marker`](struct.Clip.html#method.add_marker).
+    Marker { name: String, position: usize },
+    /// The clip started over, or turned around when playing in `PlayMode::PingPong`.
+    Looped,
+    /// The last frame of a clip played in `PlayMode::Once` is over.
+    Finished,
+}
+
+/// Plays the clips of a [`SpriteSheet`](struct.SpriteSheet.html).
+///
+/// See the [module level docs](index.html) for an example.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    name: Option<String>,
+    clip: Option<Clip>,
+    /// The position inside of the clip.
+    position: usize,
+    /// How long the current frame has been shown.
+    elapsed: Duration,
+    forward: bool,
+    playing: bool,
+    finished: bool,
+    speed: f32,
+    /// The events emitted by the last update.
+    events: Vec<AnimationEvent>,
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        AnimationPlayer {
+            name: None,
+            clip: None,
+            position: 0,
+            elapsed: Duration::ZERO,
+            forward: true,
+            playing: false,
+            finished: false,
+            speed: 1.0,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl AnimationPlayer {
+    /// Creates a player without a clip.
+    pub fn new() -> Self {
+        AnimationPlayer::default()
+    }
+
+    /// Starts playing the clip of the given name from its first frame, unless it's already
+    /// playing, so that this can be called on every update.
+    ///
+    /// Returns `false` if the sheet has no such clip.
+    pub fn play(&mut self, sheet: &SpriteSheet, name: &str) -> bool {
+        if self.playing && self.name.as_deref() == Some(name) {
+            return true;
+        }
+        match sheet.clip(name) {
+            Some(clip) => {
+                self.name = Some(name.to_string());
+                self.clip = Some(clip.clone());
+                self.restart();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Plays the current clip from its first frame again.
+    pub fn restart(&mut self) {
+        self.position = 0;
+        self.elapsed = Duration::ZERO;
+        self.forward = true;
+        self.finished = false;
+        self.playing = self.clip.is_some();
+        self.events.clear();
+        if let Some(clip) = self.clip.as_ref().filter(|clip| !clip.is_empty()) {
+            frame_events(clip, 0, &mut self.events);
+        }
+    }
+
+    /// Stops advancing, staying on the current frame.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Continues advancing after `pause`.
+    pub fn resume(&mut self) {
+        self.playing = self.clip.is_some() && !self.finished;
+    }
+
+    /// Returns whether the clip is advancing, meaning it's neither paused nor finished.
+    pub fn playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns whether a clip played in `PlayMode::Once` has reached its end.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns the name of the current clip.
+    pub fn clip_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the factor the elapsed time is multiplied with.
+    ///
+    /// Default: `1.0`
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Advances the clip by the time passed since the last frame, see `timer::delta`.
+    pub fn update(&mut self, ctx: &Context) {
+        self.advance(timer::delta(ctx));
+    }
+
+    /// Advances the clip by the given time.
+    pub fn advance(&mut self, dt: Duration) {
+        self.events.clear();
+        if !self.playing {
+            return;
+        }
+        let clip = match self.clip.take() {
+            Some(clip) if !clip.is_empty() && clip.duration() > Duration::ZERO => clip,
+            clip => {
+                self.clip = clip;
+                return;
+            }
+        };
+        self.step(&clip, dt);
+        self.clip = Some(clip);
+    }
+
+    fn step(&mut self, clip: &Clip, dt: Duration) {
+        self.elapsed += dt.mul_f64(f64::from(self.speed));
+        while self.elapsed >= clip.durations[self.position] {
+            self.elapsed -= clip.durations[self.position];
+            let last = clip.len() - 1;
+            match clip.mode {
+                PlayMode::Loop => {
+                    if self.position == last {
+                        self.position = 0;
+                        self.events.push(AnimationEvent::Looped);
+                    } else {
+                        self.position += 1;
+                    }
+                }
+                PlayMode::PingPong => {
+                    let turn = if self.forward {
+                        self.position == last
+                    } else {
+                        self.position == 0
+                    };
+                    if turn {
+                        self.forward = !self.forward;
+                        self.events.push(AnimationEvent::Looped);
+                    }
+                    if last > 0 {
+                        if self.forward {
+                            self.position += 1;
+                        } else {
+                            self.position -= 1;
+                        }
+                    }
+                }
+                PlayMode::Once => {
+                    if self.position == last {
+                        self.elapsed = Duration::ZERO;
+                        self.playing = false;
+                        self.finished = true;
+                        self.events.push(AnimationEvent::Finished);
+                        return;
+                    }
+                    self.position += 1;
+                }
+            }
+            frame_events(clip, self.position, &mut self.events);
+        }
+    }
+
+    /// Returns the events emitted by the last call to `update`, `advance`, `play` or `restart`.
+    pub fn events(&self) -> &[AnimationEvent] {
+        &self.events
+    }
+
+    /// Returns the position inside of the current clip.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the index of the current frame in the sheet, if there's a clip.
+    pub fn frame(&self) -> Option<usize> {
+        self.clip
+            .as_ref()
+            .and_then(|clip| clip.frames.get(self.position).copied())
+    }
+
+    /// Returns the part of the sheet's image showing the current frame, as expected by
+    /// `DrawParam::src`.
+    pub fn src(&self, sheet: &SpriteSheet) -> Rect {
+        self.frame()
+            .map(|frame| sheet.src(frame))
+            .unwrap_or_else(Rect::one)
+    }
+
+    /// Returns a `DrawParam` with the [`src`](#method.src) of the current frame, for drawing the
+    /// sheet's image or adding it to a `SpriteBatch`.
+    pub fn draw_param(&self, sheet: &SpriteSheet) -> DrawParam {
+        DrawParam::new().src(self.src(sheet))
+    }
+}
+
+/// Pushes the events for entering the `position`th frame of the clip.
+fn frame_events(clip: &Clip, position: usize, events: &mut Vec<AnimationEvent>) {
+    events.push(AnimationEvent::Frame {
+        position,
+        frame: clip.frames[position],
+    });
+    for (_, name) in clip.markers.iter().filter(|(p, _)| *p == position) {
+        events.push(AnimationEvent::Marker {
+            name: name.clone(),
+            position,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn playing(clip: Clip) -> AnimationPlayer {
+        let mut player = AnimationPlayer {
+            clip: Some(clip),
+            ..AnimationPlayer::default()
+        };
+        player.restart();
+        player
+    }
+
+    fn frame(position: usize, frame: usize) -> AnimationEvent {
+        AnimationEvent::Frame { position, frame }
+    }
+
+    #[test]
+    fn loop_mode() {
+        let clip = Clip::with_durations([(4, ms(100)), (5, ms(50)), (6, ms(100))], PlayMode::Loop);
+        let mut player = playing(clip);
+        assert_eq!(player.events(), [frame(0, 4)]);
+
+        player.advance(ms(120));
+        assert_eq!(player.events(), [frame(1, 5)]);
+        player.advance(ms(10));
+        assert_eq!(player.events(), []);
+        player.advance(ms(20));
+        assert_eq!(player.frame(), Some(6));
+        player.advance(ms(100));
+        assert_eq!(player.events(), [AnimationEvent::Looped, frame(0, 4)]);
+        assert!(player.playing());
+        assert!(!player.finished());
+    }
+
+    #[test]
+    fn ping_pong_mode() {
+        let clip =
+            Clip::with_durations([(0, ms(10)), (1, ms(10)), (2, ms(10))], PlayMode::PingPong);
+        let mut player = playing(clip);
+
+        let mut positions = Vec::new();
+        for _ in 0..6 {
+            player.advance(ms(10));
+            positions.push(player.position());
+        }
+        assert_eq!(positions, [1, 2, 1, 0, 1, 2]);
+
+        player.advance(ms(20));
+        assert_eq!(
+            player.events(),
+            [AnimationEvent::Looped, frame(1, 1), frame(0, 0)]
+        );
+    }
+
+    #[test]
+    fn once_mode() {
+        let clip = Clip::with_durations([(3, ms(10)), (2, ms(20))], PlayMode::Once);
+        let mut player = playing(clip);
+
+        player.advance(ms(25));
+        assert_eq!(player.events(), [frame(1, 2)]);
+        player.advance(ms(20));
+        assert_eq!(player.events(), [AnimationEvent::Finished]);
+        assert!(player.finished());
+        assert!(!player.playing());
+        assert_eq!(player.frame(), Some(2));
+
+        player.advance(ms(100));
+        assert_eq!(player.events(), []);
+        player.restart();
+        assert!(player.playing());
+        assert_eq!(player.frame(), Some(3));
+    }
+
+    #[test]
+    fn steps_over_several_frames_at_once() {
+        let mut clip = Clip::new(0..4, ms(10), PlayMode::Loop);
+        clip.add_marker(2, "step");
+        let mut player = playing(clip);
+        player.set_speed(2.0);
+
+        player.advance(ms(15));
+        assert_eq!(
+            player.events(),
+            [
+                frame(1, 1),
+                frame(2, 2),
+                AnimationEvent::Marker {
+                    name: "step".to_string(),
+                    position: 2
+                },
+                frame(3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn paused_players_and_empty_clips_stand_still() {
+        let mut player = playing(Clip::new(0..2, ms(10), PlayMode::Loop));
+        player.pause();
+        player.advance(ms(100));
+        assert_eq!(player.position(), 0);
+        player.resume();
+        player.advance(ms(10));
+        assert_eq!(player.position(), 1);
+
+        let mut player = playing(Clip::new(0..2, Duration::ZERO, PlayMode::Loop));
+        player.advance(ms(100));
+        assert_eq!(player.position(), 0);
+        let mut player = playing(Clip::new(0..0, ms(10), PlayMode::Loop));
+        player.advance(ms(100));
+        assert_eq!(player.frame(), None);
+    }
+}
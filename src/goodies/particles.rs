@@ -0,0 +1,566 @@
+//! Particle effects like explosions, smoke, sparks or rain.
+//!
+//! A [`ParticleEmitter`](struct.ParticleEmitter.html) spawns particles from a shape, either
+//! continuously or in bursts, moves them by their velocity, gravity and drag, and changes their
+//! color, scale and rotation over their lifetime along [`Curve`](struct.Curve.html)s. All
+//! particles of an emitter are kept in a single `SpriteBatch`, so drawing an emitter is a single
+//! draw call, no matter how many particles are alive:
+//!
+//! ```rust, ignore
+//! let mut sparks = ParticleEmitter::new(Image::new(ctx, quad_ctx, "/spark.png")?)
+//!     .shape(EmitterShape::Circle { radius: 8.0 })
+//!     .lifetime(0.4, 0.8)
+//!     .speed(100.0, 250.0)
+//!     .spread(std::f32::consts::TAU)
+//!     .gravity(Vector2 { x: 0.0, y: 300.0 })
+//!     .color(Curve::linear(Color::new(1.0, 0.8, 0.2, 1.0), Color::new(1.0, 0.2, 0.0, 0.0)))
+//!     .blend_mode(BlendMode::Add);
+//!
+//! // when something explodes
+//! sparks.set_position(explosion_position);
+//! sparks.burst(200);
+//!
+//! // in `update`
+//! sparks.update(ctx);
+//!
+//! // in `draw`
+//! graphics::draw(ctx, quad_ctx, &sparks, DrawParam::new())?;
+//! ```
+
+use std::time::Duration;
+
+use crate::goodies::tween::Tweenable;
+use crate::graphics::spritebatch::SpriteBatch;
+use crate::graphics::{self, BlendMode, Color, DrawParam, Drawable, Image, Rect};
+use crate::{timer, Context, GameResult};
+
+/// A value changing over the lifetime of a particle, given as points in time (from 0.0 at
+/// birth to 1.0 at death) with values in between interpolated linearly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    points: Vec<(f32, T)>,
+}
+
+impl<T: Tweenable> Curve<T> {
+    /// Creates a curve through the given points, which are sorted by time.
+    pub fn new(mut points: Vec<(f32, T)>) -> Self {
+        points.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        Curve { points }
+    }
+
+    /// Creates a curve staying at the same value.
+    pub fn constant(value: T) -> Self {
+        Curve {
+            points: vec![(0.0, value)],
+        }
+    }
+
+    /// Creates a curve moving from one value at birth to another at death.
+    pub fn linear(from: T, to: T) -> Self {
+        Curve {
+            points: vec![(0.0, from), (1.0, to)],
+        }
+    }
+
+    /// Returns the value at `t`, from 0.0 to 1.0.
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let next = self.points.iter().position(|(time, _)| *time > t);
+        match next {
+            Some(0) => self.points.first().map(|(_, value)| *value),
+            Some(i) => {
+                let (start, from) = self.points[i - 1];
+                let (end, to) = self.points[i];
+                Some(T::lerp(from, to, (t - start) / (end - start)))
+            }
+            None => self.points.last().map(|(_, value)| *value),
+        }
+    }
+}
+
+/// Where the particles of an emitter are spawned, relative to its position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EmitterShape {
+    /// Exactly at the position of the emitter.
+    Point,
+    /// Anywhere within a circle around the position.
+    Circle { radius: f32 },
+    /// Anywhere within a rectangle centered on the position.
+    Rect { width: f32, height: f32 },
+    /// Anywhere on a line from the position to the position plus `to`.
+    Line { to: mint::Vector2<f32> },
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Particle {
+    position: mint::Point2<f32>,
+    velocity: mint::Vector2<f32>,
+    rotation: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Spawns, moves and draws particles, see the [module level docs](index.html).
+#[derive(Debug)]
+pub struct ParticleEmitter {
+    batch: SpriteBatch,
+    particles: Vec<Particle>,
+    rng: oorandom::Rand32,
+    position: mint::Point2<f32>,
+    emitting: bool,
+    /// Particles owed from previous updates when emitting continuously.
+    emit_debt: f32,
+
+    shape: EmitterShape,
+    rate: f32,
+    max_particles: usize,
+    lifetime: (f32, f32),
+    speed: (f32, f32),
+    direction: f32,
+    spread: f32,
+    start_rotation: (f32, f32),
+    gravity: mint::Vector2<f32>,
+    drag: f32,
+    src: Rect,
+    color: Curve<Color>,
+    scale: Curve<f32>,
+    rotation: Curve<f32>,
+}
+
+impl ParticleEmitter {
+    /// Creates an emitter drawing every particle with the given image, centered on its position.
+    pub fn new(image: Image) -> Self {
+        ParticleEmitter {
+            batch: SpriteBatch::new(image),
+            particles: Vec::new(),
+            rng: oorandom::Rand32::new((timer::time() * 1000.0) as u64),
+            position: mint::Point2 { x: 0.0, y: 0.0 },
+            emitting: true,
+            emit_debt: 0.0,
+            shape: EmitterShape::Point,
+            rate: 0.0,
+            max_particles: 10_000,
+            lifetime: (1.0, 1.0),
+            speed: (50.0, 50.0),
+            direction: -std::f32::consts::FRAC_PI_2,
+            spread: std::f32::consts::TAU,
+            start_rotation: (0.0, 0.0),
+            gravity: mint::Vector2 { x: 0.0, y: 0.0 },
+            drag: 0.0,
+            src: Rect::one(),
+            color: Curve::constant(Color::WHITE),
+            scale: Curve::constant(1.0),
+            rotation: Curve::constant(0.0),
+        }
+    }
+
+    /// Sets the shape particles are spawned in.
+    ///
+    /// Default: `EmitterShape::Point`
+    pub fn shape(mut self, shape: EmitterShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Sets how many particles are spawned per second while emitting. Use
+    /// [`burst`](#method.burst) to spawn particles all at once instead.
+    ///
+    /// Default: `0.0`
+    pub fn rate(mut self, particles_per_second: f32) -> Self {
+        self.rate = particles_per_second;
+        self
+    }
+
+    /// Sets the number of particles alive at once, beyond which no more are spawned.
+    ///
+    /// Default: `10000`
+    pub fn max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    /// Sets the range the lifetime of every particle is randomly picked from, in seconds.
+    ///
+    /// Default: `1.0, 1.0`
+    pub fn lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = (min, max);
+        self
+    }
+
+    /// Sets the range the initial speed of every particle is randomly picked from, in pixels
+    /// per second.
+    ///
+    /// Default: `50.0, 50.0`
+    pub fn speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = (min, max);
+        self
+    }
+
+    /// Sets the direction particles move in initially, in radians.
+    ///
+    /// Default: `-PI / 2`, which is up
+    pub fn direction(mut self, direction: f32) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Sets the angle around the direction particles are randomly spread over, in radians.
+    ///
+    /// Default: `TAU`, so all directions
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    /// Sets the range the initial rotation of every particle is randomly picked from, in radians.
+    ///
+    /// Default: `0.0, 0.0`
+    pub fn start_rotation(mut self, min: f32, max: f32) -> Self {
+        self.start_rotation = (min, max);
+        self
+    }
+
+    /// Sets the acceleration applied to every particle, in pixels per second squared.
+    ///
+    /// Default: `0.0, 0.0`
+    pub fn gravity<V: Into<mint::Vector2<f32>>>(mut self, gravity: V) -> Self {
+        self.gravity = gravity.into();
+        self
+    }
+
+    /// Sets how quickly particles slow down, as the fraction of their velocity lost per second.
+    ///
+    /// Default: `0.0`
+    pub fn drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    /// Sets the part of the image particles show, as in `DrawParam::src`, e.g. a region of a
+    /// `TextureAtlas`.
+    ///
+    /// Default: the whole image
+    pub fn src(mut self, src: Rect) -> Self {
+        self.src = src;
+        self
+    }
+
+    /// Sets the color of the particles over their lifetime, which the image is multiplied with.
+    ///
+    /// Default: white
+    pub fn color(mut self, color: Curve<Color>) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the scale of the particles over their lifetime.
+    ///
+    /// Default: `1.0`
+    pub fn scale(mut self, scale: Curve<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Sets the rotation of the particles over their lifetime in radians, added on top of their
+    /// initial rotation.
+    ///
+    /// Default: `0.0`
+    pub fn rotation(mut self, rotation: Curve<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Sets the blend mode the particles are drawn with, e.g. `BlendMode::Add` for fire.
+    ///
+    /// Default: `None`, which defers to `graphics::set_blend_mode`
+    pub fn blend_mode(mut self, mode: BlendMode) -> Self {
+        self.batch.set_blend_mode(Some(mode));
+        self
+    }
+
+    /// Seeds the random number generator, to make the effect play out the same every time.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = oorandom::Rand32::new(seed);
+        self
+    }
+
+    /// Moves the emitter. Particles already spawned stay where they are.
+    pub fn set_position<P: Into<mint::Point2<f32>>>(&mut self, position: P) {
+        self.position = position.into();
+    }
+
+    pub fn position(&self) -> mint::Point2<f32> {
+        self.position
+    }
+
+    /// Starts or stops spawning particles continuously at the [`rate`](#method.rate).
+    ///
+    /// Default: `true`
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+        self.emit_debt = 0.0;
+    }
+
+    pub fn emitting(&self) -> bool {
+        self.emitting
+    }
+
+    /// Returns the number of particles alive.
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Removes all particles.
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.batch.clear();
+    }
+
+    fn random_range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.rng.rand_float()
+    }
+
+    /// Spawns `count` particles at once.
+    pub fn burst(&mut self, count: usize) {
+        let count = count.min(self.max_particles.saturating_sub(self.particles.len()));
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    fn spawn(&mut self) {
+        let offset = match self.shape {
+            EmitterShape::Point => (0.0, 0.0),
+            EmitterShape::Circle { radius } => {
+                // uniformly distributed over the area
+                let distance = radius * self.rng.rand_float().sqrt();
+                let angle = self.rng.rand_float() * std::f32::consts::TAU;
+                (distance * angle.cos(), distance * angle.sin())
+            }
+            EmitterShape::Rect { width, height } => (
+                (self.rng.rand_float() - 0.5) * width,
+                (self.rng.rand_float() - 0.5) * height,
+            ),
+            EmitterShape::Line { to } => {
+                let t = self.rng.rand_float();
+                (to.x * t, to.y * t)
+            }
+        };
+        let angle = self.direction + (self.rng.rand_float() - 0.5) * self.spread;
+        let speed = self.random_range(self.speed);
+        let particle = Particle {
+            position: mint::Point2 {
+                x: self.position.x + offset.0,
+                y: self.position.y + offset.1,
+            },
+            velocity: mint::Vector2 {
+                x: speed * angle.cos(),
+                y: speed * angle.sin(),
+            },
+            rotation: self.random_range(self.start_rotation),
+            age: 0.0,
+            lifetime: self.random_range(self.lifetime).max(f32::EPSILON),
+        };
+        self.particles.push(particle);
+    }
+
+    /// Advances all particles by the time passed since the last frame (see `timer::delta`) and
+    /// spawns new ones while emitting.
+    pub fn update(&mut self, ctx: &Context) {
+        self.advance(timer::delta(ctx));
+    }
+
+    /// Advances all particles by the given time and spawns new ones while emitting.
+    pub fn advance(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        let (gravity, drag) = (self.gravity, 1.0 / (1.0 + self.drag * dt));
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.velocity.x = (particle.velocity.x + gravity.x * dt) * drag;
+            particle.velocity.y = (particle.velocity.y + gravity.y * dt) * drag;
+            particle.position.x += particle.velocity.x * dt;
+            particle.position.y += particle.velocity.y * dt;
+            particle.age < particle.lifetime
+        });
+
+        if self.emitting && self.rate > 0.0 {
+            self.emit_debt += self.rate * dt;
+            let count = self.emit_debt.floor();
+            self.emit_debt -= count;
+            self.burst(count as usize);
+        }
+
+        self.batch.clear();
+        for particle in self.particles.iter() {
+            let t = particle.age / particle.lifetime;
+            let scale = self.scale.sample(t).unwrap_or(1.0);
+            let param = DrawParam::new()
+                .src(self.src)
+                .dest(particle.position)
+                .offset(mint::Point2 { x: 0.5, y: 0.5 })
+                .scale(mint::Vector2 { x: scale, y: scale })
+                .rotation(particle.rotation + self.rotation.sample(t).unwrap_or(0.0))
+                .color(self.color.sample(t).unwrap_or(Color::WHITE));
+            self.batch.add(param);
+        }
+    }
+}
+
+impl Drawable for ParticleEmitter {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        param: DrawParam,
+    ) -> GameResult {
+        if self.particles.is_empty() {
+            return Ok(());
+        }
+        graphics::draw(ctx, quad_ctx, &self.batch, param)
+    }
+
+    fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
+        self.batch.set_blend_mode(mode);
+    }
+
+    fn blend_mode(&self) -> Option<BlendMode> {
+        self.batch.blend_mode()
+    }
+
+    fn dimensions(&self, ctx: &mut Context) -> Option<Rect> {
+        self.batch.dimensions(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn curve_sampling() {
+        let curve = Curve::new(vec![(1.0, 4.0), (0.0, 0.0), (0.5, 2.0)]);
+        assert_eq!(curve.sample(-1.0), Some(0.0));
+        assert_eq!(curve.sample(0.0), Some(0.0));
+        assert_relative_eq!(curve.sample(0.25).unwrap(), 1.0);
+        assert_relative_eq!(curve.sample(0.5).unwrap(), 2.0);
+        assert_relative_eq!(curve.sample(0.75).unwrap(), 3.0);
+        assert_eq!(curve.sample(1.0), Some(4.0));
+        assert_eq!(curve.sample(2.0), Some(4.0));
+
+        assert_eq!(Curve::constant(3.0).sample(0.7), Some(3.0));
+        assert_relative_eq!(Curve::linear(1.0, 0.0).sample(0.25).unwrap(), 0.75);
+        assert_eq!(Curve::<f32>::new(Vec::new()).sample(0.5), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    mod emitter {
+        use super::*;
+        use crate::conf::Conf;
+        use crate::event::{EventHandler, GraphicsContext};
+        use crate::headless::{self, InputScript};
+
+        struct Particles(ParticleEmitter);
+
+        impl EventHandler for Particles {
+            fn update(&mut self, _: &mut Context, _: &mut GraphicsContext) -> GameResult {
+                Ok(())
+            }
+
+            fn draw(&mut self, ctx: &mut Context, quad_ctx: &mut GraphicsContext) -> GameResult {
+                graphics::clear(ctx, quad_ctx, Color::BLACK);
+                graphics::draw(ctx, quad_ctx, &self.0, DrawParam::new())?;
+                graphics::present(ctx, quad_ctx)
+            }
+        }
+
+        /// Runs `f` with an emitter of white pixels, drawing the emitter it returns.
+        fn run<F>(f: F) -> headless::Frame
+        where
+            F: 'static + FnOnce(ParticleEmitter) -> ParticleEmitter,
+        {
+            let conf = Conf::default().window_width(16).window_height(16);
+            headless::run(conf, 1, InputScript::new(), |ctx, quad_ctx| {
+                let image = Image::solid(ctx, quad_ctx, 1, Color::WHITE).unwrap();
+                let emitter = f(ParticleEmitter::new(image).seed(7));
+                Box::new(Particles(emitter))
+            })
+            .unwrap()
+        }
+
+        #[test]
+        fn emission() {
+            run(|emitter| {
+                let mut emitter = emitter.rate(10.0).max_particles(6);
+                emitter.advance(Duration::from_millis(250));
+                assert_eq!(emitter.len(), 2);
+                emitter.advance(Duration::from_millis(250));
+                assert_eq!(emitter.len(), 5);
+                emitter.advance(Duration::from_millis(250));
+                assert_eq!(emitter.len(), 6);
+
+                emitter.clear();
+                emitter.set_emitting(false);
+                emitter.advance(Duration::from_secs(1));
+                assert!(emitter.is_empty());
+                emitter.burst(10);
+                assert_eq!(emitter.len(), 6);
+                emitter
+            });
+        }
+
+        #[test]
+        fn lifetime_and_motion() {
+            run(|emitter| {
+                let mut emitter = emitter
+                    .lifetime(0.5, 0.5)
+                    .speed(10.0, 10.0)
+                    .direction(0.0)
+                    .spread(0.0)
+                    .gravity(mint::Vector2 { x: 0.0, y: 20.0 });
+                emitter.burst(3);
+                emitter.advance(Duration::from_millis(400));
+                assert_eq!(emitter.len(), 3);
+                let particle = emitter.particles[0];
+                assert_relative_eq!(particle.velocity.x, 10.0);
+                assert_relative_eq!(particle.velocity.y, 8.0);
+                assert_relative_eq!(particle.position.x, 4.0);
+                assert_relative_eq!(particle.position.y, 3.2);
+
+                emitter.advance(Duration::from_millis(100));
+                assert!(emitter.is_empty());
+
+                let mut emitter = emitter.drag(1.0).gravity(mint::Vector2 { x: 0.0, y: 0.0 });
+                emitter.burst(1);
+                emitter.advance(Duration::from_millis(250));
+                assert_relative_eq!(emitter.particles[0].velocity.x, 8.0);
+                emitter
+            });
+        }
+
+        #[test]
+        fn curves_are_sampled_over_the_lifetime() {
+            let frame = run(|emitter| {
+                let mut emitter = emitter
+                    .speed(0.0, 0.0)
+                    .lifetime(1.0, 1.0)
+                    .color(Curve::linear(Color::RED, Color::BLUE))
+                    .scale(Curve::new(vec![(0.0, 2.0), (0.5, 4.0), (1.0, 2.0)]));
+                emitter.set_position([8.0, 8.0]);
+                emitter.burst(1);
+                emitter.advance(Duration::from_millis(500));
+                emitter
+            });
+            // a purple square of 4x4 pixels centered on the emitter
+            let (r, g, b, _) = frame.pixel(6, 9).to_rgba();
+            assert!((126..=129).contains(&r) && g == 0 && (126..=129).contains(&b));
+            assert_eq!(frame.pixel(9, 6), frame.pixel(6, 9));
+            assert_eq!(frame.pixel(5, 8), Color::BLACK);
+            assert_eq!(frame.pixel(10, 8), Color::BLACK);
+        }
+    }
+}
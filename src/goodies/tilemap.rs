@@ -0,0 +1,1226 @@
+//! Maps made with the [Tiled](https://www.mapeditor.org/) map editor.
+//!
+//! [`TileMap::new`](struct.TileMap.html#method.new) loads maps saved as TMX (`.tmx`) or JSON
+//! (`.tmj`, `.json`) through `filesystem::open`, along with their external tilesets
+//! (`.tsx`, `.tsj`) and tileset images, all relative to the file referencing them.
+//!
+//! Supported are orthogonal and isometric maps with any number of tile and object layers (group
+//! layers are flattened into their children), custom properties of the map, layers, tiles and
+//! objects, flipped tiles and animated tiles. Tile data may be stored as XML, CSV or base64,
+//! uncompressed or compressed with zlib or gzip. Not supported are infinite maps, image layers,
+//! tilesets made of separate images and zstd compression.
+//!
+//! Every tile layer is drawn through one `SpriteBatch` per chunk of
+//! [`CHUNK_SIZE`](constant.CHUNK_SIZE.html)² tiles (and tileset), of which only the ones within
+//! the current `graphics::screen_coordinates` are drawn, so only visible tiles are uploaded
+//! to the GPU:
+//!
+//! ```rust, ignore
+//! let mut map = TileMap::new(ctx, quad_ctx, "/levels/level1.tmx")?;
+//! let spawn = map
+//!     .object_layer("entities")
+//!     .and_then(|layer| layer.objects.iter().find(|object| object.name == "spawn"));
+//!
+//! // in `update`
+//! map.update(ctx);
+//!
+//! // in `draw`
+//! graphics::draw(ctx, quad_ctx, &map, DrawParam::new().dest(-camera_position))?;
+//! ```
+//!
+//! Tileset images are loaded with `FilterMode::Nearest`, to keep neighbouring tiles from
+//! bleeding in. Objects aren't drawn, not even tile objects; they're left to the game to
+//! interpret.
+
+mod json;
+mod tmx;
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::graphics::spritebatch::{SpriteBatch, SpriteIdx};
+use crate::graphics::{self, BlendMode, Color, DrawParam, Drawable, Image, Rect, Transform};
+use crate::{filesystem, timer, Context, GameError, GameResult};
+
+/// The width and height of the chunks tile layers are split into, in tiles.
+pub const CHUNK_SIZE: u32 = 16;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Also set by Tiled on hexagonal maps, which aren't supported, so it's just ignored.
+const ROTATED_HEXAGONAL: u32 = 0x1000_0000;
+const GID_MASK: u32 =
+    !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL);
+
+/// How the tiles of a map are laid out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Orientation {
+    /// A regular grid.
+    Orthogonal,
+    /// A grid of diamonds, with the first tile at the top and rows going down to the right.
+    Isometric,
+}
+
+/// The value of a custom property.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Color(Color),
+    /// A path, relative to the file the property is defined in.
+    File(String),
+    /// The id of an object.
+    Object(u32),
+}
+
+/// Custom properties, by name.
+pub type Properties = HashMap<String, PropertyValue>;
+
+/// What a tileset knows about a single tile.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TileData {
+    pub properties: Properties,
+    /// The class (called type in older versions of Tiled) of the tile.
+    pub class: String,
+    /// The frames of the tile's animation as local tile ids with their duration, if it's
+    /// animated.
+    pub animation: Vec<(u32, Duration)>,
+}
+
+/// A single image split into tiles of the same size.
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    /// The global id of the first tile of this set in the map.
+    pub first_gid: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    /// The path of the image in the filesystem.
+    pub image_path: PathBuf,
+    pub properties: Properties,
+    /// The tiles with properties or animations, by local id.
+    pub tiles: HashMap<u32, TileData>,
+    image: Option<Image>,
+}
+
+impl Tileset {
+    pub fn image(&self) -> Option<&Image> {
+        self.image.as_ref()
+    }
+
+    /// Returns the region of the tile with the given local id in pixels.
+    pub fn tile_rect(&self, id: u32) -> Rect {
+        let columns = self.columns.max(1);
+        let (column, row) = (id % columns, id / columns);
+        Rect::new(
+            (self.margin + column * (self.tile_width + self.spacing)) as f32,
+            (self.margin + row * (self.tile_height + self.spacing)) as f32,
+            self.tile_width as f32,
+            self.tile_height as f32,
+        )
+    }
+
+    /// Returns the region of the tile with the given local id relative to the size of the
+    /// image, as expected by `DrawParam::src`.
+    fn tile_src(&self, id: u32) -> Rect {
+        let rect = self.tile_rect(id);
+        let (width, height) = match &self.image {
+            Some(image) => (f32::from(image.width()), f32::from(image.height())),
+            None => return Rect::one(),
+        };
+        Rect::new(
+            rect.x / width,
+            rect.y / height,
+            rect.w / width,
+            rect.h / height,
+        )
+    }
+}
+
+/// A grid of tiles.
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// The global ids of the tiles row by row, including the flags Tiled stores flips in; 0
+    /// means no tile. See [`TileMap::tile`](struct.TileMap.html#method.tile).
+    pub tiles: Vec<u32>,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: mint::Vector2<f32>,
+    pub properties: Properties,
+}
+
+/// The shape of an [`Object`](struct.Object.html).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rect,
+    Ellipse,
+    Point,
+    /// Points relative to the position of the object.
+    Polygon(Vec<mint::Point2<f32>>),
+    /// Points relative to the position of the object.
+    Polyline(Vec<mint::Point2<f32>>),
+    Text(String),
+}
+
+/// Anything placed freely on an object layer, like spawn points, triggers or collision shapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub id: u32,
+    pub name: String,
+    /// The class (called type in older versions of Tiled) of the object.
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// In degrees, clockwise.
+    pub rotation: f32,
+    /// The global id of the tile shown by tile objects.
+    pub gid: Option<u32>,
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+/// A layer of freely placed objects.
+#[derive(Debug, Clone)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<Object>,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: mint::Vector2<f32>,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        match self {
+            Layer::Tiles(layer) => &layer.name,
+            Layer::Objects(layer) => &layer.name,
+        }
+    }
+}
+
+/// A tile layer's tiles of a single tileset within a chunk.
+#[derive(Debug)]
+struct Chunk {
+    layer: usize,
+    tileset: usize,
+    /// The area covered by the tiles in pixels, relative to the map.
+    bounds: Rect,
+    batch: SpriteBatch,
+    /// The animated tiles, with their local id.
+    animated: Vec<(SpriteIdx, DrawParam, u32)>,
+}
+
+/// A map made with Tiled, see the [module level docs](index.html).
+#[derive(Debug)]
+pub struct TileMap {
+    orientation: Orientation,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    background_color: Option<Color>,
+    properties: Properties,
+    tilesets: Vec<Tileset>,
+    layers: Vec<Layer>,
+    chunks: Vec<Chunk>,
+    /// The time the animations have been playing for, in seconds.
+    time: f64,
+    blend_mode: Option<BlendMode>,
+}
+
+/// The parts of a map read by the parsers, before images are loaded and chunks are built.
+pub(crate) struct MapData {
+    orientation: Orientation,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    background_color: Option<Color>,
+    properties: Properties,
+    tilesets: Vec<Tileset>,
+    layers: Vec<Layer>,
+}
+
+impl TileMap {
+    /// Loads a map saved as TMX (`.tmx`) or JSON (`.tmj`, `.json`), along with its tilesets and
+    /// their images.
+    pub fn new<P: AsRef<Path>>(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        path: P,
+    ) -> GameResult<TileMap> {
+        let path = path.as_ref();
+        let bytes = read_file(ctx, path)?;
+        let text = std::str::from_utf8(&bytes).map_err(|e| map_error(path, e))?;
+
+        let mut load = |tileset_path: &Path| read_file(ctx, tileset_path);
+        let data = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") => tmx::parse_map(text, path, &mut load),
+            Some("tmj") | Some("json") => json::parse_map(text, path, &mut load),
+            _ => Err(map_error(
+                path,
+                "unknown file extension, expected tmx, tmj or json",
+            )),
+        }?;
+
+        let mut tilesets = data.tilesets;
+        for tileset in tilesets.iter_mut() {
+            let mut image = Image::new(ctx, quad_ctx, &tileset.image_path)?;
+            image.set_filter(graphics::FilterMode::Nearest);
+            tileset.image = Some(image);
+        }
+
+        let mut map = TileMap {
+            orientation: data.orientation,
+            width: data.width,
+            height: data.height,
+            tile_width: data.tile_width,
+            tile_height: data.tile_height,
+            background_color: data.background_color,
+            properties: data.properties,
+            tilesets,
+            layers: data.layers,
+            chunks: Vec::new(),
+            time: 0.0,
+            blend_mode: None,
+        };
+        map.build_chunks();
+        Ok(map)
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Returns the width of the map in tiles.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the map in tiles.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the width of a cell of the grid in pixels, which may differ from the size of the
+    /// tiles in the tilesets.
+    pub fn tile_width(&self) -> u32 {
+        self.tile_width
+    }
+
+    /// Returns the height of a cell of the grid in pixels.
+    pub fn tile_height(&self) -> u32 {
+        self.tile_height
+    }
+
+    /// Returns the size of the whole map in pixels.
+    pub fn pixel_size(&self) -> (f32, f32) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        match self.orientation {
+            Orientation::Orthogonal => (width * tile_width, height * tile_height),
+            Orientation::Isometric => (
+                (width + height) * tile_width / 2.0,
+                (width + height) * tile_height / 2.0,
+            ),
+        }
+    }
+
+    pub fn background_color(&self) -> Option<Color> {
+        self.background_color
+    }
+
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    pub fn tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    /// Returns all layers, from bottom to top.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name() == name)
+    }
+
+    pub fn tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find_map(|layer| match layer {
+            Layer::Tiles(layer) if layer.name == name => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.layers.iter().find_map(|layer| match layer {
+            Layer::Objects(layer) if layer.name == name => Some(layer),
+            _ => None,
+        })
+    }
+
+    /// Shows or hides the layer of the given name.
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) {
+        for layer in self.layers.iter_mut() {
+            match layer {
+                Layer::Tiles(layer) if layer.name == name => layer.visible = visible,
+                Layer::Objects(layer) if layer.name == name => layer.visible = visible,
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns the global id of the tile at the given position of a tile layer (without the
+    /// flip flags), or `None` if there's no tile.
+    pub fn tile(&self, layer: &str, x: u32, y: u32) -> Option<u32> {
+        let layer = self.tile_layer(layer)?;
+        if x >= layer.width || y >= layer.height {
+            return None;
+        }
+        match layer.tiles[(y * layer.width + x) as usize] & GID_MASK {
+            0 => None,
+            gid => Some(gid),
+        }
+    }
+
+    /// Returns the tileset a global tile id belongs to, along with the local id of the tile in
+    /// it.
+    pub fn tileset_of(&self, gid: u32) -> Option<(&Tileset, u32)> {
+        let gid = gid & GID_MASK;
+        self.tileset_index(gid)
+            .map(|i| (&self.tilesets[i], gid - self.tilesets[i].first_gid))
+    }
+
+    fn tileset_index(&self, gid: u32) -> Option<usize> {
+        if gid == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .enumerate()
+            .filter(|(_, tileset)| tileset.first_gid <= gid)
+            .max_by_key(|(_, tileset)| tileset.first_gid)
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the properties, class and animation of a tile by its global id.
+    pub fn tile_data(&self, gid: u32) -> Option<&TileData> {
+        let (tileset, id) = self.tileset_of(gid)?;
+        tileset.tiles.get(&id)
+    }
+
+    /// Returns the position of the top left corner of a cell in pixels (for isometric maps
+    /// the top left corner of the rectangle around the diamond).
+    pub fn tile_to_world(&self, x: f32, y: f32) -> mint::Point2<f32> {
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        match self.orientation {
+            Orientation::Orthogonal => mint::Point2 {
+                x: x * tile_width,
+                y: y * tile_height,
+            },
+            Orientation::Isometric => mint::Point2 {
+                x: (self.height as f32 - 1.0 + x - y) * tile_width / 2.0,
+                y: (x + y) * tile_height / 2.0,
+            },
+        }
+    }
+
+    /// Returns the cell at the given position in pixels, as fractional coordinates.
+    pub fn world_to_tile<P: Into<mint::Point2<f32>>>(&self, point: P) -> mint::Point2<f32> {
+        let point = point.into();
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        match self.orientation {
+            Orientation::Orthogonal => mint::Point2 {
+                x: point.x / tile_width,
+                y: point.y / tile_height,
+            },
+            Orientation::Isometric => {
+                let x = point.x - self.height as f32 * tile_width / 2.0;
+                mint::Point2 {
+                    x: point.y / tile_height + x / tile_width,
+                    y: point.y / tile_height - x / tile_width,
+                }
+            }
+        }
+    }
+
+    /// Advances the animated tiles by the time passed since the last frame, see `timer::delta`.
+    pub fn update(&mut self, ctx: &Context) {
+        self.advance(timer::delta(ctx));
+    }
+
+    /// Advances the animated tiles by the given time.
+    pub fn advance(&mut self, dt: Duration) {
+        self.time += dt.as_secs_f64();
+        let time = self.time;
+        for chunk in self.chunks.iter_mut() {
+            let tileset = &self.tilesets[chunk.tileset];
+            for (idx, param, id) in chunk.animated.iter() {
+                let frame = animation_frame(&tileset.tiles[id].animation, time);
+                let _ = chunk.batch.set(*idx, param.src(tileset.tile_src(frame)));
+            }
+        }
+    }
+
+    /// Splits all tile layers into chunks and fills their sprite batches.
+    fn build_chunks(&mut self) {
+        let mut chunks = Vec::new();
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            let layer = match layer {
+                Layer::Tiles(layer) => layer,
+                Layer::Objects(_) => continue,
+            };
+            let color = Color::new(1.0, 1.0, 1.0, layer.opacity);
+
+            for chunk_y in (0..layer.height).step_by(CHUNK_SIZE as usize) {
+                for chunk_x in (0..layer.width).step_by(CHUNK_SIZE as usize) {
+                    // the chunks of this area, one per tileset used in it
+                    let mut area: Vec<Chunk> = Vec::new();
+                    for y in chunk_y..(chunk_y + CHUNK_SIZE).min(layer.height) {
+                        for x in chunk_x..(chunk_x + CHUNK_SIZE).min(layer.width) {
+                            let raw = layer.tiles[(y * layer.width + x) as usize];
+                            let tileset_index = match self.tileset_index(raw & GID_MASK) {
+                                Some(index) => index,
+                                None => continue,
+                            };
+                            let tileset = &self.tilesets[tileset_index];
+                            let id = (raw & GID_MASK) - tileset.first_gid;
+                            let (param, bounds) =
+                                self.tile_param(tileset, x, y, raw, layer.offset, color);
+
+                            let chunk = match area.iter().position(|c| c.tileset == tileset_index) {
+                                Some(i) => &mut area[i],
+                                None => {
+                                    area.push(Chunk {
+                                        layer: layer_index,
+                                        tileset: tileset_index,
+                                        bounds,
+                                        batch: SpriteBatch::new(
+                                            tileset.image.clone().expect("tileset image"),
+                                        ),
+                                        animated: Vec::new(),
+                                    });
+                                    area.last_mut().unwrap()
+                                }
+                            };
+
+                            let animated = tileset
+                                .tiles
+                                .get(&id)
+                                .is_some_and(|tile| !tile.animation.is_empty());
+                            let src = if animated {
+                                tileset.tile_src(tileset.tiles[&id].animation[0].0)
+                            } else {
+                                tileset.tile_src(id)
+                            };
+                            let idx = chunk.batch.add(param.src(src));
+                            if animated {
+                                chunk.animated.push((idx, param, id));
+                            }
+                            chunk.bounds = chunk.bounds.combine_with(bounds);
+                        }
+                    }
+                    chunks.extend(area);
+                }
+            }
+        }
+        self.chunks = chunks;
+    }
+
+    /// Returns the `DrawParam` of a tile (without `src`) and the area it covers.
+    fn tile_param(
+        &self,
+        tileset: &Tileset,
+        x: u32,
+        y: u32,
+        raw: u32,
+        offset: mint::Vector2<f32>,
+        color: Color,
+    ) -> (DrawParam, Rect) {
+        let (width, height) = (tileset.tile_width as f32, tileset.tile_height as f32);
+        let cell = self.tile_to_world(x as f32, y as f32);
+        // tiles larger than the cell stick out at the top, centered on isometric maps
+        let left = match self.orientation {
+            Orientation::Orthogonal => cell.x,
+            Orientation::Isometric => cell.x + (self.tile_width as f32 - width) / 2.0,
+        };
+        let top = cell.y + self.tile_height as f32 - height;
+        let bounds = Rect::new(left + offset.x, top + offset.y, width, height);
+
+        let h = if raw & FLIPPED_HORIZONTALLY != 0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let v = if raw & FLIPPED_VERTICALLY != 0 {
+            -1.0
+        } else {
+            1.0
+        };
+        // a diagonal flip (applied before the others) is the same as a rotation by 90°
+        // followed by a vertical flip
+        let (rotation, scale) = if raw & FLIPPED_DIAGONALLY != 0 {
+            (std::f32::consts::FRAC_PI_2, (v, -h))
+        } else {
+            (0.0, (h, v))
+        };
+
+        let param = DrawParam::new()
+            .dest(bounds.center())
+            .offset(mint::Point2 { x: 0.5, y: 0.5 })
+            .rotation(rotation)
+            .scale(mint::Vector2 {
+                x: scale.0,
+                y: scale.1,
+            })
+            .color(color);
+        (param, bounds)
+    }
+
+    /// Returns the area of the map visible on screen when drawn with `param`, or `None` if it
+    /// can't be determined because the map is rotated.
+    fn visible_area(ctx: &Context, param: &DrawParam) -> Option<Rect> {
+        let screen = graphics::screen_coordinates(ctx);
+        match param.trans {
+            Transform::Values {
+                dest,
+                rotation,
+                scale,
+                offset,
+            } if rotation == 0.0 && offset.x == 0.0 && offset.y == 0.0 => {
+                let (scale_x, scale_y) = (scale.x.abs(), scale.y.abs());
+                if scale_x == 0.0 || scale_y == 0.0 || scale.x < 0.0 || scale.y < 0.0 {
+                    return None;
+                }
+                Some(Rect::new(
+                    (screen.x - dest.x) / scale_x,
+                    (screen.y - dest.y) / scale_y,
+                    screen.w / scale_x,
+                    screen.h / scale_y,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the local id shown by an animation `time` seconds after it started.
+fn animation_frame(animation: &[(u32, Duration)], time: f64) -> u32 {
+    let total: f64 = animation.iter().map(|(_, d)| d.as_secs_f64()).sum();
+    if total <= 0.0 {
+        return animation.first().map_or(0, |(id, _)| *id);
+    }
+    let mut time = time % total;
+    for (id, duration) in animation {
+        time -= duration.as_secs_f64();
+        if time < 0.0 {
+            return *id;
+        }
+    }
+    animation.last().map_or(0, |(id, _)| *id)
+}
+
+impl Drawable for TileMap {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        param: DrawParam,
+    ) -> GameResult {
+        let visible = Self::visible_area(ctx, &param);
+        for chunk in self.chunks.iter() {
+            let layer_visible = match &self.layers[chunk.layer] {
+                Layer::Tiles(layer) => layer.visible,
+                Layer::Objects(layer) => layer.visible,
+            };
+            if !layer_visible || visible.is_some_and(|area| !area.overlaps(&chunk.bounds)) {
+                continue;
+            }
+            graphics::draw(ctx, quad_ctx, &chunk.batch, param)?;
+        }
+        Ok(())
+    }
+
+    fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
+        self.blend_mode = mode;
+        for chunk in self.chunks.iter_mut() {
+            chunk.batch.set_blend_mode(mode);
+        }
+    }
+
+    fn blend_mode(&self) -> Option<BlendMode> {
+        self.blend_mode
+    }
+
+    fn dimensions(&self, _ctx: &mut Context) -> Option<Rect> {
+        let (width, height) = self.pixel_size();
+        Some(Rect::new(0.0, 0.0, width, height))
+    }
+}
+
+fn read_file(ctx: &mut Context, path: &Path) -> GameResult<Vec<u8>> {
+    let mut file = filesystem::open(ctx, path)?;
+    let mut bytes = vec![];
+    file.bytes.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn map_error<E: std::fmt::Display>(path: &Path, error: E) -> GameError {
+    GameError::ResourceLoadError(format!("Invalid map {}: {}", path.display(), error))
+}
+
+/// Returns the number of tiles of a layer of the given size, which has to fit into a `u32` for
+/// tiles to be addressable by their position.
+fn layer_len(path: &Path, layer: &str, width: u32, height: u32) -> GameResult<usize> {
+    width
+        .checked_mul(height)
+        .map(|count| count as usize)
+        .ok_or_else(|| {
+            map_error(
+                path,
+                format!("{}: {}x{} tiles are too many", layer, width, height),
+            )
+        })
+}
+
+/// Resolves a path found in a file relative to the directory of that file.
+fn relative_to(file: &Path, path: &str) -> PathBuf {
+    file.parent().unwrap_or_else(|| Path::new("/")).join(path)
+}
+
+/// Parses a color as written by Tiled, `#RRGGBB` or `#AARRGGBB`.
+fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(Color::from_rgb_u32(value)),
+        8 => Some(Color::from_rgba_u32(value.rotate_left(8))),
+        _ => None,
+    }
+}
+
+/// Parses a property of the given type, as named by Tiled.
+fn parse_property(kind: &str, value: &str) -> Option<PropertyValue> {
+    Some(match kind {
+        "bool" => PropertyValue::Bool(value == "true"),
+        "int" => PropertyValue::Int(value.parse().ok()?),
+        "float" => PropertyValue::Float(value.parse().ok()?),
+        "color" => {
+            PropertyValue::Color(parse_color(value).unwrap_or(Color::new(0.0, 0.0, 0.0, 0.0)))
+        }
+        "file" => PropertyValue::File(value.to_string()),
+        "object" => PropertyValue::Object(value.parse().ok()?),
+        _ => PropertyValue::String(value.to_string()),
+    })
+}
+
+/// Decodes tile data stored as base64, optionally compressed, into global ids.
+fn decode_base64_tiles(data: &str, compression: Option<&str>) -> Result<Vec<u32>, String> {
+    use base64::Engine;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| e.to_string())?;
+    let bytes = match compression {
+        None | Some("") => bytes,
+        Some("zlib") => {
+            let mut decoded = Vec::new();
+            flate2::read::ZlibDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decoded)
+                .map_err(|e| e.to_string())?;
+            decoded
+        }
+        Some("gzip") => {
+            let mut decoded = Vec::new();
+            flate2::read::GzDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decoded)
+                .map_err(|e| e.to_string())?;
+            decoded
+        }
+        Some(other) => return Err(format!("unsupported compression '{}'", other)),
+    };
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TSX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="props" tilewidth="32" tileheight="32" spacing="1" margin="2" tilecount="4" columns="2">
+ <image source="images/props.png" width="67" height="67"/>
+ <tile id="1">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+</tileset>"#;
+
+    fn load(path: &Path) -> GameResult<Vec<u8>> {
+        match path.to_str() {
+            Some("/maps/props.tsx") => Ok(TSX.as_bytes().to_vec()),
+            _ => Err(GameError::ResourceNotFound(
+                path.display().to_string(),
+                Vec::new(),
+            )),
+        }
+    }
+
+    fn parse_tmx(text: &str) -> GameResult<MapData> {
+        tmx::parse_map(text, Path::new("/maps/map.tmx"), &mut load)
+    }
+
+    fn parse_json(text: &str) -> GameResult<MapData> {
+        json::parse_map(text, Path::new("/maps/map.tmj"), &mut load)
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn gid_bytes(gids: &[u32]) -> Vec<u8> {
+        gids.iter().flat_map(|gid| gid.to_le_bytes()).collect()
+    }
+
+    fn zlib(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn tiles(data: &MapData, layer: &str) -> Vec<u32> {
+        match data.layers.iter().find(|l| l.name() == layer) {
+            Some(Layer::Tiles(layer)) => layer.tiles.clone(),
+            _ => panic!("no tile layer {}", layer),
+        }
+    }
+
+    fn objects(data: &MapData, layer: &str) -> Vec<Object> {
+        match data.layers.iter().find(|l| l.name() == layer) {
+            Some(Layer::Objects(layer)) => layer.objects.clone(),
+            _ => panic!("no object layer {}", layer),
+        }
+    }
+
+    fn to_map(data: MapData) -> TileMap {
+        TileMap {
+            orientation: data.orientation,
+            width: data.width,
+            height: data.height,
+            tile_width: data.tile_width,
+            tile_height: data.tile_height,
+            background_color: data.background_color,
+            properties: data.properties,
+            tilesets: data.tilesets,
+            layers: data.layers,
+            chunks: Vec::new(),
+            time: 0.0,
+            blend_mode: None,
+        }
+    }
+
+    /// Checks the tilesets shared by the TMX and the JSON test map.
+    fn check_tilesets(tilesets: &[Tileset]) {
+        assert_eq!(tilesets.len(), 2);
+
+        let terrain = &tilesets[0];
+        assert_eq!(terrain.name, "terrain");
+        assert_eq!(terrain.first_gid, 1);
+        assert_eq!((terrain.tile_width, terrain.tile_height), (16, 16));
+        assert_eq!((terrain.tile_count, terrain.columns), (8, 4));
+        assert_eq!(terrain.image_path, Path::new("/maps/terrain.png"));
+        assert_eq!(terrain.tiles[&2].class, "water");
+        assert_eq!(
+            terrain.tiles[&2].animation,
+            vec![
+                (2, Duration::from_millis(100)),
+                (3, Duration::from_millis(300))
+            ]
+        );
+
+        let props = &tilesets[1];
+        assert_eq!(props.name, "props");
+        assert_eq!(props.first_gid, 9);
+        assert_eq!((props.spacing, props.margin), (1, 2));
+        assert_eq!(props.image_path, Path::new("/maps/images/props.png"));
+        assert_eq!(
+            props.tiles[&1].properties["solid"],
+            PropertyValue::Bool(true)
+        );
+        assert_eq!(props.tile_rect(3), Rect::new(35.0, 35.0, 32.0, 32.0));
+    }
+
+    #[test]
+    fn parses_tmx() {
+        let data = parse_tmx(&format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" backgroundcolor="#ff8000">
+ <properties>
+  <property name="gravity" type="float" value="9.5"/>
+  <property name="title" value="Lake"/>
+ </properties>
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="8" columns="4">
+  <image source="terrain.png" width="64" height="32"/>
+  <tile id="2" type="water">
+   <animation>
+    <frame tileid="2" duration="100"/>
+    <frame tileid="3" duration="300"/>
+   </animation>
+  </tile>
+ </tileset>
+ <tileset firstgid="9" source="props.tsx"/>
+ <layer id="1" name="csv" width="3" height="2">
+  <data encoding="csv">
+1,2,3,
+2147483652,0,9
+</data>
+ </layer>
+ <group id="2" name="group">
+  <layer id="3" name="zlib" width="3" height="2" visible="0" opacity="0.5" offsetx="4">
+   <data encoding="base64" compression="zlib">{zlib}</data>
+  </layer>
+  <layer id="4" name="gzip" width="3" height="2">
+   <data encoding="base64" compression="gzip">{gzip}</data>
+  </layer>
+ </group>
+ <layer id="5" name="xml" width="3" height="2">
+  <data>
+   <tile gid="5"/>
+   <tile/>
+   <tile gid="1073741830"/>
+  </data>
+ </layer>
+ <objectgroup id="6" name="entities">
+  <object id="1" name="spawn" x="8" y="24"><point/></object>
+  <object id="2" type="water" x="0" y="0" width="10" height="20"><ellipse/></object>
+  <object id="3" x="1" y="2"><polygon points="0,0 16,0 16,16"/></object>
+ </objectgroup>
+</map>"##,
+            zlib = base64(&zlib(&gid_bytes(&[1, 2, 3, 4, 5, 6]))),
+            gzip = base64(&gzip(&gid_bytes(&[6, 5, 4, 3, 2, 1]))),
+        ))
+        .unwrap();
+
+        assert_eq!(data.orientation, Orientation::Orthogonal);
+        assert_eq!((data.width, data.height), (3, 2));
+        assert_eq!((data.tile_width, data.tile_height), (16, 16));
+        assert_eq!(data.background_color, Some(Color::from_rgb(255, 128, 0)));
+        assert_eq!(data.properties["gravity"], PropertyValue::Float(9.5));
+        assert_eq!(
+            data.properties["title"],
+            PropertyValue::String("Lake".to_string())
+        );
+        check_tilesets(&data.tilesets);
+
+        let names: Vec<&str> = data.layers.iter().map(Layer::name).collect();
+        assert_eq!(names, ["csv", "zlib", "gzip", "xml", "entities"]);
+        // the flip flags are kept
+        assert_eq!(
+            tiles(&data, "csv"),
+            [1, 2, 3, FLIPPED_HORIZONTALLY | 4, 0, 9]
+        );
+        assert_eq!(tiles(&data, "zlib"), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(tiles(&data, "gzip"), [6, 5, 4, 3, 2, 1]);
+        // missing tiles are filled up with empty ones
+        assert_eq!(tiles(&data, "xml"), [5, 0, FLIPPED_VERTICALLY | 6, 0, 0, 0]);
+        match &data.layers[1] {
+            Layer::Tiles(layer) => {
+                assert!(!layer.visible);
+                assert_eq!(layer.opacity, 0.5);
+                assert_eq!(layer.offset, mint::Vector2 { x: 4.0, y: 0.0 });
+            }
+            _ => unreachable!(),
+        }
+
+        let objects = objects(&data, "entities");
+        assert_eq!(objects.len(), 3);
+        assert_eq!(objects[0].name, "spawn");
+        assert_eq!((objects[0].x, objects[0].y), (8.0, 24.0));
+        assert_eq!(objects[0].shape, ObjectShape::Point);
+        assert_eq!(objects[1].class, "water");
+        assert_eq!((objects[1].width, objects[1].height), (10.0, 20.0));
+        assert_eq!(objects[1].shape, ObjectShape::Ellipse);
+        assert_eq!(
+            objects[2].shape,
+            ObjectShape::Polygon(vec![
+                mint::Point2 { x: 0.0, y: 0.0 },
+                mint::Point2 { x: 16.0, y: 0.0 },
+                mint::Point2 { x: 16.0, y: 16.0 },
+            ])
+        );
+
+        let map = to_map(data);
+        assert_eq!(map.tile("csv", 0, 1), Some(4));
+        assert_eq!(map.tile("csv", 1, 1), None);
+        assert_eq!(map.tile("csv", 3, 0), None);
+        assert_eq!(map.tile("xml", 2, 0), Some(6));
+        let (tileset, id) = map.tileset_of(9).unwrap();
+        assert_eq!((tileset.name.as_str(), id), ("props", 0));
+        assert_eq!(map.tile_data(3).unwrap().class, "water");
+    }
+
+    #[test]
+    fn parses_json() {
+        let data = parse_json(&format!(
+            r##"{{
+  "orientation": "isometric",
+  "width": 2,
+  "height": 2,
+  "tilewidth": 32,
+  "tileheight": 16,
+  "backgroundcolor": "#80ff0000",
+  "properties": [{{ "name": "level", "type": "int", "value": 3 }}],
+  "tilesets": [
+    {{
+      "firstgid": 1,
+      "name": "terrain",
+      "tilewidth": 16,
+      "tileheight": 16,
+      "tilecount": 8,
+      "columns": 4,
+      "image": "terrain.png",
+      "tiles": [
+        {{
+          "id": 2,
+          "type": "water",
+          "animation": [{{ "tileid": 2, "duration": 100 }}, {{ "tileid": 3, "duration": 300 }}]
+        }}
+      ]
+    }},
+    {{ "firstgid": 9, "source": "props.tsx" }}
+  ],
+  "layers": [
+    {{ "type": "tilelayer", "name": "array", "width": 2, "height": 2, "data": [1, 3221225474, 9] }},
+    {{
+      "type": "group",
+      "name": "group",
+      "layers": [
+        {{
+          "type": "tilelayer",
+          "name": "base64",
+          "width": 2,
+          "height": 2,
+          "encoding": "base64",
+          "data": "{plain}"
+        }},
+        {{
+          "type": "tilelayer",
+          "name": "zlib",
+          "width": 2,
+          "height": 2,
+          "encoding": "base64",
+          "compression": "zlib",
+          "data": "{zlib}"
+        }}
+      ]
+    }},
+    {{
+      "type": "objectgroup",
+      "name": "entities",
+      "visible": false,
+      "objects": [
+        {{ "id": 1, "name": "spawn", "x": 8, "y": 24, "point": true }},
+        {{ "id": 2, "x": 1, "y": 2, "polyline": [{{ "x": 0, "y": 0 }}, {{ "x": 4, "y": 8 }}] }},
+        {{ "id": 3, "x": 0, "y": 0, "gid": 2147483651 }}
+      ]
+    }}
+  ]
+}}"##,
+            plain = base64(&gid_bytes(&[4, 3, 2, 1])),
+            zlib = base64(&zlib(&gid_bytes(&[FLIPPED_DIAGONALLY | 1, 0, 0, 2]))),
+        ))
+        .unwrap();
+
+        assert_eq!(data.orientation, Orientation::Isometric);
+        assert_eq!((data.width, data.height), (2, 2));
+        assert_eq!((data.tile_width, data.tile_height), (32, 16));
+        assert_eq!(
+            data.background_color,
+            Some(Color::from_rgba(255, 0, 0, 128))
+        );
+        assert_eq!(data.properties["level"], PropertyValue::Int(3));
+        check_tilesets(&data.tilesets);
+
+        let names: Vec<&str> = data.layers.iter().map(Layer::name).collect();
+        assert_eq!(names, ["array", "base64", "zlib", "entities"]);
+        assert_eq!(
+            tiles(&data, "array"),
+            [1, FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | 2, 9, 0]
+        );
+        assert_eq!(tiles(&data, "base64"), [4, 3, 2, 1]);
+        assert_eq!(tiles(&data, "zlib"), [FLIPPED_DIAGONALLY | 1, 0, 0, 2]);
+
+        let objects = objects(&data, "entities");
+        assert_eq!(objects[0].shape, ObjectShape::Point);
+        assert_eq!(
+            objects[1].shape,
+            ObjectShape::Polyline(vec![
+                mint::Point2 { x: 0.0, y: 0.0 },
+                mint::Point2 { x: 4.0, y: 8.0 },
+            ])
+        );
+        assert_eq!(objects[2].gid, Some(FLIPPED_HORIZONTALLY | 3));
+        assert!(matches!(&data.layers[3], Layer::Objects(layer) if !layer.visible));
+
+        let map = to_map(data);
+        assert_eq!(map.tile("array", 1, 0), Some(2));
+        assert_eq!(map.tile("zlib", 0, 0), Some(1));
+    }
+
+    #[test]
+    fn rejects_invalid_maps() {
+        fn error(result: GameResult<MapData>) -> String {
+            match result {
+                Err(GameError::ResourceLoadError(message)) => message,
+                Err(e) => panic!("unexpected error {}", e),
+                Ok(_) => panic!("the map was accepted"),
+            }
+        }
+
+        let tmx = |attributes: &str, layer: &str| {
+            parse_tmx(&format!(
+                r#"<map orientation="orthogonal" tilewidth="16" tileheight="16" {}>{}</map>"#,
+                attributes, layer
+            ))
+        };
+        assert!(error(tmx(r#"infinite="1""#, "")).contains("infinite"));
+        assert!(error(tmx(
+            "",
+            r#"<layer name="huge" width="65536" height="65536"/>"#
+        ))
+        .contains("huge: 65536x65536 tiles are too many"));
+        assert!(error(tmx(
+            "",
+            r#"<layer name="zstd" width="1" height="1"><data encoding="base64" compression="zstd">AQAAAA==</data></layer>"#
+        ))
+        .contains("zstd"));
+        assert!(tmx("", r#"<tileset firstgid="1" source="missing.tsx"/>"#).is_err());
+        assert!(error(parse_tmx(
+            r#"<map orientation="hexagonal" tilewidth="16" tileheight="16"/>"#
+        ))
+        .contains("hexagonal"));
+
+        assert!(error(parse_json(
+            r#"{"orientation": "orthogonal", "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+                "layers": [{"type": "tilelayer", "name": "huge", "width": 4294967295, "height": 2}]}"#
+        ))
+        .contains("huge: 4294967295x2 tiles are too many"));
+        assert!(error(parse_json(
+            r#"{"orientation": "orthogonal", "width": 1, "height": 1, "tilewidth": 16, "tileheight": 16,
+                "infinite": true}"#
+        ))
+        .contains("infinite"));
+    }
+
+    #[test]
+    fn flipped_tiles() {
+        let data = parse_tmx(
+            r#"<map orientation="orthogonal" width="1" height="1" tilewidth="16" tileheight="16"/>"#,
+        )
+        .unwrap();
+        let map = to_map(data);
+        let tileset = Tileset {
+            name: String::new(),
+            first_gid: 1,
+            tile_width: 16,
+            tile_height: 16,
+            spacing: 0,
+            margin: 0,
+            tile_count: 1,
+            columns: 1,
+            image_path: PathBuf::new(),
+            properties: Properties::new(),
+            tiles: HashMap::new(),
+            image: None,
+        };
+        let transform = |flags: u32| {
+            let offset = mint::Vector2 { x: 0.0, y: 0.0 };
+            let (param, bounds) = map.tile_param(&tileset, 0, 0, flags | 1, offset, Color::WHITE);
+            assert_eq!(bounds, Rect::new(0.0, 0.0, 16.0, 16.0));
+            match param.trans {
+                Transform::Values {
+                    rotation, scale, ..
+                } => (rotation, (scale.x, scale.y)),
+                _ => unreachable!(),
+            }
+        };
+
+        let quarter = std::f32::consts::FRAC_PI_2;
+        assert_eq!(transform(0), (0.0, (1.0, 1.0)));
+        assert_eq!(transform(FLIPPED_HORIZONTALLY), (0.0, (-1.0, 1.0)));
+        assert_eq!(transform(FLIPPED_VERTICALLY), (0.0, (1.0, -1.0)));
+        assert_eq!(
+            transform(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY),
+            (0.0, (-1.0, -1.0))
+        );
+        assert_eq!(transform(FLIPPED_DIAGONALLY), (quarter, (1.0, -1.0)));
+        // rotated by 90° clockwise
+        assert_eq!(
+            transform(FLIPPED_DIAGONALLY | FLIPPED_HORIZONTALLY),
+            (quarter, (1.0, 1.0))
+        );
+        // rotated by 90° counterclockwise
+        assert_eq!(
+            transform(FLIPPED_DIAGONALLY | FLIPPED_VERTICALLY),
+            (quarter, (-1.0, -1.0))
+        );
+        // hexagonal rotations are ignored
+        assert_eq!(transform(ROTATED_HEXAGONAL), (0.0, (1.0, 1.0)));
+    }
+
+    #[test]
+    fn animation_frames() {
+        let animation = [
+            (4, Duration::from_millis(100)),
+            (5, Duration::from_millis(300)),
+            (6, Duration::from_millis(100)),
+        ];
+        assert_eq!(animation_frame(&animation, 0.0), 4);
+        assert_eq!(animation_frame(&animation, 0.05), 4);
+        assert_eq!(animation_frame(&animation, 0.1), 5);
+        assert_eq!(animation_frame(&animation, 0.35), 5);
+        assert_eq!(animation_frame(&animation, 0.45), 6);
+        // loops around
+        assert_eq!(animation_frame(&animation, 0.55), 4);
+        assert_eq!(animation_frame(&animation, 10.2), 5);
+
+        assert_eq!(animation_frame(&[], 1.0), 0);
+        assert_eq!(animation_frame(&[(7, Duration::ZERO)], 1.0), 7);
+    }
+}
@@ -0,0 +1,369 @@
+//! Reading maps and tilesets saved as JSON (`.tmj`, `.tsj`, `.json`).
+
+use std::path::Path;
+use std::time::Duration;
+
+use super::{
+    decode_base64_tiles, layer_len, map_error, parse_color, parse_property, relative_to, Layer,
+    MapData, Object, ObjectLayer, ObjectShape, Orientation, Properties, TileData, TileLayer,
+    Tileset,
+};
+use crate::GameResult;
+
+#[derive(Deserialize)]
+struct MapJson {
+    orientation: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    backgroundcolor: Option<String>,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+    #[serde(default)]
+    tilesets: Vec<TilesetJson>,
+    #[serde(default)]
+    layers: Vec<LayerJson>,
+}
+
+#[derive(Deserialize)]
+struct PropertyJson {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TilesetJson {
+    firstgid: Option<u32>,
+    /// Set for external tilesets, which have nothing else.
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    columns: u32,
+    image: Option<String>,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+    #[serde(default)]
+    tiles: Vec<TileJson>,
+}
+
+#[derive(Deserialize)]
+struct TileJson {
+    id: u32,
+    #[serde(alias = "type", default)]
+    class: String,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+    #[serde(default)]
+    animation: Vec<FrameJson>,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    tileid: u32,
+    duration: u64,
+}
+
+#[derive(Deserialize)]
+struct LayerJson {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    /// An array of global ids, or a base64 string.
+    data: Option<serde_json::Value>,
+    compression: Option<String>,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+    #[serde(default)]
+    objects: Vec<ObjectJson>,
+    /// The children of group layers.
+    #[serde(default)]
+    layers: Vec<LayerJson>,
+}
+
+#[derive(Deserialize)]
+struct ObjectJson {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(alias = "type", default)]
+    class: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    gid: Option<u32>,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    point: bool,
+    polygon: Option<Vec<PointJson>>,
+    polyline: Option<Vec<PointJson>>,
+    text: Option<TextJson>,
+    #[serde(default)]
+    properties: Vec<PropertyJson>,
+}
+
+#[derive(Deserialize)]
+struct PointJson {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct TextJson {
+    #[serde(default)]
+    text: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+pub(super) fn parse_map(
+    text: &str,
+    path: &Path,
+    load: &mut dyn FnMut(&Path) -> GameResult<Vec<u8>>,
+) -> GameResult<MapData> {
+    let map: MapJson = serde_json::from_str(text).map_err(|e| map_error(path, e))?;
+    if map.infinite {
+        return Err(map_error(path, "infinite maps aren't supported"));
+    }
+    let orientation = match map.orientation.as_str() {
+        "orthogonal" => Orientation::Orthogonal,
+        "isometric" => Orientation::Isometric,
+        other => {
+            return Err(map_error(
+                path,
+                format!("unsupported orientation {:?}", other),
+            ))
+        }
+    };
+
+    let mut tilesets = Vec::new();
+    for tileset in map.tilesets {
+        let first_gid = tileset.firstgid.unwrap_or(1);
+        let tileset = match &tileset.source {
+            Some(source) => {
+                let source_path = relative_to(path, source);
+                let bytes = load(&source_path)?;
+                let text = std::str::from_utf8(&bytes).map_err(|e| map_error(&source_path, e))?;
+                match source_path.extension().and_then(|e| e.to_str()) {
+                    Some("tsx") => super::tmx::parse_tileset(text, &source_path, first_gid)?,
+                    _ => parse_tileset(text, &source_path, first_gid)?,
+                }
+            }
+            None => convert_tileset(tileset, path, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    convert_layers(map.layers, path, &mut layers)?;
+
+    Ok(MapData {
+        orientation,
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        background_color: map.backgroundcolor.as_deref().and_then(parse_color),
+        properties: convert_properties(map.properties),
+        tilesets,
+        layers,
+    })
+}
+
+/// Parses an external tileset.
+pub(super) fn parse_tileset(text: &str, path: &Path, first_gid: u32) -> GameResult<Tileset> {
+    let tileset: TilesetJson = serde_json::from_str(text).map_err(|e| map_error(path, e))?;
+    convert_tileset(tileset, path, first_gid)
+}
+
+/// Converts a tileset, `path` being the file it's in.
+fn convert_tileset(tileset: TilesetJson, path: &Path, first_gid: u32) -> GameResult<Tileset> {
+    let image = tileset.image.ok_or_else(|| {
+        map_error(
+            path,
+            "tilesets made of separate images aren't supported, the tileset has no image",
+        )
+    })?;
+    let tiles = tileset
+        .tiles
+        .into_iter()
+        .map(|tile| {
+            let animation = tile
+                .animation
+                .iter()
+                .map(|frame| (frame.tileid, Duration::from_millis(frame.duration)))
+                .collect();
+            let data = TileData {
+                properties: convert_properties(tile.properties),
+                class: tile.class,
+                animation,
+            };
+            (tile.id, data)
+        })
+        .collect();
+
+    Ok(Tileset {
+        name: tileset.name,
+        first_gid,
+        tile_width: tileset.tilewidth,
+        tile_height: tileset.tileheight,
+        spacing: tileset.spacing,
+        margin: tileset.margin,
+        tile_count: tileset.tilecount,
+        columns: tileset.columns,
+        image_path: relative_to(path, &image),
+        properties: convert_properties(tileset.properties),
+        tiles,
+        image: None,
+    })
+}
+
+/// Converts layers, flattening groups.
+fn convert_layers(source: Vec<LayerJson>, path: &Path, layers: &mut Vec<Layer>) -> GameResult {
+    for layer in source {
+        let offset = mint::Vector2 {
+            x: layer.offsetx,
+            y: layer.offsety,
+        };
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let mut tiles = match &layer.data {
+                    Some(serde_json::Value::Array(gids)) => gids
+                        .iter()
+                        .map(|gid| gid.as_u64().unwrap_or(0) as u32)
+                        .collect(),
+                    Some(serde_json::Value::String(data)) => {
+                        decode_base64_tiles(data, layer.compression.as_deref())
+                            .map_err(|e| map_error(path, format!("{}: {}", layer.name, e)))?
+                    }
+                    _ => Vec::new(),
+                };
+                tiles.resize(layer_len(path, &layer.name, layer.width, layer.height)?, 0);
+                layers.push(Layer::Tiles(TileLayer {
+                    name: layer.name,
+                    width: layer.width,
+                    height: layer.height,
+                    tiles,
+                    visible: layer.visible,
+                    opacity: layer.opacity,
+                    offset,
+                    properties: convert_properties(layer.properties),
+                }));
+            }
+            "objectgroup" => layers.push(Layer::Objects(ObjectLayer {
+                name: layer.name,
+                objects: layer.objects.into_iter().map(convert_object).collect(),
+                visible: layer.visible,
+                opacity: layer.opacity,
+                offset,
+                properties: convert_properties(layer.properties),
+            })),
+            "group" => convert_layers(layer.layers, path, layers)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn convert_object(object: ObjectJson) -> Object {
+    let points = |points: Vec<PointJson>| {
+        points
+            .into_iter()
+            .map(|point| mint::Point2 {
+                x: point.x,
+                y: point.y,
+            })
+            .collect()
+    };
+    let shape = if object.ellipse {
+        ObjectShape::Ellipse
+    } else if object.point {
+        ObjectShape::Point
+    } else if let Some(polygon) = object.polygon {
+        ObjectShape::Polygon(points(polygon))
+    } else if let Some(polyline) = object.polyline {
+        ObjectShape::Polyline(points(polyline))
+    } else if let Some(text) = object.text {
+        ObjectShape::Text(text.text)
+    } else {
+        ObjectShape::Rect
+    };
+
+    Object {
+        id: object.id,
+        name: object.name,
+        class: object.class,
+        x: object.x,
+        y: object.y,
+        width: object.width,
+        height: object.height,
+        rotation: object.rotation,
+        gid: object.gid,
+        visible: object.visible,
+        shape,
+        properties: convert_properties(object.properties),
+    }
+}
+
+fn convert_properties(properties: Vec<PropertyJson>) -> Properties {
+    properties
+        .into_iter()
+        .filter_map(|property| {
+            let value = match property.value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            let kind = if property.kind.is_empty() {
+                "string"
+            } else {
+                &property.kind
+            };
+            Some((property.name, parse_property(kind, &value)?))
+        })
+        .collect()
+}
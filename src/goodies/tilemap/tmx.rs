@@ -0,0 +1,290 @@
+//! Reading maps and tilesets saved as XML (`.tmx`, `.tsx`).
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
+use roxmltree::{Document, Node};
+
+use super::{
+    decode_base64_tiles, layer_len, map_error, parse_color, parse_property, relative_to, Layer,
+    MapData, Object, ObjectLayer, ObjectShape, Orientation, Properties, TileData, TileLayer,
+    Tileset,
+};
+use crate::GameResult;
+
+pub(super) fn parse_map(
+    text: &str,
+    path: &Path,
+    load: &mut dyn FnMut(&Path) -> GameResult<Vec<u8>>,
+) -> GameResult<MapData> {
+    let document = Document::parse(text).map_err(|e| map_error(path, e))?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        return Err(map_error(path, "the root element isn't <map>"));
+    }
+    if attribute(map, "infinite") == Some(1) {
+        return Err(map_error(path, "infinite maps aren't supported"));
+    }
+    let orientation = match map.attribute("orientation") {
+        Some("orthogonal") => Orientation::Orthogonal,
+        Some("isometric") => Orientation::Isometric,
+        other => {
+            return Err(map_error(
+                path,
+                format!("unsupported orientation {:?}", other.unwrap_or_default()),
+            ))
+        }
+    };
+
+    let mut tilesets = Vec::new();
+    for node in map.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = attribute(node, "firstgid").unwrap_or(1);
+        let tileset = match node.attribute("source") {
+            Some(source) => {
+                let source_path = relative_to(path, source);
+                let bytes = load(&source_path)?;
+                let text = std::str::from_utf8(&bytes).map_err(|e| map_error(&source_path, e))?;
+                match source_path.extension().and_then(|e| e.to_str()) {
+                    Some("tsj") | Some("json") => {
+                        super::json::parse_tileset(text, &source_path, first_gid)?
+                    }
+                    _ => parse_tileset(text, &source_path, first_gid)?,
+                }
+            }
+            None => tileset_from_node(node, path, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    parse_layers(map, path, &mut layers)?;
+
+    Ok(MapData {
+        orientation,
+        width: attribute(map, "width").unwrap_or(0),
+        height: attribute(map, "height").unwrap_or(0),
+        tile_width: attribute(map, "tilewidth").unwrap_or(0),
+        tile_height: attribute(map, "tileheight").unwrap_or(0),
+        background_color: map.attribute("backgroundcolor").and_then(parse_color),
+        properties: properties(map),
+        tilesets,
+        layers,
+    })
+}
+
+/// Parses an external tileset.
+pub(super) fn parse_tileset(text: &str, path: &Path, first_gid: u32) -> GameResult<Tileset> {
+    let document = Document::parse(text).map_err(|e| map_error(path, e))?;
+    let node = document.root_element();
+    if !node.has_tag_name("tileset") {
+        return Err(map_error(path, "the root element isn't <tileset>"));
+    }
+    tileset_from_node(node, path, first_gid)
+}
+
+/// Reads a `<tileset>` element, `path` being the file it's in.
+fn tileset_from_node(node: Node, path: &Path, first_gid: u32) -> GameResult<Tileset> {
+    let image = node
+        .children()
+        .find(|n| n.has_tag_name("image"))
+        .and_then(|image| image.attribute("source"))
+        .ok_or_else(|| {
+            map_error(
+                path,
+                "tilesets made of separate images aren't supported, the tileset has no <image>",
+            )
+        })?;
+
+    let mut tiles = HashMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let id = attribute(tile, "id").unwrap_or(0);
+        let animation = tile
+            .children()
+            .filter(|n| n.has_tag_name("animation"))
+            .flat_map(|animation| animation.children().filter(|n| n.has_tag_name("frame")))
+            .map(|frame| {
+                (
+                    attribute(frame, "tileid").unwrap_or(0),
+                    Duration::from_millis(attribute(frame, "duration").unwrap_or(100)),
+                )
+            })
+            .collect();
+        let class = tile
+            .attribute("class")
+            .or_else(|| tile.attribute("type"))
+            .unwrap_or_default();
+        tiles.insert(
+            id,
+            TileData {
+                properties: properties(tile),
+                class: class.to_string(),
+                animation,
+            },
+        );
+    }
+
+    let tile_width = attribute(node, "tilewidth").unwrap_or(0);
+    let columns = attribute(node, "columns").unwrap_or(0);
+    Ok(Tileset {
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        first_gid,
+        tile_width,
+        tile_height: attribute(node, "tileheight").unwrap_or(0),
+        spacing: attribute(node, "spacing").unwrap_or(0),
+        margin: attribute(node, "margin").unwrap_or(0),
+        tile_count: attribute(node, "tilecount").unwrap_or(0),
+        columns,
+        image_path: relative_to(path, image),
+        properties: properties(node),
+        tiles,
+        image: None,
+    })
+}
+
+/// Reads all layers inside of `parent`, flattening groups.
+fn parse_layers(parent: Node, path: &Path, layers: &mut Vec<Layer>) -> GameResult {
+    for node in parent.children().filter(|n| n.is_element()) {
+        let name = node.attribute("name").unwrap_or_default().to_string();
+        let visible = attribute(node, "visible").unwrap_or(1) != 0;
+        let opacity = attribute(node, "opacity").unwrap_or(1.0);
+        let offset = mint::Vector2 {
+            x: attribute(node, "offsetx").unwrap_or(0.0),
+            y: attribute(node, "offsety").unwrap_or(0.0),
+        };
+
+        match node.tag_name().name() {
+            "layer" => {
+                let width = attribute(node, "width").unwrap_or(0);
+                let height = attribute(node, "height").unwrap_or(0);
+                let mut tiles = match node.children().find(|n| n.has_tag_name("data")) {
+                    Some(data) => {
+                        parse_data(data).map_err(|e| map_error(path, format!("{}: {}", name, e)))?
+                    }
+                    None => Vec::new(),
+                };
+                tiles.resize(layer_len(path, &name, width, height)?, 0);
+                layers.push(Layer::Tiles(TileLayer {
+                    name,
+                    width,
+                    height,
+                    tiles,
+                    visible,
+                    opacity,
+                    offset,
+                    properties: properties(node),
+                }));
+            }
+            "objectgroup" => {
+                let objects = node
+                    .children()
+                    .filter(|n| n.has_tag_name("object"))
+                    .map(parse_object)
+                    .collect();
+                layers.push(Layer::Objects(ObjectLayer {
+                    name,
+                    objects,
+                    visible,
+                    opacity,
+                    offset,
+                    properties: properties(node),
+                }));
+            }
+            "group" => parse_layers(node, path, layers)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the global tile ids of a `<data>` element.
+fn parse_data(data: Node) -> Result<Vec<u32>, String> {
+    let text = data.text().unwrap_or_default();
+    match data.attribute("encoding") {
+        Some("csv") => text
+            .split(',')
+            .map(|gid| gid.trim().parse::<u32>().map_err(|e| e.to_string()))
+            .collect(),
+        Some("base64") => decode_base64_tiles(text, data.attribute("compression")),
+        Some(other) => Err(format!("unsupported encoding '{}'", other)),
+        None => Ok(data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| attribute(tile, "gid").unwrap_or(0))
+            .collect()),
+    }
+}
+
+fn parse_object(node: Node) -> Object {
+    let points = |name: &str| -> Vec<mint::Point2<f32>> {
+        node.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.attribute("points"))
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|point| {
+                let (x, y) = point.split_once(',')?;
+                Some(mint::Point2 {
+                    x: x.parse().ok()?,
+                    y: y.parse().ok()?,
+                })
+            })
+            .collect()
+    };
+    let child = |name: &str| node.children().find(|n| n.has_tag_name(name));
+
+    let shape = if child("ellipse").is_some() {
+        ObjectShape::Ellipse
+    } else if child("point").is_some() {
+        ObjectShape::Point
+    } else if child("polygon").is_some() {
+        ObjectShape::Polygon(points("polygon"))
+    } else if child("polyline").is_some() {
+        ObjectShape::Polyline(points("polyline"))
+    } else if let Some(text) = child("text") {
+        ObjectShape::Text(text.text().unwrap_or_default().to_string())
+    } else {
+        ObjectShape::Rect
+    };
+
+    Object {
+        id: attribute(node, "id").unwrap_or(0),
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        class: node
+            .attribute("class")
+            .or_else(|| node.attribute("type"))
+            .unwrap_or_default()
+            .to_string(),
+        x: attribute(node, "x").unwrap_or(0.0),
+        y: attribute(node, "y").unwrap_or(0.0),
+        width: attribute(node, "width").unwrap_or(0.0),
+        height: attribute(node, "height").unwrap_or(0.0),
+        rotation: attribute(node, "rotation").unwrap_or(0.0),
+        gid: attribute(node, "gid"),
+        visible: attribute(node, "visible").unwrap_or(1) != 0,
+        shape,
+        properties: properties(node),
+    }
+}
+
+/// Reads the `<properties>` of an element.
+fn properties(node: Node) -> Properties {
+    node.children()
+        .filter(|n| n.has_tag_name("properties"))
+        .flat_map(|properties| properties.children().filter(|n| n.has_tag_name("property")))
+        .filter_map(|property| {
+            let name = property.attribute("name")?;
+            // multiline strings are stored as text instead of an attribute
+            let value = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or_default();
+            let kind = property.attribute("type").unwrap_or("string");
+            Some((name.to_string(), parse_property(kind, value)?))
+        })
+        .collect()
+}
+
+fn attribute<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+    node.attribute(name)?.parse().ok()
+}
@@ -0,0 +1,898 @@
+//! Tweening: moving values smoothly from one state to another over time.
+//!
+//! A [`Tween`](struct.Tween.html) moves a [`TweenValue`](struct.TweenValue.html), a shared cell
+//! the game reads from whenever it needs the current value, towards a target value with one of
+//! the common [`Easing`](enum.Easing.html) functions. Tweens can be chained with
+//! [`Sequence`](struct.Sequence.html), run side by side with [`Parallel`](struct.Parallel.html),
+//! delayed and repeated, and are ticked by a [`TweenManager`](struct.TweenManager.html):
+//!
+//! ```rust, ignore
+//! let position = TweenValue::new(Point2 { x: -200.0, y: 100.0 });
+//! let alpha = TweenValue::new(0.0);
+//! let slide_in = Parallel::new()
+//!     .with(Tween::new(&position, Point2 { x: 20.0, y: 100.0 }, Duration::from_millis(400))
+//!         .easing(Easing::BackOut))
+//!     .with(Tween::new(&alpha, 1.0, Duration::from_millis(250)));
+//! let id = tweens.add(slide_in);
+//!
+//! // in `update`
+//! tweens.update(ctx);
+//! if tweens.completed().contains(&id) {
+//!     // the panel is in place
+//! }
+//!
+//! // in `draw`
+//! let param = DrawParam::new()
+//!     .dest(position.get())
+//!     .color(Color::new(1.0, 1.0, 1.0, alpha.get()));
+//! ```
+//!
+//! Everything is played by seeking to the time elapsed since it started, so tweens never drift
+//! apart, no matter how many are combined or how long a frame takes.
+
+use std::cell::Cell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::graphics::{Color, DrawParam, Rect, Transform};
+use crate::{timer, Context};
+
+/// The easing functions by Robert Penner, mapping the progress of a tween (from 0.0 to 1.0) to
+/// how far the value has moved.
+///
+/// `In` starts slowly and speeds up, `Out` starts fast and slows down, `InOut` does both.
+/// `Back` and `Elastic` overshoot, so their result leaves the range of 0.0 to 1.0 for a moment.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    QuartIn,
+    QuartOut,
+    QuartInOut,
+    QuintIn,
+    QuintOut,
+    QuintInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    CircIn,
+    CircOut,
+    CircInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+impl Easing {
+    /// Applies the easing to `t`, which is clamped to the range of 0.0 to 1.0.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        let in_out = |ease_in: fn(f32) -> f32, t: f32| {
+            if t < 0.5 {
+                ease_in(2.0 * t) / 2.0
+            } else {
+                1.0 - ease_in(2.0 - 2.0 * t) / 2.0
+            }
+        };
+        let out = |ease_in: fn(f32) -> f32, t: f32| 1.0 - ease_in(1.0 - t);
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => quad(t),
+            Easing::QuadOut => out(quad, t),
+            Easing::QuadInOut => in_out(quad, t),
+            Easing::CubicIn => cubic(t),
+            Easing::CubicOut => out(cubic, t),
+            Easing::CubicInOut => in_out(cubic, t),
+            Easing::QuartIn => quart(t),
+            Easing::QuartOut => out(quart, t),
+            Easing::QuartInOut => in_out(quart, t),
+            Easing::QuintIn => quint(t),
+            Easing::QuintOut => out(quint, t),
+            Easing::QuintInOut => in_out(quint, t),
+            Easing::SineIn => sine(t),
+            Easing::SineOut => out(sine, t),
+            Easing::SineInOut => in_out(sine, t),
+            Easing::ExpoIn => expo(t),
+            Easing::ExpoOut => out(expo, t),
+            Easing::ExpoInOut => in_out(expo, t),
+            Easing::CircIn => circ(t),
+            Easing::CircOut => out(circ, t),
+            Easing::CircInOut => in_out(circ, t),
+            Easing::BackIn => back(t),
+            Easing::BackOut => out(back, t),
+            Easing::BackInOut => in_out(back, t),
+            Easing::ElasticIn => elastic(t),
+            Easing::ElasticOut => out(elastic, t),
+            Easing::ElasticInOut => in_out(elastic, t),
+            Easing::BounceIn => bounce_in(t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => in_out(bounce_in, t),
+        }
+    }
+}
+
+fn quad(t: f32) -> f32 {
+    t * t
+}
+
+fn cubic(t: f32) -> f32 {
+    t * t * t
+}
+
+fn quart(t: f32) -> f32 {
+    t * t * t * t
+}
+
+fn quint(t: f32) -> f32 {
+    t * t * t * t * t
+}
+
+fn sine(t: f32) -> f32 {
+    1.0 - (t * PI / 2.0).cos()
+}
+
+fn expo(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2f32.powf(10.0 * t - 10.0)
+    }
+}
+
+fn circ(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).max(0.0).sqrt()
+}
+
+fn back(t: f32) -> f32 {
+    const OVERSHOOT: f32 = 1.70158;
+    t * t * ((OVERSHOOT + 1.0) * t - OVERSHOOT)
+}
+
+fn elastic(t: f32) -> f32 {
+    if t <= 0.0 || t >= 1.0 {
+        t
+    } else {
+        -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+    }
+}
+
+fn bounce_in(t: f32) -> f32 {
+    1.0 - bounce_out(1.0 - t)
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// A value that can be tweened, by interpolating linearly between two states.
+pub trait Tweenable: Copy {
+    /// Returns the value `t` of the way from `from` to `to`. `t` usually ranges from 0.0 to 1.0,
+    /// but overshooting easings go beyond that.
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+impl Tweenable for f32 {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        lerp(from, to, t)
+    }
+}
+
+impl Tweenable for mint::Point2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        mint::Point2 {
+            x: lerp(from.x, to.x, t),
+            y: lerp(from.y, to.y, t),
+        }
+    }
+}
+
+impl Tweenable for mint::Vector2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        mint::Vector2 {
+            x: lerp(from.x, to.x, t),
+            y: lerp(from.y, to.y, t),
+        }
+    }
+}
+
+impl Tweenable for cgmath::Point2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        cgmath::Point2::new(lerp(from.x, to.x, t), lerp(from.y, to.y, t))
+    }
+}
+
+impl Tweenable for cgmath::Vector2<f32> {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        cgmath::Vector2::new(lerp(from.x, to.x, t), lerp(from.y, to.y, t))
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Color::new(
+            lerp(from.r, to.r, t),
+            lerp(from.g, to.g, t),
+            lerp(from.b, to.b, t),
+            lerp(from.a, to.a, t),
+        )
+    }
+}
+
+impl Tweenable for Rect {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        Rect::new(
+            lerp(from.x, to.x, t),
+            lerp(from.y, to.y, t),
+            lerp(from.w, to.w, t),
+            lerp(from.h, to.h, t),
+        )
+    }
+}
+
+impl Tweenable for DrawParam {
+    /// Interpolates source rect, color and transform. Transforms made of values are
+    /// interpolated value by value, matrices (or a mix of both) element by element.
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        let trans = match (from.trans, to.trans) {
+            (
+                Transform::Values {
+                    dest: from_dest,
+                    rotation: from_rotation,
+                    scale: from_scale,
+                    offset: from_offset,
+                },
+                Transform::Values {
+                    dest,
+                    rotation,
+                    scale,
+                    offset,
+                },
+            ) => Transform::Values {
+                dest: Tweenable::lerp(from_dest, dest, t),
+                rotation: lerp(from_rotation, rotation, t),
+                scale: Tweenable::lerp(from_scale, scale, t),
+                offset: Tweenable::lerp(from_offset, offset, t),
+            },
+            (from_trans, to_trans) => {
+                let from: [f32; 16] = from_trans.to_bare_matrix().into();
+                let to: [f32; 16] = to_trans.to_bare_matrix().into();
+                let mut matrix = [0.0; 16];
+                for (i, m) in matrix.iter_mut().enumerate() {
+                    *m = lerp(from[i], to[i], t);
+                }
+                Transform::Matrix(matrix.into())
+            }
+        };
+        DrawParam {
+            src: Tweenable::lerp(from.src, to.src, t),
+            color: Tweenable::lerp(from.color, to.color, t),
+            trans,
+        }
+    }
+}
+
+/// A value shared between the game and the tweens moving it.
+///
+/// Cloning it doesn't copy the value, but creates another handle to it.
+#[derive(Default)]
+pub struct TweenValue<T>(Rc<Cell<T>>);
+
+impl<T: Copy + std::fmt::Debug> std::fmt::Debug for TweenValue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TweenValue").field(&self.get()).finish()
+    }
+}
+
+impl<T> Clone for TweenValue<T> {
+    fn clone(&self) -> Self {
+        TweenValue(self.0.clone())
+    }
+}
+
+impl<T: Copy> TweenValue<T> {
+    pub fn new(value: T) -> Self {
+        TweenValue(Rc::new(Cell::new(value)))
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> T {
+        self.0.get()
+    }
+
+    /// Sets the value, which will be overwritten by any tween still moving it.
+    pub fn set(&self, value: T) {
+        self.0.set(value)
+    }
+}
+
+/// Something that changes values over time: a [`Tween`](struct.Tween.html) or any combination
+/// of them.
+pub trait Tweening {
+    /// Returns how long it takes to play in seconds, or `None` if it goes on forever.
+    fn duration(&self) -> Option<f32>;
+
+    /// Sets all values to the state they are in `time` seconds after starting.
+    fn seek(&mut self, time: f32);
+
+    /// Plays this `times` times in a row.
+    fn repeat(self, times: u32) -> Repeat
+    where
+        Self: Sized + 'static,
+    {
+        Repeat::new(self, Some(times))
+    }
+
+    /// Plays this over and over again.
+    fn repeat_forever(self) -> Repeat
+    where
+        Self: Sized + 'static,
+    {
+        Repeat::new(self, None)
+    }
+}
+
+/// Moves a [`TweenValue`](struct.TweenValue.html) to a new value.
+pub struct Tween<T> {
+    target: TweenValue<T>,
+    from: Option<T>,
+    to: T,
+    duration: f32,
+    delay: f32,
+    easing: Easing,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Creates a tween moving `target` to `to` within `duration`.
+    ///
+    /// Unless set through [`from`](#method.from), it starts at whatever value `target` has when
+    /// the tween starts, so tweens can be chained easily.
+    pub fn new(target: &TweenValue<T>, to: T, duration: Duration) -> Self {
+        Tween {
+            target: target.clone(),
+            from: None,
+            to,
+            duration: duration.as_secs_f32(),
+            delay: 0.0,
+            easing: Easing::Linear,
+        }
+    }
+
+    /// Sets the value to start at.
+    pub fn from(mut self, from: T) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Sets the easing function.
+    ///
+    /// Default: `Easing::Linear`
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Sets how long to wait before starting to move the value.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay.as_secs_f32();
+        self
+    }
+}
+
+impl<T: Tweenable> Tweening for Tween<T> {
+    fn duration(&self) -> Option<f32> {
+        Some(self.delay + self.duration)
+    }
+
+    fn seek(&mut self, time: f32) {
+        if time < self.delay && self.from.is_none() {
+            return;
+        }
+        let from = *self.from.get_or_insert_with(|| self.target.get());
+        let progress = if self.duration > 0.0 {
+            (time - self.delay) / self.duration
+        } else {
+            1.0
+        };
+        self.target
+            .set(T::lerp(from, self.to, self.easing.apply(progress)));
+    }
+}
+
+/// Waits without changing anything, e.g. as a pause in a [`Sequence`](struct.Sequence.html).
+#[derive(Debug, Copy, Clone)]
+pub struct Delay(f32);
+
+impl Delay {
+    pub fn new(duration: Duration) -> Self {
+        Delay(duration.as_secs_f32())
+    }
+}
+
+impl Tweening for Delay {
+    fn duration(&self) -> Option<f32> {
+        Some(self.0)
+    }
+
+    fn seek(&mut self, _time: f32) {}
+}
+
+/// Plays tweens one after another.
+#[derive(Default)]
+pub struct Sequence {
+    steps: Vec<Box<dyn Tweening>>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Sequence::default()
+    }
+
+    /// Appends a step, which starts once all steps before it are done.
+    pub fn then<A: Tweening + 'static>(mut self, step: A) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Appends a pause.
+    pub fn wait(self, duration: Duration) -> Self {
+        self.then(Delay::new(duration))
+    }
+}
+
+impl Tweening for Sequence {
+    fn duration(&self) -> Option<f32> {
+        self.steps.iter().map(|step| step.duration()).sum()
+    }
+
+    fn seek(&mut self, time: f32) {
+        // steps after `time` aren't touched, as they may start at whatever the steps before them
+        // leave their values at
+        let mut start = 0.0;
+        for step in self.steps.iter_mut() {
+            if time < start {
+                break;
+            }
+            match step.duration() {
+                Some(duration) => {
+                    step.seek((time - start).min(duration));
+                    start += duration;
+                }
+                None => {
+                    step.seek(time - start);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Plays tweens at the same time, until the longest one is done.
+#[derive(Default)]
+pub struct Parallel {
+    tracks: Vec<Box<dyn Tweening>>,
+}
+
+impl Parallel {
+    pub fn new() -> Self {
+        Parallel::default()
+    }
+
+    /// Adds a tween to play alongside the others.
+    pub fn with<A: Tweening + 'static>(mut self, track: A) -> Self {
+        self.tracks.push(Box::new(track));
+        self
+    }
+}
+
+impl Tweening for Parallel {
+    fn duration(&self) -> Option<f32> {
+        self.tracks
+            .iter()
+            .map(|track| track.duration())
+            .try_fold(0.0f32, |max, duration| duration.map(|d| max.max(d)))
+    }
+
+    fn seek(&mut self, time: f32) {
+        for track in self.tracks.iter_mut() {
+            match track.duration() {
+                Some(duration) => track.seek(time.min(duration)),
+                None => track.seek(time),
+            }
+        }
+    }
+}
+
+/// Plays a tween several times or forever, created through
+/// [`Tweening::repeat`](trait.Tweening.html#method.repeat) and
+/// [`Tweening::repeat_forever`](trait.Tweening.html#method.repeat_forever).
+pub struct Repeat {
+    inner: Box<dyn Tweening>,
+    times: Option<u32>,
+    yoyo: bool,
+}
+
+impl Repeat {
+    fn new<A: Tweening + 'static>(inner: A, times: Option<u32>) -> Self {
+        Repeat {
+            inner: Box::new(inner),
+            times,
+            yoyo: false,
+        }
+    }
+
+    /// Makes every second pass play backwards, so the values move back and forth.
+    pub fn yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+}
+
+impl Tweening for Repeat {
+    fn duration(&self) -> Option<f32> {
+        let times = self.times?;
+        self.inner.duration().map(|d| d * times as f32)
+    }
+
+    fn seek(&mut self, time: f32) {
+        let duration = match self.inner.duration() {
+            Some(duration) if duration > 0.0 => duration,
+            _ => return self.inner.seek(time),
+        };
+        let mut pass = (time / duration).floor() as u32;
+        let mut offset = time - pass as f32 * duration;
+        if self.times.is_some_and(|times| pass >= times) {
+            // stay at the end of the last pass
+            pass = self.times.unwrap_or(1).saturating_sub(1);
+            offset = duration;
+        }
+        if self.yoyo && pass % 2 == 1 {
+            offset = duration - offset;
+        }
+        self.inner.seek(offset);
+    }
+}
+
+/// Identifies a tween added to a [`TweenManager`](struct.TweenManager.html).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TweenId(usize);
+
+/// Plays tweens, advancing them by `timer::delta` on every update.
+#[derive(Default)]
+pub struct TweenManager {
+    tweens: Vec<(TweenId, f32, Box<dyn Tweening>)>,
+    next_id: usize,
+    paused: bool,
+    /// The tweens completed during the last update.
+    completed: Vec<TweenId>,
+}
+
+impl TweenManager {
+    pub fn new() -> Self {
+        TweenManager::default()
+    }
+
+    /// Starts playing a tween (or a combination of tweens) on the next update.
+    pub fn add<A: Tweening + 'static>(&mut self, tween: A) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.tweens.push((id, 0.0, Box::new(tween)));
+        id
+    }
+
+    /// Stops a tween, leaving its values where they are.
+    pub fn cancel(&mut self, id: TweenId) {
+        self.tweens.retain(|(other, ..)| *other != id);
+    }
+
+    /// Stops all tweens, leaving their values where they are.
+    pub fn clear(&mut self) {
+        self.tweens.clear();
+    }
+
+    /// Returns whether the tween is still playing.
+    pub fn is_active(&self, id: TweenId) -> bool {
+        self.tweens.iter().any(|(other, ..)| *other == id)
+    }
+
+    /// Returns the number of tweens still playing.
+    pub fn len(&self) -> usize {
+        self.tweens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tweens.is_empty()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advances all tweens by the time passed since the last frame, see `timer::delta`.
+    pub fn update(&mut self, ctx: &Context) {
+        self.advance(timer::delta(ctx));
+    }
+
+    /// Advances all tweens by the given time.
+    pub fn advance(&mut self, dt: Duration) {
+        self.completed.clear();
+        if self.paused {
+            return;
+        }
+        let dt = dt.as_secs_f32();
+        let completed = &mut self.completed;
+        self.tweens.retain_mut(|(id, elapsed, tween)| {
+            *elapsed += dt;
+            tween.seek(*elapsed);
+            let done = tween.duration().is_some_and(|d| *elapsed >= d);
+            if done {
+                completed.push(*id);
+            }
+            !done
+        });
+    }
+
+    /// Returns the tweens completed during the last update, in the order they were added.
+    pub fn completed(&self) -> &[TweenId] {
+        &self.completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn secs(secs: f32) -> Duration {
+        Duration::from_secs_f32(secs)
+    }
+
+    #[test]
+    fn easing_endpoints() {
+        use Easing::*;
+        for easing in [
+            Linear,
+            QuadIn,
+            QuadOut,
+            QuadInOut,
+            CubicIn,
+            CubicOut,
+            CubicInOut,
+            QuartIn,
+            QuartOut,
+            QuartInOut,
+            QuintIn,
+            QuintOut,
+            QuintInOut,
+            SineIn,
+            SineOut,
+            SineInOut,
+            ExpoIn,
+            ExpoOut,
+            ExpoInOut,
+            CircIn,
+            CircOut,
+            CircInOut,
+            BackIn,
+            BackOut,
+            BackInOut,
+            ElasticIn,
+            ElasticOut,
+            ElasticInOut,
+            BounceIn,
+            BounceOut,
+            BounceInOut,
+        ] {
+            assert_relative_eq!(easing.apply(0.0), 0.0, epsilon = 1e-5);
+            assert_relative_eq!(easing.apply(1.0), 1.0, epsilon = 1e-5);
+            // clamped
+            assert_relative_eq!(easing.apply(-1.0), 0.0, epsilon = 1e-5);
+            assert_relative_eq!(easing.apply(2.0), 1.0, epsilon = 1e-5);
+        }
+
+        assert_relative_eq!(Linear.apply(0.25), 0.25);
+        assert_relative_eq!(QuadIn.apply(0.5), 0.25);
+        assert_relative_eq!(QuadOut.apply(0.5), 0.75);
+        assert_relative_eq!(QuadInOut.apply(0.25), 0.125);
+        assert_relative_eq!(CubicInOut.apply(0.5), 0.5);
+        // overshooting below 0 on the way in
+        assert!(BackIn.apply(0.2) < 0.0);
+        assert!(BackOut.apply(0.8) > 1.0);
+    }
+
+    #[test]
+    fn tween_with_delay() {
+        let value = TweenValue::new(0.0f32);
+        let mut tween = Tween::new(&value, 10.0, secs(2.0))
+            .delay(secs(1.0))
+            .easing(Easing::QuadIn);
+        assert_eq!(tween.duration(), Some(3.0));
+
+        tween.seek(0.5);
+        assert_eq!(value.get(), 0.0);
+        // the start value is only taken once the delay is over
+        value.set(2.0);
+        tween.seek(1.0);
+        assert_eq!(value.get(), 2.0);
+        value.set(100.0);
+        tween.seek(2.0);
+        assert_relative_eq!(value.get(), 4.0);
+        tween.seek(3.0);
+        assert_relative_eq!(value.get(), 10.0);
+        // seeking back works as well
+        tween.seek(0.0);
+        assert_relative_eq!(value.get(), 2.0);
+
+        // with an explicit start value the delay holds it
+        let value = TweenValue::new(5.0f32);
+        let mut tween = Tween::new(&value, 10.0, secs(1.0))
+            .from(0.0)
+            .delay(secs(1.0));
+        tween.seek(0.5);
+        assert_eq!(value.get(), 0.0);
+
+        // tweens without duration jump to the end
+        let mut tween = Tween::new(&value, 7.0, Duration::ZERO);
+        tween.seek(0.0);
+        assert_eq!(value.get(), 7.0);
+    }
+
+    #[test]
+    fn sequence_starts_steps_at_the_end_of_the_previous_ones() {
+        let value = TweenValue::new(0.0f32);
+        let mut sequence = Sequence::new()
+            .then(Tween::new(&value, 10.0, secs(1.0)))
+            .wait(secs(0.5))
+            .then(Tween::new(&value, 20.0, secs(1.0)));
+        assert_eq!(sequence.duration(), Some(2.5));
+
+        sequence.seek(0.5);
+        assert_relative_eq!(value.get(), 5.0);
+        sequence.seek(1.25);
+        assert_relative_eq!(value.get(), 10.0);
+        // jumping past the first step finishes it before the second one starts
+        sequence.seek(2.0);
+        assert_relative_eq!(value.get(), 15.0);
+        sequence.seek(10.0);
+        assert_relative_eq!(value.get(), 20.0);
+
+        let forever = Sequence::new()
+            .then(Delay::new(secs(1.0)))
+            .then(Delay::new(secs(1.0)).repeat_forever());
+        assert_eq!(forever.duration(), None);
+    }
+
+    #[test]
+    fn parallel_plays_until_the_longest_track_is_done() {
+        let x = TweenValue::new(0.0f32);
+        let y = TweenValue::new(0.0f32);
+        let mut parallel = Parallel::new()
+            .with(Tween::new(&x, 1.0, secs(1.0)))
+            .with(Tween::new(&y, 4.0, secs(2.0)));
+        assert_eq!(parallel.duration(), Some(2.0));
+
+        parallel.seek(0.5);
+        assert_relative_eq!(x.get(), 0.5);
+        assert_relative_eq!(y.get(), 1.0);
+        parallel.seek(1.5);
+        assert_relative_eq!(x.get(), 1.0);
+        assert_relative_eq!(y.get(), 3.0);
+
+        let forever = Parallel::new().with(Delay::new(secs(1.0)).repeat_forever());
+        assert_eq!(forever.duration(), None);
+        assert_eq!(Parallel::new().duration(), Some(0.0));
+    }
+
+    #[test]
+    fn repeat() {
+        let value = TweenValue::new(0.0f32);
+        let mut repeat = Tween::new(&value, 10.0, secs(1.0)).from(0.0).repeat(3);
+        assert_eq!(repeat.duration(), Some(3.0));
+        repeat.seek(1.25);
+        assert_relative_eq!(value.get(), 2.5);
+        repeat.seek(2.5);
+        assert_relative_eq!(value.get(), 5.0);
+        // stays at the end of the last pass instead of starting over
+        repeat.seek(3.0);
+        assert_relative_eq!(value.get(), 10.0);
+        repeat.seek(5.2);
+        assert_relative_eq!(value.get(), 10.0);
+
+        let mut yoyo = Tween::new(&value, 10.0, secs(1.0))
+            .from(0.0)
+            .repeat(2)
+            .yoyo();
+        yoyo.seek(0.5);
+        assert_relative_eq!(value.get(), 5.0);
+        yoyo.seek(1.25);
+        assert_relative_eq!(value.get(), 7.5);
+        // an even number of passes ends back at the start
+        yoyo.seek(2.0);
+        assert_relative_eq!(value.get(), 0.0);
+        yoyo.seek(3.0);
+        assert_relative_eq!(value.get(), 0.0);
+
+        let mut forever = Tween::new(&value, 10.0, secs(1.0))
+            .from(0.0)
+            .repeat_forever()
+            .yoyo();
+        assert_eq!(forever.duration(), None);
+        forever.seek(100.25);
+        assert_relative_eq!(value.get(), 2.5, epsilon = 1e-3);
+        forever.seek(101.25);
+        assert_relative_eq!(value.get(), 7.5, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn manager() {
+        let value = TweenValue::new(0.0f32);
+        let mut manager = TweenManager::new();
+        let short = manager.add(Tween::new(&value, 10.0, secs(1.0)));
+        let long = manager.add(Delay::new(secs(2.0)));
+        let forever = manager.add(Delay::new(secs(1.0)).repeat_forever());
+        assert_eq!(manager.len(), 3);
+
+        manager.advance(secs(0.5));
+        assert_relative_eq!(value.get(), 5.0);
+        assert!(manager.completed().is_empty());
+
+        manager.advance(secs(0.75));
+        assert_relative_eq!(value.get(), 10.0);
+        assert_eq!(manager.completed(), [short]);
+        assert!(!manager.is_active(short));
+        assert!(manager.is_active(long));
+
+        // paused managers don't make progress
+        manager.set_paused(true);
+        manager.advance(secs(5.0));
+        assert!(manager.completed().is_empty());
+        assert!(manager.is_active(long));
+        manager.set_paused(false);
+
+        manager.advance(secs(1.0));
+        assert_eq!(manager.completed(), [long]);
+        manager.advance(secs(100.0));
+        assert!(manager.completed().is_empty());
+        assert!(manager.is_active(forever));
+
+        manager.cancel(forever);
+        assert!(manager.is_empty());
+    }
+}
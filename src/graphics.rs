@@ -1,9 +1,15 @@
+mod atlas;
+mod batch;
 mod canvas;
+mod clip;
 mod context;
 mod drawparam;
+pub(crate) mod hot_reload;
 mod image;
 #[cfg(feature = "mesh")]
 mod mesh;
+mod nineslice;
+mod postprocess;
 mod shader;
 mod text;
 mod types;
@@ -14,13 +20,17 @@ use crate::error::GameResult;
 use crate::Context;
 
 pub use self::{
-    canvas::*, context::GraphicsContext, drawparam::*, image::*, shader::*, text::*, types::*,
+    atlas::*, canvas::*, clip::*, context::GraphicsContext, drawparam::*, image::*, nineslice::*,
+    postprocess::*, shader::*, text::*, types::*,
 };
 
 #[cfg(feature = "mesh")]
 pub use self::mesh::*;
 
-use miniquad::PassAction;
+pub(crate) use self::atlas::load_sprite_sheet;
+
+use miniquad::{PassAction, RenderPass};
+use std::cell::Cell;
 
 /// Holds the bindings of objects that were dropped this frame.
 /// They (and the buffers inside of them) are kept alive until the beginning of the next frame
@@ -53,20 +63,43 @@ pub(crate) fn release_dropped_bindings() {
 }
 
 /// Clear the screen to the background color.
+///
+/// The depth and stencil buffer, if there are any, are cleared as well.
 pub fn clear(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext, color: Color) {
+    flush(ctx, quad_ctx);
+
     let action = PassAction::Clear {
         color: Some((color.r, color.g, color.b, color.a)),
-        depth: None,
-        stencil: None,
+        depth: Some(1.0),
+        stencil: Some(0),
     };
 
     let pass = ctx.framebuffer();
+    // drawing a stencil mask may have left writing colors disabled
+    quad_ctx.set_color_write((true, true, true, true));
     quad_ctx.begin_pass(pass, action);
-    quad_ctx.clear(Some((color.r, color.g, color.b, color.a)), None, None);
+    quad_ctx.clear(
+        Some((color.r, color.g, color.b, color.a)),
+        Some(1.0),
+        Some(0),
+    );
+}
+
+/// Draws all images drawn so far, which are otherwise held back to be drawn together with
+/// subsequent draws of the same image (see [`draw`](fn.draw.html)).
+///
+/// This happens automatically whenever anything else is drawn, the screen is cleared or the
+/// frame is presented, so there's only need to call this before drawing through miniquad
+/// directly.
+pub fn flush(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+    batch::flush(ctx, quad_ctx);
 }
 
 /// Draws the given `Drawable` object to the screen by calling its
 /// [`draw()`](trait.Drawable.html#tymethod.draw) method.
+///
+/// Consecutive draws of `Image`s which share texture, shader, uniforms and blend mode are
+/// batched together into a single draw call, see [`flush`](fn.flush.html).
 pub fn draw<D, T>(
     ctx: &mut Context,
     quad_ctx: &mut miniquad::graphics::GraphicsContext,
@@ -109,10 +142,36 @@ pub fn size(_ctx: &Context) -> (f32, f32) {
 }
 */
 
+thread_local! {
+    /// The render pass and size of the canvas standing in for the screen while running
+    /// [`headless`](../headless/index.html), as there's no window then.
+    static HEADLESS_SCREEN: Cell<Option<(RenderPass, (u16, u16))>> = const { Cell::new(None) };
+}
+
+/// Redirects everything drawn to the screen into the given canvas, or back to the screen.
+#[cfg(target_os = "linux")]
+pub(crate) fn set_headless_screen(canvas: Option<&Canvas>) {
+    let screen = canvas.map(|canvas| (canvas.offscreen_pass, (canvas.width(), canvas.height())));
+    HEADLESS_SCREEN.with(|cell| cell.set(screen));
+}
+
+/// Returns the render pass drawing to the screen, `None` being miniquad's default framebuffer.
+pub(crate) fn screen_pass() -> Option<RenderPass> {
+    HEADLESS_SCREEN.with(|cell| cell.get().map(|(pass, _)| pass))
+}
+
+/// Whether there's a window to change, which isn't the case while running headless.
+pub(crate) fn has_window() -> bool {
+    HEADLESS_SCREEN.with(|cell| cell.get().is_none())
+}
+
 /// Returns the size of the window's underlying drawable in pixels as (width, height).
 /// This may return a different value than `get_size()` when run on a platform with high-DPI support
 pub fn drawable_size(quad_ctx: &miniquad::graphics::GraphicsContext) -> (f32, f32) {
-    quad_ctx.screen_size()
+    match HEADLESS_SCREEN.with(Cell::get) {
+        Some((_, (width, height))) => (f32::from(width), f32::from(height)),
+        None => quad_ctx.screen_size(),
+    }
 }
 
 /// Sets the bounds of the screen viewport.
@@ -195,14 +254,60 @@ pub fn present(
     ctx: &mut Context,
     quad_ctx: &mut miniquad::graphics::GraphicsContext,
 ) -> GameResult<()> {
+    flush(ctx, quad_ctx);
     crate::graphics::set_canvas(ctx, None);
     quad_ctx.commit_frame();
     Ok(())
 }
 
+/// Takes a screenshot of everything drawn to the screen so far, returning it as an `Image`.
+///
+/// As the screen is cleared once the frame is over, call this before
+/// [`present`](fn.present.html). Note that drawing to a [`Canvas`](struct.Canvas.html) doesn't
+/// show up in the screenshot, use [`Canvas::to_rgba8`](struct.Canvas.html#method.to_rgba8) for that.
+pub fn screenshot(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+) -> GameResult<Image> {
+    flush(ctx, quad_ctx);
+    let (width, height, bytes) = read_screen_rgba8(quad_ctx);
+    Image::from_rgba8(ctx, quad_ctx, width, height, &bytes)
+}
+
+/// Reads back the contents of the screen as RGBA8 rows from top to bottom, together with its size.
+pub(crate) fn read_screen_rgba8(
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+) -> (u16, u16, Vec<u8>) {
+    use miniquad::gl::*;
+
+    let (width, height) = drawable_size(quad_ctx);
+    let (width, height) = (width as u16, height as u16);
+    let mut bytes = vec![0u8; 4 * usize::from(width) * usize::from(height)];
+
+    // beginning the pass binds the framebuffer of the screen
+    quad_ctx.begin_pass(screen_pass(), PassAction::Nothing);
+    unsafe {
+        glReadPixels(
+            0,
+            0,
+            i32::from(width),
+            i32::from(height),
+            GL_RGBA,
+            GL_UNSIGNED_BYTE,
+            bytes.as_mut_ptr() as _,
+        );
+    }
+    quad_ctx.end_render_pass();
+
+    image::flip_rows(&mut bytes, usize::from(width), usize::from(height));
+    (width, height, bytes)
+}
+
 /// Sets the window to fullscreen or back.
 pub fn set_fullscreen(quad_ctx: &mut miniquad::graphics::GraphicsContext, fullscreen: bool) {
-    quad_ctx.set_fullscreen(fullscreen);
+    if has_window() {
+        quad_ctx.set_fullscreen(fullscreen);
+    }
 }
 
 /// Sets the window size (in physical pixels) / resolution to the specified width and height.
@@ -213,35 +318,21 @@ pub fn set_drawable_size(
     width: u32,
     height: u32,
 ) {
-    quad_ctx.set_window_size(width, height);
+    if has_window() {
+        quad_ctx.set_window_size(width, height);
+    }
 }
 
-/// Deletes all cached font data.
+/// Deletes all glyphs from the glyph cache, shrinking its texture back to its initial size.
+/// Loaded fonts stay loaded, use [`Font::unload()`](struct.Font.html#method.unload) to get rid
+/// of fonts.
 ///
 /// Suggest this only gets used if you're sure you actually need it.
 pub fn clear_font_cache(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
-    use glyph_brush::GlyphBrushBuilder;
-    use std::cell::RefCell;
-    use std::rc::Rc;
-    let font_vec =
-        glyph_brush::ab_glyph::FontArc::try_from_slice(Font::default_font_bytes()).unwrap();
-    let glyph_brush = GlyphBrushBuilder::using_font(font_vec).build();
-    let (glyph_cache_width, glyph_cache_height) = glyph_brush.texture_dimensions();
-    let initial_contents = vec![255; 4 * glyph_cache_width as usize * glyph_cache_height as usize];
-    let glyph_cache = Image::from_rgba8(
-        ctx,
-        quad_ctx,
-        glyph_cache_width.try_into().unwrap(),
-        glyph_cache_height.try_into().unwrap(),
-        &initial_contents,
-    )
-    .unwrap();
-    let glyph_state = Rc::new(RefCell::new(spritebatch::SpriteBatch::new(
-        glyph_cache.clone(),
-    )));
-    ctx.gfx_context.glyph_brush = Rc::new(RefCell::new(glyph_brush));
-    ctx.gfx_context.glyph_cache = glyph_cache;
-    ctx.gfx_context.glyph_state = glyph_state;
+    let initial_size = ctx.gfx_context.glyph_cache_settings.initial_size;
+    if let Err(e) = text::resize_glyph_cache(ctx, quad_ctx, initial_size) {
+        warn!("Couldn't clear the glyph cache: {}", e);
+    }
 }
 
 /// All types that can be drawn on the screen implement the `Drawable` trait.
@@ -460,4 +551,345 @@ mod tests {
             assert_relative_eq!(real, expected);
         }
     }
+
+    #[test]
+    fn headless_test_flip_rows() {
+        // 1x3 pixels, the rows get mirrored while the middle one stays in place
+        let mut bytes: Vec<u8> = (0..12).collect();
+        super::image::flip_rows(&mut bytes, 1, 3);
+        assert_eq!(bytes, vec![8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3]);
+
+        let mut bytes: Vec<u8> = (0..16).collect();
+        super::image::flip_rows(&mut bytes, 2, 2);
+        assert_eq!(
+            bytes,
+            vec![8, 9, 10, 11, 12, 13, 14, 15, 0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn headless_test_atlas_packing() {
+        use super::atlas::{pack, PackingAlgorithm};
+
+        let sizes = [
+            (30, 10),
+            (10, 30),
+            (16, 16),
+            (5, 5),
+            (40, 2),
+            (1, 1),
+            (20, 20),
+        ];
+        for algorithm in [PackingAlgorithm::Shelf, PackingAlgorithm::MaxRects] {
+            let (side, positions) = pack(&sizes, algorithm, 1024).unwrap();
+            assert!(side.is_power_of_two());
+            let rects: Vec<Rect> = sizes
+                .iter()
+                .zip(positions)
+                .map(|(&(w, h), (x, y))| Rect::new(x as f32, y as f32, w as f32, h as f32))
+                .collect();
+            for (i, a) in rects.iter().enumerate() {
+                assert!(a.right() <= side as f32 && a.bottom() <= side as f32);
+                for b in &rects[i + 1..] {
+                    // touching is fine, `Rect::overlaps` counts that as overlapping
+                    let overlaps = a.left() < b.right()
+                        && b.left() < a.right()
+                        && a.top() < b.bottom()
+                        && b.top() < a.bottom();
+                    assert!(!overlaps, "{:?} overlaps {:?}", a, b);
+                }
+            }
+        }
+
+        assert!(pack(&[(100, 10)], PackingAlgorithm::MaxRects, 64).is_none());
+    }
+
+    #[test]
+    fn headless_test_scissor_pixels() {
+        use crate::graphics::clip::scissor_pixels;
+
+        let rect = Rect::new(100.0, 50.0, 200.0, 100.0);
+        let projection = cgmath::ortho(0.0, 800.0, 600.0, 0.0, -1.0, 1.0);
+        // the origin of the pixels is at the bottom left
+        assert_eq!(
+            scissor_pixels(rect, projection, (800.0, 600.0)),
+            (100, 450, 200, 100)
+        );
+        // screen coordinates scaled up by 2
+        let projection = cgmath::ortho(0.0, 400.0, 300.0, 0.0, -1.0, 1.0);
+        assert_eq!(
+            scissor_pixels(rect, projection, (800.0, 600.0)),
+            (200, 300, 400, 200)
+        );
+        // clamped to the render target
+        let rect = Rect::new(-50.0, -50.0, 100.0, 1000.0);
+        let projection = cgmath::ortho(0.0, 800.0, 600.0, 0.0, -1.0, 1.0);
+        assert_eq!(
+            scissor_pixels(rect, projection, (800.0, 600.0)),
+            (0, 0, 50, 600)
+        );
+    }
+
+    #[test]
+    fn headless_test_markup() {
+        use crate::graphics::{Color, Text};
+
+        let text = Text::from_markup("Deal [color=#f00]12[/color] [b]fire[/b] damage").unwrap();
+        assert_eq!(text.contents(), "Deal 12 fire damage");
+        let fragments = text.fragments();
+        assert_eq!(fragments.len(), 5);
+        assert_eq!(fragments[1].text, "12");
+        assert_eq!(fragments[1].color, Some(Color::RED));
+        assert_eq!(fragments[2].color, None);
+
+        let text = Text::from_markup("[size=24][u]a[s]b[/s][/u][img=coin][/size] [[c]").unwrap();
+        let fragments = text.fragments();
+        assert!(fragments[0].underline && !fragments[0].strikethrough);
+        assert!(fragments[1].underline && fragments[1].strikethrough);
+        assert_eq!(fragments[2].image.as_deref(), Some("coin"));
+        assert_eq!(fragments[2].scale.map(|scale| scale.y), Some(24.0));
+        assert_eq!(fragments[3].text, " [c]");
+
+        assert!(Text::from_markup("[b]bold").is_err());
+        assert!(Text::from_markup("[b][i]x[/b][/i]").is_err());
+        assert!(Text::from_markup("[blink]x[/blink]").is_err());
+        assert!(Text::from_markup("[color=#ff]x[/color]").is_err());
+    }
+
+    #[test]
+    fn headless_test_text_layout() {
+        use crate::graphics::{Font, PxScale, TextDirection, TextLayout};
+        use glyph_brush::ab_glyph::FontRef;
+        use glyph_brush::{FontId, GlyphPositioner, SectionGeometry, SectionText};
+
+        let fonts = [FontRef::try_from_slice(Font::default_font_bytes()).unwrap()];
+        let layout = |layout: TextLayout, text: &str, width: f32| {
+            let geometry = SectionGeometry {
+                screen_position: (0.0, 0.0),
+                bounds: (width, f32::INFINITY),
+            };
+            let section = SectionText {
+                text,
+                scale: PxScale::from(16.0),
+                font_id: FontId(0),
+            };
+            layout
+                .calculate_glyphs(&fonts, &geometry, &[section])
+                .into_iter()
+                .map(|glyph| (glyph.byte_index, glyph.glyph.position))
+                .collect::<Vec<_>>()
+        };
+        // the font is monospaced
+        let advance = layout(TextLayout::new(), "ab", f32::INFINITY)[1].1.x;
+
+        // wraps between words, with the space staying on the first line
+        let glyphs = layout(TextLayout::new(), "aaa bbb ccc", advance * 7.5);
+        assert_eq!(glyphs.len(), 11);
+        assert_eq!(glyphs[7].1.y, glyphs[0].1.y);
+        assert_eq!(glyphs[8].1.x, 0.0);
+        let line_height = glyphs[8].1.y - glyphs[0].1.y;
+        assert!(line_height > 0.0);
+
+        let glyphs = layout(
+            TextLayout::new().line_spacing(2.0),
+            "aaa bbb ccc",
+            advance * 7.5,
+        );
+        assert_relative_eq!(glyphs[8].1.y - glyphs[0].1.y, line_height * 2.0);
+
+        let glyphs = layout(TextLayout::new().letter_spacing(2.0), "ab", f32::INFINITY);
+        assert_relative_eq!(glyphs[1].1.x, advance + 2.0);
+
+        // the ellipsis has to fit in as well, replacing the last "b"
+        let glyphs = layout(
+            TextLayout::new().max_lines(Some(1)),
+            "aaa bbb ccc",
+            advance * 7.5,
+        );
+        assert_eq!(glyphs.len(), 7);
+        assert!(glyphs
+            .iter()
+            .all(|(_, position)| position.y == glyphs[0].1.y));
+        let glyphs = layout(
+            TextLayout::new().max_lines(Some(0)),
+            "aaa bbb ccc",
+            advance * 7.5,
+        );
+        assert!(glyphs.is_empty());
+
+        // a long word without a place to wrap at stays on its own line
+        let glyphs = layout(TextLayout::new(), "a bbbbbbbbbb c", advance * 4.5);
+        assert_eq!(glyphs[2].1.x, 0.0);
+        assert_eq!(glyphs[11].1.y, glyphs[2].1.y);
+        assert_eq!(glyphs[13].1.x, 0.0);
+        assert!(glyphs[13].1.y > glyphs[2].1.y);
+
+        // right-to-left runs are reversed, but glyphs stay in the order of the text
+        let glyphs = layout(TextLayout::new(), "ab \u{5d0}\u{5d1}", f32::INFINITY);
+        assert_eq!(glyphs[3].0, 3);
+        assert_relative_eq!(glyphs[3].1.x, advance * 4.0);
+        assert_relative_eq!(glyphs[4].1.x, advance * 3.0);
+        let glyphs = layout(
+            TextLayout::new().direction(TextDirection::RightToLeft),
+            "ab \u{5d0}\u{5d1}",
+            f32::INFINITY,
+        );
+        assert_relative_eq!(glyphs[0].1.x, advance * 3.0);
+        assert_relative_eq!(glyphs[3].1.x, advance * 1.0);
+    }
+
+    #[test]
+    fn headless_test_bmfont() {
+        use crate::graphics::{empty_font, grid_glyphs, parse_bmfont};
+        use glyph_brush::ab_glyph::Font as _;
+
+        let fnt = r#"info face="Pixel Font" size=8 bold=0 italic=0 padding=0,0,0,0
+common lineHeight=10 base=8 scaleW=64 scaleH=64 pages=2 packed=0
+page id=0 file="pixel_0.png"
+page id=1 file="pixel 1.png"
+chars count=3
+char id=-1   x=0     y=0     width=4     height=8     xoffset=0     yoffset=0     xadvance=5     page=0  chnl=15
+char id=65   x=5     y=0     width=5     height=8     xoffset=0     yoffset=1     xadvance=6     page=0  chnl=15
+char id=86   x=0     y=9     width=5     height=8     xoffset=1     yoffset=1     xadvance=6     page=1  chnl=15
+kernings count=1
+kerning first=65  second=86  amount=-1
+"#;
+        let (data, pages) = parse_bmfont(fnt).unwrap();
+        assert_eq!(pages, ["pixel_0.png", "pixel 1.png"]);
+        assert_eq!((data.line_height, data.base), (10.0, 8.0));
+        let a = data.chars[&'A'];
+        let v = data.chars[&'V'];
+        let glyph = data.glyphs[usize::from(v.0)].unwrap();
+        assert_eq!(glyph.page, 1);
+        assert_eq!(glyph.src, (0, 9, 5, 8));
+        assert_eq!(glyph.offset, (1.0, 1.0));
+        assert_eq!(data.kerning[&(a, v)], -1.0);
+        assert_eq!(data.chars.len(), 2);
+
+        assert!(parse_bmfont("char id=65 x=0").is_err());
+        assert!(parse_bmfont("<?xml version=\"1.0\"?>").is_err());
+        assert!(parse_bmfont(&fnt.replace("page=1", "page=2")).is_err());
+        assert!(parse_bmfont(&fnt.replace("lineHeight=10", "lineHeight=0")).is_err());
+        assert!(parse_bmfont(&fnt.replace("page id=1", "page id=1000000000")).is_err());
+        assert!(parse_bmfont(&fnt.replace("page id=1", "page id=0")).is_err());
+        assert!(parse_bmfont(&fnt.replace("pages=2", "pages=1")).is_err());
+        // glyph ids up to u16::MAX, after the missing glyph, 'A' and 'V'
+        let chars: String = (3..=u32::from(u16::MAX))
+            .map(|id| {
+                format!(
+                    "char id={} x=0 y=0 width=1 height=1 xoffset=0 yoffset=0 xadvance=1 page=0\n",
+                    id + 0x10000
+                )
+            })
+            .collect();
+        assert!(parse_bmfont(&format!("{}{}", fnt, chars)).is_ok());
+        let one_more = "char id=65 x=0 y=0 width=1 height=1 xoffset=0 yoffset=0 xadvance=1 page=0";
+        assert!(parse_bmfont(&format!("{}{}{}", fnt, chars, one_more)).is_err());
+
+        let data = grid_glyphs((32, 16), (8, 8), "abcde").unwrap();
+        let e = data.glyphs[usize::from(data.chars[&'e'].0)].unwrap();
+        assert_eq!(e.src, (0, 8, 8, 8));
+        assert_eq!(e.advance, 8.0);
+        assert!(grid_glyphs((4, 8), (8, 8), "a").is_err());
+        assert!(grid_glyphs((32, 4), (8, 8), "a").is_err());
+        // only 8 cells
+        assert!(grid_glyphs((32, 16), (8, 8), "abcdefghi").is_err());
+        // cells at the far end of the largest images
+        let chars: String = ('\u{100}'..).take(63 * 63).collect();
+        let data = grid_glyphs((u16::MAX, u16::MAX), (1024, 1024), &chars).unwrap();
+        let last = data.chars[&chars.chars().last().unwrap()];
+        let last = data.glyphs[usize::from(last.0)].unwrap();
+        assert_eq!(last.src, (62 * 1024, 62 * 1024, 1024, 1024));
+
+        // bitmap fonts don't list their characters
+        assert_eq!(empty_font().codepoint_ids().count(), 0);
+    }
+
+    #[test]
+    fn headless_test_glyph_cache_stats() {
+        use crate::graphics::GlyphCacheTracker;
+        use glyph_brush::Rectangle;
+
+        let rect = |x: u32, y: u32, w: u32, h: u32| Rectangle {
+            min: [x, y],
+            max: [x + w, y + h],
+        };
+        let mut tracker = GlyphCacheTracker::default();
+        tracker.upload(rect(0, 0, 10, 16));
+        tracker.upload(rect(10, 0, 8, 16));
+        tracker.upload(rect(0, 16, 12, 12));
+        let stats = tracker.stats((64, 64));
+        assert_eq!(stats.glyphs, 3);
+        assert_eq!(stats.used_pixels, 160 + 128 + 144);
+        assert_relative_eq!(stats.occupancy(), 432.0 / 4096.0);
+        assert!(!tracker.take_evicted());
+
+        // the first row was evicted to make room for a taller glyph
+        tracker.upload(rect(0, 0, 20, 16));
+        let stats = tracker.stats((64, 64));
+        assert_eq!((stats.glyphs, stats.evicted), (2, 2));
+        assert!(tracker.take_evicted());
+        assert!(!tracker.take_evicted());
+
+        // glyphs starting in rows above a new one are evicted as well
+        tracker.upload(rect(40, 0, 8, 40));
+        tracker.upload(rect(48, 16, 8, 8));
+        tracker.upload(rect(30, 28, 16, 8));
+        let stats = tracker.stats((64, 64));
+        assert_eq!((stats.glyphs, stats.evicted), (4, 3));
+        assert_eq!(stats.used_pixels, 320 + 144 + 64 + 128);
+        assert!(tracker.take_evicted());
+
+        tracker.clear();
+        assert_eq!(tracker.stats((64, 64)).glyphs, 0);
+        tracker.upload(rect(0, 0, 10, 16));
+        assert!(!tracker.take_evicted());
+
+        tracker.grew();
+        let stats = tracker.stats((128, 128));
+        assert_eq!((stats.glyphs, stats.used_pixels, stats.grown), (0, 0, 1));
+        assert_eq!(stats.occupancy(), 0.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    mod readback {
+        use crate::conf::Conf;
+        use crate::event::{EventHandler, GraphicsContext};
+        use crate::graphics::{self, BlendMode, Color, DrawParam, Image};
+        use crate::headless::{self, InputScript};
+        use crate::{Context, GameResult};
+
+        struct BlendModes(Image);
+
+        impl EventHandler for BlendModes {
+            fn update(&mut self, _: &mut Context, _: &mut GraphicsContext) -> GameResult {
+                Ok(())
+            }
+
+            fn draw(&mut self, ctx: &mut Context, quad_ctx: &mut GraphicsContext) -> GameResult {
+                graphics::clear(ctx, quad_ctx, Color::new(0.5, 0.5, 0.5, 1.0));
+                graphics::set_blend_mode(ctx, quad_ctx, BlendMode::Alpha)?;
+                let param = DrawParam::new().scale([4.0, 4.0]);
+                graphics::draw(ctx, quad_ctx, &self.0, param)?;
+                graphics::set_blend_mode(ctx, quad_ctx, BlendMode::Add)?;
+                graphics::draw(ctx, quad_ctx, &self.0, param.dest([4.0, 0.0]))?;
+                graphics::set_blend_mode(ctx, quad_ctx, BlendMode::Alpha)?;
+                graphics::present(ctx, quad_ctx)
+            }
+        }
+
+        #[test]
+        fn batches_end_with_the_blend_mode() {
+            let conf = Conf::default().window_width(8).window_height(4);
+            let frame = headless::run(conf, 1, InputScript::new(), |ctx, quad_ctx| {
+                let image = Image::solid(ctx, quad_ctx, 1, Color::RED).unwrap();
+                Box::new(BlendModes(image))
+            })
+            .unwrap();
+            let (r, g, b, _) = frame.pixel(1, 1).to_rgba();
+            assert_eq!((r, g, b), (255, 0, 0));
+            let (r, g, b, _) = frame.pixel(5, 1).to_rgba();
+            assert!(r == 255 && (127..=128).contains(&g) && (127..=128).contains(&b));
+        }
+    }
 }
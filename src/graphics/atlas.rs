@@ -0,0 +1,613 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{
+    error::GameResult,
+    filesystem,
+    graphics::{image::decode_rgba8, Image, Rect},
+    Context, GameError,
+};
+
+/// How the images of a [`TextureAtlas`](struct.TextureAtlas.html) are arranged.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PackingAlgorithm {
+    /// Places the images in rows, sorted by height. Fast, and good enough for images of
+    /// similar height, e.g. the glyphs of a font or the frames of an animation.
+    Shelf,
+    /// Keeps track of all free space left and places every image where it fits best, which
+    /// wastes a lot less space when the images differ in size.
+    MaxRects,
+}
+
+/// Packs multiple images into a single [`TextureAtlas`](struct.TextureAtlas.html), so that they
+/// can be drawn together in one draw call, e.g. through a `SpriteBatch`.
+///
+/// ```rust, ignore
+/// let atlas = TextureAtlasBuilder::new()
+///     .padding(1)
+///     .extrude(1)
+///     .add_file(ctx, "player", "/player.png")?
+///     .add_file(ctx, "enemy", "/enemy.png")?
+///     .build(ctx, quad_ctx)?;
+/// let param = DrawParam::new().src(atlas.src("player").unwrap());
+/// graphics::draw(ctx, quad_ctx, atlas.image(), param)?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextureAtlasBuilder {
+    algorithm: PackingAlgorithm,
+    padding: u16,
+    extrude: u16,
+    max_size: u16,
+    entries: Vec<(String, u16, u16, Vec<u8>)>,
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        TextureAtlasBuilder {
+            algorithm: PackingAlgorithm::MaxRects,
+            padding: 0,
+            extrude: 0,
+            max_size: 4096,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl TextureAtlasBuilder {
+    /// Creates a new, empty builder.
+    pub fn new() -> Self {
+        TextureAtlasBuilder::default()
+    }
+
+    /// Sets how the images are arranged.
+    ///
+    /// Default: `PackingAlgorithm::MaxRects`
+    pub fn algorithm(&mut self, algorithm: PackingAlgorithm) -> &mut Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the number of transparent pixels left between images.
+    ///
+    /// Default: `0`
+    pub fn padding(&mut self, padding: u16) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the number of pixels by which the border of every image is repeated around it, which
+    /// keeps neighbouring images from bleeding in when drawing with linear filtering or at
+    /// fractional positions.
+    ///
+    /// Default: `0`
+    pub fn extrude(&mut self, extrude: u16) -> &mut Self {
+        self.extrude = extrude;
+        self
+    }
+
+    /// Sets the maximum width and height of the atlas, which is always square, with a power of
+    /// two as side length.
+    ///
+    /// Default: `4096`
+    pub fn max_size(&mut self, max_size: u16) -> &mut Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Adds an image given as RGBA8 pixels, row by row from top to bottom.
+    ///
+    /// Fails for images without any pixels.
+    pub fn add_rgba8(
+        &mut self,
+        name: &str,
+        width: u16,
+        height: u16,
+        bytes: &[u8],
+    ) -> GameResult<&mut Self> {
+        if width == 0 || height == 0 {
+            return Err(GameError::RenderError(format!(
+                "The image named '{}' is empty ({}x{} pixels)",
+                name, width, height
+            )));
+        }
+        if bytes.len() != 4 * usize::from(width) * usize::from(height) {
+            return Err(GameError::RenderError(format!(
+                "Expected {} bytes for a {}x{} image named '{}', got {}",
+                4 * usize::from(width) * usize::from(height),
+                width,
+                height,
+                name,
+                bytes.len()
+            )));
+        }
+        if self.entries.iter().any(|(other, ..)| other == name) {
+            return Err(GameError::RenderError(format!(
+                "There's already an image named '{}' in the atlas",
+                name
+            )));
+        }
+        self.entries
+            .push((name.to_string(), width, height, bytes.to_vec()));
+        Ok(self)
+    }
+
+    /// Adds an encoded image (usually a PNG).
+    pub fn add_png_bytes(&mut self, name: &str, bytes: &[u8]) -> GameResult<&mut Self> {
+        let (width, height, rgba) = decode_rgba8(bytes)?;
+        self.add_rgba8(name, width, height, &rgba)
+    }
+
+    /// Adds the image at the given path.
+    pub fn add_file<P: AsRef<Path>>(
+        &mut self,
+        ctx: &mut Context,
+        name: &str,
+        path: P,
+    ) -> GameResult<&mut Self> {
+        let bytes = read_file(ctx, path.as_ref())?;
+        self.add_png_bytes(name, &bytes)
+    }
+
+    /// Adds an `Image`, reading its pixels back from the GPU.
+    pub fn add_image(
+        &mut self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        name: &str,
+        image: &Image,
+    ) -> GameResult<&mut Self> {
+        let rgba = image.to_rgba8(ctx, quad_ctx)?;
+        self.add_rgba8(name, image.width(), image.height(), &rgba)
+    }
+
+    /// Packs all images added so far into a new `TextureAtlas`.
+    ///
+    /// Fails if they don't fit into the maximum size.
+    pub fn build(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    ) -> GameResult<TextureAtlas> {
+        let border = 2 * u32::from(self.extrude) + u32::from(self.padding);
+        let sizes: Vec<(u32, u32)> = self
+            .entries
+            .iter()
+            .map(|(_, w, h, _)| (u32::from(*w) + border, u32::from(*h) + border))
+            .collect();
+        let (side, positions) =
+            pack(&sizes, self.algorithm, u32::from(self.max_size)).ok_or_else(|| {
+                GameError::RenderError(format!(
+                    "The images don't fit into a texture atlas of {}x{} pixels",
+                    self.max_size, self.max_size
+                ))
+            })?;
+
+        let stride = 4 * side as usize;
+        let mut pixels = vec![0u8; stride * side as usize];
+        let mut regions = Vec::with_capacity(self.entries.len());
+        let extrude = i32::from(self.extrude);
+        for ((name, width, height, rgba), (x, y)) in self.entries.iter().zip(positions) {
+            let (width, height) = (i32::from(*width), i32::from(*height));
+            // copy the image, repeating its outermost pixels `extrude` times around it
+            for dy in -extrude..height + extrude {
+                let source_row = dy.clamp(0, height - 1) as usize;
+                let target_y = (y as i32 + extrude + dy) as usize;
+                for dx in -extrude..width + extrude {
+                    let source =
+                        4 * (source_row * width as usize + dx.clamp(0, width - 1) as usize);
+                    let target_x = (x as i32 + extrude + dx) as usize;
+                    let target = target_y * stride + 4 * target_x;
+                    pixels[target..target + 4].copy_from_slice(&rgba[source..source + 4]);
+                }
+            }
+            let rect = Rect::new(
+                (x as i32 + extrude) as f32,
+                (y as i32 + extrude) as f32,
+                width as f32,
+                height as f32,
+            );
+            regions.push((name.clone(), rect));
+        }
+
+        let image = Image::from_rgba8(ctx, quad_ctx, side as u16, side as u16, &pixels)?;
+        Ok(TextureAtlas::new(image, regions))
+    }
+}
+
+fn read_file(ctx: &mut Context, path: &Path) -> GameResult<Vec<u8>> {
+    use std::io::Read;
+
+    let mut file = filesystem::open(ctx, path)?;
+    let mut bytes = vec![];
+    file.bytes.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Finds a place for rects of the given sizes inside of the smallest square with a power of two
+/// as side length possible, returning that length and the top left corner of every rect.
+pub(crate) fn pack(
+    sizes: &[(u32, u32)],
+    algorithm: PackingAlgorithm,
+    max_size: u32,
+) -> Option<(u32, Vec<(u32, u32)>)> {
+    let area: u32 = sizes.iter().map(|(w, h)| w * h).sum();
+    let mut side = (area as f32).sqrt().ceil() as u32;
+    for (w, h) in sizes {
+        side = side.max(*w).max(*h);
+    }
+    let mut side = side.max(1).next_power_of_two();
+
+    while side <= max_size {
+        let positions = match algorithm {
+            PackingAlgorithm::Shelf => pack_shelf(sizes, side),
+            PackingAlgorithm::MaxRects => pack_max_rects(sizes, side),
+        };
+        if let Some(positions) = positions {
+            return Some((side, positions));
+        }
+        side *= 2;
+    }
+    None
+}
+
+/// Returns the indices of the sizes, sorted by the given key in descending order.
+fn sorted_by_key<K: Ord>(sizes: &[(u32, u32)], key: impl Fn(u32, u32) -> K) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(key(sizes[i].0, sizes[i].1)));
+    order
+}
+
+fn pack_shelf(sizes: &[(u32, u32)], side: u32) -> Option<Vec<(u32, u32)>> {
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in sorted_by_key(sizes, |w, h| (h, w)) {
+        let (w, h) = sizes[i];
+        if x + w > side {
+            // start a new shelf
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        if x + w > side || y + h > side {
+            return None;
+        }
+        positions[i] = (x, y);
+        x += w;
+        shelf_height = shelf_height.max(h);
+    }
+    Some(positions)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FreeRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl FreeRect {
+    fn contains(&self, other: &FreeRect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.w <= self.x + self.w
+            && other.y + other.h <= self.y + self.h
+    }
+
+    fn intersects(&self, other: &FreeRect) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
+    /// Returns what's left of this rect after cutting out `used`, as up to four overlapping rects.
+    fn split(&self, used: &FreeRect) -> Vec<FreeRect> {
+        let mut rects = Vec::with_capacity(4);
+        if used.x > self.x {
+            rects.push(FreeRect {
+                w: used.x - self.x,
+                ..*self
+            });
+        }
+        if used.x + used.w < self.x + self.w {
+            rects.push(FreeRect {
+                x: used.x + used.w,
+                w: self.x + self.w - (used.x + used.w),
+                ..*self
+            });
+        }
+        if used.y > self.y {
+            rects.push(FreeRect {
+                h: used.y - self.y,
+                ..*self
+            });
+        }
+        if used.y + used.h < self.y + self.h {
+            rects.push(FreeRect {
+                y: used.y + used.h,
+                h: self.y + self.h - (used.y + used.h),
+                ..*self
+            });
+        }
+        rects
+    }
+}
+
+fn pack_max_rects(sizes: &[(u32, u32)], side: u32) -> Option<Vec<(u32, u32)>> {
+    let mut positions = vec![(0, 0); sizes.len()];
+    let mut free = vec![FreeRect {
+        x: 0,
+        y: 0,
+        w: side,
+        h: side,
+    }];
+    for i in sorted_by_key(sizes, |w, h| (w.max(h), w * h)) {
+        let (w, h) = sizes[i];
+        if w == 0 || h == 0 {
+            continue;
+        }
+
+        // best short side fit: the free rect leaving the least space along its shorter side
+        let best = free
+            .iter()
+            .filter(|rect| rect.w >= w && rect.h >= h)
+            .min_by_key(|rect| {
+                let (dw, dh) = (rect.w - w, rect.h - h);
+                (dw.min(dh), dw.max(dh))
+            })?;
+        let used = FreeRect {
+            x: best.x,
+            y: best.y,
+            w,
+            h,
+        };
+        positions[i] = (used.x, used.y);
+
+        let mut next = Vec::with_capacity(free.len() + 4);
+        for rect in free.iter() {
+            if rect.intersects(&used) {
+                next.extend(rect.split(&used));
+            } else {
+                next.push(*rect);
+            }
+        }
+        // drop all free rects lying completely inside of another one
+        let mut pruned: Vec<FreeRect> = Vec::with_capacity(next.len());
+        for (j, rect) in next.iter().enumerate() {
+            let redundant = next
+                .iter()
+                .enumerate()
+                .any(|(k, other)| k != j && other.contains(rect) && (other != rect || k < j));
+            if !redundant {
+                pruned.push(*rect);
+            }
+        }
+        free = pruned;
+    }
+    Some(positions)
+}
+
+/// A single image containing many smaller ones, which can be drawn separately by passing their
+/// [`src`](#method.src) to `DrawParam::src`.
+///
+/// Built at runtime through a [`TextureAtlasBuilder`](struct.TextureAtlasBuilder.html), or loaded
+/// from a sprite sheet exported by TexturePacker or Aseprite through
+/// [`from_json`](#method.from_json).
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    image: Image,
+    /// The regions in pixels, in the order they were added or listed in.
+    regions: Vec<(String, Rect)>,
+    indices: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+struct SheetJson {
+    frames: serde_json::Value,
+    meta: MetaJson,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaJson {
+    image: String,
+    /// Only exported by Aseprite.
+    #[serde(default)]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    /// Only present in the array format.
+    filename: Option<String>,
+    frame: RectJson,
+    #[serde(default)]
+    rotated: bool,
+    /// Whether transparent space was cut off around the frame, see `from_json`.
+    #[serde(default)]
+    trimmed: bool,
+    /// In milliseconds, only exported by Aseprite.
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RectJson {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+/// A frame of a sprite sheet loaded through `load_sprite_sheet`.
+pub(crate) struct SheetFrame {
+    pub(crate) name: String,
+    /// In pixels.
+    pub(crate) rect: Rect,
+    /// In milliseconds.
+    pub(crate) duration: Option<u32>,
+}
+
+/// A named range of frames, as exported by Aseprite.
+#[derive(Deserialize)]
+pub(crate) struct FrameTag {
+    pub(crate) name: String,
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+    /// `"forward"`, `"reverse"` or `"pingpong"`.
+    #[serde(default)]
+    pub(crate) direction: String,
+}
+
+/// Loads a sprite sheet in the JSON format exported by TexturePacker or Aseprite, along with
+/// its image.
+pub(crate) fn load_sprite_sheet(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    path: &Path,
+) -> GameResult<(Image, Vec<SheetFrame>, Vec<FrameTag>)> {
+    let to_error = |e: String| {
+        GameError::ResourceLoadError(format!("Invalid sprite sheet {}: {}", path.display(), e))
+    };
+
+    let bytes = read_file(ctx, path)?;
+    let sheet: SheetJson = serde_json::from_slice(&bytes).map_err(|e| to_error(e.to_string()))?;
+
+    let frames: Vec<(String, FrameJson)> = match sheet.frames {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(name, frame)| Ok((name, serde_json::from_value(frame)?)))
+            .collect::<Result<_, serde_json::Error>>(),
+        serde_json::Value::Array(array) => array
+            .into_iter()
+            .map(|frame| {
+                let frame: FrameJson = serde_json::from_value(frame)?;
+                Ok((frame.filename.clone().unwrap_or_default(), frame))
+            })
+            .collect(),
+        _ => {
+            return Err(to_error(
+                "'frames' is neither an object nor an array".into(),
+            ))
+        }
+    }
+    .map_err(|e| to_error(e.to_string()))?;
+
+    let mut sheet_frames = Vec::with_capacity(frames.len());
+    let mut trimmed = 0;
+    for (name, frame) in frames {
+        if frame.rotated {
+            return Err(to_error(format!("frame '{}' is rotated", name)));
+        }
+        trimmed += usize::from(frame.trimmed);
+        let RectJson { x, y, w, h } = frame.frame;
+        sheet_frames.push(SheetFrame {
+            name,
+            rect: Rect::new(x as f32, y as f32, w as f32, h as f32),
+            duration: frame.duration,
+        });
+    }
+
+    if trimmed > 0 {
+        warn!(
+            "{} frames of sprite sheet {} are trimmed, their offsets are ignored",
+            trimmed,
+            path.display()
+        );
+    }
+
+    let image_path = path
+        .parent()
+        .unwrap_or_else(|| Path::new("/"))
+        .join(&sheet.meta.image);
+    let image = Image::new(ctx, quad_ctx, image_path)?;
+    Ok((image, sheet_frames, sheet.meta.frame_tags))
+}
+
+impl TextureAtlas {
+    fn new(image: Image, regions: Vec<(String, Rect)>) -> Self {
+        let indices = regions
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.clone(), i))
+            .collect();
+        TextureAtlas {
+            image,
+            regions,
+            indices,
+        }
+    }
+
+    /// Creates an atlas from an existing image and the regions of the images inside of it,
+    /// in pixels.
+    pub fn from_regions(image: Image, regions: Vec<(String, Rect)>) -> Self {
+        Self::new(image, regions)
+    }
+
+    /// Loads a sprite sheet in the JSON format exported by TexturePacker or Aseprite, as either
+    /// a hash or an array of frames.
+    ///
+    /// The image is loaded from the path stored in the sheet, relative to the sheet itself.
+    /// Rotated frames aren't supported. Trimmed frames are loaded as the trimmed region only,
+    /// ignoring where it was cut out of the original frame (`spriteSourceSize`), so frames of
+    /// different sizes which need to line up should be exported without trimming.
+    pub fn from_json<P: AsRef<Path>>(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        path: P,
+    ) -> GameResult<Self> {
+        let (image, frames, _) = load_sprite_sheet(ctx, quad_ctx, path.as_ref())?;
+        let regions = frames
+            .into_iter()
+            .map(|frame| (frame.name, frame.rect))
+            .collect();
+        Ok(Self::new(image, regions))
+    }
+
+    /// Returns the image containing all regions.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns the region with the given name in pixels.
+    pub fn region(&self, name: &str) -> Option<Rect> {
+        self.indices.get(name).map(|&i| self.regions[i].1)
+    }
+
+    /// Returns the region with the given name relative to the size of the atlas, as expected by
+    /// `DrawParam::src`.
+    pub fn src(&self, name: &str) -> Option<Rect> {
+        self.region(name).map(|rect| self.to_src(rect))
+    }
+
+    fn to_src(&self, rect: Rect) -> Rect {
+        let (width, height) = (
+            f32::from(self.image.width()),
+            f32::from(self.image.height()),
+        );
+        Rect::new(
+            rect.x / width,
+            rect.y / height,
+            rect.w / width,
+            rect.h / height,
+        )
+    }
+
+    /// Returns the names and regions (in pixels) of all images in the atlas, in the order they
+    /// were added or listed in the sprite sheet.
+    pub fn regions(&self) -> impl Iterator<Item = (&str, Rect)> {
+        self.regions
+            .iter()
+            .map(|(name, rect)| (name.as_str(), *rect))
+    }
+
+    /// Returns the number of regions.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Returns whether the atlas has no regions.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
@@ -0,0 +1,186 @@
+//! Automatic batching of consecutive image draws.
+//!
+//! Drawing an `Image` doesn't draw anything right away, but adds an instance to the batch kept
+//! in the `GraphicsContext`. As long as the following draws use the same texture, shader,
+//! uniforms, blend mode, projection and render target they are added to the same batch, which
+//! is then drawn in a single instanced draw call once anything changes. Everything else that
+//! draws (meshes, sprite batches, text, clearing) as well as `present` and reading back pixels
+//! flushes the batch first, so the order of draws is always kept.
+
+use cgmath::Matrix4;
+use miniquad::{Bindings, Buffer, BufferType, FilterMode, PassAction, RenderPass, Texture};
+
+use crate::graphics::{apply_uniforms, BlendMode, ClipState, InstanceAttributes, ShaderId};
+use crate::Context;
+
+/// Everything that has to be the same for two draws to end up in the same batch.
+struct BatchState {
+    texture: Texture,
+    filter: FilterMode,
+    shader: ShaderId,
+    /// The uniforms of the shader at the time of drawing (without the projection).
+    uniforms: Vec<u8>,
+    /// The blend mode of the image, or the global one if it has none.
+    blend_mode: BlendMode,
+    pass: Option<RenderPass>,
+    projection: Matrix4<f32>,
+    clip: ClipState,
+}
+
+pub(crate) struct Batch {
+    state: Option<BatchState>,
+    instances: Vec<InstanceAttributes>,
+    /// A unit quad and an instance buffer with space for `capacity` instances, the texture is
+    /// swapped out for every batch.
+    bindings: Bindings,
+    capacity: usize,
+}
+
+impl Batch {
+    pub(crate) fn new(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        white_texture: Texture,
+    ) -> Batch {
+        #[rustfmt::skip]
+        let vertices: [f32; 32] = [0.0, 0.0, // first pos
+                                  0.0, 0.0, // first texcoord
+                                  1.0, 1.0, 1.0, 1.0, // first color
+                                  1.0, 0.0, // second pos
+                                  1.0, 0.0, // second texcoord
+                                  1.0, 1.0, 1.0, 1.0, // second color
+                                  1.0, 1.0, // third pos
+                                  1.0, 1.0, // third texcoord
+                                  1.0, 1.0, 1.0, 1.0, // third color
+                                  0.0, 1.0, // fourth pos
+                                  0.0, 1.0, // fourth texcoord
+                                  1.0, 1.0, 1.0, 1.0]; // fourth color
+        let vertex_buffer = Buffer::immutable(quad_ctx, BufferType::VertexBuffer, &vertices);
+
+        let capacity = 64;
+        let instance_buffer = Buffer::stream(
+            quad_ctx,
+            BufferType::VertexBuffer,
+            std::mem::size_of::<InstanceAttributes>() * capacity,
+        );
+
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let index_buffer = Buffer::immutable(quad_ctx, BufferType::IndexBuffer, &indices);
+
+        Batch {
+            state: None,
+            instances: Vec::new(),
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer, instance_buffer],
+                index_buffer,
+                images: vec![white_texture],
+            },
+            capacity,
+        }
+    }
+}
+
+/// Adds an instance of the given texture to the batch, drawing the batch first if it doesn't
+/// fit in.
+pub(crate) fn add(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    texture: Texture,
+    filter: FilterMode,
+    blend_mode: Option<BlendMode>,
+    instance: InstanceAttributes,
+) {
+    let pass = ctx.framebuffer();
+    let clip = ClipState::current(ctx, quad_ctx);
+    let gfx = &ctx.gfx_context;
+    let shader = *gfx.current_shader.borrow();
+    let uniforms = shader_uniforms(&gfx.shaders[shader].uniforms);
+    // the global blend mode may change between draws without flushing
+    let blend_mode = blend_mode.unwrap_or(gfx.blend_mode);
+
+    let fits = gfx.batch.state.as_ref().is_some_and(|state| {
+        state.texture == texture
+            && state.filter == filter
+            && state.shader == shader
+            && state.blend_mode == blend_mode
+            && state.pass == pass
+            && state.projection == gfx.projection
+            && state.uniforms == uniforms
+            && state.clip == clip
+    });
+
+    if !fits {
+        flush(ctx, quad_ctx);
+        let gfx = &mut ctx.gfx_context;
+        let uniforms = shader_uniforms(&gfx.shaders[shader].uniforms).to_vec();
+        gfx.batch.state = Some(BatchState {
+            texture,
+            filter,
+            shader,
+            uniforms,
+            blend_mode,
+            pass,
+            projection: gfx.projection,
+            clip,
+        });
+    }
+
+    ctx.gfx_context.batch.instances.push(instance);
+}
+
+/// Returns the uniforms set by the user, without the projection appended to them.
+fn shader_uniforms(uniforms: &[u8]) -> &[u8] {
+    &uniforms[..uniforms.len() - std::mem::size_of::<Matrix4<f32>>()]
+}
+
+/// Draws everything in the batch.
+pub(crate) fn flush(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+    let state = match ctx.gfx_context.batch.state.take() {
+        Some(state) => state,
+        None => return,
+    };
+    let mut instances = std::mem::take(&mut ctx.gfx_context.batch.instances);
+    if instances.is_empty() {
+        return;
+    }
+
+    let batch = &mut ctx.gfx_context.batch;
+    if batch.capacity < instances.len() {
+        batch.capacity = instances.len().next_power_of_two();
+        batch.bindings.vertex_buffers[1].delete();
+        batch.bindings.vertex_buffers[1] = Buffer::stream(
+            quad_ctx,
+            BufferType::VertexBuffer,
+            std::mem::size_of::<InstanceAttributes>() * batch.capacity,
+        );
+    }
+    batch.bindings.vertex_buffers[1].update(quad_ctx, &instances);
+    batch.bindings.images[0] = state.texture;
+    state.texture.set_filter(quad_ctx, state.filter);
+
+    quad_ctx.begin_pass(state.pass, PassAction::Nothing);
+    quad_ctx.apply_pipeline(&ctx.gfx_context.shaders[state.shader].pipeline);
+    state.clip.apply(quad_ctx);
+    quad_ctx.apply_bindings(&ctx.gfx_context.batch.bindings);
+
+    // draw with the uniforms and projection the instances were added with, even if they've
+    // changed since
+    let mut uniforms = state.uniforms;
+    uniforms.extend_from_slice(&[0; std::mem::size_of::<Matrix4<f32>>()]);
+    let gfx = &mut ctx.gfx_context;
+    std::mem::swap(&mut gfx.shaders[state.shader].uniforms, &mut uniforms);
+    let current_projection = std::mem::replace(&mut gfx.projection, state.projection);
+    apply_uniforms(ctx, quad_ctx, state.shader, None);
+    let gfx = &mut ctx.gfx_context;
+    gfx.projection = current_projection;
+    std::mem::swap(&mut gfx.shaders[state.shader].uniforms, &mut uniforms);
+
+    crate::graphics::set_current_blend_mode(quad_ctx, state.blend_mode);
+    quad_ctx.draw(0, 6, instances.len() as i32);
+    crate::graphics::restore_blend_mode(ctx, quad_ctx);
+
+    quad_ctx.end_render_pass();
+
+    // keep the allocation around for the next batch
+    instances.clear();
+    ctx.gfx_context.batch.instances = instances;
+}
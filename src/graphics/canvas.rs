@@ -1,15 +1,88 @@
+use std::rc::Rc;
+
 use crate::{
+    error::GameError,
     graphics::{BlendMode, DrawParam, Drawable, FilterMode, Image, Rect},
     Context, GameResult,
 };
 
 use crate::graphics::drawparam::Transform;
+use crate::graphics::image::{flip_rows, read_texture_rgba8};
 use miniquad::{RenderPass, Texture, TextureFormat, TextureParams};
 
+/// The format of the color attachment of a [`Canvas`](struct.Canvas.html).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CanvasFormat {
+    /// 8 bits per channel.
+    #[default]
+    Rgba8,
+    /// 16 bit floats per channel, e.g. for HDR lighting or bloom, where values may exceed 1.0.
+    ///
+    /// Not available on WASM.
+    Rgba16F,
+    /// A single 8 bit channel, e.g. for masks or light maps. The red channel of what's drawn is
+    /// stored and reads back as the alpha channel.
+    ///
+    /// Not available on WASM.
+    R8,
+}
+
+/// Options for creating a [`Canvas`](struct.Canvas.html) through
+/// [`Canvas::with_settings`](struct.Canvas.html#method.with_settings).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CanvasSettings {
+    pub(crate) sample_count: i32,
+    pub(crate) depth_stencil: bool,
+    pub(crate) format: CanvasFormat,
+}
+
+impl Default for CanvasSettings {
+    fn default() -> CanvasSettings {
+        CanvasSettings {
+            sample_count: 1,
+            depth_stencil: false,
+            format: CanvasFormat::Rgba8,
+        }
+    }
+}
+
+impl CanvasSettings {
+    /// Set how many samples should be used in MSAA. The canvas is drawn to multisampled and
+    /// resolved into its image whenever the image is used.
+    ///
+    /// The count is clamped to what the hardware supports, and multisampling isn't available on
+    /// WASM.
+    ///
+    /// Default: `1`
+    pub fn sample_count(mut self, val: i32) -> Self {
+        self.sample_count = val;
+        self
+    }
+    /// Set whether the canvas gets a depth and stencil buffer, next to its color.
+    ///
+    /// On WASM only a depth buffer is available, without stencil.
+    ///
+    /// Default: `false`
+    pub fn depth_stencil(mut self, val: bool) -> Self {
+        self.depth_stencil = val;
+        self
+    }
+    /// Set the format of the color.
+    ///
+    /// Default: `CanvasFormat::Rgba8`
+    pub fn format(mut self, val: CanvasFormat) -> Self {
+        self.format = val;
+        self
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Canvas {
     image: Image,
     pub(crate) offscreen_pass: RenderPass,
+    settings: CanvasSettings,
+    /// The attachments not managed by miniquad, shared between all clones of the canvas.
+    attachments: Option<Rc<Attachments>>,
 }
 
 impl Canvas {
@@ -20,23 +93,95 @@ impl Canvas {
         width: u16,
         height: u16,
     ) -> GameResult<Canvas> {
+        Canvas::with_settings(ctx, quad_ctx, width, height, CanvasSettings::default())
+    }
+
+    /// Create a new `Canvas` with the specified size, multisampling, depth and stencil buffer
+    /// and color format.
+    ///
+    /// Returns an error if the combination of settings isn't supported by the hardware.
+    ///
+    /// ```rust, ignore
+    /// let settings = CanvasSettings::default()
+    ///     .sample_count(4)
+    ///     .depth_stencil(true)
+    ///     .format(CanvasFormat::Rgba16F);
+    /// let canvas = Canvas::with_settings(ctx, quad_ctx, 800, 600, settings)?;
+    /// ```
+    pub fn with_settings(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        width: u16,
+        height: u16,
+        settings: CanvasSettings,
+    ) -> GameResult<Canvas> {
+        let filter = ctx.gfx_context.default_filter;
+        Canvas::create(quad_ctx, width, height, settings, filter)
+    }
+
+    /// Like `with_settings`, but without the need for a `Context`.
+    pub(crate) fn create(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        width: u16,
+        height: u16,
+        mut settings: CanvasSettings,
+        filter: FilterMode,
+    ) -> GameResult<Canvas> {
+        let format = match settings.format {
+            CanvasFormat::Rgba8 | CanvasFormat::Rgba16F => TextureFormat::RGBA8,
+            CanvasFormat::R8 => TextureFormat::Alpha,
+        };
+        if cfg!(target_arch = "wasm32") && settings.format != CanvasFormat::Rgba8 {
+            return Err(GameError::RenderError(format!(
+                "{:?} canvases aren't supported on WASM",
+                settings.format
+            )));
+        }
+
         let texture = Texture::new_render_texture(
             quad_ctx,
             TextureParams {
                 width: width as u32,
                 height: height as u32,
-                format: TextureFormat::RGBA8,
+                format,
                 ..Default::default()
             },
         );
 
-        let image = Image::from_texture(quad_ctx, texture, ctx.gfx_context.default_filter)?;
+        #[cfg(target_arch = "wasm32")]
+        let (offscreen_pass, attachments) = {
+            settings.sample_count = 1;
+            let depth = settings.depth_stencil.then(|| {
+                Texture::new_render_texture(
+                    quad_ctx,
+                    TextureParams {
+                        width: width as u32,
+                        height: height as u32,
+                        format: TextureFormat::Depth,
+                        ..Default::default()
+                    },
+                )
+            });
+            (RenderPass::new(quad_ctx, texture, depth), None)
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let (offscreen_pass, attachments) = {
+            if settings.format == CanvasFormat::Rgba16F {
+                attachments::set_float_storage(texture);
+            }
+            let pass = RenderPass::new(quad_ctx, texture, None);
+            let attachments = attachments::create(quad_ctx, pass, texture, &mut settings)
+                .inspect_err(|_| pass.delete(quad_ctx))?;
+            (pass, attachments.map(Rc::new))
+        };
 
-        let offscreen_pass = RenderPass::new(quad_ctx, texture, None);
+        let image = Image::from_texture(quad_ctx, texture, filter)?;
 
         Ok(Canvas {
             image,
             offscreen_pass,
+            settings,
+            attachments,
         })
     }
 
@@ -52,10 +197,43 @@ impl Canvas {
     }
 
     /// Gets the backend `Image` that is being rendered to.
+    ///
+    /// For multisampled canvases the image only holds what was drawn up to the last
+    /// [`resolve`](#method.resolve), which happens automatically when the canvas is drawn or read.
     pub fn image(&self) -> &Image {
         &self.image
     }
 
+    /// Returns the settings the canvas was created with, with the sample count being the one
+    /// actually used.
+    pub fn settings(&self) -> CanvasSettings {
+        self.settings
+    }
+
+    /// Returns the number of samples used in MSAA.
+    pub fn sample_count(&self) -> i32 {
+        self.settings.sample_count
+    }
+
+    /// Draws everything drawn so far and, for multisampled canvases, resolves the samples
+    /// into the image of the canvas.
+    ///
+    /// Only necessary when using [`image`](#method.image) directly, e.g. as a texture in a
+    /// shader.
+    pub fn resolve(&self, ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+        crate::graphics::flush(ctx, quad_ctx);
+        if let Some(attachments) = &self.attachments {
+            attachments.resolve(self.width(), self.height());
+        }
+    }
+
+    /// Marks the canvas as drawn to, so that it gets resolved before its image is used.
+    pub(crate) fn mark_drawn(&self) {
+        if let Some(attachments) = &self.attachments {
+            attachments.needs_resolve.set(true);
+        }
+    }
+
     /// Return the width of the canvas.
     pub fn width(&self) -> u16 {
         self.image.width
@@ -81,7 +259,26 @@ impl Canvas {
         self.image.set_filter(mode)
     }
 
+    /// Reads the pixels of the canvas back from the GPU, as RGBA8 rows from top to bottom.
+    pub fn to_rgba8(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    ) -> GameResult<Vec<u8>> {
+        self.resolve(ctx, quad_ctx);
+        let mut bytes = read_texture_rgba8(&self.image.texture)?;
+        // the rows of render targets are stored bottom-up
+        flip_rows(
+            &mut bytes,
+            usize::from(self.width()),
+            usize::from(self.height()),
+        );
+        Ok(bytes)
+    }
+
     /// Destroys the `Canvas` and returns the `Image` it contains.
+    ///
+    /// Multisampled canvases have to be [resolved](#method.resolve) first.
     pub fn into_inner(self) -> Image {
         // TODO: This texture is created with different settings
         // than the default; does that matter?
@@ -96,6 +293,8 @@ impl Drawable for Canvas {
         quad_ctx: &mut miniquad::graphics::GraphicsContext,
         param: DrawParam,
     ) -> GameResult {
+        self.resolve(ctx, quad_ctx);
+
         // We have to mess with the scale to make everything
         // be its-unit-size-in-pixels.
         let scale_x = param.src.w * f32::from(self.width());
@@ -166,3 +365,227 @@ fn flip_draw_param_vertical(param: DrawParam) -> DrawParam {
 pub fn set_canvas(ctx: &mut Context, target: Option<&Canvas>) {
     ctx.gfx_context.canvas = target.cloned();
 }
+
+/// The multisampled color buffer and the depth and stencil buffer of a canvas, which miniquad
+/// doesn't support itself, so they're attached to the framebuffer of the render pass through GL.
+#[cfg(not(target_arch = "wasm32"))]
+mod attachments {
+    use std::cell::Cell;
+
+    use miniquad::gl::*;
+    use miniquad::{PassAction, RenderPass, Texture};
+
+    use super::{CanvasFormat, CanvasSettings};
+    use crate::{error::GameError, GameResult};
+
+    // missing from `miniquad::gl`
+    const GL_MAX_SAMPLES: GLenum = 0x8D57;
+    const GL_DEPTH24_STENCIL8: GLenum = 0x88F0;
+    const GL_DEPTH_STENCIL_ATTACHMENT: GLenum = 0x821A;
+    const GL_TEXTURE_BINDING_2D: GLenum = 0x8069;
+
+    #[derive(Debug)]
+    pub(super) struct Attachments {
+        /// The framebuffer of the render pass.
+        framebuffer: GLuint,
+        /// The framebuffer the image is attached to, if the render pass is multisampled.
+        resolve_framebuffer: Option<GLuint>,
+        renderbuffers: Vec<GLuint>,
+        pub(super) needs_resolve: Cell<bool>,
+    }
+
+    /// Gives the texture 16 bit float storage.
+    pub(super) fn set_float_storage(texture: Texture) {
+        unsafe {
+            let mut bound_texture = 0;
+            glGetIntegerv(GL_TEXTURE_BINDING_2D, &mut bound_texture);
+            glBindTexture(GL_TEXTURE_2D, texture.gl_internal_id());
+            glTexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                GL_RGBA16F as i32,
+                texture.width as i32,
+                texture.height as i32,
+                0,
+                GL_RGBA,
+                GL_FLOAT,
+                std::ptr::null(),
+            );
+            glBindTexture(GL_TEXTURE_2D, bound_texture as GLuint);
+        }
+    }
+
+    /// Attaches the buffers asked for in `settings` to the framebuffer of `pass`, clamping the
+    /// sample count of `settings` to the supported one.
+    ///
+    /// Returns `None` if the render pass needs nothing else than its texture.
+    pub(super) fn create(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        pass: RenderPass,
+        texture: Texture,
+        settings: &mut CanvasSettings,
+    ) -> GameResult<Option<Attachments>> {
+        let mut max_samples = 1;
+        unsafe { glGetIntegerv(GL_MAX_SAMPLES, &mut max_samples) };
+        settings.sample_count = settings.sample_count.clamp(1, max_samples.max(1));
+        let multisampled = settings.sample_count > 1;
+        if !multisampled && !settings.depth_stencil {
+            return Ok(None);
+        }
+
+        // miniquad doesn't hand out the framebuffer of a render pass, but binds it when
+        // beginning the pass
+        let mut framebuffer = 0;
+        quad_ctx.begin_pass(pass, PassAction::Nothing);
+        unsafe { glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut framebuffer) };
+
+        let mut attachments = Attachments {
+            framebuffer: framebuffer as GLuint,
+            resolve_framebuffer: None,
+            renderbuffers: Vec::new(),
+            needs_resolve: Cell::new(false),
+        };
+        let (width, height) = (texture.width as i32, texture.height as i32);
+        let storage = |internal_format: GLenum| -> GLuint {
+            let mut renderbuffer = 0;
+            unsafe {
+                glGenRenderbuffers(1, &mut renderbuffer);
+                glBindRenderbuffer(GL_RENDERBUFFER, renderbuffer);
+                if multisampled {
+                    glRenderbufferStorageMultisample(
+                        GL_RENDERBUFFER,
+                        settings.sample_count,
+                        internal_format,
+                        width,
+                        height,
+                    );
+                } else {
+                    glRenderbufferStorage(GL_RENDERBUFFER, internal_format, width, height);
+                }
+                glBindRenderbuffer(GL_RENDERBUFFER, 0);
+            }
+            renderbuffer
+        };
+
+        let result = unsafe {
+            if multisampled {
+                let color = storage(match settings.format {
+                    CanvasFormat::Rgba8 => GL_RGBA8,
+                    CanvasFormat::Rgba16F => GL_RGBA16F,
+                    CanvasFormat::R8 => GL_R8,
+                });
+                attachments.renderbuffers.push(color);
+                glFramebufferRenderbuffer(
+                    GL_FRAMEBUFFER,
+                    GL_COLOR_ATTACHMENT0,
+                    GL_RENDERBUFFER,
+                    color,
+                );
+            }
+            if settings.depth_stencil {
+                let depth_stencil = storage(GL_DEPTH24_STENCIL8);
+                attachments.renderbuffers.push(depth_stencil);
+                glFramebufferRenderbuffer(
+                    GL_FRAMEBUFFER,
+                    GL_DEPTH_STENCIL_ATTACHMENT,
+                    GL_RENDERBUFFER,
+                    depth_stencil,
+                );
+            }
+            let mut result = check_status();
+
+            if multisampled && result.is_ok() {
+                let mut resolve_framebuffer = 0;
+                glGenFramebuffers(1, &mut resolve_framebuffer);
+                attachments.resolve_framebuffer = Some(resolve_framebuffer);
+                glBindFramebuffer(GL_FRAMEBUFFER, resolve_framebuffer);
+                glFramebufferTexture2D(
+                    GL_FRAMEBUFFER,
+                    GL_COLOR_ATTACHMENT0,
+                    GL_TEXTURE_2D,
+                    texture.gl_internal_id(),
+                    0,
+                );
+                result = check_status();
+            }
+            result
+        };
+        quad_ctx.end_render_pass();
+
+        result.map(|_| Some(attachments)).map_err(|status| {
+            GameError::RenderError(format!(
+                "Canvas settings {:?} aren't supported, the framebuffer is incomplete ({:#x})",
+                settings, status
+            ))
+        })
+    }
+
+    /// Checks whether the bound framebuffer is complete, returning its status if not.
+    unsafe fn check_status() -> Result<(), GLenum> {
+        let status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
+        if status == GL_FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(status)
+        }
+    }
+
+    impl Attachments {
+        /// Copies the samples into the image, if anything was drawn since the last time.
+        pub(super) fn resolve(&self, width: u16, height: u16) {
+            let resolve_framebuffer = match self.resolve_framebuffer {
+                Some(framebuffer) if self.needs_resolve.replace(false) => framebuffer,
+                _ => return,
+            };
+            let (width, height) = (i32::from(width), i32::from(height));
+            unsafe {
+                let mut bound_framebuffer = 0;
+                glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut bound_framebuffer);
+                glBindFramebuffer(GL_READ_FRAMEBUFFER, self.framebuffer);
+                glBindFramebuffer(GL_DRAW_FRAMEBUFFER, resolve_framebuffer);
+                // miniquad keeps the scissor test enabled, which would limit the blit
+                glDisable(GL_SCISSOR_TEST);
+                glBlitFramebuffer(
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    GL_COLOR_BUFFER_BIT,
+                    GL_NEAREST,
+                );
+                glEnable(GL_SCISSOR_TEST);
+                glBindFramebuffer(GL_FRAMEBUFFER, bound_framebuffer as GLuint);
+            }
+        }
+    }
+
+    impl Drop for Attachments {
+        fn drop(&mut self) {
+            unsafe {
+                glDeleteRenderbuffers(self.renderbuffers.len() as i32, self.renderbuffers.as_ptr());
+                if let Some(framebuffer) = &self.resolve_framebuffer {
+                    glDeleteFramebuffers(1, framebuffer);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+use attachments::Attachments;
+
+/// Nothing but miniquad's own textures can be attached to render passes on WASM.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+struct Attachments {
+    needs_resolve: std::cell::Cell<bool>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Attachments {
+    fn resolve(&self, _width: u16, _height: u16) {}
+}
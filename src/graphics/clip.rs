@@ -0,0 +1,229 @@
+//! Clipping draws to a rectangle through the scissor test, or to any shape through the stencil
+//! buffer.
+
+use cgmath::{Matrix4, Vector4};
+use miniquad::{CompareFunc, StencilFaceState, StencilOp, StencilState};
+
+use crate::{
+    error::GameResult,
+    graphics::{self, DrawParam, Drawable, Rect},
+    Context,
+};
+
+/// How the value in the stencil buffer is compared to the one given to
+/// [`set_stencil_test`](fn.set_stencil_test.html), with a pixel being drawn only if the
+/// comparison holds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StencilCompare {
+    /// The stored value equals the given one.
+    Equal,
+    NotEqual,
+    /// The stored value is less than the given one.
+    Less,
+    LessOrEqual,
+    /// The stored value is greater than the given one.
+    Greater,
+    GreaterOrEqual,
+}
+
+impl From<StencilCompare> for CompareFunc {
+    fn from(compare: StencilCompare) -> Self {
+        // GL compares the reference value to the stored one, so the sides are swapped
+        match compare {
+            StencilCompare::Equal => CompareFunc::Equal,
+            StencilCompare::NotEqual => CompareFunc::NotEqual,
+            StencilCompare::Less => CompareFunc::Greater,
+            StencilCompare::LessOrEqual => CompareFunc::GreaterOrEqual,
+            StencilCompare::Greater => CompareFunc::Less,
+            StencilCompare::GreaterOrEqual => CompareFunc::LessOrEqual,
+        }
+    }
+}
+
+/// Restricts all following draws to the given rectangle, in screen coordinates (see
+/// [`set_screen_coordinates`](fn.set_screen_coordinates.html)), or lifts the restriction if
+/// `None`.
+///
+/// The rectangle is mapped to pixels of the current render target (the screen or the current
+/// [`Canvas`](struct.Canvas.html)) with the projection at the time of each draw, so it keeps
+/// covering the same area when switching targets or changing the screen coordinates.
+pub fn set_scissor(ctx: &mut Context, rect: Option<Rect>) {
+    ctx.gfx_context.scissor = rect;
+}
+
+/// Returns the rectangle draws are restricted to, if any.
+pub fn scissor(ctx: &Context) -> Option<Rect> {
+    ctx.gfx_context.scissor
+}
+
+/// Draws the `Drawable` into the stencil buffer of the current render target instead of its
+/// color, setting the stencil value of every pixel it covers to `value`. Together with
+/// [`set_stencil_test`](fn.set_stencil_test.html) this clips later draws to any shape.
+///
+/// Only the shape of what's drawn counts, so the transparent parts of images are part of the
+/// mask as well; draw a `Mesh` to get an exact shape. The stencil test isn't applied while
+/// drawing the mask, the scissor rectangle is.
+///
+/// The screen has a stencil buffer on desktop, but not on WASM. Canvases need to be created
+/// with [`CanvasSettings::depth_stencil`](struct.CanvasSettings.html#method.depth_stencil).
+///
+/// ```rust, ignore
+/// // only draw the scene inside of a circle
+/// let circle = Mesh::new_circle(ctx, quad_ctx, DrawMode::fill(), [400.0, 300.0], 100.0, 0.5, Color::WHITE)?;
+/// graphics::clear_stencil(ctx, quad_ctx);
+/// graphics::draw_stencil_mask(ctx, quad_ctx, &circle, DrawParam::default(), 1)?;
+/// graphics::set_stencil_test(ctx, Some((StencilCompare::Equal, 1)));
+/// draw_scene(ctx, quad_ctx)?;
+/// graphics::set_stencil_test(ctx, None);
+/// ```
+pub fn draw_stencil_mask<D, T>(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    drawable: &D,
+    params: T,
+    value: u8,
+) -> GameResult
+where
+    D: Drawable,
+    T: Into<DrawParam>,
+{
+    let previous = ctx.gfx_context.stencil_mask.replace(value);
+    let result = graphics::draw(ctx, quad_ctx, drawable, params);
+    ctx.gfx_context.stencil_mask = previous;
+    result
+}
+
+/// Resets the stencil buffer of the current render target to 0.
+///
+/// [`clear`](fn.clear.html) does so as well.
+pub fn clear_stencil(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+    graphics::flush(ctx, quad_ctx);
+
+    let action = miniquad::PassAction::Clear {
+        color: None,
+        depth: None,
+        stencil: Some(0),
+    };
+    let pass = ctx.framebuffer();
+    quad_ctx.begin_pass(pass, action);
+    quad_ctx.end_render_pass();
+}
+
+/// Restricts all following draws to the pixels where the value in the stencil buffer compares
+/// to `value` as given, or lifts the restriction if `None`. See
+/// [`draw_stencil_mask`](fn.draw_stencil_mask.html).
+pub fn set_stencil_test(ctx: &mut Context, test: Option<(StencilCompare, u8)>) {
+    ctx.gfx_context.stencil_test = test;
+}
+
+/// Returns the stencil test draws are restricted by, if any.
+pub fn stencil_test(ctx: &Context) -> Option<(StencilCompare, u8)> {
+    ctx.gfx_context.stencil_test
+}
+
+/// The scissor rectangle and stencil state of a draw, taken at the time it's done.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct ClipState {
+    /// In pixels of the render target, with the origin at the bottom left.
+    scissor: Option<(i32, i32, i32, i32)>,
+    stencil: Option<StencilState>,
+    /// Whether only the stencil buffer is drawn to.
+    mask: bool,
+}
+
+impl ClipState {
+    pub(crate) fn current(ctx: &Context, quad_ctx: &miniquad::graphics::GraphicsContext) -> Self {
+        let gfx = &ctx.gfx_context;
+        let target_size = match &gfx.canvas {
+            Some(canvas) => (f32::from(canvas.width()), f32::from(canvas.height())),
+            None => graphics::drawable_size(quad_ctx),
+        };
+        let scissor = gfx
+            .scissor
+            .map(|rect| scissor_pixels(rect, gfx.projection, target_size));
+
+        let stencil = match (gfx.stencil_mask, gfx.stencil_test) {
+            (Some(value), _) => Some(stencil_state(
+                CompareFunc::Always,
+                StencilOp::Replace,
+                value,
+            )),
+            (None, Some((compare, value))) => {
+                Some(stencil_state(compare.into(), StencilOp::Keep, value))
+            }
+            (None, None) => None,
+        };
+
+        ClipState {
+            scissor,
+            stencil,
+            mask: gfx.stencil_mask.is_some(),
+        }
+    }
+
+    /// Applies the state, which has to happen after beginning the render pass and applying the
+    /// pipeline, as both reset it.
+    pub(crate) fn apply(&self, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+        if let Some((x, y, w, h)) = self.scissor {
+            quad_ctx.apply_scissor_rect(x, y, w, h);
+        }
+        quad_ctx.set_stencil(self.stencil);
+        if self.mask {
+            quad_ctx.set_color_write((false, false, false, false));
+        }
+    }
+}
+
+fn stencil_state(test_func: CompareFunc, pass_op: StencilOp, value: u8) -> StencilState {
+    let face = StencilFaceState {
+        fail_op: StencilOp::Keep,
+        depth_fail_op: StencilOp::Keep,
+        pass_op,
+        test_func,
+        test_ref: i32::from(value),
+        test_mask: 0xff,
+        write_mask: 0xff,
+    };
+    StencilState {
+        front: face,
+        back: face,
+    }
+}
+
+/// Maps a rectangle in screen coordinates to pixels of a render target of the given size.
+pub(crate) fn scissor_pixels(
+    rect: Rect,
+    projection: Matrix4<f32>,
+    (target_width, target_height): (f32, f32),
+) -> (i32, i32, i32, i32) {
+    let corners = [
+        (rect.x, rect.y),
+        (rect.x + rect.w, rect.y),
+        (rect.x, rect.y + rect.h),
+        (rect.x + rect.w, rect.y + rect.h),
+    ];
+    let (mut left, mut bottom) = (f32::INFINITY, f32::INFINITY);
+    let (mut right, mut top) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for (x, y) in corners {
+        // normalized device coordinates, from -1 to 1 with y pointing up
+        let ndc = projection * Vector4::new(x, y, 0.0, 1.0);
+        let (x, y) = (ndc.x / ndc.w, ndc.y / ndc.w);
+        left = left.min(x);
+        right = right.max(x);
+        bottom = bottom.min(y);
+        top = top.max(y);
+    }
+
+    let to_pixels =
+        |ndc: f32, size: f32| ((ndc + 1.0) / 2.0 * size).round().clamp(0.0, size) as i32;
+    let (x, y) = (
+        to_pixels(left, target_width),
+        to_pixels(bottom, target_height),
+    );
+    (
+        x,
+        y,
+        to_pixels(right, target_width) - x,
+        to_pixels(top, target_height) - y,
+    )
+}
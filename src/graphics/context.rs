@@ -1,11 +1,18 @@
-use crate::graphics::{types::Rect, Canvas, FilterMode, Shader, ShaderId};
+use crate::graphics::{types::Rect, Canvas, FilterMode, Shader, ShaderId, StencilCompare};
 use std::rc::Rc;
 
-use crate::graphics::{spritebatch, BlendMode, DrawParam, Font, Image};
+use crate::graphics::{
+    spritebatch, BitmapFont, BlendMode, DrawParam, Font, GlyphCacheSettings, GlyphCacheTracker,
+    Image, SdfFont,
+};
 use cgmath::Matrix4;
-use glyph_brush::{GlyphBrush, GlyphBrushBuilder};
+use glyph_brush::{FontId, GlyphBrush, GlyphBrushBuilder};
 use miniquad::{BufferLayout, PipelineParams, Texture, VertexAttribute, VertexFormat, VertexStep};
 use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::batch::Batch;
+use super::hot_reload::HotReloader;
 
 pub struct GraphicsContext {
     pub(crate) screen_rect: Rect,
@@ -16,10 +23,28 @@ pub struct GraphicsContext {
     pub(crate) shaders: Vec<Shader>,
     pub(crate) blend_mode: BlendMode,
     pub(crate) default_filter: FilterMode,
+    pub(crate) scissor: Option<Rect>,
+    pub(crate) stencil_test: Option<(StencilCompare, u8)>,
+    /// The value written to the stencil buffer while drawing a stencil mask.
+    pub(crate) stencil_mask: Option<u8>,
 
     pub(crate) glyph_brush: Rc<RefCell<GlyphBrush<DrawParam>>>,
     pub(crate) glyph_cache: Image,
     pub(crate) glyph_state: Rc<RefCell<spritebatch::SpriteBatch>>,
+    pub(crate) glyph_cache_settings: GlyphCacheSettings,
+    pub(crate) glyph_cache_tracker: GlyphCacheTracker,
+    /// The atlases of the fonts drawn through signed distance fields.
+    pub(crate) sdf_fonts: HashMap<FontId, SdfFont>,
+    /// Created on the first draw with an SDF font.
+    pub(crate) sdf_shader: Option<ShaderId>,
+    /// The pages of the fonts drawn from images.
+    pub(crate) bitmap_fonts: HashMap<FontId, BitmapFont>,
+
+    /// Image draws waiting to be drawn together.
+    pub(crate) batch: Batch,
+
+    /// Only `Some` if hot reloading is enabled.
+    pub(crate) hot_reload: Option<HotReloader>,
 }
 
 impl GraphicsContext {
@@ -72,7 +97,10 @@ impl GraphicsContext {
         // Glyph cache stuff.
         let font_vec = glyph_brush::ab_glyph::FontArc::try_from_slice(Font::default_font_bytes())
             .expect("Invalid default font bytes, should never happen");
-        let glyph_brush = GlyphBrushBuilder::using_font(font_vec).build();
+        let glyph_cache_settings = GlyphCacheSettings::default();
+        let glyph_brush = GlyphBrushBuilder::using_font(font_vec)
+            .initial_cache_size(glyph_cache_settings.initial_size)
+            .build();
         let (glyph_cache_width, glyph_cache_height) = glyph_brush.texture_dimensions();
         let initial_contents = vec![
             255;
@@ -92,6 +120,8 @@ impl GraphicsContext {
             glyph_cache.clone(),
         )));
 
+        let batch = Batch::new(quad_ctx, white_texture);
+
         GraphicsContext {
             projection,
             screen_rect,
@@ -101,9 +131,19 @@ impl GraphicsContext {
             shaders: vec![gwg_default_shader],
             blend_mode: BlendMode::Alpha,
             default_filter: FilterMode::Linear,
+            scissor: None,
+            stencil_test: None,
+            stencil_mask: None,
             glyph_brush: Rc::new(RefCell::new(glyph_brush)),
             glyph_cache,
             glyph_state,
+            glyph_cache_settings,
+            glyph_cache_tracker: GlyphCacheTracker::default(),
+            sdf_fonts: HashMap::new(),
+            sdf_shader: None,
+            bitmap_fonts: HashMap::new(),
+            batch,
+            hot_reload: None,
         }
     }
 }
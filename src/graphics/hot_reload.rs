@@ -0,0 +1,264 @@
+//! Reloading of images, shaders and fonts once their files change on disk.
+//!
+//! Enabled through [`Conf::hot_reload`](../../conf/struct.Conf.html#method.hot_reload). Everything
+//! loaded through `Image::new`, `Shader::new` or `Font::new` from the `physical_root_dir` is
+//! watched by polling the modification times of its files. Once one of them changes the
+//! underlying texture, pipeline or font is swapped out in place, so all existing `Image` clones,
+//! `ShaderId`s and `Font`s pick up the change.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::SystemTime;
+
+use glyph_brush::FontId;
+use miniquad::{ShaderMeta, Texture};
+
+use crate::graphics::{BlendMode, Image, Shader, ShaderId};
+use crate::{timer, Context, GameResult};
+
+/// How often the files are checked for changes, in seconds.
+const POLL_INTERVAL: f64 = 0.5;
+
+enum Target {
+    Image {
+        texture: Texture,
+        width: u16,
+        height: u16,
+        /// Dead once all clones of the image are gone, meaning the texture was deleted.
+        alive: Weak<()>,
+    },
+    Shader {
+        id: ShaderId,
+        meta: ShaderMeta,
+        blend_mode: Option<BlendMode>,
+    },
+    Font {
+        id: FontId,
+    },
+}
+
+struct Watched {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    target: Target,
+}
+
+impl Watched {
+    /// Updates the stored modification times, returning whether any of them changed.
+    fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn read_files(&self) -> GameResult<Vec<Vec<u8>>> {
+        self.files
+            .iter()
+            .map(|(path, _)| Ok(std::fs::read(path)?))
+            .collect()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Keeps track of everything watched for changes.
+#[derive(Default)]
+pub(crate) struct HotReloader {
+    watched: Vec<Watched>,
+    last_poll: f64,
+}
+
+impl HotReloader {
+    fn watch(&mut self, files: Vec<PathBuf>, target: Target) {
+        let files = files
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect();
+        self.watched.push(Watched { files, target });
+    }
+}
+
+/// Returns the files on disk backing the given paths, if hot reloading is enabled and all of
+/// them are actually inside of the physical root dir.
+fn files_to_watch<P: AsRef<Path>>(ctx: &Context, paths: &[P]) -> Option<Vec<PathBuf>> {
+    ctx.gfx_context.hot_reload.as_ref()?;
+    paths
+        .iter()
+        .map(|path| ctx.filesystem.physical_file(path))
+        .collect()
+}
+
+pub(crate) fn watch_image<P: AsRef<Path>>(ctx: &mut Context, path: P, image: &Image) {
+    if let Some(files) = files_to_watch(ctx, &[path]) {
+        let target = Target::Image {
+            texture: image.texture,
+            width: image.width,
+            height: image.height,
+            alive: Arc::downgrade(&image.texture_clones_hack),
+        };
+        if let Some(reloader) = ctx.gfx_context.hot_reload.as_mut() {
+            reloader.watch(files, target);
+        }
+    }
+}
+
+pub(crate) fn watch_shader<P: AsRef<Path>>(
+    ctx: &mut Context,
+    paths: &[P],
+    id: ShaderId,
+    meta: ShaderMeta,
+    blend_mode: Option<BlendMode>,
+) {
+    if let Some(files) = files_to_watch(ctx, paths) {
+        let target = Target::Shader {
+            id,
+            meta,
+            blend_mode,
+        };
+        if let Some(reloader) = ctx.gfx_context.hot_reload.as_mut() {
+            reloader.watch(files, target);
+        }
+    }
+}
+
+pub(crate) fn watch_font<P: AsRef<Path>>(ctx: &mut Context, path: P, id: FontId) {
+    if let Some(files) = files_to_watch(ctx, &[path]) {
+        if let Some(reloader) = ctx.gfx_context.hot_reload.as_mut() {
+            reloader.watch(files, Target::Font { id });
+        }
+    }
+}
+
+/// Stops watching the file of an unloaded font.
+pub(crate) fn unwatch_font(ctx: &mut Context, id: FontId) {
+    if let Some(reloader) = ctx.gfx_context.hot_reload.as_mut() {
+        reloader.watched.retain(
+            |watched| !matches!(watched.target, Target::Font { id: font_id } if font_id == id),
+        );
+    }
+}
+
+/// Checks all watched files for changes and reloads whatever they belong to.
+///
+/// Called at the start of every frame, but only actually polls every `POLL_INTERVAL` seconds.
+pub(crate) fn poll(ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+    let mut reloader = match ctx.gfx_context.hot_reload.take() {
+        Some(reloader) => reloader,
+        None => return,
+    };
+
+    let now = timer::time();
+    if now - reloader.last_poll >= POLL_INTERVAL {
+        reloader.last_poll = now;
+
+        // forget about images which have been dropped in the meantime
+        reloader.watched.retain(|watched| match watched.target {
+            Target::Image { ref alive, .. } => alive.upgrade().is_some(),
+            _ => true,
+        });
+
+        for watched in reloader.watched.iter_mut() {
+            if watched.poll() {
+                let name = watched.files[0].0.display().to_string();
+                match reload(ctx, quad_ctx, watched) {
+                    Ok(()) => info!("Reloaded {}", name),
+                    Err(e) => error!("Couldn't reload {}: {}", name, e),
+                }
+            }
+        }
+    }
+
+    ctx.gfx_context.hot_reload = Some(reloader);
+}
+
+fn reload(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    watched: &Watched,
+) -> GameResult {
+    let files = watched.read_files()?;
+    match watched.target {
+        Target::Image {
+            texture,
+            width,
+            height,
+            ..
+        } => {
+            let (new_width, new_height, rgba) = super::image::decode_rgba8(&files[0])?;
+            if (new_width, new_height) != (width, height) {
+                // all clones of the image store their size, so there's no way to update those
+                warn!(
+                    "Not reloading image, as its size changed from {}x{} to {}x{}",
+                    width, height, new_width, new_height
+                );
+                return Ok(());
+            }
+            texture.update(quad_ctx, &rgba);
+        }
+        Target::Shader {
+            id,
+            ref meta,
+            blend_mode,
+        } => {
+            let vertex_source = String::from_utf8_lossy(&files[0]);
+            let pixel_source = String::from_utf8_lossy(&files[1]);
+            let mini_shader =
+                miniquad::Shader::new(quad_ctx, &vertex_source, &pixel_source, meta.clone())?;
+            let shader = Shader::from_mini_shader(quad_ctx, mini_shader, blend_mode);
+            // keep the uniforms, only the pipeline changes
+            let old_pipeline =
+                std::mem::replace(&mut ctx.gfx_context.shaders[id].pipeline, shader.pipeline);
+            delete_program(quad_ctx, &old_pipeline);
+        }
+        Target::Font { id } => {
+            let font = glyph_brush::ab_glyph::FontArc::try_from_vec(files[0].clone())
+                .map_err(|e| crate::GameError::ResourceLoadError(e.to_string()))?;
+            // this also drops all glyphs cached for the old version of the font
+            crate::graphics::text::replace_font(ctx, id, font);
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the GL program of a pipeline which won't be applied anymore.
+///
+/// miniquad can't delete pipelines, so the pipeline itself stays around, but without the
+/// program it only takes up a few bytes.
+#[cfg(not(target_arch = "wasm32"))]
+fn delete_program(
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    pipeline: &miniquad::Pipeline,
+) {
+    use miniquad::gl::*;
+
+    // missing from `miniquad::gl`
+    const GL_CURRENT_PROGRAM: GLenum = 0x8B8D;
+
+    // miniquad doesn't hand out the program of a pipeline, but uses it when applying the
+    // pipeline; every draw applies its own pipeline again, so this doesn't break anything
+    let mut program = 0;
+    quad_ctx.apply_pipeline(pipeline);
+    unsafe {
+        glGetIntegerv(GL_CURRENT_PROGRAM, &mut program);
+        glUseProgram(0);
+        glDeleteProgram(program as GLuint);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn delete_program(
+    _quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    _pipeline: &miniquad::Pipeline,
+) {
+    // nothing is ever watched on wasm
+}
@@ -9,12 +9,19 @@ use crate::{
     Context, GameError,
 };
 
-use miniquad::{Bindings, Buffer, BufferType, PassAction, Texture};
+use miniquad::{Bindings, Buffer, BufferType, Texture, TextureFormat};
 
-use crate::graphics::{apply_uniforms, Color};
+use crate::graphics::Color;
 pub use miniquad::graphics::FilterMode;
 use std::sync::Arc;
 
+/// Formats an `Image` can be encoded to, see [`Image::encode`](struct.Image.html#method.encode).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// A PNG image.
+    Png,
+}
+
 #[derive(Clone, Debug)]
 pub struct Image {
     pub(crate) texture: Texture,
@@ -36,12 +43,14 @@ impl Image {
     ) -> GameResult<Self> {
         use std::io::Read;
 
-        let mut file = filesystem::open(ctx, path)?;
+        let mut file = filesystem::open(ctx, path.as_ref())?;
 
         let mut bytes = vec![];
         file.bytes.read_to_end(&mut bytes)?;
 
-        Self::from_png_bytes(ctx, quad_ctx, &bytes)
+        let image = Self::from_png_bytes(ctx, quad_ctx, &bytes)?;
+        crate::graphics::hot_reload::watch_image(ctx, path, &image);
+        Ok(image)
     }
 
     pub fn from_png_bytes(
@@ -49,18 +58,8 @@ impl Image {
         quad_ctx: &mut miniquad::graphics::GraphicsContext,
         bytes: &[u8],
     ) -> GameResult<Self> {
-        match image::load_from_memory(bytes) {
-            Ok(img) => {
-                let rgba = img.to_rgba();
-
-                let width = rgba.width() as u16;
-                let height = rgba.height() as u16;
-                let bytes = rgba.into_raw();
-
-                Image::from_rgba8(ctx, quad_ctx, width, height, &bytes)
-            }
-            Err(e) => Err(GameError::ResourceLoadError(e.to_string())),
-        }
+        let (width, height, bytes) = decode_rgba8(bytes)?;
+        Image::from_rgba8(ctx, quad_ctx, width, height, &bytes)
     }
 
     pub fn from_rgba8(
@@ -164,6 +163,39 @@ impl Image {
         self.filter
     }
 
+    /// Reads the pixels of the image back from the GPU, as RGBA8 rows from top to bottom.
+    pub fn to_rgba8(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    ) -> GameResult<Vec<u8>> {
+        crate::graphics::flush(ctx, quad_ctx);
+        read_texture_rgba8(&self.texture)
+    }
+
+    /// Encodes the image to the given format and writes it to a file in the
+    /// [user data directory](../filesystem/fn.user_data_dir.html).
+    pub fn encode<P: AsRef<path::Path>>(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        format: ImageFormat,
+        path: P,
+    ) -> GameResult {
+        let rgba = self.to_rgba8(ctx, quad_ctx)?;
+        let mut file = filesystem::create(ctx, path)?;
+        match format {
+            ImageFormat::Png => image::png::PNGEncoder::new(&mut file).encode(
+                &rgba,
+                u32::from(self.width),
+                u32::from(self.height),
+                image::ColorType::RGBA(8),
+            )?,
+        }
+        std::io::Write::flush(&mut file)?;
+        Ok(())
+    }
+
     /// Draws without adapting the scaling.
     pub(crate) fn draw_image_raw(
         &self,
@@ -171,40 +203,99 @@ impl Image {
         quad_ctx: &mut miniquad::graphics::GraphicsContext,
         param: DrawParam,
     ) -> GameResult {
-        let instance = InstanceAttributes::from(&param);
-        self.bindings.vertex_buffers[1].update(quad_ctx, &[instance]);
-
         if self.dirty_filter.load() {
             self.dirty_filter.store(false);
             self.texture.set_filter(quad_ctx, self.filter);
         }
 
-        let pass = ctx.framebuffer();
-        quad_ctx.begin_pass(pass, PassAction::Nothing);
-        quad_ctx.apply_bindings(&self.bindings);
+        let instance = InstanceAttributes::from(&param);
+        crate::graphics::batch::add(
+            ctx,
+            quad_ctx,
+            self.texture,
+            self.filter,
+            self.blend_mode,
+            instance,
+        );
+        Ok(())
+    }
+}
 
-        let shader_id = *ctx.gfx_context.current_shader.borrow();
-        let current_shader = &mut ctx.gfx_context.shaders[shader_id];
-        quad_ctx.apply_pipeline(&current_shader.pipeline);
+/// Reads the contents of a texture back as RGBA8, with the first row being the first one
+/// uploaded (or the bottom one, for render targets).
+pub(crate) fn read_texture_rgba8(texture: &Texture) -> GameResult<Vec<u8>> {
+    use miniquad::gl::*;
 
-        apply_uniforms(ctx, quad_ctx, shader_id, None);
+    if texture.format == TextureFormat::Depth {
+        return Err(GameError::RenderError(
+            "Can't read back a depth texture".to_string(),
+        ));
+    }
 
-        let mut custom_blend = false;
-        if let Some(blend_mode) = self.blend_mode() {
-            custom_blend = true;
-            crate::graphics::set_current_blend_mode(quad_ctx, blend_mode)
+    let mut bytes = vec![0u8; 4 * texture.width as usize * texture.height as usize];
+    // always read as RGBA, as that's the only format guaranteed to be supported by GLES2/WebGL1
+    unsafe {
+        let mut bound_fbo: i32 = 0;
+        glGetIntegerv(GL_DRAW_FRAMEBUFFER_BINDING, &mut bound_fbo);
+        let mut fbo = 0;
+        glGenFramebuffers(1, &mut fbo);
+        glBindFramebuffer(GL_FRAMEBUFFER, fbo);
+        glFramebufferTexture2D(
+            GL_FRAMEBUFFER,
+            GL_COLOR_ATTACHMENT0,
+            GL_TEXTURE_2D,
+            texture.gl_internal_id(),
+            0,
+        );
+        glReadPixels(
+            0,
+            0,
+            texture.width as _,
+            texture.height as _,
+            GL_RGBA,
+            GL_UNSIGNED_BYTE,
+            bytes.as_mut_ptr() as _,
+        );
+        glBindFramebuffer(GL_FRAMEBUFFER, bound_fbo as _);
+        glDeleteFramebuffers(1, &fbo);
+    }
+
+    if texture.format == TextureFormat::Alpha {
+        // alpha textures are stored in the red channel on desktop and in the alpha channel on WASM
+        for pixel in bytes.chunks_exact_mut(4) {
+            let alpha = if cfg!(target_arch = "wasm32") {
+                pixel[3]
+            } else {
+                pixel[0]
+            };
+            pixel.copy_from_slice(&[255, 255, 255, alpha]);
         }
+    }
 
-        quad_ctx.draw(0, 6, 1);
+    Ok(bytes)
+}
 
-        // restore default blend mode
-        if custom_blend {
-            crate::graphics::restore_blend_mode(ctx, quad_ctx);
-        }
+/// Mirrors RGBA8 pixel data vertically, turning bottom-up rows as returned by OpenGL into
+/// top-down ones, or vice versa.
+pub(crate) fn flip_rows(bytes: &mut [u8], width: usize, height: usize) {
+    let stride = 4 * width;
+    for row in 0..height / 2 {
+        let (top, bottom) = bytes.split_at_mut((height - row - 1) * stride);
+        top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+    }
+}
 
-        quad_ctx.end_render_pass();
+/// Decodes an encoded image (usually a PNG) into its width, height and RGBA8 pixels.
+pub(crate) fn decode_rgba8(bytes: &[u8]) -> GameResult<(u16, u16, Vec<u8>)> {
+    match image::load_from_memory(bytes) {
+        Ok(img) => {
+            let rgba = img.to_rgba();
 
-        Ok(())
+            let width = rgba.width() as u16;
+            let height = rgba.height() as u16;
+            Ok((width, height, rgba.into_raw()))
+        }
+        Err(e) => Err(GameError::ResourceLoadError(e.to_string())),
     }
 }
 
@@ -827,10 +827,13 @@ impl Drawable for Mesh {
         quad_ctx: &mut miniquad::graphics::GraphicsContext,
         param: DrawParam,
     ) -> GameResult {
+        crate::graphics::flush(ctx, quad_ctx);
+
         let instance = InstanceAttributes::from(&param);
         self.bindings.vertex_buffers[1].update(quad_ctx, &[instance]);
 
         let pass = ctx.framebuffer();
+        let clip = ClipState::current(ctx, quad_ctx);
 
         quad_ctx.begin_pass(pass, PassAction::Nothing);
         quad_ctx.apply_bindings(&self.bindings);
@@ -838,6 +841,7 @@ impl Drawable for Mesh {
         let shader_id = *ctx.gfx_context.current_shader.borrow();
         let current_shader = &mut ctx.gfx_context.shaders[shader_id];
         quad_ctx.apply_pipeline(&current_shader.pipeline);
+        clip.apply(quad_ctx);
 
         apply_uniforms(ctx, quad_ctx, shader_id, None);
 
@@ -1090,13 +1094,17 @@ impl MeshBatch {
                 self.flush(ctx, quad_ctx)?;
             }
 
+            crate::graphics::flush(ctx, quad_ctx);
+
             let pass = ctx.framebuffer();
+            let clip = ClipState::current(ctx, quad_ctx);
             quad_ctx.begin_pass(pass, PassAction::Nothing);
             quad_ctx.apply_bindings(&self.mesh.bindings);
 
             let shader_id = *ctx.gfx_context.current_shader.borrow();
             let current_shader = &mut ctx.gfx_context.shaders[shader_id];
             quad_ctx.apply_pipeline(&current_shader.pipeline);
+            clip.apply(quad_ctx);
 
             apply_uniforms(
                 ctx,
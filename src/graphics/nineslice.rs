@@ -0,0 +1,303 @@
+use std::cell::{Cell, RefCell};
+
+use crate::{
+    error::GameResult,
+    graphics::{
+        self, spritebatch::SpriteBatch, BlendMode, Color, DrawParam, Drawable, Image, Rect,
+    },
+    Context,
+};
+
+/// How the edges or the center of a [`NineSlice`](struct.NineSlice.html) fill the space between
+/// the corners.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SliceMode {
+    /// Scales the part of the image to fit.
+    #[default]
+    Stretch,
+    /// Repeats the part of the image at its original size, cutting off the last repetition.
+    Tile,
+}
+
+/// The widths of the borders of a [`NineSlice`](struct.NineSlice.html), in pixels of the image.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Insets {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// Creates insets of the same width on all sides.
+    pub fn uniform(inset: f32) -> Self {
+        Insets::new(inset, inset, inset, inset)
+    }
+}
+
+/// An image split into nine parts by its [`Insets`](struct.Insets.html), so that it can be drawn
+/// at any size while its corners keep their size and its edges only grow along one axis, e.g.
+/// for the panels and buttons of a UI.
+///
+/// All parts are drawn together through a `SpriteBatch`. `DrawParam` works as with every other
+/// drawable: its color tints the whole image, and rotation and scale apply around its offset (in
+/// pixels, relative to the origin, not to `rect`).
+///
+/// ```rust, ignore
+/// let mut panel = NineSlice::new(Image::new(ctx, quad_ctx, "/panel.png")?, Insets::uniform(8.0));
+/// panel.set_rect(Rect::new(20.0, 20.0, 300.0, 120.0));
+/// graphics::draw(ctx, quad_ctx, &panel, DrawParam::new().color(Color::new(1.0, 1.0, 1.0, 0.8)))?;
+/// ```
+#[derive(Debug)]
+pub struct NineSlice {
+    batch: RefCell<SpriteBatch>,
+    image_size: (f32, f32),
+    insets: Insets,
+    rect: Rect,
+    edge_mode: SliceMode,
+    center_mode: SliceMode,
+    /// The color the sprites of the batch were last built with, `None` if they have to be
+    /// rebuilt.
+    built_with: Cell<Option<Color>>,
+}
+
+impl NineSlice {
+    /// Creates a new nine slice, initially covering the size of the whole image.
+    pub fn new(image: Image, insets: Insets) -> Self {
+        let rect = image.dimensions();
+        NineSlice {
+            image_size: (rect.w, rect.h),
+            batch: RefCell::new(SpriteBatch::new(image)),
+            insets,
+            rect,
+            edge_mode: SliceMode::Stretch,
+            center_mode: SliceMode::Stretch,
+            built_with: Cell::new(None),
+        }
+    }
+
+    /// Sets the area to fill, in pixels.
+    pub fn set_rect(&mut self, rect: Rect) {
+        self.rect = rect;
+        self.built_with.set(None);
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn set_insets(&mut self, insets: Insets) {
+        self.insets = insets;
+        self.built_with.set(None);
+    }
+
+    pub fn insets(&self) -> Insets {
+        self.insets
+    }
+
+    /// Sets how the edges fill the space between the corners.
+    ///
+    /// Default: `SliceMode::Stretch`
+    pub fn set_edge_mode(&mut self, mode: SliceMode) {
+        self.edge_mode = mode;
+        self.built_with.set(None);
+    }
+
+    pub fn edge_mode(&self) -> SliceMode {
+        self.edge_mode
+    }
+
+    /// Sets how the center fills the space between the edges.
+    ///
+    /// Default: `SliceMode::Stretch`
+    pub fn set_center_mode(&mut self, mode: SliceMode) {
+        self.center_mode = mode;
+        self.built_with.set(None);
+    }
+
+    pub fn center_mode(&self) -> SliceMode {
+        self.center_mode
+    }
+
+    /// Replaces the image, returning the old one.
+    pub fn set_image(&mut self, image: Image) -> Image {
+        self.built_with.set(None);
+        self.image_size = (f32::from(image.width()), f32::from(image.height()));
+        self.batch.get_mut().set_image(image)
+    }
+
+    /// Fills the sprite batch with the parts of the image.
+    fn build(&self, color: Color) {
+        let mut batch = self.batch.borrow_mut();
+        batch.clear();
+
+        let (image_width, image_height) = self.image_size;
+        let Insets {
+            left,
+            right,
+            top,
+            bottom,
+        } = self.insets;
+        let Rect { x, y, w, h } = self.rect;
+
+        // shrink the borders if they don't fit into the rect
+        let shrink_x = if left + right > w {
+            w / (left + right)
+        } else {
+            1.0
+        };
+        let shrink_y = if top + bottom > h {
+            h / (top + bottom)
+        } else {
+            1.0
+        };
+
+        let columns = [
+            (0.0, left, x, left * shrink_x),
+            (
+                left,
+                image_width - right,
+                x + left * shrink_x,
+                w - (left + right) * shrink_x,
+            ),
+            (
+                image_width - right,
+                image_width,
+                x + w - right * shrink_x,
+                right * shrink_x,
+            ),
+        ];
+        let rows = [
+            (0.0, top, y, top * shrink_y),
+            (
+                top,
+                image_height - bottom,
+                y + top * shrink_y,
+                h - (top + bottom) * shrink_y,
+            ),
+            (
+                image_height - bottom,
+                image_height,
+                y + h - bottom * shrink_y,
+                bottom * shrink_y,
+            ),
+        ];
+
+        for (row, &(src_top, src_bottom, dest_y, dest_h)) in rows.iter().enumerate() {
+            for (column, &(src_left, src_right, dest_x, dest_w)) in columns.iter().enumerate() {
+                let mode = match (row, column) {
+                    (1, 1) => self.center_mode,
+                    (1, _) | (_, 1) => self.edge_mode,
+                    _ => SliceMode::Stretch,
+                };
+                let src = Rect::new(
+                    src_left,
+                    src_top,
+                    src_right - src_left,
+                    src_bottom - src_top,
+                );
+                let dest = Rect::new(dest_x, dest_y, dest_w, dest_h);
+                let tile_x = mode == SliceMode::Tile && column == 1;
+                let tile_y = mode == SliceMode::Tile && row == 1;
+                add_part(
+                    &mut batch,
+                    (image_width, image_height),
+                    src,
+                    dest,
+                    (tile_x, tile_y),
+                    color,
+                );
+            }
+        }
+
+        self.built_with.set(Some(color));
+    }
+}
+
+/// Adds the `src` part of the image (in pixels) to the batch, filling `dest` by stretching or
+/// tiling along each axis.
+fn add_part(
+    batch: &mut SpriteBatch,
+    (image_width, image_height): (f32, f32),
+    src: Rect,
+    dest: Rect,
+    (tile_x, tile_y): (bool, bool),
+    color: Color,
+) {
+    if src.w <= 0.0 || src.h <= 0.0 || dest.w <= 0.0 || dest.h <= 0.0 {
+        return;
+    }
+
+    // the pieces along an axis as (offset into dest, length in dest, length in src)
+    let pieces = |tile: bool, src_length: f32, dest_length: f32| -> Vec<(f32, f32, f32)> {
+        if !tile {
+            return vec![(0.0, dest_length, src_length)];
+        }
+        let mut pieces = Vec::new();
+        let mut offset = 0.0;
+        while offset < dest_length {
+            let length = src_length.min(dest_length - offset);
+            pieces.push((offset, length, length));
+            offset += src_length;
+        }
+        pieces
+    };
+
+    for (offset_y, dest_h, src_h) in pieces(tile_y, src.h, dest.h) {
+        for &(offset_x, dest_w, src_w) in pieces(tile_x, src.w, dest.w).iter() {
+            let param = DrawParam::new()
+                .src(Rect::new(
+                    src.x / image_width,
+                    src.y / image_height,
+                    src_w / image_width,
+                    src_h / image_height,
+                ))
+                .dest(mint::Point2 {
+                    x: dest.x + offset_x,
+                    y: dest.y + offset_y,
+                })
+                .scale(mint::Vector2 {
+                    x: dest_w / src_w,
+                    y: dest_h / src_h,
+                })
+                .color(color);
+            batch.add(param);
+        }
+    }
+}
+
+impl Drawable for NineSlice {
+    fn draw(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        param: DrawParam,
+    ) -> GameResult {
+        // the color is baked into the sprites, as sprite batches ignore the color of `param`
+        if self.built_with.get() != Some(param.color) {
+            self.build(param.color);
+        }
+        graphics::draw(ctx, quad_ctx, &*self.batch.borrow(), param)
+    }
+
+    fn set_blend_mode(&mut self, mode: Option<BlendMode>) {
+        self.batch.get_mut().set_blend_mode(mode);
+    }
+
+    fn blend_mode(&self) -> Option<BlendMode> {
+        self.batch.borrow().blend_mode()
+    }
+
+    fn dimensions(&self, _ctx: &mut Context) -> Option<Rect> {
+        Some(self.rect)
+    }
+}
@@ -0,0 +1,696 @@
+//! Fullscreen effects applied to everything drawn in a frame.
+
+use bytemuck::Pod;
+use bytemuck_derive::{Pod, Zeroable};
+use miniquad::{
+    Bindings, Buffer, BufferLayout, BufferType, PassAction, Pipeline, PipelineParams, RenderPass,
+    ShaderMeta, Texture, UniformBlockLayout, UniformDesc, UniformType, VertexAttribute,
+    VertexFormat,
+};
+
+use crate::{
+    error::GameResult,
+    graphics::{self, Canvas, CanvasSettings},
+    Context,
+};
+
+const VERTEX: &str = r#"#version 100
+attribute vec2 position;
+
+varying vec2 uv;
+
+void main() {
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+    uv = position;
+}"#;
+
+/// The declarations every built-in pass starts with.
+const HEADER: &str = r#"#version 100
+precision mediump float;
+
+varying vec2 uv;
+
+uniform sampler2D Texture;
+uniform sampler2D Input;
+uniform vec2 Resolution;
+"#;
+
+const COPY: &str = r#"
+void main() {
+    gl_FragColor = texture2D(Texture, uv);
+}"#;
+
+/// A 9 tap gaussian blur along `DIRECTION`, spreading over `Radius` pixels.
+const BLUR: &str = r#"
+void main() {
+    vec2 offset = DIRECTION * Radius / (4.0 * Resolution);
+    vec4 color = texture2D(Texture, uv) * 0.2270270270;
+    color += (texture2D(Texture, uv + offset) + texture2D(Texture, uv - offset)) * 0.1945945946;
+    color += (texture2D(Texture, uv + 2.0 * offset) + texture2D(Texture, uv - 2.0 * offset)) * 0.1216216216;
+    color += (texture2D(Texture, uv + 3.0 * offset) + texture2D(Texture, uv - 3.0 * offset)) * 0.0540540541;
+    color += (texture2D(Texture, uv + 4.0 * offset) + texture2D(Texture, uv - 4.0 * offset)) * 0.0162162162;
+    gl_FragColor = color;
+}"#;
+
+const BLOOM_UNIFORMS: &str = r#"
+uniform float Threshold;
+uniform float Intensity;
+uniform float Radius;
+"#;
+
+const BLOOM_THRESHOLD: &str = r#"
+void main() {
+    vec3 color = texture2D(Texture, uv).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    gl_FragColor = vec4(color * max(brightness - Threshold, 0.0) / max(brightness, 0.0001), 1.0);
+}"#;
+
+const BLOOM_COMBINE: &str = r#"
+void main() {
+    vec4 color = texture2D(Input, uv);
+    gl_FragColor = vec4(color.rgb + texture2D(Texture, uv).rgb * Intensity, color.a);
+}"#;
+
+const CRT: &str = r#"
+uniform float Curvature;
+uniform float Scanlines;
+uniform float Aberration;
+
+void main() {
+    vec2 centered = uv * 2.0 - 1.0;
+    centered += centered * centered.yx * centered.yx * Curvature;
+    vec2 curved = centered * 0.5 + 0.5;
+    if (curved.x < 0.0 || curved.x > 1.0 || curved.y < 0.0 || curved.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec2 offset = vec2(Aberration / Resolution.x, 0.0);
+    vec3 color = vec3(
+        texture2D(Texture, curved + offset).r,
+        texture2D(Texture, curved).g,
+        texture2D(Texture, curved - offset).b
+    );
+    float scanline = sin(curved.y * Resolution.y * 3.14159265) * 0.5 + 0.5;
+    gl_FragColor = vec4(color * (1.0 - Scanlines * (1.0 - scanline)), 1.0);
+}"#;
+
+const VIGNETTE: &str = r#"
+uniform float Radius;
+uniform float Softness;
+uniform float Intensity;
+
+void main() {
+    vec4 color = texture2D(Texture, uv);
+    // 0 in the center, 1 in the corners
+    float dist = length(uv - 0.5) * 1.41421356;
+    float vignette = 1.0 - smoothstep(Radius - Softness, Radius, dist);
+    gl_FragColor = vec4(color.rgb * mix(1.0, vignette, Intensity), color.a);
+}"#;
+
+const COLOR_GRADING: &str = r#"
+uniform float Brightness;
+uniform float Contrast;
+uniform float Saturation;
+uniform vec3 Tint;
+
+void main() {
+    vec4 color = texture2D(Texture, uv);
+    vec3 rgb = (color.rgb + Brightness - 0.5) * Contrast + 0.5;
+    float luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    gl_FragColor = vec4(mix(vec3(luma), rgb, Saturation) * Tint, color.a);
+}"#;
+
+/// The uniforms of [`PostEffect::blur`](struct.PostEffect.html#method.blur).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Zeroable, Pod)]
+pub struct BlurParams {
+    /// How far the blur spreads, in pixels.
+    pub radius: f32,
+}
+
+impl Default for BlurParams {
+    fn default() -> Self {
+        BlurParams { radius: 4.0 }
+    }
+}
+
+/// The uniforms of [`PostEffect::bloom`](struct.PostEffect.html#method.bloom).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Zeroable, Pod)]
+pub struct BloomParams {
+    /// How bright (in its brightest channel) a color has to be to glow.
+    pub threshold: f32,
+    /// How strong the glow is added to the image.
+    pub intensity: f32,
+    /// How far the glow spreads, in pixels.
+    pub radius: f32,
+}
+
+impl Default for BloomParams {
+    fn default() -> Self {
+        BloomParams {
+            threshold: 0.7,
+            intensity: 1.0,
+            radius: 8.0,
+        }
+    }
+}
+
+/// The uniforms of [`PostEffect::crt`](struct.PostEffect.html#method.crt).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Zeroable, Pod)]
+pub struct CrtParams {
+    /// How much the screen bulges, 0 being flat.
+    pub curvature: f32,
+    /// How dark the scanlines are, from 0 to 1.
+    pub scanlines: f32,
+    /// How far the red and blue channels are shifted apart, in pixels.
+    pub aberration: f32,
+}
+
+impl Default for CrtParams {
+    fn default() -> Self {
+        CrtParams {
+            curvature: 0.1,
+            scanlines: 0.3,
+            aberration: 1.0,
+        }
+    }
+}
+
+/// The uniforms of [`PostEffect::vignette`](struct.PostEffect.html#method.vignette).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Zeroable, Pod)]
+pub struct VignetteParams {
+    /// Where the darkening is complete, relative to the distance from the center to the corners.
+    pub radius: f32,
+    /// How far inwards from `radius` the darkening starts.
+    pub softness: f32,
+    /// How dark it gets, from 0 to 1.
+    pub intensity: f32,
+}
+
+impl Default for VignetteParams {
+    fn default() -> Self {
+        VignetteParams {
+            radius: 1.0,
+            softness: 0.6,
+            intensity: 0.8,
+        }
+    }
+}
+
+/// The uniforms of [`PostEffect::color_grading`](struct.PostEffect.html#method.color_grading).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Zeroable, Pod)]
+pub struct ColorGradingParams {
+    /// Added to every channel.
+    pub brightness: f32,
+    /// Scales the distance of every channel from 0.5.
+    pub contrast: f32,
+    /// 0 is grayscale, 1 leaves the colors as they are.
+    pub saturation: f32,
+    /// Multiplied with the result.
+    pub tint: [f32; 3],
+}
+
+impl Default for ColorGradingParams {
+    fn default() -> Self {
+        ColorGradingParams {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            tint: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// A fullscreen fragment shader, drawn as one step of a [`PostEffect`](struct.PostEffect.html).
+///
+/// Its fragment shader is written in GLSL 100 and gets the following inputs, next to the
+/// uniforms it's created with:
+///
+/// ```glsl
+/// varying vec2 uv;
+///
+/// // the result of the previous pass, or the input of the effect for its first pass
+/// uniform sampler2D Texture;
+/// // the input of the effect
+/// uniform sampler2D Input;
+/// // the size of the target in pixels, appended to the uniforms internally
+/// uniform vec2 Resolution;
+/// ```
+#[derive(Debug)]
+pub struct PostPass {
+    pipeline: Pipeline,
+    uniforms: Vec<f32>,
+}
+
+impl PostPass {
+    /// Creates a pass from the source of a fragment shader, with `uniforms` describing the
+    /// uniforms in `values` (without `Resolution`, which always comes last).
+    pub fn new<U: Pod>(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        fragment_source: &str,
+        mut uniforms: Vec<UniformDesc>,
+        values: U,
+    ) -> GameResult<PostPass> {
+        uniforms.push(UniformDesc::new("Resolution", UniformType::Float2));
+        let meta = ShaderMeta {
+            images: vec!["Texture".to_string(), "Input".to_string()],
+            uniforms: UniformBlockLayout { uniforms },
+        };
+        let shader = miniquad::Shader::new(quad_ctx, VERTEX, fragment_source, meta)?;
+        let pipeline = Pipeline::with_params(
+            quad_ctx,
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("position", VertexFormat::Float2)],
+            shader,
+            PipelineParams::default(),
+        );
+        let mut pass = PostPass {
+            pipeline,
+            uniforms: Vec::new(),
+        };
+        pass.set_uniforms(values);
+        Ok(pass)
+    }
+
+    /// Sets the values of the uniforms, which have to match the layout the pass was created
+    /// with.
+    pub fn set_uniforms<U: Pod>(&mut self, values: U) {
+        let bytes = bytemuck::bytes_of(&values);
+        // uniforms are made of 4 byte floats and ints, stored as floats to keep them aligned
+        self.uniforms = bytes
+            .chunks_exact(4)
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+    }
+}
+
+/// An effect of a [`PostProcess`](struct.PostProcess.html), made of one or more passes drawn
+/// one after another.
+///
+/// Next to custom effects there are built-in blur, bloom, CRT, vignette and color grading effects.
+/// All passes of a built-in effect share the same uniforms, so they can be changed through
+/// [`set_uniforms`](#method.set_uniforms) with the params they were created with.
+#[derive(Debug)]
+pub struct PostEffect {
+    passes: Vec<PostPass>,
+    enabled: bool,
+}
+
+impl PostEffect {
+    /// Creates an effect drawing the given passes in order.
+    pub fn new(passes: Vec<PostPass>) -> Self {
+        PostEffect {
+            passes,
+            enabled: true,
+        }
+    }
+
+    /// Creates an effect of a single pass, see [`PostPass::new`](struct.PostPass.html#method.new).
+    pub fn from_fragment<U: Pod>(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        fragment_source: &str,
+        uniforms: Vec<UniformDesc>,
+        values: U,
+    ) -> GameResult<Self> {
+        let pass = PostPass::new(quad_ctx, fragment_source, uniforms, values)?;
+        Ok(PostEffect::new(vec![pass]))
+    }
+
+    /// A gaussian blur, drawn horizontally and then vertically.
+    pub fn blur(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        params: BlurParams,
+    ) -> GameResult<Self> {
+        let uniforms = || vec![UniformDesc::new("Radius", UniformType::Float1)];
+        let pass = |quad_ctx: &mut _, direction: &str| {
+            let source = format!(
+                "{}uniform float Radius;\n#define DIRECTION {}\n{}",
+                HEADER, direction, BLUR
+            );
+            PostPass::new(quad_ctx, &source, uniforms(), params)
+        };
+        Ok(PostEffect::new(vec![
+            pass(quad_ctx, "vec2(1.0, 0.0)")?,
+            pass(quad_ctx, "vec2(0.0, 1.0)")?,
+        ]))
+    }
+
+    /// Makes bright colors glow by blurring them and adding them on top.
+    ///
+    /// Works best on a canvas with the `CanvasFormat::Rgba16F` format, where colors can be
+    /// brighter than 1.
+    pub fn bloom(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        params: BloomParams,
+    ) -> GameResult<Self> {
+        let uniforms = || {
+            vec![
+                UniformDesc::new("Threshold", UniformType::Float1),
+                UniformDesc::new("Intensity", UniformType::Float1),
+                UniformDesc::new("Radius", UniformType::Float1),
+            ]
+        };
+        let blur = |direction: &str| {
+            format!(
+                "{}{}#define DIRECTION {}\n{}",
+                HEADER, BLOOM_UNIFORMS, direction, BLUR
+            )
+        };
+        let sources = [
+            format!("{}{}{}", HEADER, BLOOM_UNIFORMS, BLOOM_THRESHOLD),
+            blur("vec2(1.0, 0.0)"),
+            blur("vec2(0.0, 1.0)"),
+            format!("{}{}{}", HEADER, BLOOM_UNIFORMS, BLOOM_COMBINE),
+        ];
+        let passes = sources
+            .iter()
+            .map(|source| PostPass::new(quad_ctx, source, uniforms(), params))
+            .collect::<GameResult<_>>()?;
+        Ok(PostEffect::new(passes))
+    }
+
+    /// Makes the image look like it's shown on an old CRT screen, with a curved screen,
+    /// scanlines and color fringes.
+    pub fn crt(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        params: CrtParams,
+    ) -> GameResult<Self> {
+        let uniforms = vec![
+            UniformDesc::new("Curvature", UniformType::Float1),
+            UniformDesc::new("Scanlines", UniformType::Float1),
+            UniformDesc::new("Aberration", UniformType::Float1),
+        ];
+        let source = format!("{}{}", HEADER, CRT);
+        PostEffect::from_fragment(quad_ctx, &source, uniforms, params)
+    }
+
+    /// Darkens the image towards its corners.
+    pub fn vignette(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        params: VignetteParams,
+    ) -> GameResult<Self> {
+        let uniforms = vec![
+            UniformDesc::new("Radius", UniformType::Float1),
+            UniformDesc::new("Softness", UniformType::Float1),
+            UniformDesc::new("Intensity", UniformType::Float1),
+        ];
+        let source = format!("{}{}", HEADER, VIGNETTE);
+        PostEffect::from_fragment(quad_ctx, &source, uniforms, params)
+    }
+
+    /// Adjusts brightness, contrast and saturation and tints the image.
+    pub fn color_grading(
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        params: ColorGradingParams,
+    ) -> GameResult<Self> {
+        let uniforms = vec![
+            UniformDesc::new("Brightness", UniformType::Float1),
+            UniformDesc::new("Contrast", UniformType::Float1),
+            UniformDesc::new("Saturation", UniformType::Float1),
+            UniformDesc::new("Tint", UniformType::Float3),
+        ];
+        let source = format!("{}{}", HEADER, COLOR_GRADING);
+        PostEffect::from_fragment(quad_ctx, &source, uniforms, params)
+    }
+
+    /// Sets the uniforms of all passes.
+    pub fn set_uniforms<U: Pod>(&mut self, values: U) {
+        for pass in &mut self.passes {
+            pass.set_uniforms(values);
+        }
+    }
+
+    pub fn passes(&self) -> &[PostPass] {
+        &self.passes
+    }
+
+    pub fn passes_mut(&mut self) -> &mut [PostPass] {
+        &mut self.passes
+    }
+
+    /// Sets whether the effect is applied, skipping it if not.
+    ///
+    /// Default: `true`
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+/// Applies a chain of [`PostEffect`](struct.PostEffect.html)s to everything drawn between
+/// [`begin`](#method.begin) and [`end`](#method.end).
+///
+/// Drawing is redirected into a canvas the size of the window, which is then drawn through the
+/// passes of all enabled effects in order, bouncing between internal canvases, with the last
+/// pass drawing into the render target that was active before `begin`. The canvases have to be
+/// reallocated through [`resize_event`](#method.resize_event) whenever the window is resized.
+///
+/// ```rust, ignore
+/// let mut post = PostProcess::new(ctx, quad_ctx)?;
+/// post.effects_mut().push(PostEffect::bloom(quad_ctx, BloomParams::default())?);
+/// post.effects_mut().push(PostEffect::vignette(quad_ctx, VignetteParams::default())?);
+///
+/// // in `draw`
+/// post.begin(ctx);
+/// graphics::clear(ctx, quad_ctx, Color::BLACK);
+/// draw_scene(ctx, quad_ctx)?;
+/// post.end(ctx, quad_ctx);
+/// graphics::present(ctx, quad_ctx)?;
+///
+/// // in `resize_event`
+/// post.resize_event(ctx, quad_ctx, width, height)?;
+/// ```
+#[derive(Debug)]
+pub struct PostProcess {
+    settings: CanvasSettings,
+    scene: Canvas,
+    /// Three canvases are needed, as a pass may read both the input of its effect and the
+    /// output of the previous pass.
+    targets: [Canvas; 3],
+    effects: Vec<PostEffect>,
+    /// Draws the scene as it is if no effect is enabled.
+    copy: PostPass,
+    /// A unit quad, with the textures swapped out for every pass.
+    bindings: Bindings,
+    /// The render target that was active before `begin`.
+    previous_canvas: Option<Canvas>,
+}
+
+impl PostProcess {
+    /// Creates a post process without any effects, drawing into a canvas the size of the window.
+    pub fn new(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    ) -> GameResult<Self> {
+        PostProcess::with_settings(ctx, quad_ctx, CanvasSettings::default())
+    }
+
+    /// Creates a post process without any effects, drawing into a canvas the size of the window
+    /// with the given settings, e.g. to draw with multisampling or in HDR.
+    ///
+    /// The canvases in between passes share the format but aren't multisampled and have no
+    /// depth or stencil buffer.
+    pub fn with_settings(
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        settings: CanvasSettings,
+    ) -> GameResult<Self> {
+        let (width, height) = graphics::drawable_size(quad_ctx);
+        let (scene, targets) = create_canvases(ctx, quad_ctx, settings, width, height)?;
+        let copy = PostPass::new(quad_ctx, &format!("{}{}", HEADER, COPY), Vec::new(), ())?;
+
+        let vertices: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let bindings = Bindings {
+            vertex_buffers: vec![Buffer::immutable(
+                quad_ctx,
+                BufferType::VertexBuffer,
+                &vertices,
+            )],
+            index_buffer: Buffer::immutable(quad_ctx, BufferType::IndexBuffer, &indices),
+            images: vec![scene.image().texture; 2],
+        };
+
+        Ok(PostProcess {
+            settings,
+            scene,
+            targets,
+            effects: Vec::new(),
+            copy,
+            bindings,
+            previous_canvas: None,
+        })
+    }
+
+    /// The effects, applied in order.
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    /// The effects, applied in order, to add, remove or reorder them.
+    pub fn effects_mut(&mut self) -> &mut Vec<PostEffect> {
+        &mut self.effects
+    }
+
+    /// The canvas drawn into between [`begin`](#method.begin) and [`end`](#method.end).
+    pub fn canvas(&self) -> &Canvas {
+        &self.scene
+    }
+
+    /// Reallocates the canvases for the new size of the window. Call this from
+    /// [`EventHandler::resize_event`](../event/trait.EventHandler.html#method.resize_event).
+    pub fn resize_event(
+        &mut self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        width: f32,
+        height: f32,
+    ) -> GameResult {
+        // draw anything still waiting for the old canvases before they're dropped
+        graphics::flush(ctx, quad_ctx);
+        let (scene, targets) = create_canvases(ctx, quad_ctx, self.settings, width, height)?;
+        self.scene = scene;
+        self.targets = targets;
+        Ok(())
+    }
+
+    /// Redirects all drawing into the canvas of the post process, until [`end`](#method.end).
+    pub fn begin(&mut self, ctx: &mut Context) {
+        self.previous_canvas = ctx.gfx_context.canvas.take();
+        graphics::set_canvas(ctx, Some(&self.scene));
+    }
+
+    /// Draws what was drawn since [`begin`](#method.begin) through all enabled effects into the
+    /// render target that was active before, which becomes active again.
+    ///
+    /// The scissor rectangle and the stencil test don't apply to the passes.
+    pub fn end(&mut self, ctx: &mut Context, quad_ctx: &mut miniquad::graphics::GraphicsContext) {
+        self.scene.resolve(ctx, quad_ctx);
+        let previous_canvas = self.previous_canvas.take();
+        graphics::set_canvas(ctx, previous_canvas.as_ref());
+
+        let (target_pass, target_size) = match &previous_canvas {
+            Some(canvas) => {
+                canvas.mark_drawn();
+                let size = (f32::from(canvas.width()), f32::from(canvas.height()));
+                (Some(canvas.offscreen_pass), size)
+            }
+            None => (graphics::screen_pass(), graphics::drawable_size(quad_ctx)),
+        };
+
+        let mut remaining: usize = self
+            .effects
+            .iter()
+            .filter(|effect| effect.enabled)
+            .map(|effect| effect.passes.len())
+            .sum();
+        if remaining == 0 {
+            let texture = self.scene.image().texture;
+            draw_pass(
+                quad_ctx,
+                &mut self.bindings,
+                &self.copy,
+                [texture, texture],
+                target_pass,
+                target_size,
+            );
+            return;
+        }
+
+        // `None` standing for the scene, otherwise the index into `targets`
+        let mut input: Option<usize> = None;
+        let mut texture: Option<usize> = None;
+        for effect in self.effects.iter().filter(|effect| effect.enabled) {
+            for pass in &effect.passes {
+                remaining -= 1;
+                let textures = [texture, input].map(|canvas| match canvas {
+                    Some(index) => self.targets[index].image().texture,
+                    None => self.scene.image().texture,
+                });
+                if remaining == 0 {
+                    draw_pass(
+                        quad_ctx,
+                        &mut self.bindings,
+                        pass,
+                        textures,
+                        target_pass,
+                        target_size,
+                    );
+                    break;
+                }
+
+                let output = (0..self.targets.len())
+                    .find(|&index| Some(index) != texture && Some(index) != input)
+                    .expect("there's always a free canvas");
+                let canvas = &self.targets[output];
+                let size = (f32::from(canvas.width()), f32::from(canvas.height()));
+                draw_pass(
+                    quad_ctx,
+                    &mut self.bindings,
+                    pass,
+                    textures,
+                    Some(canvas.offscreen_pass),
+                    size,
+                );
+                texture = Some(output);
+            }
+            input = texture;
+        }
+    }
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        // the textures belong to the canvases
+        graphics::add_dropped_bindings(self.bindings.clone(), false);
+    }
+}
+
+/// Creates the canvas to draw the scene into and the ones in between passes.
+fn create_canvases(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    settings: CanvasSettings,
+    width: f32,
+    height: f32,
+) -> GameResult<(Canvas, [Canvas; 3])> {
+    let (width, height) = (width as u16, height as u16);
+    let scene = Canvas::with_settings(ctx, quad_ctx, width, height, settings)?;
+    let settings = CanvasSettings::default().format(settings.format);
+    let mut target = || Canvas::with_settings(ctx, quad_ctx, width, height, settings);
+    Ok((scene, [target()?, target()?, target()?]))
+}
+
+/// Draws a fullscreen pass reading `Texture` and `Input` from the given textures.
+fn draw_pass(
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    bindings: &mut Bindings,
+    pass: &PostPass,
+    [texture, input]: [Texture; 2],
+    target: Option<RenderPass>,
+    (width, height): (f32, f32),
+) {
+    bindings.images[0] = texture;
+    bindings.images[1] = input;
+
+    let mut uniforms = pass.uniforms.clone();
+    uniforms.extend_from_slice(&[width, height]);
+
+    quad_ctx.begin_pass(target, PassAction::Nothing);
+    quad_ctx.apply_pipeline(&pass.pipeline);
+    quad_ctx.apply_bindings(bindings);
+    quad_ctx.apply_uniforms_from_bytes(
+        uniforms.as_ptr() as *const u8,
+        uniforms.len() * std::mem::size_of::<f32>(),
+    );
+    quad_ctx.draw(0, 6, 1);
+    quad_ctx.end_render_pass();
+}
@@ -171,24 +171,32 @@ impl Shader {
     ) -> GameResult<ShaderId> {
         let vertex_source = {
             let mut buf = Vec::new();
-            let mut reader = ctx.filesystem.open(vertex_path)?;
+            let mut reader = ctx.filesystem.open(vertex_path.as_ref())?;
             let _ = reader.read_to_end(&mut buf)?;
             buf
         };
         let pixel_source = {
             let mut buf = Vec::new();
-            let mut reader = ctx.filesystem.open(pixel_path)?;
+            let mut reader = ctx.filesystem.open(pixel_path.as_ref())?;
             let _ = reader.read_to_end(&mut buf)?;
             buf
         };
-        Self::from_u8(
+        let id = Self::from_u8(
             ctx,
             quad_ctx,
             &vertex_source,
             &pixel_source,
+            shader_meta.clone(),
+            blend_mode,
+        )?;
+        crate::graphics::hot_reload::watch_shader(
+            ctx,
+            &[vertex_path, pixel_path],
+            id,
             shader_meta,
             blend_mode,
-        )
+        );
+        Ok(id)
     }
 
     /// Create a new `Shader` directly from GLSL source code, given as byte slices.
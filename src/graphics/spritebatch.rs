@@ -1,8 +1,8 @@
 use crate::{
     error::GameResult,
     graphics::{
-        self, apply_uniforms, transform_rect, BlendMode, DrawParam, FilterMode, InstanceAttributes,
-        Rect,
+        self, apply_uniforms, transform_rect, BlendMode, ClipState, DrawParam, FilterMode,
+        InstanceAttributes, Rect,
     },
     Context,
 };
@@ -110,6 +110,8 @@ impl graphics::Drawable for SpriteBatch {
         quad_ctx: &mut miniquad::graphics::GraphicsContext,
         param: DrawParam,
     ) -> GameResult {
+        crate::graphics::flush(ctx, quad_ctx);
+
         {
             let mut image = self.image.borrow_mut();
             let mut gpu_sprites = self.gpu_sprites.borrow_mut();
@@ -158,9 +160,11 @@ impl graphics::Drawable for SpriteBatch {
             quad_ctx.begin_pass(pass, PassAction::Nothing);
             quad_ctx.apply_bindings(&image.bindings);
         }
+        let clip = ClipState::current(ctx, quad_ctx);
         let shader_id = *ctx.gfx_context.current_shader.borrow();
         let current_shader = &mut ctx.gfx_context.shaders[shader_id];
         quad_ctx.apply_pipeline(&current_shader.pipeline);
+        clip.apply(quad_ctx);
 
         apply_uniforms(
             ctx,
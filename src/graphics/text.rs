@@ -1,6 +1,7 @@
+use glyph_brush::ab_glyph::{Font as _, FontArc, ScaleFont};
 use glyph_brush::GlyphPositioner;
-use glyph_brush::{self, FontId, Layout, Section, Text as GbText};
-pub use glyph_brush::{ab_glyph::PxScale, GlyphBrush, HorizontalAlign as Align};
+use glyph_brush::{self, FontId, GlyphCruncher, Section, Text as GbText};
+pub use glyph_brush::{ab_glyph::PxScale, GlyphBrush, HorizontalAlign as Align, VerticalAlign};
 use std::borrow::Cow;
 use std::cell::RefCell;
 use std::convert::TryFrom;
@@ -10,14 +11,28 @@ use std::path;
 use std::rc::Rc;
 
 use super::*;
+use crate::GameError;
 
-/// A handle referring to a loaded Truetype font.
+mod bitmap;
+mod glyph_cache;
+mod layout;
+mod markup;
+mod sdf;
+
+pub(crate) use self::bitmap::{empty_font, grid_glyphs, parse_bmfont, BitmapFont};
+pub(crate) use self::glyph_cache::GlyphCacheTracker;
+pub use self::glyph_cache::{GlyphCacheEviction, GlyphCacheSettings, GlyphCacheStats};
+pub use self::layout::{TextDirection, TextLayout};
+pub use self::markup::MarkupFonts;
+pub(crate) use self::sdf::SdfFont;
+pub use self::sdf::{Glow, Outline, SdfSettings, Shadow};
+
+/// A handle referring to a loaded Truetype or bitmap font.
 ///
 /// This is just an integer referring to a loaded font stored in the
-/// `Context`, so is cheap to copy.  Note that fonts are cached and
-/// currently never *removed* from the cache, since that would
-/// invalidate the whole cache and require re-loading all the other
-/// fonts.  So, you do not want to load a font more than once.
+/// `Context`, so is cheap to copy.  Fonts stay loaded until they're
+/// [`unload`](#method.unload)ed, so you do not want to load a font
+/// more than once.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Font {
     font_id: FontId,
@@ -55,6 +70,14 @@ pub struct TextFragment {
     pub font: Option<Font>,
     /// Fragment's scale, defaults to text's scale.
     pub scale: Option<PxScale>,
+    /// Whether the fragment is underlined. Decorations are only drawn when drawing the `Text`
+    /// itself, not through [`queue_text()`](fn.queue_text.html).
+    pub underline: bool,
+    /// Whether the fragment is struck through, see `underline`.
+    pub strikethrough: bool,
+    /// The name of the image this fragment is a placeholder for, see
+    /// [`TextFragment::inline_image()`](struct.TextFragment.html#method.inline_image).
+    pub image: Option<String>,
 }
 
 impl Default for TextFragment {
@@ -64,6 +87,9 @@ impl Default for TextFragment {
             color: None,
             font: None,
             scale: None,
+            underline: false,
+            strikethrough: false,
+            image: None,
         }
     }
 }
@@ -91,6 +117,30 @@ impl TextFragment {
         self.scale = Some(scale.into());
         self
     }
+
+    /// Set whether the fragment is underlined.
+    pub fn underline(mut self, underline: bool) -> TextFragment {
+        self.underline = underline;
+        self
+    }
+
+    /// Set whether the fragment is struck through.
+    pub fn strikethrough(mut self, strikethrough: bool) -> TextFragment {
+        self.strikethrough = strikethrough;
+        self
+    }
+
+    /// Creates a placeholder for an image inside of the text, a blank square as high as the
+    /// fragment's scale. Nothing is drawn for it; draw the image yourself at the position
+    /// reported by [`Text::inline_images()`](struct.Text.html#method.inline_images).
+    pub fn inline_image<S: Into<String>>(name: S) -> TextFragment {
+        TextFragment {
+            // the space is widened into a square when laying out the text
+            text: " ".to_string(),
+            image: Some(name.into()),
+            ..Default::default()
+        }
+    }
 }
 
 impl<'a> From<&'a str> for TextFragment {
@@ -137,6 +187,20 @@ struct CachedMetrics {
     width: Option<f32>,
     height: Option<f32>,
     glyph_positions: Vec<mint::Point2<f32>>,
+    /// The underlines and strikethroughs as the index of their fragment and their rectangle.
+    decorations: Option<Vec<(usize, Rect)>>,
+}
+
+/// The placeholder of an image inside of a [`Text`](struct.Text.html), see
+/// [`TextFragment::inline_image()`](struct.TextFragment.html#method.inline_image).
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    /// The name the placeholder was created with.
+    pub name: String,
+    /// The index of the placeholder in [`Text::glyph_positions()`](struct.Text.html#method.glyph_positions).
+    pub glyph: usize,
+    /// The space reserved for the image, relative to the text's origin.
+    pub rect: Rect,
 }
 
 /// Drawable text object.  Essentially a list of [`TextFragment`](struct.TextFragment.html)'s
@@ -151,9 +215,10 @@ pub struct Text {
     blend_mode: Option<BlendMode>,
     filter_mode: FilterMode,
     bounds: Point2,
-    layout: Layout<glyph_brush::BuiltInLineBreaker>,
+    layout: TextLayout,
     font_id: FontId,
     font_scale: PxScale,
+    sdf_style: sdf::SdfStyle,
     cached_metrics: RefCell<CachedMetrics>,
 }
 
@@ -164,9 +229,10 @@ impl Default for Text {
             blend_mode: None,
             filter_mode: FilterMode::Linear,
             bounds: Point2::new(f32::INFINITY, f32::INFINITY),
-            layout: Layout::default(),
+            layout: TextLayout::default(),
             font_id: FontId::default(),
             font_scale: PxScale::from(Font::DEFAULT_FONT_SCALE),
+            sdf_style: sdf::SdfStyle::default(),
             cached_metrics: RefCell::new(CachedMetrics::default()),
         }
     }
@@ -190,6 +256,43 @@ impl Text {
         text
     }
 
+    /// Creates a `Text` from markup, splitting it into fragments at its tags:
+    ///
+    /// - `[color=#f00]...[/color]` sets the color, as `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`
+    ///   or the name of one of the constants of `Color`, like `red`.
+    /// - `[size=24]...[/size]` sets the scale.
+    /// - `[b]...[/b]` and `[i]...[/i]` switch to a bold or italic font,
+    ///   `[font=name]...[/font]` to a named one; see
+    ///   [`from_markup_with_fonts()`](#method.from_markup_with_fonts).
+    /// - `[u]...[/u]` underlines, `[s]...[/s]` strikes through.
+    /// - `[img=name]` reserves space for an image, see
+    ///   [`TextFragment::inline_image()`](struct.TextFragment.html#method.inline_image).
+    /// - `[[` is a literal `[`.
+    ///
+    /// Tags nest and have to be closed in order. Unknown tags, bad values and unbalanced tags
+    /// return an error.
+    ///
+    /// ```rust
+    /// # use good_web_game::graphics::Text;
+    /// # fn main() {
+    /// let text = Text::from_markup("Deal [color=#f00]12[/color] [b]fire[/b] damage").unwrap();
+    /// assert_eq!(text.contents(), "Deal 12 fire damage");
+    /// # }
+    /// ```
+    pub fn from_markup(markup: &str) -> GameResult<Text> {
+        Text::from_markup_with_fonts(markup, &MarkupFonts::default())
+    }
+
+    /// Like [`from_markup()`](#method.from_markup), with the fonts used for `[b]`, `[i]` and
+    /// `[font=name]`.
+    pub fn from_markup_with_fonts(markup: &str, fonts: &MarkupFonts) -> GameResult<Text> {
+        let mut text = Text::default();
+        for fragment in markup::parse(markup, fonts)? {
+            let _ = text.add(fragment);
+        }
+        Ok(text)
+    }
+
     /// Appends a `TextFragment` to the `Text`.
     pub fn add<F>(&mut self, fragment: F) -> &mut Text
     where
@@ -219,16 +322,63 @@ impl Text {
         P: Into<mint::Point2<f32>>,
     {
         self.bounds = Point2::from(bounds.into());
-        if self.bounds.x == f32::INFINITY {
-            // Layouts don't make any sense if we don't wrap text at all.
-            self.layout = Layout::default();
-        } else {
-            self.layout = self.layout.h_align(alignment);
-        }
+        self.layout = self.layout.h_align(alignment);
+        self.invalidate_cached_metrics();
+        self
+    }
+
+    /// Specifies how the text is aligned vertically within the height of the bounds, which
+    /// does nothing if it's infinite.
+    pub fn set_vertical_align(&mut self, alignment: VerticalAlign) -> &mut Text {
+        self.layout = self.layout.v_align(alignment);
+        self.invalidate_cached_metrics();
+        self
+    }
+
+    /// Specifies the distance between lines as a multiple of the line height of their fonts.
+    pub fn set_line_spacing(&mut self, line_spacing: f32) -> &mut Text {
+        self.layout = self.layout.line_spacing(line_spacing);
+        self.invalidate_cached_metrics();
+        self
+    }
+
+    /// Specifies the space added after every glyph, in pixels.
+    pub fn set_letter_spacing(&mut self, letter_spacing: f32) -> &mut Text {
+        self.layout = self.layout.letter_spacing(letter_spacing);
         self.invalidate_cached_metrics();
         self
     }
 
+    /// Specifies how many lines are shown at most, the last one ending in an ellipsis if the
+    /// text is cut off. `Some(0)` shows no text at all.
+    pub fn set_max_lines(&mut self, max_lines: Option<usize>) -> &mut Text {
+        self.layout = self.layout.max_lines(max_lines);
+        self.invalidate_cached_metrics();
+        self
+    }
+
+    /// Specifies the direction the text is written in, which decides the order of runs of
+    /// left-to-right and right-to-left text within a line.
+    pub fn set_direction(&mut self, direction: TextDirection) -> &mut Text {
+        self.layout = self.layout.direction(direction);
+        self.invalidate_cached_metrics();
+        self
+    }
+
+    /// Returns the layout the text is laid out with, e.g. to lay out other sections the same
+    /// way through [`queue_text_raw()`](fn.queue_text_raw.html).
+    pub fn layout(&self) -> TextLayout {
+        // alignments don't make any sense without bounds to align within
+        let mut layout = self.layout;
+        if self.bounds.x == f32::INFINITY {
+            layout = layout.h_align(Align::Left);
+        }
+        if self.bounds.y == f32::INFINITY {
+            layout = layout.v_align(VerticalAlign::Top);
+        }
+        layout
+    }
+
     /// Specifies text's font and font scale; used for fragments that don't have their own.
     pub fn set_font(&mut self, font: Font, font_scale: PxScale) -> &mut Text {
         self.font_id = font.font_id;
@@ -237,8 +387,44 @@ impl Text {
         self
     }
 
+    /// Sets the outline drawn around the glyphs of SDF fonts (see
+    /// [`Font::new_sdf()`](struct.Font.html#method.new_sdf)); other fonts ignore it.
+    pub fn set_outline(&mut self, outline: Option<Outline>) -> &mut Text {
+        self.sdf_style.outline = outline;
+        self
+    }
+
+    pub fn outline(&self) -> Option<Outline> {
+        self.sdf_style.outline
+    }
+
+    /// Sets the shadow drawn behind the glyphs of SDF fonts; other fonts ignore it.
+    pub fn set_shadow(&mut self, shadow: Option<Shadow>) -> &mut Text {
+        self.sdf_style.shadow = shadow;
+        self
+    }
+
+    pub fn shadow(&self) -> Option<Shadow> {
+        self.sdf_style.shadow
+    }
+
+    /// Sets the glow drawn around the glyphs of SDF fonts; other fonts ignore it.
+    pub fn set_glow(&mut self, glow: Option<Glow>) -> &mut Text {
+        self.sdf_style.glow = glow;
+        self
+    }
+
+    pub fn glow(&self) -> Option<Glow> {
+        self.sdf_style.glow
+    }
+
     /// Converts `Text` to a type `glyph_brush` can understand and queue.
-    fn generate_varied_section(&self, relative_dest: Point2, color: Option<Color>) -> Section {
+    fn generate_varied_section(
+        &self,
+        relative_dest: Point2,
+        color: Option<Color>,
+        fonts: &[FontArc],
+    ) -> Section {
         let sections: Vec<GbText> = self
             .fragments
             .iter()
@@ -248,7 +434,15 @@ impl Text {
                     .font
                     .map(|font| font.font_id)
                     .unwrap_or(self.font_id);
-                let scale = fragment.scale.unwrap_or(self.font_scale);
+                let mut scale = fragment.scale.unwrap_or(self.font_scale);
+                if fragment.image.is_some() {
+                    // widen the space of image placeholders into a square
+                    let font = &fonts[font_id.0];
+                    let advance = font.as_scaled(scale).h_advance(font.glyph_id(' '));
+                    if advance > 0.0 {
+                        scale.x *= scale.y / advance;
+                    }
+                }
                 GbText::default()
                     .with_text(&fragment.text)
                     .with_font_id(font_id)
@@ -257,30 +451,23 @@ impl Text {
             })
             .collect();
 
-        let relative_dest_x = {
-            // This positions text within bounds with relative_dest being to the left, always.
-            let mut dest_x = relative_dest.x;
-            if self.bounds.x != f32::INFINITY {
-                use glyph_brush::Layout::Wrap;
-                match self.layout {
-                    Wrap {
-                        h_align: Align::Center,
-                        ..
-                    } => dest_x += self.bounds.x * 0.5,
-                    Wrap {
-                        h_align: Align::Right,
-                        ..
-                    } => dest_x += self.bounds.x,
-                    _ => (),
-                }
-            }
-            dest_x
+        // This positions text within bounds with relative_dest being to the top left, always.
+        let layout = self.layout();
+        let relative_dest_x = match layout.h_align {
+            Align::Left => relative_dest.x,
+            Align::Center => relative_dest.x + self.bounds.x * 0.5,
+            Align::Right => relative_dest.x + self.bounds.x,
+        };
+        let relative_dest_y = match layout.v_align {
+            VerticalAlign::Top => relative_dest.y,
+            VerticalAlign::Center => relative_dest.y + self.bounds.y * 0.5,
+            VerticalAlign::Bottom => relative_dest.y + self.bounds.y,
         };
-        let relative_dest = (relative_dest_x, relative_dest.y);
         Section {
-            screen_position: relative_dest,
+            screen_position: (relative_dest_x, relative_dest_y),
             bounds: (self.bounds.x, self.bounds.y),
-            layout: self.layout,
+            // unused, the text is laid out by `self.layout()`
+            layout: glyph_brush::Layout::default(),
             text: sections,
         }
     }
@@ -326,9 +513,9 @@ impl Text {
             }
         }
         let glyph_positions: Vec<mint::Point2<f32>> = {
-            let varied_section = self.generate_varied_section(Point2::new(0.0, 0.0), None);
-            use glyph_brush::GlyphCruncher;
-            gb.glyphs(varied_section)
+            let varied_section =
+                self.generate_varied_section(Point2::new(0.0, 0.0), None, gb.fonts());
+            gb.glyphs_custom_layout(varied_section, &self.layout())
                 .map(|glyph| glyph.glyph.position)
                 .map(|pos| mint::Point2 { x: pos.x, y: pos.y })
                 .collect()
@@ -350,6 +537,183 @@ impl Text {
         self.calculate_glyph_positions(&mut context.gfx_context.glyph_brush.borrow_mut())
     }
 
+    /// Returns the placeholders of the inline images of the text, in the order of the fragments.
+    pub fn inline_images(&self, context: &Context) -> Vec<InlineImage> {
+        let mut gb = context.gfx_context.glyph_brush.borrow_mut();
+        let positions = self.calculate_glyph_positions(&mut gb);
+        let fonts = gb.fonts();
+
+        let mut images = Vec::new();
+        // `glyph_brush` lays out a glyph for every character but control characters
+        let mut glyph = 0;
+        for fragment in &self.fragments {
+            if let (Some(name), Some(position)) = (&fragment.image, positions.get(glyph)) {
+                let font_id = fragment.font.map_or(self.font_id, |font| font.font_id);
+                let scale = fragment.scale.unwrap_or(self.font_scale);
+                let ascent = fonts[font_id.0].as_scaled(scale).ascent();
+                images.push(InlineImage {
+                    name: name.clone(),
+                    glyph,
+                    rect: Rect::new(position.x, position.y - ascent, scale.y, scale.y),
+                });
+            }
+            glyph += fragment.text.chars().filter(|c| !c.is_control()).count();
+        }
+        images
+    }
+
+    /// Calculates, caches, and returns the underlines and strikethroughs, merged into one
+    /// rectangle per fragment and line.
+    fn calculate_decorations(&self, gb: &mut GlyphBrush<DrawParam>) -> Vec<(usize, Rect)> {
+        if let Ok(metrics) = self.cached_metrics.try_borrow() {
+            if let Some(decorations) = &metrics.decorations {
+                return decorations.clone();
+            }
+        }
+
+        let varied_section = self.generate_varied_section(Point2::new(0.0, 0.0), None, gb.fonts());
+        let glyphs: Vec<_> = gb
+            .glyphs_custom_layout(varied_section, &self.layout())
+            .cloned()
+            .collect();
+        let fonts = gb.fonts();
+
+        // the runs of glyphs as (fragment, first glyph, left, right)
+        let mut runs: Vec<(usize, &glyph_brush::SectionGlyph, f32, f32)> = Vec::new();
+        for glyph in &glyphs {
+            let fragment = &self.fragments[glyph.section_index];
+            if !fragment.underline && !fragment.strikethrough {
+                continue;
+            }
+            let font = fonts[glyph.font_id.0].as_scaled(glyph.glyph.scale);
+            let left = glyph.glyph.position.x;
+            let right = left + font.h_advance(glyph.glyph.id);
+            match runs.last_mut() {
+                Some((index, first, _, run_right))
+                    if *index == glyph.section_index
+                        && first.glyph.position.y == glyph.glyph.position.y =>
+                {
+                    *run_right = right
+                }
+                _ => runs.push((glyph.section_index, glyph, left, right)),
+            }
+        }
+
+        let mut decorations = Vec::new();
+        for (index, first, left, right) in runs {
+            let fragment = &self.fragments[index];
+            let font = fonts[first.font_id.0].as_scaled(first.glyph.scale);
+            let baseline = first.glyph.position.y;
+            let thickness = (first.glyph.scale.y / 14.0).max(1.0);
+            if fragment.underline {
+                let y = baseline - font.descent() * 0.4 - thickness / 2.0;
+                decorations.push((index, Rect::new(left, y, right - left, thickness)));
+            }
+            if fragment.strikethrough {
+                let y = baseline - font.ascent() * 0.3 - thickness / 2.0;
+                decorations.push((index, Rect::new(left, y, right - left, thickness)));
+            }
+        }
+
+        if let Ok(mut metrics) = self.cached_metrics.try_borrow_mut() {
+            metrics.decorations = Some(decorations.clone());
+        }
+        decorations
+    }
+
+    /// Draws the underlines and strikethroughs on top of the glyphs.
+    fn draw_decorations(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        param: DrawParam,
+    ) {
+        let decorations = self.calculate_decorations(&mut ctx.gfx_context.glyph_brush.borrow_mut());
+        let transform = cgmath::Matrix4::from(param.trans.to_bare_matrix());
+        let white_texture = ctx.gfx_context.white_texture;
+        for (index, rect) in decorations {
+            let color = self.fragments[index].color.unwrap_or(param.color);
+            let rect_transform = transform
+                * cgmath::Matrix4::from_translation(cgmath::Vector3::new(rect.x, rect.y, 0.0))
+                * cgmath::Matrix4::from_nonuniform_scale(rect.w, rect.h, 1.0);
+            let instance =
+                InstanceAttributes::from(&DrawParam::new().transform(rect_transform).color(color));
+            batch::add(
+                ctx,
+                quad_ctx,
+                white_texture,
+                FilterMode::Nearest,
+                self.blend_mode,
+                instance,
+            );
+        }
+    }
+
+    /// Whether any of the fonts of the text isn't drawn from the glyph cache.
+    fn uses_custom_fonts(&self, ctx: &Context) -> bool {
+        let gfx = &ctx.gfx_context;
+        let is_custom = |font_id: &FontId| {
+            gfx.sdf_fonts.contains_key(font_id) || gfx.bitmap_fonts.contains_key(font_id)
+        };
+        (!gfx.sdf_fonts.is_empty() || !gfx.bitmap_fonts.is_empty())
+            && (is_custom(&self.font_id)
+                || self
+                    .fragments
+                    .iter()
+                    .any(|fragment| fragment.font.is_some_and(|font| is_custom(&font.font_id))))
+    }
+
+    /// Draws the glyphs of SDF fonts through their atlases, the glyphs of bitmap fonts from
+    /// their pages and the rest as usual.
+    fn draw_with_custom_fonts(
+        &self,
+        ctx: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        param: DrawParam,
+    ) -> GameResult {
+        let (sdf_glyphs, bitmap_glyphs) = {
+            let gfx = &ctx.gfx_context;
+            let mut gb = gfx.glyph_brush.borrow_mut();
+            let varied_section =
+                self.generate_varied_section(Point2::new(0.0, 0.0), Some(param.color), gb.fonts());
+            let bounds = self
+                .layout()
+                .bounds_rect(&glyph_brush::SectionGeometry::from(&varied_section));
+            let extra: Vec<glyph_brush::Extra> =
+                varied_section.text.iter().map(|text| text.extra).collect();
+            let mut sdf_glyphs = Vec::new();
+            let mut bitmap_glyphs = Vec::new();
+            let mut glyphs = Vec::new();
+            for glyph in gb
+                .glyphs_custom_layout(&varied_section, &self.layout())
+                .cloned()
+            {
+                let color = Color::from(extra[glyph.section_index].color);
+                if gfx.sdf_fonts.contains_key(&glyph.font_id) {
+                    sdf_glyphs.push((glyph, color));
+                } else if gfx.bitmap_fonts.contains_key(&glyph.font_id) {
+                    bitmap_glyphs.push((glyph, color));
+                } else {
+                    glyphs.push(glyph);
+                }
+            }
+            gb.queue_pre_positioned(glyphs, extra, bounds);
+            (sdf_glyphs, bitmap_glyphs)
+        };
+        draw_queued_text(ctx, quad_ctx, param, self.blend_mode, self.filter_mode)?;
+        if !sdf_glyphs.is_empty() {
+            sdf::draw_glyphs(
+                ctx,
+                quad_ctx,
+                &sdf_glyphs,
+                self.sdf_style,
+                self.blend_mode,
+                param,
+            )?;
+        }
+        bitmap::draw_glyphs(ctx, quad_ctx, &bitmap_glyphs, self.blend_mode, param)
+    }
+
     /// Calculates, caches, and returns width and height of formatted and wrapped text.
     fn calculate_dimensions(&self, gb: &mut GlyphBrush<DrawParam>) -> Rect {
         if let Ok(metrics) = self.cached_metrics.try_borrow() {
@@ -365,9 +729,9 @@ impl Text {
         let mut max_width = 0.0;
         let mut max_height = 0.0;
         {
-            let varied_section = self.generate_varied_section(Point2::new(0.0, 0.0), None);
-            use glyph_brush::GlyphCruncher;
-            if let Some(bounds) = gb.glyph_bounds(varied_section) {
+            let varied_section =
+                self.generate_varied_section(Point2::new(0.0, 0.0), None, gb.fonts());
+            if let Some(bounds) = gb.glyph_bounds_custom_layout(varied_section, &self.layout()) {
                 max_width = bounds.width().ceil();
                 max_height = bounds.height().ceil();
             }
@@ -408,8 +772,20 @@ impl Drawable for Text {
         param: DrawParam,
     ) -> GameResult {
         // Converts fraction-of-bounding-box to screen coordinates, as required by `draw_queued()`.
-        queue_text(ctx, self, Point2::new(0.0, 0.0), Some(param.color));
-        draw_queued_text(ctx, quad_ctx, param, self.blend_mode, self.filter_mode)
+        if self.uses_custom_fonts(ctx) {
+            self.draw_with_custom_fonts(ctx, quad_ctx, param)?;
+        } else {
+            queue_text(ctx, self, Point2::new(0.0, 0.0), Some(param.color));
+            draw_queued_text(ctx, quad_ctx, param, self.blend_mode, self.filter_mode)?;
+        }
+        if self
+            .fragments
+            .iter()
+            .any(|fragment| fragment.underline || fragment.strikethrough)
+        {
+            self.draw_decorations(ctx, quad_ctx, param);
+        }
+        Ok(())
     }
 
     fn dimensions(&self, ctx: &mut Context) -> Option<Rect> {
@@ -439,7 +815,9 @@ impl Font {
         let mut buf = Vec::new();
         let _ = stream.read_to_end(&mut buf)?;
 
-        Font::new_glyph_font_bytes(context, &buf)
+        let font = Font::new_glyph_font_bytes(context, &buf)?;
+        crate::graphics::hot_reload::watch_font(context, path, font.font_id);
+        Ok(font)
     }
 
     /// Loads a new TrueType font from given bytes and into a `gfx::GlyphBrush` owned
@@ -447,12 +825,154 @@ impl Font {
     pub fn new_glyph_font_bytes(context: &mut Context, bytes: &[u8]) -> GameResult<Self> {
         // Take a Cow here to avoid this clone where unnecessary?
         // Nah, let's not complicate things more than necessary.
-        let font = glyph_brush::ab_glyph::FontArc::try_from_vec(bytes.to_vec()).unwrap();
+        let font = glyph_brush::ab_glyph::FontArc::try_from_vec(bytes.to_vec())
+            .map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
         let font_id = context.gfx_context.glyph_brush.borrow_mut().add_font(font);
 
         Ok(Font { font_id })
     }
 
+    /// Loads a TTF font from the given file that's drawn through a signed distance field, so
+    /// that it stays sharp at any scale, zoom or `DrawParam` scale without rasterizing it again
+    /// for every size. `Text`s using it can also have an outline, a shadow and a glow, see
+    /// [`Text::set_outline()`](struct.Text.html#method.set_outline).
+    ///
+    /// SDF fonts are laid out like any other font, but are only drawn through their atlas when
+    /// drawing a `Text` with [`graphics::draw()`](fn.draw.html); through
+    /// [`queue_text()`](fn.queue_text.html) they're rasterized like other fonts. Small text
+    /// looks a bit softer than with regular fonts.
+    pub fn new_sdf<P>(context: &mut Context, path: P, settings: SdfSettings) -> GameResult<Font>
+    where
+        P: AsRef<path::Path> + fmt::Debug,
+    {
+        use crate::filesystem;
+        let mut stream = filesystem::open(context, path.as_ref())?;
+        let mut buf = Vec::new();
+        let _ = stream.read_to_end(&mut buf)?;
+
+        Font::new_sdf_bytes(context, &buf, settings)
+    }
+
+    /// Loads a TrueType font drawn through a signed distance field from the given bytes, see
+    /// [`new_sdf()`](#method.new_sdf).
+    pub fn new_sdf_bytes(
+        context: &mut Context,
+        bytes: &[u8],
+        settings: SdfSettings,
+    ) -> GameResult<Self> {
+        let font = glyph_brush::ab_glyph::FontArc::try_from_vec(bytes.to_vec())
+            .map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+        let font_id = context
+            .gfx_context
+            .glyph_brush
+            .borrow_mut()
+            .add_font(font.clone());
+        let _ = context
+            .gfx_context
+            .sdf_fonts
+            .insert(font_id, SdfFont::new(font, settings));
+
+        Ok(Font { font_id })
+    }
+
+    /// Returns whether the font is drawn through a signed distance field.
+    pub fn is_sdf(&self, context: &Context) -> bool {
+        context.gfx_context.sdf_fonts.contains_key(&self.font_id)
+    }
+
+    /// Loads a bitmap font from an AngelCode BMFont `.fnt` file in the text format, along with
+    /// the page images it refers to, which are looked up next to it.
+    ///
+    /// Bitmap fonts are laid out like any other font and can be mixed with TrueType fonts in a
+    /// `Text`; a scale of the line height of the font draws it at its native size. They're drawn
+    /// with nearest filtering, and only when drawing a `Text` with
+    /// [`graphics::draw()`](fn.draw.html), not through [`queue_text()`](fn.queue_text.html).
+    pub fn from_bmfont<P>(
+        context: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        path: P,
+    ) -> GameResult<Font>
+    where
+        P: AsRef<path::Path> + fmt::Debug,
+    {
+        use crate::filesystem;
+        let mut stream = filesystem::open(context, path.as_ref())?;
+        let mut buf = String::new();
+        let _ = stream.read_to_string(&mut buf)?;
+        let (data, page_files) = parse_bmfont(&buf)?;
+
+        let directory = path
+            .as_ref()
+            .parent()
+            .unwrap_or_else(|| path::Path::new("/"));
+        let pages = page_files
+            .iter()
+            .map(|file| Image::new(context, quad_ctx, directory.join(file)))
+            .collect::<GameResult<Vec<_>>>()?;
+        Ok(Font::add_bitmap_font(context, data, pages))
+    }
+
+    /// Loads a bitmap font from an image with its glyphs in a grid of cells of
+    /// `glyph_width`x`glyph_height` pixels. The cells hold the characters of `chars`, from left
+    /// to right and top to bottom, and every glyph advances by the width of a cell.
+    ///
+    /// Fails if the image doesn't have a cell for every character. See
+    /// [`from_bmfont()`](#method.from_bmfont) for how bitmap fonts are drawn.
+    pub fn from_grid_image<P>(
+        context: &mut Context,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+        path: P,
+        glyph_width: u16,
+        glyph_height: u16,
+        chars: &str,
+    ) -> GameResult<Font>
+    where
+        P: AsRef<path::Path> + fmt::Debug,
+    {
+        let image = Image::new(context, quad_ctx, path)?;
+        let data = grid_glyphs(
+            (image.width(), image.height()),
+            (glyph_width, glyph_height),
+            chars,
+        )?;
+        Ok(Font::add_bitmap_font(context, data, vec![image]))
+    }
+
+    fn add_bitmap_font(context: &mut Context, data: bitmap::BitmapData, pages: Vec<Image>) -> Font {
+        let (metrics, font) = BitmapFont::new(data, pages);
+        let font_id = context
+            .gfx_context
+            .glyph_brush
+            .borrow_mut()
+            .add_font(metrics);
+        let _ = context.gfx_context.bitmap_fonts.insert(font_id, font);
+        Font { font_id }
+    }
+
+    /// Returns whether the font is drawn from images.
+    pub fn is_bitmap(&self, context: &Context) -> bool {
+        context.gfx_context.bitmap_fonts.contains_key(&self.font_id)
+    }
+
+    /// Unloads the font, freeing its data, its glyphs and, for SDF and bitmap fonts, its
+    /// textures. Glyphs of other fonts in the glyph cache are rasterized again as they're drawn.
+    ///
+    /// The font (and all its copies) mustn't be used anymore afterwards, text using it is drawn
+    /// without any glyphs. The default font can't be unloaded.
+    pub fn unload(self, context: &mut Context) -> GameResult {
+        if self == Font::default() {
+            return Err(GameError::RenderError(
+                "The default font can't be unloaded".to_string(),
+            ));
+        }
+        // font ids are indices, so the font is replaced rather than removed
+        replace_font(context, self.font_id, empty_font());
+        let _ = context.gfx_context.sdf_fonts.remove(&self.font_id);
+        let _ = context.gfx_context.bitmap_fonts.remove(&self.font_id);
+        crate::graphics::hot_reload::unwatch_font(context, self.font_id);
+        Ok(())
+    }
+
     /// Returns the baked-in bytes of default font (currently `LiberationSans-Regular.ttf`).
     pub(crate) fn default_font_bytes() -> &'static [u8] {
         include_bytes!(concat!(
@@ -475,6 +995,113 @@ pub fn font_cache(context: &Context) -> FontCache {
     }
 }
 
+/// Returns how full the glyph cache is.
+pub fn glyph_cache_stats(context: &Context) -> GlyphCacheStats {
+    let size = context
+        .gfx_context
+        .glyph_brush
+        .borrow()
+        .texture_dimensions();
+    context.gfx_context.glyph_cache_tracker.stats(size)
+}
+
+/// Returns the size and eviction policy of the glyph cache.
+pub fn glyph_cache_settings(context: &Context) -> GlyphCacheSettings {
+    context.gfx_context.glyph_cache_settings
+}
+
+/// Sets the size and eviction policy of the glyph cache, the texture the glyphs of TrueType
+/// fonts are rasterized into. This clears the cache, shrinking its texture to the new initial
+/// size.
+///
+/// Fails, keeping the previous settings, if the initial size is empty or larger than the
+/// maximum size, or if the maximum size exceeds 65535x65535.
+pub fn set_glyph_cache_settings(
+    context: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    settings: GlyphCacheSettings,
+) -> GameResult {
+    let (width, height) = settings.initial_size;
+    let (max_width, max_height) = settings.max_size;
+    let limit = u32::from(u16::MAX);
+    if width == 0
+        || height == 0
+        || width > max_width
+        || height > max_height
+        || max_width > limit
+        || max_height > limit
+    {
+        return Err(GameError::RenderError(format!(
+            "Invalid glyph cache size {}x{}, with a maximum of {}x{}",
+            width, height, max_width, max_height
+        )));
+    }
+    resize_glyph_cache(context, quad_ctx, settings.initial_size)?;
+    context.gfx_context.glyph_cache_settings = settings;
+    Ok(())
+}
+
+/// Replaces the font with the given id in the glyph brush. Rebuilding the brush drops all glyphs
+/// cached for any font, so the glyph cache is tracked from scratch as well.
+pub(crate) fn replace_font(context: &mut Context, font_id: FontId, font: FontArc) {
+    let mut glyph_brush = context.gfx_context.glyph_brush.borrow_mut();
+    let builder = glyph_brush.to_builder().replace_fonts(|mut fonts| {
+        fonts[font_id.0] = font;
+        fonts
+    });
+    *glyph_brush = builder.build();
+    context.gfx_context.glyph_cache_tracker.clear();
+}
+
+/// Replaces the glyph cache texture with an empty one of the given size.
+pub(crate) fn resize_glyph_cache(
+    context: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    (width, height): (u32, u32),
+) -> GameResult {
+    let to_u16 = |size: u32| {
+        u16::try_from(size)
+            .map_err(|_| GameError::RenderError(format!("Glyph cache size {} is too large", size)))
+    };
+    let data = vec![255; 4 * width as usize * height as usize];
+    let new_glyph_cache =
+        Image::from_rgba8(context, quad_ctx, to_u16(width)?, to_u16(height)?, &data)?;
+    context.gfx_context.glyph_cache = new_glyph_cache.clone();
+    let _ = context
+        .gfx_context
+        .glyph_state
+        .borrow_mut()
+        .set_image(new_glyph_cache);
+    context
+        .gfx_context
+        .glyph_brush
+        .borrow_mut()
+        .resize_texture(width, height);
+    context.gfx_context.glyph_cache_tracker.clear();
+    Ok(())
+}
+
+/// Doubles the size of the glyph cache texture, up to its maximum size. Returns `false` if
+/// it's already as large as it can get.
+fn grow_glyph_cache(
+    context: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+) -> GameResult<bool> {
+    let (width, height) = context
+        .gfx_context
+        .glyph_brush
+        .borrow()
+        .texture_dimensions();
+    let (max_width, max_height) = context.gfx_context.glyph_cache_settings.max_size;
+    let size = ((width * 2).min(max_width), (height * 2).min(max_height));
+    if size.0 <= width && size.1 <= height {
+        return Ok(false);
+    }
+    resize_glyph_cache(context, quad_ctx, size)?;
+    context.gfx_context.glyph_cache_tracker.grew();
+    Ok(true)
+}
+
 /// Queues the `Text` to be drawn by [`draw_queued_text()`](fn.draw_queued_text.html).
 /// `relative_dest` is relative to the [`DrawParam::dest`](struct.DrawParam.html#structfield.dest)
 /// passed to `draw_queued()`. Note, any `Text` drawn via [`graphics::draw()`](fn.draw.html)
@@ -484,12 +1111,9 @@ where
     P: Into<mint::Point2<f32>>,
 {
     let p = Point2::from(relative_dest.into());
-    let varied_section = batch.generate_varied_section(p, color);
-    context
-        .gfx_context
-        .glyph_brush
-        .borrow_mut()
-        .queue(varied_section);
+    let mut glyph_brush = context.gfx_context.glyph_brush.borrow_mut();
+    let varied_section = batch.generate_varied_section(p, color, glyph_brush.fonts());
+    glyph_brush.queue_custom_layout(varied_section, &batch.layout());
 }
 
 /// Exposes `glyph_brush`'s drawing API in case `ggez`'s text drawing is insufficient.
@@ -529,30 +1153,51 @@ where
 {
     let param: DrawParam = param.into();
 
-    let gb = &mut ctx.gfx_context.glyph_brush;
-    let gc = &ctx.gfx_context.glyph_cache.texture;
+    let action = loop {
+        let gb = &mut ctx.gfx_context.glyph_brush;
+        let gc = &ctx.gfx_context.glyph_cache.texture;
+        let tracker = &mut ctx.gfx_context.glyph_cache_tracker;
 
-    let action = gb.borrow_mut().process_queued(
-        |rect, tex_data| {
-            // transform single byte alpha texture to (255, 255, 255, a)
-            let mut tex_data_chunks: Vec<u8> = vec![255; tex_data.len() * 4];
-            for i in 0..tex_data.len() {
-                tex_data_chunks[i * 4 + 3] = tex_data[i];
-            }
+        let result = gb.borrow_mut().process_queued(
+            |rect, tex_data| {
+                // transform single byte alpha texture to (255, 255, 255, a)
+                let mut tex_data_chunks: Vec<u8> = vec![255; tex_data.len() * 4];
+                for i in 0..tex_data.len() {
+                    tex_data_chunks[i * 4 + 3] = tex_data[i];
+                }
 
-            update_texture(quad_ctx, gc, rect, &tex_data_chunks[..])
-        },
-        to_vertex,
-    );
+                tracker.upload(rect);
+                update_texture(quad_ctx, gc, rect, &tex_data_chunks[..])
+            },
+            to_vertex,
+        );
+        match result {
+            Ok(action) => break action,
+            Err(glyph_brush::BrushError::TextureTooSmall { .. }) => {
+                if !grow_glyph_cache(ctx, quad_ctx)? {
+                    // drop the queued text, or every following draw would fail as well
+                    let mut glyph_brush = ctx.gfx_context.glyph_brush.borrow_mut();
+                    let builder = glyph_brush.to_builder();
+                    *glyph_brush = builder.build();
+                    ctx.gfx_context.glyph_cache_tracker.clear();
+                    let (width, height) = glyph_brush.texture_dimensions();
+                    return Err(GameError::RenderError(format!(
+                        "The glyphs of the queued text don't fit into a glyph cache of {}x{}",
+                        width, height
+                    )));
+                }
+            }
+        }
+    };
     match action {
-        Ok(glyph_brush::BrushAction::ReDraw) => {
+        glyph_brush::BrushAction::ReDraw => {
             let spritebatch = ctx.gfx_context.glyph_state.clone();
             let spritebatch = &mut *spritebatch.borrow_mut();
             spritebatch.set_blend_mode(blend);
             spritebatch.set_filter(filter);
             draw(ctx, quad_ctx, &*spritebatch, param)?;
         }
-        Ok(glyph_brush::BrushAction::Draw(drawparams)) => {
+        glyph_brush::BrushAction::Draw(drawparams) => {
             // Gotta clone the image to avoid double-borrow's.
             let spritebatch = ctx.gfx_context.glyph_state.clone();
             let spritebatch = &mut *spritebatch.borrow_mut();
@@ -565,25 +1210,13 @@ where
             }
             draw(ctx, quad_ctx, &*spritebatch, param)?;
         }
-        Err(glyph_brush::BrushError::TextureTooSmall { suggested }) => {
-            let (new_width, new_height) = suggested;
-            let data = vec![255; 4 * new_width as usize * new_height as usize];
-            let new_glyph_cache = Image::from_rgba8(
-                ctx,
-                quad_ctx,
-                u16::try_from(new_width).unwrap(),
-                u16::try_from(new_height).unwrap(),
-                &data,
-            )?;
-            ctx.gfx_context.glyph_cache = new_glyph_cache.clone();
-            let spritebatch = ctx.gfx_context.glyph_state.clone();
-            let spritebatch = &mut *spritebatch.borrow_mut();
-            let _ = spritebatch.set_image(new_glyph_cache);
-            ctx.gfx_context
-                .glyph_brush
-                .borrow_mut()
-                .resize_texture(new_width, new_height);
-        }
+    }
+
+    // growing drops all cached glyphs, so only once they've been drawn
+    if ctx.gfx_context.glyph_cache_settings.eviction == GlyphCacheEviction::GrowFirst
+        && ctx.gfx_context.glyph_cache_tracker.take_evicted()
+    {
+        let _ = grow_glyph_cache(ctx, quad_ctx)?;
     }
     Ok(())
 }
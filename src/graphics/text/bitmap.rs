@@ -0,0 +1,490 @@
+//! Pixel fonts drawn from images instead of being rasterized from outlines.
+//!
+//! A bitmap font is handed to the `GlyphBrush` like any other font, with metrics taken from its
+//! glyph table instead of from outlines, so it's laid out by the same `TextLayout` as TrueType
+//! fonts. Its glyphs are then drawn as sprites of its page images.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+
+use glyph_brush::ab_glyph::{self, GlyphId, Outline};
+use glyph_brush::{FontId, SectionGlyph};
+
+use crate::{
+    error::{GameError, GameResult},
+    graphics::{
+        self, spritebatch::SpriteBatch, BlendMode, Color, DrawParam, Drawable, FilterMode, Image,
+        Rect,
+    },
+    Context,
+};
+
+/// A glyph of a bitmap font, in pixels of its page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BitmapGlyph {
+    pub page: usize,
+    pub src: (u16, u16, u16, u16),
+    /// From the top left of the line to the top left of the glyph.
+    pub offset: (f32, f32),
+    pub advance: f32,
+}
+
+/// The glyph table of a bitmap font. All values are in pixels at its native size.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct BitmapData {
+    pub line_height: f32,
+    /// From the top of the line to the baseline.
+    pub base: f32,
+    /// Index 0 is the missing glyph, which is never drawn.
+    pub glyphs: Vec<Option<BitmapGlyph>>,
+    pub chars: HashMap<char, GlyphId>,
+    pub kerning: HashMap<(GlyphId, GlyphId), f32>,
+}
+
+impl BitmapData {
+    fn new(line_height: f32, base: f32) -> Self {
+        BitmapData {
+            line_height,
+            base,
+            glyphs: vec![None],
+            ..Default::default()
+        }
+    }
+
+    /// Fails if the font already has as many glyphs as a `GlyphId` can tell apart.
+    fn add_glyph(&mut self, c: char, glyph: BitmapGlyph) -> GameResult {
+        let id = u16::try_from(self.glyphs.len()).map_err(|_| {
+            GameError::ResourceLoadError(format!(
+                "Bitmap fonts can't have more than {} glyphs",
+                u16::MAX
+            ))
+        })?;
+        self.glyphs.push(Some(glyph));
+        let _ = self.chars.insert(c, GlyphId(id));
+        Ok(())
+    }
+
+    fn glyph(&self, id: GlyphId) -> Option<&BitmapGlyph> {
+        self.glyphs.get(usize::from(id.0)).and_then(Option::as_ref)
+    }
+}
+
+/// The metrics of a bitmap font, for laying it out through `glyph_brush`.
+///
+/// A `PxScale` of the line height of the font draws it at its native size.
+#[derive(Debug, Clone)]
+struct BitmapMetrics(Arc<BitmapData>);
+
+impl ab_glyph::Font for BitmapMetrics {
+    fn units_per_em(&self) -> Option<f32> {
+        Some(self.0.line_height)
+    }
+
+    fn ascent_unscaled(&self) -> f32 {
+        self.0.base
+    }
+
+    fn descent_unscaled(&self) -> f32 {
+        self.0.base - self.0.line_height
+    }
+
+    fn line_gap_unscaled(&self) -> f32 {
+        0.0
+    }
+
+    fn glyph_id(&self, c: char) -> GlyphId {
+        self.0.chars.get(&c).copied().unwrap_or(GlyphId(0))
+    }
+
+    fn h_advance_unscaled(&self, id: GlyphId) -> f32 {
+        self.0.glyph(id).map_or(0.0, |glyph| glyph.advance)
+    }
+
+    fn h_side_bearing_unscaled(&self, _id: GlyphId) -> f32 {
+        0.0
+    }
+
+    fn v_advance_unscaled(&self, _id: GlyphId) -> f32 {
+        self.0.line_height
+    }
+
+    fn v_side_bearing_unscaled(&self, _id: GlyphId) -> f32 {
+        0.0
+    }
+
+    fn kern_unscaled(&self, first: GlyphId, second: GlyphId) -> f32 {
+        self.0.kerning.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+
+    fn outline(&self, _id: GlyphId) -> Option<Outline> {
+        None
+    }
+
+    fn glyph_count(&self) -> usize {
+        self.0.glyphs.len()
+    }
+
+    fn codepoint_ids(&self) -> ab_glyph::CodepointIdIter<'_> {
+        // nothing in glyph_brush or here ever asks for the characters, so there's no need to
+        // list them
+        empty_truetype_font().codepoint_ids()
+    }
+
+    fn glyph_raster_image2(
+        &self,
+        _id: GlyphId,
+        _size: u16,
+    ) -> Option<ab_glyph::v2::GlyphImage<'_>> {
+        None
+    }
+}
+
+/// Returns a TrueType font without any characters.
+///
+/// A `CodepointIdIter` can only be created by ab_glyph itself, so this is where bitmap fonts
+/// get an empty one from.
+fn empty_truetype_font() -> &'static ab_glyph::FontVec {
+    static FONT: OnceLock<ab_glyph::FontVec> = OnceLock::new();
+    FONT.get_or_init(|| {
+        // nothing but the tables needed to parse a font, with a single glyph (the missing glyph
+        // every font has)
+        let mut head = vec![0; 54];
+        head[..4].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // version
+        head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes()); // magic number
+        head[18..20].copy_from_slice(&1024u16.to_be_bytes()); // units per em
+        let mut hhea = vec![0; 36];
+        hhea[..4].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // version
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec(); // version
+        maxp.extend_from_slice(&1u16.to_be_bytes()); // number of glyphs
+        let tables: [(&[u8; 4], Vec<u8>); 3] = [(b"head", head), (b"hhea", hhea), (b"maxp", maxp)];
+
+        let mut font = 0x0001_0000u32.to_be_bytes().to_vec(); // TrueType outlines
+        font.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+        font.extend_from_slice(&[0; 6]); // search hints, unused
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in tables.iter() {
+            font.extend_from_slice(*tag);
+            font.extend_from_slice(&[0; 4]); // checksum, unchecked
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += (table.len() + 3) & !3;
+        }
+        for (_, table) in tables.iter() {
+            font.extend_from_slice(table);
+            font.resize((font.len() + 3) & !3, 0);
+        }
+        ab_glyph::FontVec::try_from_vec(font).expect("the empty font is valid")
+    })
+}
+
+/// Returns a font without any glyphs, taking the place of unloaded fonts in the `GlyphBrush`.
+pub(crate) fn empty_font() -> ab_glyph::FontArc {
+    ab_glyph::FontArc::new(BitmapMetrics(Arc::new(BitmapData::new(1.0, 1.0))))
+}
+
+/// A page image of a bitmap font.
+#[derive(Debug)]
+struct Page {
+    size: (f32, f32),
+    batch: SpriteBatch,
+}
+
+/// A bitmap font in the `GraphicsContext`, with its pages.
+#[derive(Debug)]
+pub(crate) struct BitmapFont {
+    data: Arc<BitmapData>,
+    pages: Vec<Page>,
+}
+
+impl BitmapFont {
+    /// Returns the font to add to the `GlyphBrush` along with the font to draw it with.
+    pub fn new(data: BitmapData, pages: Vec<Image>) -> (ab_glyph::FontArc, BitmapFont) {
+        let data = Arc::new(data);
+        let pages = pages
+            .into_iter()
+            .map(|mut image| {
+                image.set_filter(FilterMode::Nearest);
+                Page {
+                    size: (f32::from(image.width()), f32::from(image.height())),
+                    batch: SpriteBatch::new(image),
+                }
+            })
+            .collect();
+        let metrics = ab_glyph::FontArc::new(BitmapMetrics(data.clone()));
+        (metrics, BitmapFont { data, pages })
+    }
+}
+
+/// Parses the text format of an AngelCode BMFont `.fnt` file, returning the glyphs and the
+/// file names of the pages.
+pub(crate) fn parse_bmfont(fnt: &str) -> GameResult<(BitmapData, Vec<String>)> {
+    let error = |line: usize, message: String| {
+        GameError::ResourceLoadError(format!("Invalid BMFont at line {}: {}", line + 1, message))
+    };
+
+    if fnt.starts_with("BMF") || fnt.trim_start().starts_with('<') {
+        return Err(GameError::ResourceLoadError(
+            "Only the text format of BMFont files is supported".to_string(),
+        ));
+    }
+
+    let mut data = None;
+    let mut page_count = 0;
+    let mut pages = BTreeMap::new();
+    for (number, line) in fnt.lines().enumerate() {
+        let (tag, attributes) = split_attributes(line);
+        let number_of = |key: &str| -> GameResult<f32> {
+            let value = attributes
+                .iter()
+                .find(|(name, _)| *name == key)
+                .ok_or_else(|| error(number, format!("'{}' has no {}", tag, key)))?
+                .1;
+            value
+                .parse()
+                .map_err(|_| error(number, format!("invalid {} '{}'", key, value)))
+        };
+        match tag {
+            "common" => {
+                // glyphs are scaled by the line height when drawn
+                let line_height = number_of("lineHeight")?;
+                if line_height <= 0.0 || !line_height.is_finite() {
+                    return Err(error(
+                        number,
+                        format!("invalid lineHeight '{}'", line_height),
+                    ));
+                }
+                data = Some(BitmapData::new(line_height, number_of("base")?));
+                page_count = number_of("pages")? as usize;
+            }
+            "page" => {
+                if data.is_none() {
+                    return Err(error(number, "'page' before 'common'".to_string()));
+                }
+                let id = number_of("id")?;
+                if id < 0.0 || id as usize >= page_count {
+                    return Err(error(
+                        number,
+                        format!("page {} doesn't exist, the font has {}", id, page_count),
+                    ));
+                }
+                let file = attributes
+                    .iter()
+                    .find(|(name, _)| *name == "file")
+                    .ok_or_else(|| error(number, "'page' has no file".to_string()))?
+                    .1;
+                let _ = pages.insert(id as usize, file.to_string());
+            }
+            "char" => {
+                let data = data
+                    .as_mut()
+                    .ok_or_else(|| error(number, "'char' before 'common'".to_string()))?;
+                let id = number_of("id")?;
+                // id -1 is the glyph for missing characters in some generators
+                let c = match char::from_u32(id as u32).filter(|_| id >= 0.0) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let glyph = BitmapGlyph {
+                    page: number_of("page")? as usize,
+                    src: (
+                        number_of("x")? as u16,
+                        number_of("y")? as u16,
+                        number_of("width")? as u16,
+                        number_of("height")? as u16,
+                    ),
+                    offset: (number_of("xoffset")?, number_of("yoffset")?),
+                    advance: number_of("xadvance")?,
+                };
+                data.add_glyph(c, glyph)?;
+            }
+            "kerning" => {
+                let data = data
+                    .as_mut()
+                    .ok_or_else(|| error(number, "'kerning' before 'common'".to_string()))?;
+                let id =
+                    |c: f32| char::from_u32(c as u32).and_then(|c| data.chars.get(&c).copied());
+                // pairs with unknown characters don't matter
+                if let (Some(first), Some(second)) =
+                    (id(number_of("first")?), id(number_of("second")?))
+                {
+                    let _ = data.kerning.insert((first, second), number_of("amount")?);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let data = data.ok_or_else(|| {
+        GameError::ResourceLoadError("Invalid BMFont: missing 'common' line".to_string())
+    })?;
+    // all ids are below the page count, so it's only missing pages if there are fewer
+    if pages.len() < page_count {
+        return Err(GameError::ResourceLoadError(format!(
+            "Invalid BMFont: {} of its {} pages are missing",
+            page_count - pages.len(),
+            page_count
+        )));
+    }
+    let pages: Vec<String> = pages.into_values().collect();
+    if let Some(glyph) = data.glyphs.iter().flatten().find(|g| g.page >= pages.len()) {
+        return Err(GameError::ResourceLoadError(format!(
+            "Invalid BMFont: page {} doesn't exist",
+            glyph.page
+        )));
+    }
+    Ok((data, pages))
+}
+
+/// Splits a line of a `.fnt` file into its tag and its `key=value` pairs, removing the quotes
+/// around values.
+fn split_attributes(line: &str) -> (&str, Vec<(&str, &str)>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        let (key, after) = match rest.split_once('=') {
+            Some(pair) => pair,
+            None => break,
+        };
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after.split_once(' ').unwrap_or((after, "")),
+        };
+        attributes.push((key.trim(), value));
+        rest = after;
+    }
+    (tag, attributes)
+}
+
+/// Creates the glyphs of a font drawn as a grid of equally sized cells, holding the given
+/// characters from left to right and top to bottom.
+///
+/// Fails if the image doesn't have a cell for every character.
+pub(crate) fn grid_glyphs(
+    (image_width, image_height): (u16, u16),
+    (width, height): (u16, u16),
+    chars: &str,
+) -> GameResult<BitmapData> {
+    if width == 0 || height == 0 || width > image_width || height > image_height {
+        return Err(GameError::ResourceLoadError(format!(
+            "Invalid glyph size {}x{} for an image of {}x{} pixels",
+            width, height, image_width, image_height
+        )));
+    }
+    let columns = u32::from(image_width / width);
+    let rows = u32::from(image_height / height);
+    let count = chars.chars().count();
+    if count > (columns * rows) as usize {
+        return Err(GameError::ResourceLoadError(format!(
+            "An image of {}x{} pixels only has room for {} glyphs of {}x{} pixels, not {}",
+            image_width,
+            image_height,
+            columns * rows,
+            width,
+            height,
+            count
+        )));
+    }
+    let mut data = BitmapData::new(f32::from(height), f32::from(height));
+    for (i, c) in (0u32..).zip(chars.chars()) {
+        let (column, row) = (i % columns, i / columns);
+        // within the image, so within u16
+        let x = (column * u32::from(width)) as u16;
+        let y = (row * u32::from(height)) as u16;
+        data.add_glyph(
+            c,
+            BitmapGlyph {
+                page: 0,
+                src: (x, y, width, height),
+                offset: (0.0, 0.0),
+                advance: f32::from(width),
+            },
+        )?;
+    }
+    Ok(data)
+}
+
+/// Draws the glyphs of bitmap fonts.
+pub(crate) fn draw_glyphs(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    glyphs: &[(SectionGlyph, Color)],
+    blend_mode: Option<BlendMode>,
+    param: DrawParam,
+) -> GameResult {
+    let mut font_ids: Vec<FontId> = glyphs.iter().map(|(glyph, _)| glyph.font_id).collect();
+    font_ids.sort_unstable_by_key(|id| id.0);
+    font_ids.dedup();
+    for font_id in font_ids {
+        // taken out while drawing, as drawing needs the whole context
+        let mut font = match ctx.gfx_context.bitmap_fonts.remove(&font_id) {
+            Some(font) => font,
+            None => continue,
+        };
+        let result = draw_font_glyphs(
+            ctx,
+            quad_ctx,
+            &mut font,
+            glyphs.iter().filter(|(glyph, _)| glyph.font_id == font_id),
+            blend_mode,
+            param,
+        );
+        let _ = ctx.gfx_context.bitmap_fonts.insert(font_id, font);
+        result?;
+    }
+    Ok(())
+}
+
+fn draw_font_glyphs<'a>(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    font: &mut BitmapFont,
+    glyphs: impl Iterator<Item = &'a (SectionGlyph, Color)>,
+    blend_mode: Option<BlendMode>,
+    param: DrawParam,
+) -> GameResult {
+    for page in &mut font.pages {
+        page.batch.clear();
+        page.batch.set_blend_mode(blend_mode);
+    }
+    for (section_glyph, color) in glyphs {
+        let glyph = &section_glyph.glyph;
+        let bitmap_glyph = match font.data.glyph(glyph.id) {
+            Some(bitmap_glyph) => bitmap_glyph,
+            None => continue,
+        };
+        let page = &mut font.pages[bitmap_glyph.page];
+        let (scale_x, scale_y) = (
+            glyph.scale.x / font.data.line_height,
+            glyph.scale.y / font.data.line_height,
+        );
+        let (x, y, w, h) = bitmap_glyph.src;
+        let top = glyph.position.y - font.data.base * scale_y;
+        let _ = page.batch.add(
+            DrawParam::new()
+                .src(Rect::new(
+                    f32::from(x) / page.size.0,
+                    f32::from(y) / page.size.1,
+                    f32::from(w) / page.size.0,
+                    f32::from(h) / page.size.1,
+                ))
+                // whole pixels keep pixel fonts crisp
+                .dest(mint::Point2 {
+                    x: (glyph.position.x + bitmap_glyph.offset.0 * scale_x).round(),
+                    y: (top + bitmap_glyph.offset.1 * scale_y).round(),
+                })
+                .scale(mint::Vector2 {
+                    x: scale_x,
+                    y: scale_y,
+                })
+                .color(*color),
+        );
+    }
+    for page in &font.pages {
+        if !page.batch.get_sprites().is_empty() {
+            graphics::draw(ctx, quad_ctx, &page.batch, param)?;
+        }
+    }
+    Ok(())
+}
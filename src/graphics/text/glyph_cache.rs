@@ -0,0 +1,177 @@
+//! Settings and bookkeeping of the glyph cache, the texture the glyphs of TrueType fonts are
+//! rasterized into before being drawn.
+
+use std::collections::BTreeMap;
+
+use glyph_brush::Rectangle;
+
+/// What makes room in the glyph cache once its texture is full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphCacheEviction {
+    /// The texture is left to glyph_brush's own cache, which replaces the glyphs that haven't
+    /// been drawn for the longest time by new ones. The texture only grows when the glyphs of
+    /// a single draw don't fit into it.
+    #[default]
+    LeastRecentlyUsed,
+    /// The texture grows as soon as glyphs had to be replaced, until it reaches its maximum
+    /// size; only then glyphs are replaced like with `LeastRecentlyUsed`. Rasterizes glyphs
+    /// less often, at the cost of memory.
+    GrowFirst,
+}
+
+/// The size and eviction policy of the glyph cache, see
+/// [`set_glyph_cache_settings()`](fn.set_glyph_cache_settings.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphCacheSettings {
+    /// The size of the texture in pixels to start out with, 256x256 by default.
+    pub initial_size: (u32, u32),
+    /// The size in pixels the texture grows to at most, 4096x4096 by default. Drawing text
+    /// whose glyphs don't all fit into a texture of this size fails.
+    pub max_size: (u32, u32),
+    pub eviction: GlyphCacheEviction,
+}
+
+impl Default for GlyphCacheSettings {
+    fn default() -> Self {
+        GlyphCacheSettings {
+            initial_size: (256, 256),
+            max_size: (4096, 4096),
+            eviction: GlyphCacheEviction::LeastRecentlyUsed,
+        }
+    }
+}
+
+impl GlyphCacheSettings {
+    pub fn initial_size(mut self, width: u32, height: u32) -> Self {
+        self.initial_size = (width, height);
+        self
+    }
+
+    pub fn max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = (width, height);
+        self
+    }
+
+    pub fn eviction(mut self, eviction: GlyphCacheEviction) -> Self {
+        self.eviction = eviction;
+        self
+    }
+}
+
+/// How full the glyph cache is, see [`glyph_cache_stats()`](fn.glyph_cache_stats.html).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphCacheStats {
+    /// The size of the texture in pixels.
+    pub size: (u32, u32),
+    /// The number of glyphs in the texture.
+    pub glyphs: usize,
+    /// The number of pixels of the texture these glyphs cover.
+    pub used_pixels: u64,
+    /// The number of glyphs that had to make room for others so far.
+    pub evicted: usize,
+    /// How often the texture had to grow so far.
+    pub grown: usize,
+}
+
+impl GlyphCacheStats {
+    /// Returns the fraction of the texture covered by glyphs, from 0 to 1.
+    pub fn occupancy(&self) -> f32 {
+        let pixels = u64::from(self.size.0) * u64::from(self.size.1);
+        if pixels == 0 {
+            0.0
+        } else {
+            self.used_pixels as f32 / pixels as f32
+        }
+    }
+}
+
+/// Keeps track of the glyphs uploaded into the glyph cache texture.
+///
+/// `glyph_brush` doesn't tell which glyphs it evicts, but it only ever puts new glyphs where
+/// evicted ones were, so every cached glyph a new one overlaps is gone.
+#[derive(Debug, Default)]
+pub(crate) struct GlyphCacheTracker {
+    /// The glyphs by the y coordinate of their top edge. `glyph_brush` packs glyphs into rows,
+    /// so the glyphs a new one may overlap are found among a few neighbouring keys.
+    rows: BTreeMap<u32, Vec<Rectangle<u32>>>,
+    /// The height of the tallest glyph, how far above a new glyph overlapped ones may start.
+    max_height: u32,
+    glyphs: usize,
+    used_pixels: u64,
+    evicted: usize,
+    grown: usize,
+    /// Whether glyphs have been evicted since the last call of `take_evicted`.
+    recently_evicted: bool,
+}
+
+fn pixels(rect: &Rectangle<u32>) -> u64 {
+    u64::from(rect.width()) * u64::from(rect.height())
+}
+
+impl GlyphCacheTracker {
+    pub fn upload(&mut self, rect: Rectangle<u32>) {
+        let overlaps = |glyph: &Rectangle<u32>| {
+            glyph.min[0] < rect.max[0]
+                && rect.min[0] < glyph.max[0]
+                && glyph.min[1] < rect.max[1]
+                && rect.min[1] < glyph.max[1]
+        };
+        let top = rect.min[1].saturating_sub(self.max_height);
+        let mut evicted = 0;
+        let mut freed = 0;
+        let mut emptied = Vec::new();
+        for (&y, row) in self.rows.range_mut(top..rect.max[1]) {
+            row.retain(|glyph| {
+                if overlaps(glyph) {
+                    evicted += 1;
+                    freed += pixels(glyph);
+                    false
+                } else {
+                    true
+                }
+            });
+            if row.is_empty() {
+                emptied.push(y);
+            }
+        }
+        for y in emptied {
+            let _ = self.rows.remove(&y);
+        }
+
+        self.evicted += evicted;
+        self.recently_evicted |= evicted > 0;
+        self.glyphs = self.glyphs - evicted + 1;
+        self.used_pixels = self.used_pixels - freed + pixels(&rect);
+        self.max_height = self.max_height.max(rect.height());
+        self.rows.entry(rect.min[1]).or_default().push(rect);
+    }
+
+    /// Forgets all glyphs, as the texture was cleared.
+    pub fn clear(&mut self) {
+        self.rows.clear();
+        self.max_height = 0;
+        self.glyphs = 0;
+        self.used_pixels = 0;
+        self.recently_evicted = false;
+    }
+
+    /// Forgets all glyphs, as the texture grew.
+    pub fn grew(&mut self) {
+        self.clear();
+        self.grown += 1;
+    }
+
+    pub fn take_evicted(&mut self) -> bool {
+        std::mem::take(&mut self.recently_evicted)
+    }
+
+    pub fn stats(&self, size: (u32, u32)) -> GlyphCacheStats {
+        GlyphCacheStats {
+            size,
+            glyphs: self.glyphs,
+            used_pixels: self.used_pixels,
+            evicted: self.evicted,
+            grown: self.grown,
+        }
+    }
+}
@@ -0,0 +1,480 @@
+//! The layout of `Text`, placing glyphs into lines.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use glyph_brush::ab_glyph::{point, Font, GlyphId, PxScale, Rect as GbRect, ScaleFont};
+use glyph_brush::{
+    BuiltInLineBreaker, FontId, GlyphPositioner, HorizontalAlign, LineBreak, LineBreaker,
+    SectionGeometry, SectionGlyph, ToSectionText, VerticalAlign,
+};
+
+/// The direction a [`Text`](struct.Text.html) is written in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TextDirection {
+    /// The direction of the first letter that has one.
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+/// The `GlyphPositioner` laying out [`Text`](struct.Text.html), which can also be used with
+/// [`queue_text_raw()`](fn.queue_text_raw.html).
+///
+/// Lines wrap between words at the width of the bounds of the section. Right-to-left scripts
+/// (like Hebrew or Arabic) are reordered within each line, left-to-right runs like numbers
+/// keeping their order, but their letters aren't shaped.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextLayout {
+    pub(crate) h_align: HorizontalAlign,
+    pub(crate) v_align: VerticalAlign,
+    line_spacing: f32,
+    letter_spacing: f32,
+    max_lines: Option<usize>,
+    direction: TextDirection,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        TextLayout {
+            h_align: HorizontalAlign::Left,
+            v_align: VerticalAlign::Top,
+            line_spacing: 1.0,
+            letter_spacing: 0.0,
+            max_lines: None,
+            direction: TextDirection::Auto,
+        }
+    }
+}
+
+impl Hash for TextLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.h_align.hash(state);
+        self.v_align.hash(state);
+        self.line_spacing.to_bits().hash(state);
+        self.letter_spacing.to_bits().hash(state);
+        self.max_lines.hash(state);
+        self.direction.hash(state);
+    }
+}
+
+impl TextLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how lines are aligned to the x of the screen position of the section: starting at
+    /// it, centered around it or ending at it.
+    pub fn h_align(mut self, h_align: HorizontalAlign) -> Self {
+        self.h_align = h_align;
+        self
+    }
+
+    /// Sets how the lines are aligned to the y of the screen position of the section: starting
+    /// at it, centered around it or ending at it.
+    pub fn v_align(mut self, v_align: VerticalAlign) -> Self {
+        self.v_align = v_align;
+        self
+    }
+
+    /// Sets the distance between lines as a multiple of the line height of their fonts.
+    ///
+    /// Default: 1.0
+    pub fn line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    /// Sets the space added after every glyph, in pixels.
+    ///
+    /// Default: 0.0
+    pub fn letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = letter_spacing;
+        self
+    }
+
+    /// Sets how many lines are shown at most. Further lines are dropped, and the last line shown
+    /// ends in an ellipsis, shortened to fit into the bounds. `Some(0)` shows no text at all.
+    pub fn max_lines(mut self, max_lines: Option<usize>) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    pub fn direction(mut self, direction: TextDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+}
+
+/// A character to lay out.
+#[derive(Debug, Copy, Clone)]
+struct Item {
+    section_index: usize,
+    byte_index: usize,
+    font_id: FontId,
+    scale: PxScale,
+    id: GlyphId,
+    advance: f32,
+    whitespace: bool,
+    /// `Some(true)` for right-to-left letters, `Some(false)` for other letters and digits,
+    /// `None` for everything else, which takes the direction of its surroundings.
+    rtl: Option<bool>,
+}
+
+/// The vertical metrics of a line, the max of all of its glyphs.
+#[derive(Debug, Default, Copy, Clone)]
+struct LineMetrics {
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl LineMetrics {
+    fn of<F: Font>(font: &F, scale: PxScale) -> Self {
+        let font = font.as_scaled(scale);
+        LineMetrics {
+            ascent: font.ascent(),
+            descent: font.descent(),
+            line_gap: font.line_gap(),
+        }
+    }
+
+    fn max(self, other: LineMetrics) -> Self {
+        LineMetrics {
+            ascent: self.ascent.max(other.ascent),
+            descent: self.descent.min(other.descent),
+            line_gap: self.line_gap.max(other.line_gap),
+        }
+    }
+
+    fn height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+impl GlyphPositioner for TextLayout {
+    fn calculate_glyphs<F, S>(
+        &self,
+        fonts: &[F],
+        geometry: &SectionGeometry,
+        sections: &[S],
+    ) -> Vec<SectionGlyph>
+    where
+        F: Font,
+        S: ToSectionText,
+    {
+        let sections: Vec<_> = sections.iter().map(|s| s.to_section_text()).collect();
+        let text: String = sections.iter().map(|section| section.text).collect();
+        let breaks: HashMap<usize, bool> = BuiltInLineBreaker::default()
+            .line_breaks(&text)
+            .map(|line_break| match line_break {
+                LineBreak::Soft(offset) => (offset, false),
+                LineBreak::Hard(offset) => (offset, true),
+            })
+            .collect();
+
+        let mut items = Vec::new();
+        // lines as the items in them, and whether a line can wrap before each item
+        let mut lines: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut can_wrap: Vec<bool> = Vec::new();
+        let mut offset = 0;
+        for (section_index, section) in sections.iter().enumerate() {
+            let font = &fonts[section.font_id.0];
+            let scaled = font.as_scaled(section.scale);
+            for (byte_index, c) in section.text.char_indices() {
+                let line_break = breaks.get(&(offset + byte_index)).copied();
+                if line_break == Some(true) {
+                    lines.push(Vec::new());
+                }
+                if c.is_control() {
+                    continue;
+                }
+                let id = font.glyph_id(c);
+                items.push(Item {
+                    section_index,
+                    byte_index,
+                    font_id: section.font_id,
+                    scale: section.scale,
+                    id,
+                    advance: scaled.h_advance(id),
+                    whitespace: c.is_whitespace(),
+                    rtl: direction(c),
+                });
+                can_wrap.push(line_break.is_some());
+                lines.last_mut().unwrap().push(items.len() - 1);
+            }
+            offset += section.text.len();
+        }
+
+        // wrap at the last place possible before the line leaves the bounds
+        let max_width = geometry.bounds.0;
+        let mut wrapped = Vec::new();
+        for line in lines {
+            let mut current: Vec<usize> = Vec::new();
+            // the advances of the line with the spacing after each glyph, and the last place to
+            // wrap at with the advances before it
+            let mut advances = 0.0;
+            let mut wrap_at: Option<(usize, f32)> = None;
+            for index in line {
+                if can_wrap[index] && !current.is_empty() {
+                    wrap_at = Some((current.len(), advances));
+                }
+                current.push(index);
+                advances += items[index].advance + self.letter_spacing;
+                // the line ends in this glyph, so only the spacing after it isn't part of its width
+                if items[index].whitespace || advances - self.letter_spacing <= max_width {
+                    continue;
+                }
+                if let Some((at, advances_before)) = wrap_at.take() {
+                    let rest = current.split_off(at);
+                    wrapped.push(std::mem::replace(&mut current, rest));
+                    advances -= advances_before;
+                }
+            }
+            wrapped.push(current);
+        }
+
+        match self.max_lines {
+            Some(0) => wrapped.clear(),
+            Some(max_lines) => {
+                let truncated = wrapped.len() > max_lines;
+                wrapped.truncate(max_lines);
+                let last = wrapped.last_mut().unwrap();
+                if truncated || self.width(&items, last) > max_width {
+                    self.add_ellipsis(fonts, &mut items, last, max_width);
+                }
+            }
+            None => {}
+        }
+
+        let base_rtl = match self.direction {
+            TextDirection::LeftToRight => false,
+            TextDirection::RightToLeft => true,
+            TextDirection::Auto => items.iter().find_map(|item| item.rtl).unwrap_or(false),
+        };
+        let default_metrics = sections
+            .first()
+            .map(|section| LineMetrics::of(&fonts[section.font_id.0], section.scale))
+            .unwrap_or_default();
+        let metrics: Vec<LineMetrics> = wrapped
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|&index| {
+                        LineMetrics::of(&fonts[items[index].font_id.0], items[index].scale)
+                    })
+                    .reduce(LineMetrics::max)
+                    .unwrap_or(default_metrics)
+            })
+            .collect();
+        let height = metrics
+            .iter()
+            .enumerate()
+            .map(|(i, metrics)| {
+                if i + 1 < wrapped.len() {
+                    metrics.height() * self.line_spacing
+                } else {
+                    metrics.height()
+                }
+            })
+            .sum::<f32>();
+
+        let (screen_x, screen_y) = geometry.screen_position;
+        let mut top = match self.v_align {
+            VerticalAlign::Top => screen_y,
+            VerticalAlign::Center => screen_y - height / 2.0,
+            VerticalAlign::Bottom => screen_y - height,
+        };
+        let mut glyphs = Vec::new();
+        for (line, metrics) in wrapped.iter().zip(&metrics) {
+            let width = self.width(&items, line);
+            let mut x = match self.h_align {
+                HorizontalAlign::Left => screen_x,
+                HorizontalAlign::Center => screen_x - width / 2.0,
+                HorizontalAlign::Right => screen_x - width,
+            };
+            let baseline = top + metrics.ascent;
+            let mut previous: Option<&Item> = None;
+            let first = glyphs.len();
+            for index in visual_order(&items, line, base_rtl) {
+                let item = &items[index];
+                if let Some(previous) = previous {
+                    if previous.font_id == item.font_id && previous.scale == item.scale {
+                        x += fonts[item.font_id.0]
+                            .as_scaled(item.scale)
+                            .kern(previous.id, item.id);
+                    }
+                }
+                glyphs.push((
+                    index,
+                    SectionGlyph {
+                        section_index: item.section_index,
+                        byte_index: item.byte_index,
+                        glyph: item
+                            .id
+                            .with_scale_and_position(item.scale, point(x, baseline)),
+                        font_id: item.font_id,
+                    },
+                ));
+                x += item.advance + self.letter_spacing;
+                previous = Some(item);
+            }
+            // keep the glyphs of each line in the order of the text
+            glyphs[first..].sort_by_key(|(index, _)| *index);
+            top += metrics.height() * self.line_spacing;
+        }
+        glyphs.into_iter().map(|(_, glyph)| glyph).collect()
+    }
+
+    fn bounds_rect(&self, geometry: &SectionGeometry) -> GbRect {
+        let SectionGeometry {
+            screen_position: (screen_x, screen_y),
+            bounds: (bound_w, bound_h),
+        } = *geometry;
+        let (x_min, x_max) = match self.h_align {
+            HorizontalAlign::Left => (screen_x, screen_x + bound_w),
+            HorizontalAlign::Center => (screen_x - bound_w / 2.0, screen_x + bound_w / 2.0),
+            HorizontalAlign::Right => (screen_x - bound_w, screen_x),
+        };
+        let (y_min, y_max) = match self.v_align {
+            VerticalAlign::Top => (screen_y, screen_y + bound_h),
+            VerticalAlign::Center => (screen_y - bound_h / 2.0, screen_y + bound_h / 2.0),
+            VerticalAlign::Bottom => (screen_y - bound_h, screen_y),
+        };
+        GbRect {
+            min: point(x_min.floor(), y_min.floor()),
+            max: point(x_max.ceil(), y_max.ceil()),
+        }
+    }
+}
+
+impl TextLayout {
+    /// The width of a line, without the whitespace at its end.
+    fn width(&self, items: &[Item], line: &[usize]) -> f32 {
+        let end = line
+            .iter()
+            .rposition(|&index| !items[index].whitespace)
+            .map_or(0, |last| last + 1);
+        line[..end]
+            .iter()
+            .map(|&index| items[index].advance + self.letter_spacing)
+            .sum::<f32>()
+            - if end > 0 { self.letter_spacing } else { 0.0 }
+    }
+
+    /// Appends an ellipsis to the line, dropping glyphs from its end until it fits.
+    fn add_ellipsis<F: Font>(
+        &self,
+        fonts: &[F],
+        items: &mut Vec<Item>,
+        line: &mut Vec<usize>,
+        max_width: f32,
+    ) {
+        // the ellipsis looks like the last glyph kept, or the last one dropped
+        let template = match line.last().copied().or(items.len().checked_sub(1)) {
+            Some(index) => items[index],
+            None => return,
+        };
+        let font = &fonts[template.font_id.0];
+        // fall back to three dots if the font has no ellipsis
+        let ellipsis: &[char] = if font.glyph_id('…').0 != 0 {
+            &['…']
+        } else {
+            &['.', '.', '.']
+        };
+        let dots: Vec<Item> = ellipsis
+            .iter()
+            .map(|&c| {
+                let id = font.glyph_id(c);
+                Item {
+                    id,
+                    advance: font.as_scaled(template.scale).h_advance(id),
+                    whitespace: false,
+                    rtl: None,
+                    ..template
+                }
+            })
+            .collect();
+        let dots_width = dots
+            .iter()
+            .map(|dot| dot.advance + self.letter_spacing)
+            .sum::<f32>();
+
+        // the width of the line followed by the dots, as long as it ends in a glyph that isn't
+        // whitespace
+        let mut width = line
+            .iter()
+            .map(|&index| items[index].advance + self.letter_spacing)
+            .sum::<f32>()
+            + dots_width;
+        while let Some(&last) = line.last() {
+            if !items[last].whitespace && width <= max_width {
+                break;
+            }
+            width -= items[last].advance + self.letter_spacing;
+            let _ = line.pop();
+        }
+        for dot in dots {
+            items.push(dot);
+            line.push(items.len() - 1);
+        }
+    }
+}
+
+/// Returns whether the character is written right-to-left, `None` if it has no direction of its
+/// own.
+fn direction(c: char) -> Option<bool> {
+    match u32::from(c) {
+        // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and their presentation forms
+        0x0590..=0x08FF
+        | 0xFB1D..=0xFDFF
+        | 0xFE70..=0xFEFF
+        | 0x10800..=0x10FFF
+        | 0x1E800..=0x1EFFF => Some(true),
+        _ if c.is_alphanumeric() => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the items of the line from left to right, reversing right-to-left runs.
+fn visual_order(items: &[Item], line: &[usize], base_rtl: bool) -> Vec<usize> {
+    // characters without a direction take the one of their surroundings if it's the same on
+    // both sides, or the one of the text otherwise
+    let mut before = None;
+    let befores: Vec<Option<bool>> = line
+        .iter()
+        .map(|&index| {
+            let rtl = items[index].rtl.or(before);
+            std::mem::replace(&mut before, rtl)
+        })
+        .collect();
+    let mut after = None;
+    let mut directions = vec![base_rtl; line.len()];
+    for (i, &index) in line.iter().enumerate().rev() {
+        directions[i] = items[index].rtl.unwrap_or(match (befores[i], after) {
+            (Some(before), Some(after)) if before == after => before,
+            _ => base_rtl,
+        });
+        after = items[index].rtl.or(after);
+    }
+
+    let mut runs: Vec<(bool, Vec<usize>)> = Vec::new();
+    for (&index, &rtl) in line.iter().zip(&directions) {
+        match runs.last_mut() {
+            Some((run_rtl, run)) if *run_rtl == rtl => run.push(index),
+            _ => runs.push((rtl, vec![index])),
+        }
+    }
+    if base_rtl {
+        runs.reverse();
+    }
+    runs.into_iter()
+        .flat_map(|(rtl, mut run)| {
+            if rtl {
+                run.reverse();
+            }
+            run
+        })
+        .collect()
+}
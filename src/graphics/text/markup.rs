@@ -0,0 +1,238 @@
+//! Parsing the markup of [`Text::from_markup`](struct.Text.html#method.from_markup).
+
+use std::collections::HashMap;
+
+use glyph_brush::ab_glyph::PxScale;
+
+use crate::graphics::{Color, Font, TextFragment};
+use crate::{GameError, GameResult};
+
+/// The fonts the `[b]`, `[i]` and `[font=name]` tags of
+/// [`Text::from_markup_with_fonts`](struct.Text.html#method.from_markup_with_fonts) switch to.
+///
+/// There's no way to derive a bold or italic version of a font, so `[b]` and `[i]` leave the
+/// font unchanged unless the corresponding font is set here.
+#[derive(Debug, Default, Clone)]
+pub struct MarkupFonts {
+    pub bold: Option<Font>,
+    pub italic: Option<Font>,
+    /// Used for text that's both bold and italic, falls back to `bold` and then `italic`.
+    pub bold_italic: Option<Font>,
+    /// The fonts `[font=name]` can refer to.
+    pub named: HashMap<String, Font>,
+}
+
+impl MarkupFonts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bold(mut self, font: Font) -> Self {
+        self.bold = Some(font);
+        self
+    }
+
+    pub fn italic(mut self, font: Font) -> Self {
+        self.italic = Some(font);
+        self
+    }
+
+    pub fn bold_italic(mut self, font: Font) -> Self {
+        self.bold_italic = Some(font);
+        self
+    }
+
+    /// Makes the font available as `[font=name]`.
+    pub fn named<S: Into<String>>(mut self, name: S, font: Font) -> Self {
+        let _ = self.named.insert(name.into(), font);
+        self
+    }
+}
+
+/// The formatting inside of a tag.
+#[derive(Debug, Default, Clone, Copy)]
+struct Style {
+    color: Option<Color>,
+    font: Option<Font>,
+    scale: Option<PxScale>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl Style {
+    fn fragment(&self, text: String, fonts: &MarkupFonts) -> TextFragment {
+        let font = match (self.bold, self.italic) {
+            (true, true) => fonts.bold_italic.or(fonts.bold).or(fonts.italic),
+            (true, false) => fonts.bold,
+            (false, true) => fonts.italic,
+            (false, false) => None,
+        };
+        TextFragment {
+            text,
+            color: self.color,
+            font: font.or(self.font),
+            scale: self.scale,
+            underline: self.underline,
+            strikethrough: self.strikethrough,
+            image: None,
+        }
+    }
+}
+
+/// Splits the markup into fragments.
+pub(crate) fn parse(markup: &str, fonts: &MarkupFonts) -> GameResult<Vec<TextFragment>> {
+    let error = |position: usize, message: String| {
+        GameError::ResourceLoadError(format!("Invalid markup at byte {}: {}", position, message))
+    };
+
+    let mut fragments = Vec::new();
+    // the open tags, with the style before each of them
+    let mut open: Vec<(&str, usize, Style)> = Vec::new();
+    let mut style = Style::default();
+    let mut text = String::new();
+
+    let mut rest = markup;
+    while let Some(start) = rest.find('[') {
+        text.push_str(&rest[..start]);
+        let position = markup.len() - rest.len() + start;
+        rest = &rest[start + 1..];
+        // `[[` is a literal `[`
+        if let Some(after) = rest.strip_prefix('[') {
+            text.push('[');
+            rest = after;
+            continue;
+        }
+
+        let end = rest
+            .find(']')
+            .ok_or_else(|| error(position, "unterminated tag".to_string()))?;
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        if !text.is_empty() {
+            fragments.push(style.fragment(std::mem::take(&mut text), fonts));
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            match open.pop() {
+                Some((open_name, _, previous)) if open_name == name => style = previous,
+                Some((open_name, _, _)) => {
+                    return Err(error(
+                        position,
+                        format!("[/{}] closes [{}]", name, open_name),
+                    ))
+                }
+                None => return Err(error(position, format!("[/{}] closes no tag", name))),
+            }
+            continue;
+        }
+
+        let (name, value) = match tag.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (tag, None),
+        };
+        let value = |kind: &str| {
+            value.ok_or_else(|| error(position, format!("[{}] needs a {}", name, kind)))
+        };
+        let previous = style;
+        match name {
+            "color" => {
+                let color = value("color")?;
+                style.color = Some(
+                    parse_color(color)
+                        .ok_or_else(|| error(position, format!("invalid color '{}'", color)))?,
+                );
+            }
+            "size" => {
+                let size = value("size")?;
+                let size = size
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|size| *size > 0.0)
+                    .ok_or_else(|| error(position, format!("invalid size '{}'", size)))?;
+                style.scale = Some(PxScale::from(size));
+            }
+            "font" => {
+                let font = value("font name")?;
+                style.font = Some(
+                    *fonts
+                        .named
+                        .get(font)
+                        .ok_or_else(|| error(position, format!("unknown font '{}'", font)))?,
+                );
+            }
+            "b" => style.bold = true,
+            "i" => style.italic = true,
+            "u" => style.underline = true,
+            "s" => style.strikethrough = true,
+            "img" => {
+                let mut image = TextFragment::inline_image(value("image name")?);
+                image.color = style.color;
+                image.scale = style.scale;
+                fragments.push(image);
+                continue;
+            }
+            _ => return Err(error(position, format!("unknown tag [{}]", tag))),
+        }
+        open.push((name, position, previous));
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        fragments.push(style.fragment(text, fonts));
+    }
+
+    match open.pop() {
+        Some((name, position, _)) => Err(error(position, format!("[{}] is never closed", name))),
+        None => Ok(fragments),
+    }
+}
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa` or the name of one of the constants of
+/// `Color`.
+fn parse_color(color: &str) -> Option<Color> {
+    let hex = match color.strip_prefix('#') {
+        Some(hex) => hex,
+        None => {
+            return match color.to_ascii_lowercase().as_str() {
+                "white" => Some(Color::WHITE),
+                "black" => Some(Color::BLACK),
+                "red" => Some(Color::RED),
+                "green" => Some(Color::GREEN),
+                "blue" => Some(Color::BLUE),
+                "cyan" => Some(Color::CYAN),
+                "magenta" => Some(Color::MAGENTA),
+                "yellow" => Some(Color::YELLOW),
+                _ => None,
+            }
+        }
+    };
+    if !hex.is_ascii() {
+        return None;
+    }
+    let digit = |i: usize, len: usize| u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok();
+    match hex.len() {
+        3 | 4 => {
+            // `#f00` is short for `#ff0000`
+            let channel = |i: usize| digit(i, 1).map(|c| c * 17);
+            let alpha = if hex.len() == 4 { channel(3)? } else { 255 };
+            Some(Color::from_rgba(
+                channel(0)?,
+                channel(1)?,
+                channel(2)?,
+                alpha,
+            ))
+        }
+        6 | 8 => {
+            let alpha = if hex.len() == 8 { digit(3, 2)? } else { 255 };
+            Some(Color::from_rgba(
+                digit(0, 2)?,
+                digit(1, 2)?,
+                digit(2, 2)?,
+                alpha,
+            ))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,687 @@
+//! Drawing fonts through signed distance fields, which stay sharp at any scale.
+//!
+//! Every glyph of an SDF font is rasterized once at `SdfSettings::base_size` into an atlas of
+//! the font, storing for each texel how far it is from the outline of the glyph instead of its
+//! coverage. Glyphs are then drawn at any scale from that atlas with a shader that finds their
+//! edges (and the outline, glow and shadow of the `Text`) in the distances.
+
+use std::collections::HashMap;
+
+use bytemuck_derive::{Pod, Zeroable};
+use glyph_brush::ab_glyph::{Font as _, FontArc, GlyphId, PxScale};
+use glyph_brush::{FontId, SectionGlyph};
+use miniquad::{ShaderMeta, UniformBlockLayout, UniformDesc, UniformType};
+
+use crate::{
+    error::GameResult,
+    graphics::{
+        self, spritebatch::SpriteBatch, BlendMode, Color, DrawParam, Drawable, Image, Rect, Shader,
+    },
+    Context,
+};
+
+const VERTEX: &str = r#"#version 100
+attribute vec2 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+attribute vec4 Source;
+attribute vec4 Color;
+attribute mat4 Model;
+
+varying lowp vec4 color;
+varying mediump vec2 uv;
+varying mediump float smoothing;
+
+uniform mat4 Projection;
+uniform vec2 AtlasSize;
+uniform vec2 ScreenSize;
+uniform float DistanceScale;
+
+void main() {
+    mat4 transform = Projection * Model;
+    gl_Position = transform * vec4(position, 0, 1);
+    color = Color * color0;
+    uv = texcoord * Source.zw + Source.xy;
+
+    // the change of distance per pixel on the screen, to anti-alias the outline at any scale
+    float pixels = length(transform[0].xy * ScreenSize * 0.5);
+    smoothing = DistanceScale * Source.z * AtlasSize.x / max(pixels, 0.0001);
+}"#;
+
+const FRAGMENT: &str = r#"#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying mediump vec2 uv;
+varying mediump float smoothing;
+
+uniform sampler2D Texture;
+uniform vec4 OutlineColor;
+uniform vec4 GlowColor;
+uniform vec4 ShadowColor;
+uniform float OutlineWidth;
+uniform float GlowWidth;
+uniform float Softness;
+uniform float ShadowPass;
+
+void main() {
+    // 0.5 on the outline of the glyph, growing towards its inside
+    float dist = texture2D(Texture, uv).a;
+    float edge = 0.5 - OutlineWidth;
+
+    if (ShadowPass > 0.5) {
+        float soft = smoothing + Softness;
+        float alpha = smoothstep(edge - soft, edge + soft, dist);
+        gl_FragColor = vec4(ShadowColor.rgb, ShadowColor.a * color.a * alpha);
+        return;
+    }
+
+    vec4 fill = color;
+    if (OutlineWidth > 0.0) {
+        fill = mix(OutlineColor, color, smoothstep(0.5 - smoothing, 0.5 + smoothing, dist));
+    }
+    float alpha = fill.a * smoothstep(edge - smoothing, edge + smoothing, dist);
+
+    if (GlowWidth > 0.0) {
+        float glow = GlowColor.a * color.a * smoothstep(edge - GlowWidth, edge, dist);
+        float combined = alpha + glow * (1.0 - alpha);
+        vec3 rgb = mix(GlowColor.rgb, fill.rgb, alpha / max(combined, 0.0001));
+        gl_FragColor = vec4(rgb, combined);
+    } else {
+        gl_FragColor = vec4(fill.rgb, alpha);
+    }
+}"#;
+
+fn meta() -> ShaderMeta {
+    ShaderMeta {
+        images: vec!["Texture".to_string()],
+        uniforms: UniformBlockLayout {
+            uniforms: vec![
+                UniformDesc::new("OutlineColor", UniformType::Float4),
+                UniformDesc::new("GlowColor", UniformType::Float4),
+                UniformDesc::new("ShadowColor", UniformType::Float4),
+                UniformDesc::new("AtlasSize", UniformType::Float2),
+                UniformDesc::new("ScreenSize", UniformType::Float2),
+                UniformDesc::new("DistanceScale", UniformType::Float1),
+                UniformDesc::new("OutlineWidth", UniformType::Float1),
+                UniformDesc::new("GlowWidth", UniformType::Float1),
+                UniformDesc::new("Softness", UniformType::Float1),
+                UniformDesc::new("ShadowPass", UniformType::Float1),
+                UniformDesc::new("Projection", UniformType::Mat4),
+            ],
+        },
+    }
+}
+
+/// The uniforms of the shader, in the order of `meta()`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct SdfUniforms {
+    outline_color: [f32; 4],
+    glow_color: [f32; 4],
+    shadow_color: [f32; 4],
+    atlas_size: [f32; 2],
+    screen_size: [f32; 2],
+    distance_scale: f32,
+    outline_width: f32,
+    glow_width: f32,
+    softness: f32,
+    shadow_pass: f32,
+}
+
+/// How the glyphs of a font loaded with [`Font::new_sdf()`](struct.Font.html#method.new_sdf)
+/// are rasterized.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SdfSettings {
+    pub(crate) base_size: f32,
+    pub(crate) spread: f32,
+}
+
+impl Default for SdfSettings {
+    fn default() -> Self {
+        SdfSettings {
+            base_size: 48.0,
+            spread: 6.0,
+        }
+    }
+}
+
+impl SdfSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The scale glyphs are rasterized at. Larger sizes keep more of the details of a font, like
+    /// sharp corners, at the cost of a larger atlas.
+    ///
+    /// Default: 48
+    pub fn base_size(mut self, base_size: f32) -> Self {
+        self.base_size = base_size.max(1.0);
+        self
+    }
+
+    /// How far from the outline of a glyph distances are stored, in pixels at the base size.
+    /// This limits how wide outlines, glows and soft shadows can be.
+    ///
+    /// Default: 6
+    pub fn spread(mut self, spread: f32) -> Self {
+        self.spread = spread.max(1.0);
+        self
+    }
+}
+
+/// An outline around the glyphs of a [`Text`](struct.Text.html) drawn with an SDF font.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Outline {
+    pub color: Color,
+    /// The width as a fraction of the font scale, e.g. `0.05` for 0.8 pixels at the default
+    /// scale of 16. It can't grow beyond the spread of the font.
+    pub width: f32,
+}
+
+impl Outline {
+    pub fn new(color: Color, width: f32) -> Self {
+        Outline { color, width }
+    }
+}
+
+/// A copy of the glyphs of a [`Text`](struct.Text.html) drawn with an SDF font, drawn behind
+/// them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shadow {
+    pub color: Color,
+    /// The offset from the text, in the same units as the glyph positions.
+    pub offset: mint::Vector2<f32>,
+    /// How blurry the shadow is, as a fraction of the font scale. `0.0` gives a sharp shadow.
+    pub softness: f32,
+}
+
+impl Shadow {
+    pub fn new<V: Into<mint::Vector2<f32>>>(color: Color, offset: V, softness: f32) -> Self {
+        Shadow {
+            color,
+            offset: offset.into(),
+            softness,
+        }
+    }
+}
+
+/// A glow fading out around the glyphs of a [`Text`](struct.Text.html) drawn with an SDF font.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glow {
+    pub color: Color,
+    /// How far the glow reaches, as a fraction of the font scale. It can't grow beyond the
+    /// spread of the font.
+    pub radius: f32,
+}
+
+impl Glow {
+    pub fn new(color: Color, radius: f32) -> Self {
+        Glow { color, radius }
+    }
+}
+
+/// The outline, shadow and glow of a `Text`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct SdfStyle {
+    pub(crate) outline: Option<Outline>,
+    pub(crate) shadow: Option<Shadow>,
+    pub(crate) glow: Option<Glow>,
+}
+
+/// Where a glyph is in the atlas, in texels.
+#[derive(Debug, Copy, Clone)]
+struct SdfGlyph {
+    src: (u16, u16, u16, u16),
+    /// The top left corner of the texels relative to the origin of the glyph, in pixels at the
+    /// base size.
+    offset: (f32, f32),
+}
+
+/// The atlas of an SDF font.
+#[derive(Debug)]
+pub(crate) struct SdfFont {
+    font: FontArc,
+    settings: SdfSettings,
+    /// `None` for glyphs without an outline, like spaces.
+    glyphs: HashMap<GlyphId, Option<SdfGlyph>>,
+    /// RGBA8, white with the distance in alpha.
+    pixels: Vec<u8>,
+    size: u16,
+    /// The left and top of the free space in the current row, and the height of the row.
+    cursor: (u16, u16, u16),
+    /// Whether `pixels` changed since they were last uploaded.
+    dirty: bool,
+    /// The texture of the atlas, and the batch drawing from it, created on the first draw.
+    texture: Option<miniquad::Texture>,
+    batch: Option<SpriteBatch>,
+}
+
+const INITIAL_ATLAS_SIZE: u16 = 256;
+const MAX_ATLAS_SIZE: u16 = 4096;
+
+impl SdfFont {
+    pub(crate) fn new(font: FontArc, settings: SdfSettings) -> Self {
+        let size = INITIAL_ATLAS_SIZE;
+        SdfFont {
+            font,
+            settings,
+            glyphs: HashMap::new(),
+            pixels: blank_pixels(size),
+            size,
+            cursor: (0, 0, 0),
+            dirty: true,
+            texture: None,
+            batch: None,
+        }
+    }
+
+    /// Returns where the glyph is in the atlas, rasterizing it first if it isn't yet.
+    fn glyph(&mut self, id: GlyphId) -> Option<SdfGlyph> {
+        if let Some(glyph) = self.glyphs.get(&id) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(id);
+        let _ = self.glyphs.insert(id, glyph);
+        glyph
+    }
+
+    fn rasterize(&mut self, id: GlyphId) -> Option<SdfGlyph> {
+        let SdfSettings { base_size, spread } = self.settings;
+        let outlined = self
+            .font
+            .outline_glyph(id.with_scale(PxScale::from(base_size)))?;
+        let bounds = outlined.px_bounds();
+        let padding = spread.ceil() as u32;
+        let width = bounds.width() as u32 + 2 * padding;
+        let height = bounds.height() as u32 + 2 * padding;
+
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outlined.draw(|x, y, c| {
+            coverage[((y + padding) * width + x + padding) as usize] = c;
+        });
+        let distances = distance_field(&coverage, width as usize, height as usize, spread);
+
+        let (x, y) = match self.allocate(width as u16, height as u16) {
+            Some(position) => position,
+            None => {
+                warn!(
+                    "The atlas of an SDF font is full, glyph {:?} is skipped",
+                    id
+                );
+                return None;
+            }
+        };
+        let size = usize::from(self.size);
+        for row in 0..height as usize {
+            for column in 0..width as usize {
+                let texel = (usize::from(y) + row) * size + usize::from(x) + column;
+                self.pixels[texel * 4 + 3] = distances[row * width as usize + column];
+            }
+        }
+        self.dirty = true;
+
+        Some(SdfGlyph {
+            src: (x, y, width as u16, height as u16),
+            offset: (bounds.min.x - padding as f32, bounds.min.y - padding as f32),
+        })
+    }
+
+    /// Finds space for a glyph of the given size, growing the atlas if necessary.
+    fn allocate(&mut self, width: u16, height: u16) -> Option<(u16, u16)> {
+        // leave a gap between glyphs so filtering doesn't bleed into neighbors
+        let (gap_width, gap_height) = (width + 1, height + 1);
+        loop {
+            let (mut x, mut y, mut row_height) = self.cursor;
+            if u32::from(x) + u32::from(gap_width) > u32::from(self.size) {
+                x = 0;
+                y += row_height;
+                row_height = 0;
+            }
+            if u32::from(x) + u32::from(gap_width) <= u32::from(self.size)
+                && u32::from(y) + u32::from(gap_height) <= u32::from(self.size)
+            {
+                self.cursor = (x + gap_width, y, row_height.max(gap_height));
+                return Some((x, y));
+            }
+            if self.size >= MAX_ATLAS_SIZE {
+                return None;
+            }
+            self.grow();
+        }
+    }
+
+    /// Doubles the size of the atlas, keeping the glyphs where they are.
+    fn grow(&mut self) {
+        let old_size = usize::from(self.size);
+        self.size *= 2;
+        let mut pixels = blank_pixels(self.size);
+        let row_bytes = old_size * 4;
+        for row in 0..old_size {
+            let start = row * usize::from(self.size) * 4;
+            pixels[start..start + row_bytes]
+                .copy_from_slice(&self.pixels[row * row_bytes..(row + 1) * row_bytes]);
+        }
+        self.pixels = pixels;
+        self.dirty = true;
+    }
+
+    /// Uploads the atlas if it changed, returning the batch drawing from it.
+    fn batch(
+        &mut self,
+        quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    ) -> GameResult<&mut SpriteBatch> {
+        if self.dirty {
+            match (&self.texture, &mut self.batch) {
+                (Some(texture), Some(_)) if texture.width == u32::from(self.size) => {
+                    texture.update(quad_ctx, &self.pixels)
+                }
+                (_, batch) => {
+                    let texture =
+                        miniquad::Texture::from_rgba8(quad_ctx, self.size, self.size, &self.pixels);
+                    let image =
+                        Image::from_texture(quad_ctx, texture, miniquad::FilterMode::Linear)?;
+                    match batch {
+                        Some(batch) => {
+                            let _ = batch.set_image(image);
+                        }
+                        None => *batch = Some(SpriteBatch::new(image)),
+                    }
+                    self.texture = Some(texture);
+                }
+            }
+            self.dirty = false;
+        }
+        Ok(self.batch.as_mut().unwrap())
+    }
+}
+
+fn blank_pixels(size: u16) -> Vec<u8> {
+    let texels = usize::from(size) * usize::from(size);
+    let mut pixels = vec![255; texels * 4];
+    for texel in 0..texels {
+        pixels[texel * 4 + 3] = 0;
+    }
+    pixels
+}
+
+/// Turns the coverage of a glyph into distances from its outline, mapped from `-spread..spread`
+/// pixels to `0..255` with the outline at 128 and the inside above it.
+fn distance_field(coverage: &[f32], width: usize, height: usize, spread: f32) -> Vec<u8> {
+    let inside = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && coverage[y as usize * width + x as usize] >= 0.5
+    };
+    let radius = spread.ceil() as i32;
+
+    let mut distances = Vec::with_capacity(width * height);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let c = coverage[y as usize * width + x as usize];
+            // the rasterizer leaves tiny amounts of coverage next to the outline
+            let signed = if c > 0.02 && c < 0.98 {
+                // the outline runs through this texel
+                c - 0.5
+            } else {
+                // the distance to the closest texel on the other side of the outline
+                let is_inside = inside(x, y);
+                let mut nearest = spread;
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        if inside(x + dx, y + dy) != is_inside {
+                            nearest = nearest.min(((dx * dx + dy * dy) as f32).sqrt());
+                        }
+                    }
+                }
+                if is_inside {
+                    nearest - 0.5
+                } else {
+                    0.5 - nearest
+                }
+            };
+            let value = (0.5 + signed / (2.0 * spread)).clamp(0.0, 1.0);
+            distances.push((value * 255.0).round() as u8);
+        }
+    }
+    distances
+}
+
+/// Draws glyphs laid out by `glyph_brush` in SDF fonts, with the given color for each of them.
+pub(crate) fn draw_glyphs(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    glyphs: &[(SectionGlyph, Color)],
+    style: SdfStyle,
+    blend_mode: Option<BlendMode>,
+    param: DrawParam,
+) -> GameResult {
+    let shader = match ctx.gfx_context.sdf_shader {
+        Some(shader) => shader,
+        None => {
+            let shader = Shader::from_str(ctx, quad_ctx, VERTEX, FRAGMENT, meta(), None)?;
+            ctx.gfx_context.sdf_shader = Some(shader);
+            shader
+        }
+    };
+    let screen_size = match &ctx.gfx_context.canvas {
+        Some(canvas) => (f32::from(canvas.width()), f32::from(canvas.height())),
+        None => graphics::drawable_size(quad_ctx),
+    };
+
+    let mut font_ids: Vec<FontId> = glyphs.iter().map(|(glyph, _)| glyph.font_id).collect();
+    font_ids.sort_unstable_by_key(|id| id.0);
+    font_ids.dedup();
+    for font_id in font_ids {
+        // taken out while drawing, as drawing needs the whole context
+        let mut font = match ctx.gfx_context.sdf_fonts.remove(&font_id) {
+            Some(font) => font,
+            None => continue,
+        };
+        let result = draw_font_glyphs(
+            ctx,
+            quad_ctx,
+            &mut font,
+            glyphs.iter().filter(|(glyph, _)| glyph.font_id == font_id),
+            (shader, screen_size),
+            style,
+            blend_mode,
+            param,
+        );
+        let _ = ctx.gfx_context.sdf_fonts.insert(font_id, font);
+        result?;
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_font_glyphs<'a>(
+    ctx: &mut Context,
+    quad_ctx: &mut miniquad::graphics::GraphicsContext,
+    font: &mut SdfFont,
+    glyphs: impl Iterator<Item = &'a (SectionGlyph, Color)>,
+    (shader, screen_size): (graphics::ShaderId, (f32, f32)),
+    style: SdfStyle,
+    blend_mode: Option<BlendMode>,
+    param: DrawParam,
+) -> GameResult {
+    let SdfSettings { base_size, spread } = font.settings;
+
+    let mut sprites = Vec::new();
+    for (section_glyph, color) in glyphs {
+        let glyph = &section_glyph.glyph;
+        let sdf_glyph = match font.glyph(glyph.id) {
+            Some(sdf_glyph) => sdf_glyph,
+            None => continue,
+        };
+        let (scale_x, scale_y) = (glyph.scale.x / base_size, glyph.scale.y / base_size);
+        sprites.push((sdf_glyph, glyph.position, (scale_x, scale_y), *color));
+    }
+    if sprites.is_empty() {
+        return Ok(());
+    }
+
+    let atlas_size = f32::from(font.size);
+    let batch = font.batch(quad_ctx)?;
+    batch.clear();
+    batch.set_blend_mode(blend_mode);
+    for (glyph, position, (scale_x, scale_y), color) in sprites {
+        let (x, y, w, h) = glyph.src;
+        let _ = batch.add(
+            DrawParam::new()
+                .src(Rect::new(
+                    f32::from(x) / atlas_size,
+                    f32::from(y) / atlas_size,
+                    f32::from(w) / atlas_size,
+                    f32::from(h) / atlas_size,
+                ))
+                .dest(mint::Point2 {
+                    x: position.x + glyph.offset.0 * scale_x,
+                    y: position.y + glyph.offset.1 * scale_y,
+                })
+                .scale(mint::Vector2 {
+                    x: scale_x,
+                    y: scale_y,
+                })
+                .color(color),
+        );
+    }
+
+    // distances are stored as 0.5 + pixels / (2 * spread), pixels at the base size
+    let to_distance = |fraction: f32| (fraction * base_size / (2.0 * spread)).clamp(0.0, 0.5);
+    let outline = style.outline.unwrap_or(Outline::new(Color::BLACK, 0.0));
+    let glow = style.glow.unwrap_or(Glow::new(Color::BLACK, 0.0));
+    let mut uniforms = SdfUniforms {
+        outline_color: outline.color.into(),
+        glow_color: glow.color.into(),
+        shadow_color: [0.0; 4],
+        atlas_size: [atlas_size, atlas_size],
+        screen_size: [screen_size.0, screen_size.1],
+        distance_scale: 1.0 / (2.0 * spread),
+        outline_width: to_distance(outline.width),
+        glow_width: to_distance(glow.radius),
+        softness: 0.0,
+        shadow_pass: 0.0,
+    };
+
+    let _lock = graphics::use_shader(ctx, shader);
+    if let Some(shadow) = style.shadow {
+        let shadow_uniforms = SdfUniforms {
+            shadow_color: shadow.color.into(),
+            softness: to_distance(shadow.softness),
+            shadow_pass: 1.0,
+            ..uniforms
+        };
+        graphics::set_uniforms(ctx, shader, shadow_uniforms);
+        let transform = cgmath::Matrix4::from(param.trans.to_bare_matrix())
+            * cgmath::Matrix4::from_translation(cgmath::Vector3::new(
+                shadow.offset.x,
+                shadow.offset.y,
+                0.0,
+            ));
+        graphics::draw(
+            ctx,
+            quad_ctx,
+            &*batch,
+            DrawParam::new().transform(transform),
+        )?;
+    }
+    uniforms.shadow_pass = 0.0;
+    graphics::set_uniforms(ctx, shader, uniforms);
+    graphics::draw(ctx, quad_ctx, &*batch, param)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Font;
+
+    fn font() -> SdfFont {
+        let font = FontArc::try_from_slice(Font::default_font_bytes()).unwrap();
+        SdfFont::new(font, SdfSettings::default())
+    }
+
+    #[test]
+    fn distance_field_of_a_square() {
+        // a square from 4 to 12, whose right edge runs through the middle of column 12
+        let (width, height) = (16, 16);
+        let mut coverage = vec![0.0; width * height];
+        for y in 4..12 {
+            for x in 4..12 {
+                coverage[y * width + x] = 1.0;
+            }
+            coverage[y * width + 12] = 0.5;
+        }
+        let distances = distance_field(&coverage, width, height, 4.0);
+        let at = |x: usize, y: usize| distances[y * width + x];
+
+        assert!(at(8, 8) > 128);
+        assert!(at(0, 0) < 128);
+        assert!(at(8, 15) < 128);
+        // further inside is further above 128, further outside further below
+        assert!(at(8, 8) > at(5, 8) && at(5, 8) > 128);
+        assert!(at(0, 8) < at(2, 8) && at(2, 8) < 128);
+        // on the outline
+        assert_eq!(at(12, 8), 128);
+        assert!((128..=144).contains(&at(4, 8)));
+        assert!((112..128).contains(&at(3, 8)));
+        // beyond the spread the distances are clamped
+        assert_eq!(at(0, 0), at(15, 0));
+    }
+
+    #[test]
+    fn allocation_wraps_rows_and_grows_the_atlas() {
+        let mut font = font();
+        assert_eq!(font.size, INITIAL_ATLAS_SIZE);
+        assert_eq!(font.allocate(100, 10), Some((0, 0)));
+        assert_eq!(font.allocate(100, 20), Some((101, 0)));
+        // the next row starts below the tallest glyph of the row before, with a gap of 1
+        assert_eq!(font.allocate(100, 10), Some((0, 21)));
+        assert_eq!(font.size, INITIAL_ATLAS_SIZE);
+
+        // doesn't fit below, so the atlas grows
+        assert_eq!(font.allocate(200, 240), Some((101, 21)));
+        assert_eq!(font.size, 2 * INITIAL_ATLAS_SIZE);
+
+        // grows up to the maximum size before giving up
+        assert_eq!(font.allocate(MAX_ATLAS_SIZE, 10), None);
+        assert_eq!(font.size, MAX_ATLAS_SIZE);
+        assert_eq!(font.pixels.len(), 4 * 4096 * 4096);
+        assert_eq!(font.allocate(100, 10), Some((302, 21)));
+    }
+
+    #[test]
+    fn growing_keeps_texels_in_place() {
+        let mut font = font();
+        let size = usize::from(INITIAL_ATLAS_SIZE);
+        let texel = |size: usize, x: usize, y: usize| (y * size + x) * 4 + 3;
+        font.pixels[texel(size, 10, 20)] = 77;
+        font.pixels[texel(size, size - 1, size - 1)] = 99;
+        font.dirty = false;
+
+        font.grow();
+        assert!(font.dirty);
+        assert_eq!(font.pixels.len(), 4 * 4 * size * size);
+        let new_size = 2 * size;
+        assert_eq!(font.pixels[texel(new_size, 10, 20)], 77);
+        assert_eq!(font.pixels[texel(new_size, size - 1, size - 1)], 99);
+        assert_eq!(font.pixels[texel(new_size, size, size - 1)], 0);
+        assert_eq!(font.pixels[texel(new_size, size - 1, size)], 0);
+        let alpha: u32 = font
+            .pixels
+            .iter()
+            .skip(3)
+            .step_by(4)
+            .map(|&a| u32::from(a))
+            .sum();
+        assert_eq!(alpha, 77 + 99);
+        // the color stays white
+        assert!(font
+            .pixels
+            .chunks_exact(4)
+            .all(|texel| texel[..3] == [255, 255, 255]));
+    }
+}
@@ -0,0 +1,539 @@
+//! Running an `EventHandler` for a fixed number of frames, to test drawing code automatically.
+//!
+//! [`run`](fn.run.html) works just like [`start`](../fn.start.html), except that it feeds a
+//! scripted sequence of input events to the game, stops after the given number of frames and
+//! returns what was drawn in the last one:
+//!
+//! ```rust
+//! use good_web_game::{graphics, headless, GameResult};
+//! # struct MyGame;
+//! # impl good_web_game::event::EventHandler for MyGame {
+//! #     fn update(&mut self, _: &mut good_web_game::Context, _: &mut good_web_game::event::GraphicsContext) -> GameResult { Ok(()) }
+//! #     fn draw(&mut self, ctx: &mut good_web_game::Context, quad_ctx: &mut good_web_game::event::GraphicsContext) -> GameResult {
+//! #         graphics::clear(ctx, quad_ctx, graphics::Color::RED);
+//! #         graphics::present(ctx, quad_ctx)
+//! #     }
+//! # }
+//!
+//! let script = headless::InputScript::new().at(
+//!     2,
+//!     headless::InputEvent::MouseButtonDown(good_web_game::input::MouseButton::Left, 10.0, 10.0),
+//! );
+//! let frame = headless::run(
+//!     good_web_game::conf::Conf::default(),
+//!     5,
+//!     script,
+//!     |_ctx, _quad_ctx| Box::new(MyGame),
+//! )
+//! .unwrap();
+//! assert_eq!(frame.pixel(0, 0), graphics::Color::RED);
+//! ```
+//!
+//! Nothing is shown on screen: the game draws into an offscreen canvas of the size given by
+//! [`Conf::window_width`](../conf/struct.Conf.html#method.window_width) and
+//! [`Conf::window_height`](../conf/struct.Conf.html#method.window_height), through a GL context
+//! created by EGL without any window. On machines without GPU (like most CI runners) Mesa renders
+//! it in software, so plain `cargo test` works as long as Mesa's `libEGL` is installed. As there's
+//! no window, changing the window or the mouse cursor does nothing.
+//!
+//! Games run one at a time, parallel tests calling `run` wait for each other.
+//!
+//! Only available on Linux.
+
+use std::sync::{Mutex, PoisonError};
+
+use crate::conf::Conf;
+use crate::event::{EventHandler, KeyCode, KeyMods, TouchPhase};
+use crate::filesystem::Filesystem;
+use crate::graphics::{self, Canvas, CanvasSettings, Color, FilterMode};
+use crate::input::MouseButton;
+use crate::{Context, EventHandlerWrapper, GameError, GameResult};
+
+/// Held while a game runs, as miniquad loads the GL functions into globals.
+static RUNNING: Mutex<()> = Mutex::new(());
+
+/// An input event fed to the game by [`run`](fn.run.html).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputEvent {
+    /// A key was pressed.
+    KeyDown(KeyCode, KeyMods),
+    /// A key was released.
+    KeyUp(KeyCode, KeyMods),
+    /// A character was typed.
+    TextInput(char),
+    /// The mouse moved to the given position.
+    MouseMotion(f32, f32),
+    /// A mouse button was pressed at the given position.
+    MouseButtonDown(MouseButton, f32, f32),
+    /// A mouse button was released at the given position.
+    MouseButtonUp(MouseButton, f32, f32),
+    /// A touch with the given id changed its phase at the given position.
+    Touch(TouchPhase, u64, f32, f32),
+    /// The window was resized to the given size.
+    Resize(f32, f32),
+}
+
+/// The input events to feed to the game, each at a certain frame.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InputScript {
+    events: Vec<(usize, InputEvent)>,
+}
+
+impl InputScript {
+    /// Creates a new, empty script.
+    pub fn new() -> InputScript {
+        InputScript::default()
+    }
+
+    /// Adds an event, which is handed to the game right before `EventHandler::update` is
+    /// called in the given frame (counting from 0).
+    ///
+    /// Events added for the same frame are handed over in the order they were added in.
+    pub fn at(mut self, frame: usize, event: InputEvent) -> Self {
+        self.events.push((frame, event));
+        self
+    }
+}
+
+/// The contents of the screen at the end of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// The width of the frame in pixels.
+    pub width: u16,
+    /// The height of the frame in pixels.
+    pub height: u16,
+    /// The pixels of the frame as RGBA8, row by row from top to bottom.
+    pub rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// Returns the color of the pixel at the given position, with (0, 0) being the top left corner.
+    pub fn pixel(&self, x: u16, y: u16) -> Color {
+        let i = 4 * (usize::from(y) * usize::from(self.width) + usize::from(x));
+        Color::from_rgba(
+            self.rgba[i],
+            self.rgba[i + 1],
+            self.rgba[i + 2],
+            self.rgba[i + 3],
+        )
+    }
+}
+
+/// The canvas standing in for the screen, redirecting all drawing to the screen into itself for
+/// as long as it lives.
+struct Screen {
+    canvas: Canvas,
+}
+
+impl Screen {
+    fn new(
+        quad_ctx: &mut miniquad::GraphicsContext,
+        width: u16,
+        height: u16,
+    ) -> GameResult<Screen> {
+        // windows usually come with a depth and stencil buffer as well
+        let settings = CanvasSettings::default().depth_stencil(true);
+        let canvas = Canvas::create(quad_ctx, width, height, settings, FilterMode::Linear)?;
+        graphics::set_headless_screen(Some(&canvas));
+        Ok(Screen { canvas })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        graphics::set_headless_screen(None);
+    }
+}
+
+struct HeadlessWrapper<E: std::error::Error> {
+    inner: EventHandlerWrapper<E>,
+    screen: Screen,
+    script: Vec<(usize, InputEvent)>,
+    frame: usize,
+}
+
+fn to_quad_keymods(keymods: KeyMods) -> miniquad::KeyMods {
+    miniquad::KeyMods {
+        shift: keymods.contains(KeyMods::SHIFT),
+        ctrl: keymods.contains(KeyMods::CTRL),
+        alt: keymods.contains(KeyMods::ALT),
+        logo: keymods.contains(KeyMods::LOGO),
+    }
+}
+
+fn to_quad_button(button: MouseButton) -> miniquad::MouseButton {
+    match button {
+        MouseButton::Left => miniquad::MouseButton::Left,
+        MouseButton::Right => miniquad::MouseButton::Right,
+        MouseButton::Middle => miniquad::MouseButton::Middle,
+        _ => miniquad::MouseButton::Unknown,
+    }
+}
+
+impl<E: std::error::Error> HeadlessWrapper<E> {
+    fn dispatch(
+        &mut self,
+        quad_ctx: &mut miniquad::GraphicsContext,
+        event: InputEvent,
+    ) -> GameResult {
+        use miniquad::EventHandler as _;
+
+        let inner = &mut self.inner;
+        match event {
+            InputEvent::KeyDown(keycode, keymods) => {
+                inner.key_down_event(quad_ctx, keycode, to_quad_keymods(keymods), false)
+            }
+            InputEvent::KeyUp(keycode, keymods) => {
+                inner.key_up_event(quad_ctx, keycode, to_quad_keymods(keymods))
+            }
+            InputEvent::TextInput(character) => {
+                inner.char_event(quad_ctx, character, Default::default(), false)
+            }
+            InputEvent::MouseMotion(x, y) => inner.mouse_motion_event(quad_ctx, x, y),
+            InputEvent::MouseButtonDown(button, x, y) => {
+                inner.mouse_button_down_event(quad_ctx, to_quad_button(button), x, y)
+            }
+            InputEvent::MouseButtonUp(button, x, y) => {
+                inner.mouse_button_up_event(quad_ctx, to_quad_button(button), x, y)
+            }
+            InputEvent::Touch(phase, id, x, y) => inner.touch_event(quad_ctx, phase, id, x, y),
+            InputEvent::Resize(width, height) => {
+                // the old screen has to be gone before the new one takes over
+                self.screen.canvas = Canvas::create(
+                    quad_ctx,
+                    width as u16,
+                    height as u16,
+                    self.screen.canvas.settings(),
+                    FilterMode::Linear,
+                )?;
+                graphics::set_headless_screen(Some(&self.screen.canvas));
+                inner.resize_event(quad_ctx, width, height)
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a single frame, returning whether the game is still running.
+    fn frame(&mut self, quad_ctx: &mut miniquad::GraphicsContext) -> GameResult<bool> {
+        use miniquad::EventHandler as _;
+
+        while !self.script.is_empty() && self.script[0].0 <= self.frame {
+            let (_, event) = self.script.remove(0);
+            self.dispatch(quad_ctx, event)?;
+        }
+        // miniquad would quit instead of running the frame
+        if !self.inner.context.continuing {
+            return Ok(false);
+        }
+        self.inner.update(quad_ctx);
+        self.inner.draw(quad_ctx);
+        self.frame += 1;
+        Ok(true)
+    }
+}
+
+/// Runs the game for `frames` frames, feeding it the input events of `script`, and returns the
+/// contents of the screen at the end of the last frame.
+///
+/// Fails if the game quits early, or if no GL context could be created, see the
+/// [module level docs](index.html).
+pub fn run<F, E>(conf: Conf, frames: usize, script: InputScript, f: F) -> GameResult<Frame>
+where
+    E: std::error::Error + 'static,
+    F: 'static + FnOnce(&mut Context, &mut miniquad::GraphicsContext) -> Box<dyn EventHandler<E>>,
+{
+    if frames == 0 {
+        return Err(GameError::RenderError(
+            "Need to run for at least one frame".to_string(),
+        ));
+    }
+    let (width, height) = (conf.quad_conf.window_width, conf.quad_conf.window_height);
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+        _ => {
+            return Err(GameError::RenderError(format!(
+                "Invalid window size {}x{}",
+                width, height
+            )))
+        }
+    };
+
+    let fs = Filesystem::new(&conf);
+    let hot_reload = conf.hot_reload;
+
+    let mut script = script.events;
+    // stable, so events of the same frame keep their order
+    script.sort_by_key(|(frame, _)| *frame);
+
+    // a panicking game only poisons the lock, it doesn't leave anything behind
+    let _running = RUNNING.lock().unwrap_or_else(PoisonError::into_inner);
+    let _gl = egl::Context::new()?;
+    let mut quad_ctx = miniquad::GraphicsContext::new();
+
+    let result = (|| {
+        let screen = Screen::new(&mut quad_ctx, width, height)?;
+        let mut wrapper = HeadlessWrapper {
+            inner: crate::create_wrapper(&mut quad_ctx, fs, hot_reload, f),
+            screen,
+            script,
+            frame: 0,
+        };
+        while wrapper.frame < frames {
+            if !wrapper.frame(&mut quad_ctx)? {
+                return Err(GameError::RenderError(format!(
+                    "The game quit before reaching frame {}",
+                    frames
+                )));
+            }
+        }
+        let (width, height, rgba) = graphics::read_screen_rgba8(&mut quad_ctx);
+        Ok(Frame {
+            width,
+            height,
+            rgba,
+        })
+    })();
+
+    // the buffers of everything dropped during the last two frames are still around, and have
+    // to be deleted while the GL context is
+    graphics::release_dropped_bindings();
+    graphics::release_dropped_bindings();
+    result
+}
+
+/// A GL context without any window or surface, created through Mesa's surfaceless EGL platform.
+mod egl {
+    use std::ffi::{c_void, CString};
+    use std::ptr::{null, null_mut};
+
+    use miniquad::native::egl::*;
+
+    use crate::{GameError, GameResult};
+
+    // missing from `miniquad::native::egl`
+    const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+    const EGL_RENDERABLE_TYPE: u32 = 0x3040;
+    const EGL_OPENGL_ES3_BIT: u32 = 0x40;
+    const EGL_PBUFFER_BIT: u32 = 0x1;
+
+    type GetPlatformDisplay = unsafe extern "C" fn(
+        platform: u32,
+        display: *mut c_void,
+        attributes: *const EGLint,
+    ) -> EGLDisplay;
+
+    fn error(message: &str) -> GameError {
+        GameError::RenderError(format!(
+            "Couldn't create a headless GL context: {}",
+            message
+        ))
+    }
+
+    pub(super) struct Context {
+        egl: LibEgl,
+        display: EGLDisplay,
+        context: EGLContext,
+    }
+
+    impl Context {
+        /// Creates a GLES 3 context and makes it current, loading the GL functions from it.
+        pub(super) fn new() -> GameResult<Context> {
+            let egl = LibEgl::try_load().ok_or_else(|| error("libEGL not found"))?;
+            let missing = || error("libEGL is incomplete");
+            let get_proc_address = egl.eglGetProcAddress.ok_or_else(missing)?;
+            let initialize = egl.eglInitialize.ok_or_else(missing)?;
+            let choose_config = egl.eglChooseConfig.ok_or_else(missing)?;
+            let create_context = egl.eglCreateContext.ok_or_else(missing)?;
+            let make_current = egl.eglMakeCurrent.ok_or_else(missing)?;
+            egl.eglDestroyContext.ok_or_else(missing)?;
+            egl.eglTerminate.ok_or_else(missing)?;
+
+            unsafe {
+                let name = CString::new("eglGetPlatformDisplayEXT").unwrap();
+                let get_platform_display: Option<GetPlatformDisplay> =
+                    std::mem::transmute(get_proc_address(name.as_ptr()));
+                let get_platform_display = get_platform_display
+                    .ok_or_else(|| error("EGL_EXT_platform_base isn't supported"))?;
+                let display =
+                    get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, null_mut(), null());
+                if display.is_null() {
+                    return Err(error("EGL_MESA_platform_surfaceless isn't supported"));
+                }
+                if initialize(display, null_mut(), null_mut()) == 0 {
+                    return Err(error("EGL couldn't be initialized"));
+                }
+
+                #[rustfmt::skip]
+                let config_attributes = [
+                    // the surfaceless platform has no windows, and only asking for no surface at
+                    // all is not an option
+                    EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+                    EGL_RENDERABLE_TYPE, EGL_OPENGL_ES3_BIT,
+                    EGL_RED_SIZE, 8,
+                    EGL_GREEN_SIZE, 8,
+                    EGL_BLUE_SIZE, 8,
+                    EGL_ALPHA_SIZE, 8,
+                    EGL_NONE,
+                ];
+                let mut config = null_mut();
+                let mut config_count = 0;
+                choose_config(
+                    display,
+                    config_attributes.as_ptr() as _,
+                    &mut config,
+                    1,
+                    &mut config_count,
+                );
+                if config_count == 0 {
+                    return Err(error("there's no config supporting GLES 3"));
+                }
+
+                let context_attributes = [EGL_CONTEXT_CLIENT_VERSION, 3, EGL_NONE];
+                let context = create_context(
+                    display,
+                    config,
+                    /* EGL_NO_CONTEXT */ null_mut(),
+                    context_attributes.as_ptr() as _,
+                );
+                if context.is_null() {
+                    return Err(error("the context couldn't be created"));
+                }
+                // everything is drawn into canvases, so there's no need for a surface
+                if make_current(display, null_mut(), null_mut(), context) == 0 {
+                    (egl.eglDestroyContext.unwrap())(display, context);
+                    return Err(error("EGL_KHR_surfaceless_context isn't supported"));
+                }
+
+                miniquad::gl::load_gl_funcs(|name| {
+                    let name = CString::new(name).unwrap();
+                    get_proc_address(name.as_ptr())
+                });
+
+                Ok(Context {
+                    egl,
+                    display,
+                    context,
+                })
+            }
+        }
+    }
+
+    impl Drop for Context {
+        fn drop(&mut self) {
+            unsafe {
+                (self.egl.eglMakeCurrent.unwrap())(
+                    self.display,
+                    null_mut(),
+                    null_mut(),
+                    null_mut(),
+                );
+                (self.egl.eglDestroyContext.unwrap())(self.display, self.context);
+                (self.egl.eglTerminate.unwrap())(self.display);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, InputEvent, InputScript};
+    use crate::conf::Conf;
+    use crate::event::{self, EventHandler, GraphicsContext};
+    use crate::graphics::{self, Color, DrawParam, Image};
+    use crate::input::MouseButton;
+    use crate::{Context, GameError, GameResult};
+
+    /// Draws a white square wherever the mouse was clicked, and quits on key presses.
+    struct Clicks {
+        square: Image,
+        clicks: Vec<(f32, f32)>,
+    }
+
+    impl EventHandler for Clicks {
+        fn update(&mut self, _: &mut Context, _: &mut GraphicsContext) -> GameResult {
+            Ok(())
+        }
+
+        fn draw(&mut self, ctx: &mut Context, quad_ctx: &mut GraphicsContext) -> GameResult {
+            graphics::clear(ctx, quad_ctx, Color::BLACK);
+            for &(x, y) in &self.clicks {
+                graphics::draw(ctx, quad_ctx, &self.square, DrawParam::new().dest([x, y]))?;
+            }
+            graphics::present(ctx, quad_ctx)
+        }
+
+        fn mouse_button_down_event(
+            &mut self,
+            _: &mut Context,
+            _: &mut GraphicsContext,
+            _: MouseButton,
+            x: f32,
+            y: f32,
+        ) {
+            self.clicks.push((x, y));
+        }
+
+        fn key_down_event(
+            &mut self,
+            ctx: &mut Context,
+            _: &mut GraphicsContext,
+            _: event::KeyCode,
+            _: event::KeyMods,
+            _: bool,
+        ) {
+            event::quit(ctx);
+        }
+    }
+
+    fn clicks(ctx: &mut Context, quad_ctx: &mut GraphicsContext) -> Box<dyn EventHandler> {
+        Box::new(Clicks {
+            square: Image::solid(ctx, quad_ctx, 4, Color::WHITE).unwrap(),
+            clicks: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn scripted_input() {
+        let conf = Conf::default().window_width(64).window_height(32);
+        let script = InputScript::new()
+            .at(3, InputEvent::MouseButtonDown(MouseButton::Left, 8.0, 4.0))
+            .at(
+                1,
+                InputEvent::MouseButtonDown(MouseButton::Left, 40.0, 20.0),
+            );
+        let frame = run(conf, 4, script, clicks).unwrap();
+        assert_eq!((frame.width, frame.height), (64, 32));
+        assert_eq!(frame.rgba.len(), 64 * 32 * 4);
+        assert_eq!(frame.pixel(9, 5), Color::WHITE);
+        assert_eq!(frame.pixel(41, 21), Color::WHITE);
+        assert_eq!(frame.pixel(7, 5), Color::BLACK);
+        assert_eq!(frame.pixel(0, 31), Color::BLACK);
+
+        // the click of the last frame hasn't happened yet in the second to last one
+        let script =
+            InputScript::new().at(3, InputEvent::MouseButtonDown(MouseButton::Left, 8.0, 4.0));
+        let frame = run(Conf::default(), 3, script, clicks).unwrap();
+        assert_eq!(frame.pixel(9, 5), Color::BLACK);
+    }
+
+    #[test]
+    fn resize() {
+        let script = InputScript::new().at(0, InputEvent::Resize(20.0, 10.0));
+        let frame = run(Conf::default(), 1, script, clicks).unwrap();
+        assert_eq!((frame.width, frame.height), (20, 10));
+        assert!(graphics::has_window());
+    }
+
+    #[test]
+    fn quitting_early() {
+        let script = InputScript::new().at(
+            1,
+            InputEvent::KeyDown(event::KeyCode::Escape, event::KeyMods::NONE),
+        );
+        match run(Conf::default(), 3, script, clicks) {
+            Err(GameError::RenderError(e)) => assert!(e.contains("quit"), "{}", e),
+            result => panic!("{:?}", result.map(|frame| frame.width)),
+        }
+        assert!(run(Conf::default(), 0, InputScript::new(), clicks).is_err());
+    }
+}
@@ -89,7 +89,9 @@ pub fn set_cursor_grabbed(
     grabbed: bool,
 ) {
     ctx.mouse_context.cursor_grabbed = grabbed;
-    quad_ctx.set_cursor_grab(grabbed);
+    if crate::graphics::has_window() {
+        quad_ctx.set_cursor_grab(grabbed);
+    }
 }
 
 /// Returns the current mouse cursor type of the window.
@@ -104,7 +106,9 @@ pub fn set_cursor_type(
     cursor_type: miniquad::CursorIcon,
 ) {
     ctx.mouse_context.cursor_type = cursor_type;
-    quad_ctx.set_mouse_cursor(cursor_type);
+    if crate::graphics::has_window() {
+        quad_ctx.set_mouse_cursor(cursor_type);
+    }
 }
 
 /// Set whether or not the mouse is hidden (invisible)
@@ -119,5 +123,7 @@ pub fn set_cursor_hidden(
     hidden: bool,
 ) {
     ctx.mouse_context.cursor_hidden = hidden;
-    quad_ctx.show_mouse(!hidden);
+    if crate::graphics::has_window() {
+        quad_ctx.show_mouse(!hidden);
+    }
 }
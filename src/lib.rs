@@ -25,12 +25,10 @@
 //!
 //! ### Missing / Not available:
 //!
-//! * filesystem with writing access (if you need it take a look at [`quad-storage`](https://github.com/optozorax/quad-storage))
 //! * writing your own event loop (doesn't make much sense on callback-only platforms like HTML5)
-//! * spatial audio (overall audio support is still relatively limited)
+//! * audio effects (overall audio support is still relatively limited)
 //! * resolution control in fullscreen mode
 //! * setting window position / size (the latter is available on Windows, but buggy)
-//! * screenshot function
 //! * window icon
 //! * gamepad support on WASM (as `gilrs` depends on wasm-bindgen)
 //!
@@ -49,6 +47,7 @@
     html_logo_url = "https://raw.githubusercontent.com/ggez/good-web-game/master/about/logo.png"
 )]
 
+pub mod assets;
 pub mod audio;
 pub mod conf;
 pub mod error;
@@ -56,6 +55,8 @@ pub mod event;
 pub mod filesystem;
 pub mod goodies;
 pub mod graphics;
+#[cfg(target_os = "linux")]
+pub mod headless;
 pub mod input;
 pub mod timer;
 
@@ -108,6 +109,12 @@ impl<E: std::error::Error> miniquad::EventHandler for EventHandlerWrapper<E> {
         // release all buffers that were kept alive for the previous frame
         graphics::release_dropped_bindings();
 
+        // swap out everything whose files changed on disk
+        graphics::hot_reload::poll(&mut self.context, quad_ctx);
+
+        // duck the music while voices play
+        self.context.audio_context.update();
+
         // before running the game logic update the gamepad state
         #[cfg(not(any(target_arch = "wasm32", target_os = "ios", target_os = "android",)))]
         {
@@ -286,6 +293,40 @@ impl<E: std::error::Error> miniquad::EventHandler for EventHandlerWrapper<E> {
     }
 }
 
+/// Creates the context and the event handler once miniquad is up and running.
+fn create_wrapper<F, E>(
+    quad_ctx: &mut GraphicsContext,
+    fs: Filesystem,
+    hot_reload: bool,
+    f: F,
+) -> EventHandlerWrapper<E>
+where
+    E: std::error::Error + 'static,
+    F: FnOnce(&mut Context, &mut miniquad::GraphicsContext) -> Box<dyn event::EventHandler<E>>,
+{
+    let mut context = Context::new(quad_ctx, fs);
+    #[cfg(not(any(target_arch = "wasm32", target_os = "ios", target_os = "android",)))]
+    if hot_reload {
+        context.gfx_context.hot_reload = Some(Default::default());
+    }
+    #[cfg(any(target_arch = "wasm32", target_os = "ios", target_os = "android",))]
+    let _ = hot_reload;
+
+    // uncommenting this leads to wrong window sizes as `set_window_size` is currently buggy
+    //context.quad_ctx.set_window_size(800 as u32, 600 as u32);
+    let (d_w, d_h) = graphics::drawable_size(quad_ctx);
+    context
+        .gfx_context
+        .set_screen_coordinates(graphics::Rect::new(0., 0., d_w, d_h));
+
+    let event_handler = f(&mut context, quad_ctx);
+
+    EventHandlerWrapper {
+        event_handler,
+        context,
+    }
+}
+
 /// Starts the game. Takes a start configuration, allowing you to specify additional options like
 /// high-dpi behavior, as well as a function specifying how to create the event handler from the
 /// new context.
@@ -296,24 +337,11 @@ where
         + FnOnce(&mut Context, &mut miniquad::GraphicsContext) -> Box<dyn event::EventHandler<E>>,
 {
     let fs = Filesystem::new(&conf);
+    let hot_reload = conf.hot_reload;
     let quad_conf = conf.into();
 
-    miniquad::start(quad_conf, |ctx| {
-        let mut context = Context::new(ctx, fs);
-
-        // uncommenting this leads to wrong window sizes as `set_window_size` is currently buggy
-        //context.quad_ctx.set_window_size(800 as u32, 600 as u32);
-        let (d_w, d_h) = ctx.screen_size();
-        context
-            .gfx_context
-            .set_screen_coordinates(graphics::Rect::new(0., 0., d_w, d_h));
-
-        let event_handler = f(&mut context, ctx);
-
-        Box::new(EventHandlerWrapper {
-            event_handler,
-            context,
-        })
+    miniquad::start(quad_conf, move |ctx| {
+        Box::new(create_wrapper(ctx, fs, hot_reload, f))
     });
     Ok(())
 }